@@ -0,0 +1,92 @@
+use crate::models::gpr_file::GprMetadata;
+use crate::models::{GprFile, OutputFormat};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Bump when `GprMetadata` gains fields or the metadata reader changes, so
+/// entries cached by an older version are read again
+pub const CATALOG_VERSION: u32 = 2;
+
+/// Size and modification time that identify one version of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub size: u64,
+    /// Nanoseconds since the Unix epoch
+    pub modified: u64,
+}
+
+impl FileStamp {
+    pub fn read(path: &Path) -> std::io::Result<FileStamp> {
+        let metadata = std::fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Ok(FileStamp {
+            size: metadata.len(),
+            modified,
+        })
+    }
+}
+
+/// One conversion made from a catalogued file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversionRecord {
+    pub output: PathBuf,
+    pub format: OutputFormat,
+    /// When the conversion finished, as `YYYY:MM:DD HH:MM:SS` UTC
+    pub converted_at: String,
+}
+
+/// Cached state of one GPR file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub version: u32,
+    /// Canonical path of the source file
+    pub path: PathBuf,
+    pub stamp: FileStamp,
+    pub metadata: Option<GprMetadata>,
+    /// Why the metadata could not be read, so broken files are not retried
+    /// until they change
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Cached preview JPEG
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conversions: Vec<ConversionRecord>,
+}
+
+impl CatalogEntry {
+    pub fn new(path: PathBuf, stamp: FileStamp) -> CatalogEntry {
+        CatalogEntry {
+            version: CATALOG_VERSION,
+            path,
+            stamp,
+            metadata: None,
+            error: None,
+            thumbnail: None,
+            conversions: Vec::new(),
+        }
+    }
+
+    /// Whether the cached values still describe the file on disk
+    pub fn is_current(&self, stamp: &FileStamp) -> bool {
+        self.version == CATALOG_VERSION && self.stamp == *stamp
+    }
+
+    /// The catalogued file with its cached metadata
+    pub fn file(&self) -> GprFile {
+        GprFile {
+            size: self.stamp.size,
+            metadata: self.metadata.clone(),
+            ..GprFile::new(self.path.clone())
+        }
+    }
+
+    pub fn last_conversion(&self) -> Option<&ConversionRecord> {
+        self.conversions.last()
+    }
+}
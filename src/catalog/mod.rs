@@ -0,0 +1,5 @@
+pub mod entry;
+pub mod store;
+
+pub use entry::CatalogEntry;
+pub use store::Catalog;
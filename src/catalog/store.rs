@@ -0,0 +1,437 @@
+use super::entry::{CatalogEntry, ConversionRecord, FileStamp};
+use crate::gpr::{self, GprConverter};
+use crate::models::gpr_file::GprMetadata;
+use crate::models::{ConversionConfig, GprFile, OrientationMode, OutputFormat};
+use crate::utils::datetime::ExifDateTime;
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Longest edge of cached previews, in pixels
+const THUMBNAIL_SIZE: u32 = 320;
+
+/// Persistent index of GPR files, stored as one JSON object per line
+///
+/// Entries are keyed by canonical path and stay valid while the file's size
+/// and modification time are unchanged, so large libraries only pay for
+/// reading metadata once.
+pub struct Catalog {
+    /// Where the catalog is saved; `None` keeps it in memory only
+    path: Option<PathBuf>,
+    entries: BTreeMap<PathBuf, CatalogEntry>,
+    dirty: bool,
+    /// Read metadata in the decoder process rather than in this one
+    isolated: bool,
+}
+
+/// What an update of the catalog did
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UpdateSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    pub failed: usize,
+    pub thumbnails: usize,
+}
+
+impl Catalog {
+    /// Catalog that is never written to disk
+    pub fn in_memory() -> Catalog {
+        Catalog {
+            path: None,
+            entries: BTreeMap::new(),
+            dirty: false,
+            isolated: false,
+        }
+    }
+
+    /// Load the catalog at `path`; a missing file gives an empty catalog.
+    /// Lines that cannot be parsed are dropped and re-read on demand.
+    pub fn open(path: &Path) -> Result<Catalog> {
+        let mut catalog = Catalog {
+            path: Some(path.to_path_buf()),
+            ..Catalog::in_memory()
+        };
+
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(catalog),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to open catalog: {}", path.display()))
+            }
+        };
+
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.with_context(|| format!("Failed to read catalog: {}", path.display()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<CatalogEntry>(&line) {
+                Ok(entry) => {
+                    catalog.entries.insert(entry.path.clone(), entry);
+                }
+                Err(e) => {
+                    log::debug!("Skipping catalog line {}: {}", number + 1, e);
+                    catalog.dirty = true;
+                }
+            }
+        }
+
+        Ok(catalog)
+    }
+
+    /// Open the catalog at [`Catalog::default_path`], falling back to an
+    /// in-memory catalog when there is no usable location
+    pub fn open_default() -> Catalog {
+        let Some(path) = Self::default_path() else {
+            log::warn!("No cache directory found, the catalog will not be saved");
+            return Catalog::in_memory();
+        };
+        Catalog::open(&path).unwrap_or_else(|e| {
+            log::warn!("{:#}, starting with an empty catalog", e);
+            Catalog::in_memory()
+        })
+    }
+
+    /// `$GPRPROTOOL_CATALOG`, else `catalog.jsonl` in the user cache directory
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("GPRPROTOOL_CATALOG") {
+            return Some(PathBuf::from(path));
+        }
+        let cache = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+        Some(cache.join("gprprotool").join("catalog.jsonl"))
+    }
+
+    /// Read new metadata with [`GprConverter::read_metadata_isolated`], so a
+    /// malformed file cannot crash this process through the SDK parser
+    pub fn set_isolated(&mut self, isolated: bool) {
+        self.isolated = isolated;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &CatalogEntry> {
+        self.entries.values()
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&CatalogEntry> {
+        self.entries.get(&canonical(path))
+    }
+
+    /// Write the catalog if anything changed since it was loaded
+    pub fn save(&mut self) -> Result<()> {
+        let Some(ref path) = self.path else {
+            return Ok(());
+        };
+        if !self.dirty {
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create catalog directory: {}", parent.display()))?;
+        }
+
+        // Write next to the catalog and rename, so an interrupted save never
+        // leaves a truncated file behind
+        let temp = path.with_extension("jsonl.tmp");
+        {
+            let file = std::fs::File::create(&temp)
+                .with_context(|| format!("Failed to write catalog: {}", temp.display()))?;
+            let mut writer = BufWriter::new(file);
+            for entry in self.entries.values() {
+                serde_json::to_writer(&mut writer, entry).context("Failed to serialize catalog entry")?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+        }
+        std::fs::rename(&temp, path)
+            .with_context(|| format!("Failed to write catalog: {}", path.display()))?;
+
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Metadata of the file at `path`, read from disk only when the file is
+    /// new to the catalog or has changed since it was catalogued
+    pub fn metadata(&mut self, path: &Path) -> Result<GprMetadata> {
+        let entry = self.refresh(path)?;
+        match (&entry.metadata, &entry.error) {
+            (Some(metadata), _) => Ok(metadata.clone()),
+            (None, Some(error)) => Err(anyhow!("{}", error)),
+            (None, None) => Err(anyhow!("No metadata for {}", path.display())),
+        }
+    }
+
+    /// Fill in the metadata of every file that does not have it yet. Files
+    /// that cannot be read are left without metadata.
+    pub fn load_all(&mut self, files: &mut [GprFile]) {
+        for file in files.iter_mut().filter(|f| f.metadata.is_none()) {
+            match self.metadata(&file.path) {
+                Ok(metadata) => file.metadata = Some(metadata),
+                Err(e) => log::warn!("Failed to read metadata for {}: {}", file.filename, e),
+            }
+        }
+    }
+
+    /// Bring the entries for `files` up to date, optionally caching
+    /// thumbnails, and drop entries under `roots` whose files are gone
+    pub fn update(&mut self, roots: &[PathBuf], files: &[PathBuf], thumbnails: bool) -> UpdateSummary {
+        let mut summary = UpdateSummary::default();
+        let mut seen = BTreeSet::new();
+
+        for file in files {
+            let key = canonical(file);
+            let before = self.entries.get(&key).map(|entry| entry.stamp);
+            let result = self.refresh(file);
+            seen.insert(key.clone());
+
+            match result {
+                Ok(entry) if entry.metadata.is_none() => summary.failed += 1,
+                Ok(entry) if before == Some(entry.stamp) => summary.unchanged += 1,
+                Ok(_) if before.is_some() => summary.updated += 1,
+                Ok(_) => summary.added += 1,
+                Err(e) => {
+                    log::warn!("{:#}", e);
+                    summary.failed += 1;
+                    continue;
+                }
+            }
+
+            if thumbnails {
+                match self.thumbnail(file) {
+                    Ok(true) => summary.thumbnails += 1,
+                    Ok(false) => {}
+                    Err(e) => log::warn!("No thumbnail for {}: {:#}", file.display(), e),
+                }
+            }
+        }
+
+        let roots: Vec<PathBuf> = roots.iter().map(|root| canonical(root)).collect();
+        let stale: Vec<PathBuf> = self
+            .entries
+            .keys()
+            .filter(|path| !seen.contains(*path))
+            .filter(|path| roots.iter().any(|root| path.starts_with(root)))
+            .filter(|path| !path.exists())
+            .cloned()
+            .collect();
+        for path in stale {
+            self.remove(&path);
+            summary.removed += 1;
+        }
+
+        summary
+    }
+
+    /// Drop every entry whose file no longer exists
+    pub fn prune(&mut self) -> usize {
+        let missing: Vec<PathBuf> = self
+            .entries
+            .keys()
+            .filter(|path| !path.exists())
+            .cloned()
+            .collect();
+        for path in &missing {
+            self.remove(path);
+        }
+        missing.len()
+    }
+
+    /// Cache a preview of `path` next to the catalog. Returns whether a new
+    /// thumbnail was written.
+    pub fn thumbnail(&mut self, path: &Path) -> Result<bool> {
+        let Some(directory) = self.thumbnail_directory() else {
+            bail!("An in-memory catalog cannot store thumbnails");
+        };
+        let entry = self.refresh(path)?;
+        if entry.thumbnail.as_ref().is_some_and(|thumb| thumb.exists()) {
+            return Ok(false);
+        }
+
+        let key = entry.path.clone();
+        let gpr_file = GprFile {
+            metadata: entry.metadata.clone(),
+            ..GprFile::new(key.clone())
+        };
+        let preview = GprConverter::preview(&gpr_file, OrientationMode::default(), self.isolated)?;
+        let preview = image::DynamicImage::ImageRgb8(preview).thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+
+        std::fs::create_dir_all(&directory)
+            .with_context(|| format!("Failed to create thumbnail directory: {}", directory.display()))?;
+        let thumbnail = directory.join(format!("{:016x}.jpg", path_hash(&key)));
+        preview
+            .to_rgb8()
+            .save(&thumbnail)
+            .with_context(|| format!("Failed to save thumbnail: {}", thumbnail.display()))?;
+
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.thumbnail = Some(thumbnail);
+        }
+        self.dirty = true;
+        Ok(true)
+    }
+
+    /// Remember that `source` was converted to `output`. The format is read
+    /// from the output name, as a camera preset may have chosen it.
+    pub fn record_conversion(&mut self, source: &Path, output: &Path, config: &ConversionConfig) {
+        let entry = match self.refresh(source) {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::warn!("Conversion of {} not recorded: {:#}", source.display(), e);
+                return;
+            }
+        };
+        entry.conversions.push(ConversionRecord {
+            output: canonical(output),
+            format: OutputFormat::from_path(output).unwrap_or(config.output_format),
+            converted_at: now().to_string(),
+        });
+        self.dirty = true;
+    }
+
+    /// Entry for `path`, re-reading its metadata when the file changed.
+    /// Conversion history survives changes; thumbnails do not.
+    fn refresh(&mut self, path: &Path) -> Result<&mut CatalogEntry> {
+        let key = canonical(path);
+        let stamp = FileStamp::read(&key).with_context(|| format!("Failed to stat {}", path.display()))?;
+
+        let current = self.entries.get(&key).is_some_and(|entry| entry.is_current(&stamp));
+        if !current {
+            log::debug!("Cataloguing {}", key.display());
+            let mut entry = CatalogEntry::new(key.clone(), stamp);
+            let metadata = if self.isolated {
+                GprConverter::read_metadata_isolated(&key)
+            } else {
+                gpr::read_metadata(&key)
+            };
+            match metadata {
+                Ok(metadata) => entry.metadata = Some(metadata),
+                Err(e) => entry.error = Some(format!("{:#}", e)),
+            }
+            if let Some(old) = self.entries.remove(&key) {
+                remove_thumbnail(&old);
+                entry.conversions = old.conversions;
+            }
+            self.entries.insert(key.clone(), entry);
+            self.dirty = true;
+        }
+
+        Ok(self.entries.get_mut(&key).expect("entry was just inserted"))
+    }
+
+    fn remove(&mut self, key: &Path) {
+        if let Some(entry) = self.entries.remove(key) {
+            remove_thumbnail(&entry);
+            self.dirty = true;
+        }
+    }
+
+    fn thumbnail_directory(&self) -> Option<PathBuf> {
+        let path = self.path.as_ref()?;
+        Some(path.parent().unwrap_or(Path::new(".")).join("thumbnails"))
+    }
+}
+
+fn remove_thumbnail(entry: &CatalogEntry) {
+    if let Some(ref thumbnail) = entry.thumbnail {
+        let _ = std::fs::remove_file(thumbnail);
+    }
+}
+
+/// Absolute, symlink-free form of `path`, or `path` itself if it cannot be
+/// resolved
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn path_hash(path: &Path) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn now() -> ExifDateTime {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    ExifDateTime::from_timestamp(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::entry::CATALOG_VERSION;
+
+    #[test]
+    fn test_round_trip_and_invalidation() {
+        let dir = std::env::temp_dir().join(format!("gprprotool-catalog-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let gpr = dir.join("GOPR0001.GPR");
+        std::fs::write(&gpr, b"not a gpr file").unwrap();
+        let catalog_path = dir.join("catalog.jsonl");
+
+        let mut catalog = Catalog::open(&catalog_path).unwrap();
+        let summary = catalog.update(std::slice::from_ref(&dir), std::slice::from_ref(&gpr), false);
+        assert_eq!(summary.failed, 1);
+        catalog.record_conversion(&gpr, &dir.join("GOPR0001.jpg"), &ConversionConfig::default());
+        catalog.save().unwrap();
+
+        let mut catalog = Catalog::open(&catalog_path).unwrap();
+        assert_eq!(catalog.len(), 1);
+        assert!(catalog.get(&gpr).unwrap().error.is_some());
+
+        // A changed file is read again but keeps its history
+        std::fs::write(&gpr, b"still not a gpr file").unwrap();
+        catalog.update(std::slice::from_ref(&dir), std::slice::from_ref(&gpr), false);
+        let entry = catalog.get(&gpr).unwrap();
+        assert_eq!(entry.stamp.size, 20);
+        assert_eq!(entry.conversions.len(), 1);
+
+        std::fs::remove_file(&gpr).unwrap();
+        let summary = catalog.update(std::slice::from_ref(&dir), &[], false);
+        assert_eq!(summary.removed, 1);
+        assert_eq!(catalog.len(), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_older_version_is_reread() {
+        let dir = std::env::temp_dir().join(format!("gprprotool-catalog-version-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let gpr = dir.join("GOPR0002.GPR");
+        std::fs::write(&gpr, b"not a gpr file").unwrap();
+        let catalog_path = dir.join("catalog.jsonl");
+
+        // Cached metadata for the unchanged file, from this and the previous version
+        let cached = |version| {
+            let entry = CatalogEntry {
+                version,
+                metadata: Some(GprMetadata { camera_model: "HERO5 Black".to_string(), ..GprMetadata::default() }),
+                ..CatalogEntry::new(canonical(&gpr), FileStamp::read(&gpr).unwrap())
+            };
+            std::fs::write(&catalog_path, serde_json::to_string(&entry).unwrap()).unwrap();
+            Catalog::open(&catalog_path).unwrap()
+        };
+
+        assert_eq!(cached(CATALOG_VERSION).metadata(&gpr).unwrap().camera_model, "HERO5 Black");
+        let mut catalog = cached(CATALOG_VERSION - 1);
+        assert!(catalog.metadata(&gpr).is_err());
+        assert_eq!(catalog.get(&gpr).unwrap().version, CATALOG_VERSION);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,116 @@
+use super::convert::{collect_inputs, parse_filter, ReportPrivacyArgs};
+use crate::catalog::{Catalog, CatalogEntry};
+use crate::models::Filter;
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct CatalogArgs {
+    /// Catalog file (defaults to $GPRPROTOOL_CATALOG or the user cache directory)
+    #[arg(long, value_name = "FILE", global = true)]
+    pub catalog: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub action: CatalogAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CatalogAction {
+    /// Add new and changed files, and forget deleted ones
+    Update {
+        /// GPR files or directories (searched recursively)
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Also cache a preview of every file
+        #[arg(long)]
+        thumbnails: bool,
+    },
+    /// List catalogued files with their camera, capture time and conversions
+    List {
+        /// Print the entries as JSON lines instead
+        #[arg(long)]
+        json: bool,
+
+        /// Only list files matching this query, e.g. "iso>800 within:47.6,-122.3,5km"
+        #[arg(long, value_name = "QUERY", value_parser = parse_filter)]
+        filter: Option<Filter>,
+
+        #[command(flatten)]
+        privacy: ReportPrivacyArgs,
+    },
+    /// Forget every file that no longer exists
+    Prune,
+}
+
+pub fn run(args: CatalogArgs) -> Result<()> {
+    let path = args
+        .catalog
+        .or_else(Catalog::default_path)
+        .ok_or_else(|| anyhow!("No catalog location; pass --catalog"))?;
+    let mut catalog = Catalog::open(&path)?;
+
+    match args.action {
+        CatalogAction::Update { inputs, thumbnails } => {
+            let files: Vec<PathBuf> = collect_inputs(&inputs)?.into_iter().map(|f| f.path).collect();
+            let roots: Vec<PathBuf> = inputs.into_iter().filter(|input| input.is_dir()).collect();
+            let summary = catalog.update(&roots, &files, thumbnails);
+            catalog.save()?;
+
+            println!(
+                "{} added, {} updated, {} unchanged, {} removed, {} unreadable",
+                summary.added, summary.updated, summary.unchanged, summary.removed, summary.failed
+            );
+            if thumbnails {
+                println!("{} thumbnails cached", summary.thumbnails);
+            }
+            println!("{} files in {}", catalog.len(), path.display());
+        }
+        CatalogAction::List { json, filter, privacy } => {
+            let policy = privacy.policy();
+            let matching = catalog
+                .entries()
+                .filter(|entry| filter.as_ref().is_none_or(|f| f.matches(&entry.file())));
+            for entry in matching {
+                if json {
+                    let mut entry = entry.clone();
+                    if let Some(ref mut metadata) = entry.metadata {
+                        policy.apply(metadata);
+                    }
+                    println!("{}", serde_json::to_string(&entry).context("Failed to serialize catalog entry")?);
+                } else {
+                    println!("{}", list_line(entry));
+                }
+            }
+        }
+        CatalogAction::Prune => {
+            let removed = catalog.prune();
+            catalog.save()?;
+            println!("Removed {} missing files, {} left", removed, catalog.len());
+        }
+    }
+
+    Ok(())
+}
+
+fn list_line(entry: &CatalogEntry) -> String {
+    let description = match entry.metadata {
+        Some(ref metadata) => format!(
+            "{}  {}",
+            metadata.camera_model,
+            metadata.date_taken.as_deref().unwrap_or("unknown date")
+        ),
+        None => "unreadable".to_string(),
+    };
+    let conversions = match entry.last_conversion() {
+        Some(last) => format!(
+            "  {} conversion(s), last {} -> {}",
+            entry.conversions.len(),
+            last.converted_at,
+            last.output.display()
+        ),
+        None => String::new(),
+    };
+    format!("{}  {}{}", entry.path.display(), description, conversions)
+}
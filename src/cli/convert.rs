@@ -0,0 +1,708 @@
+use super::geotag::GeotagArgs;
+use crate::catalog::Catalog;
+use crate::gpr::{self, GprConverter};
+use crate::models::{
+    ColorSpace, ConversionConfig, CropAspect, CropRect, DemosaicMethod, DenoiseMethod, DevelopSettings, EdgeMode, Filter,
+    Geometry, GprFile, GpsPolicy, LensCorrection, LensSettings, MetadataEdits, OrientationMode, OutputFormat,
+    PrivacyPolicy, ResizeFilter, ResizeMode, ResizeSettings, Rotation, Sharpening, TimeShift, ToneCurve,
+    WhiteBalance,
+};
+use crate::utils::datetime::{parse_offset, parse_zone_offset};
+use crate::utils::file_utils::{find_gpr_files, is_gpr_file};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct ConvertArgs {
+    /// GPR files or directories (searched recursively)
+    #[arg(required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Output format: jpeg, png or tiff. Without --format and --quality,
+    /// each file uses its camera's preset (JPEG at 95 for most cameras)
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+
+    /// JPEG quality (1-100), 95 by default
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub quality: Option<u8>,
+
+    /// Directory for converted files (defaults to next to each source)
+    #[arg(long)]
+    pub output_dir: Option<PathBuf>,
+
+    /// Do not write EXIF metadata into outputs
+    #[arg(long)]
+    pub strip_metadata: bool,
+
+    /// Output name template using {stem}, {date}, {time} and {camera}
+    #[arg(long)]
+    pub name_template: Option<String>,
+
+    /// Fixed correction added to the camera clock, e.g. +02:00 or -3h
+    #[arg(long, allow_hyphen_values = true, conflicts_with = "time_reference")]
+    pub time_shift: Option<String>,
+
+    /// Photo whose real capture time is known, used to derive the shift
+    #[arg(long, requires = "true_time")]
+    pub time_reference: Option<PathBuf>,
+
+    /// Real capture time of --time-reference (YYYY:MM:DD HH:MM:SS)
+    #[arg(long, requires = "time_reference")]
+    pub true_time: Option<String>,
+
+    /// Zone offset to record as OffsetTimeOriginal, e.g. +02:00
+    #[arg(long, allow_hyphen_values = true)]
+    pub utc_offset: Option<String>,
+
+    /// Write an XMP sidecar next to each output
+    #[arg(long)]
+    pub xmp: bool,
+
+    /// Only convert files matching this query, e.g. "iso>800 date:2024-06"
+    #[arg(long, value_name = "QUERY", value_parser = parse_filter)]
+    pub filter: Option<Filter>,
+
+    /// Orientation handling: ignore (keep pixels as decoded), bake (rotate
+    /// pixels by the source tag) or tag (copy the source tag)
+    #[arg(long, default_value = "ignore", value_parser = parse_orientation_mode)]
+    pub orientation: OrientationMode,
+
+    /// Decode each file in a child process, so a file that crashes the GPR
+    /// SDK is reported as failed instead of aborting the run
+    #[arg(long)]
+    pub isolate_decoder: bool,
+
+    #[command(flatten)]
+    pub develop: DevelopArgs,
+
+    /// Remove lens distortion with coefficients K1[,K2] measured from a
+    /// shot of straight lines
+    #[arg(long, value_name = "LENS", allow_hyphen_values = true, value_parser = parse_lens_correction)]
+    pub lens_correction: Option<LensCorrection>,
+
+    /// After lens correction: crop to remove empty edges, or fill them with black
+    #[arg(long, value_name = "MODE", default_value = "crop", value_parser = parse_edge_mode)]
+    pub lens_edges: EdgeMode,
+
+    /// Stitch Fusion front and back files (GPFR/GPBK with the same number)
+    /// into one equirectangular 360 panorama, GP360_<number>
+    #[arg(long)]
+    pub stitch_fusion: bool,
+
+    /// Output size: a long edge such as 2048, or fit:, fill: or exact:
+    /// WIDTHxHEIGHT (fit and long edge never enlarge). Adds a suffix such
+    /// as _2048 to output names.
+    #[arg(long, value_name = "SIZE", value_parser = parse_resize)]
+    pub resize: Option<ResizeMode>,
+
+    /// Resampling filter for --resize: lanczos (sharper) or mitchell (softer)
+    #[arg(long, value_name = "FILTER", default_value = "lanczos", value_parser = parse_resize_filter)]
+    pub resize_filter: ResizeFilter,
+
+    /// Unsharp mask amount in percent (0-500), applied after resizing
+    #[arg(long, value_name = "PERCENT", value_parser = parse_sharpen_amount)]
+    pub sharpen: Option<f32>,
+
+    /// Unsharp mask radius in output pixels (0.2-5)
+    #[arg(long, value_name = "PX", default_value_t = 1.0, value_parser = parse_sharpen_radius)]
+    pub sharpen_radius: f32,
+
+    /// Smallest brightness difference sharpened, in 0-255 levels
+    #[arg(long, value_name = "LEVELS", default_value_t = 0)]
+    pub sharpen_threshold: u8,
+
+    #[command(flatten)]
+    pub geometry: GeometryArgs,
+
+    #[command(flatten)]
+    pub edits: MetadataEditArgs,
+
+    #[command(flatten)]
+    pub privacy: PrivacyArgs,
+
+    #[command(flatten)]
+    pub geotag: GeotagArgs,
+}
+
+/// Raw development flags; any of them switches from the GPR SDK's RGB
+/// conversion to developing the sensor data
+#[derive(Debug, Args)]
+pub struct DevelopArgs {
+    /// Demosaic algorithm: sdk, bilinear (fast), ppg or ahd (finest detail).
+    /// Other development flags with sdk use ahd.
+    #[arg(long, value_name = "METHOD", value_parser = parse_demosaic)]
+    pub demosaic: Option<DemosaicMethod>,
+
+    /// Noise reduction method: bilateral (fast) or wavelet (keeps more texture)
+    #[arg(long, value_name = "METHOD", value_parser = parse_denoise_method)]
+    pub denoise: Option<DenoiseMethod>,
+
+    /// Luma (grain) noise reduction, 0-100, scaled to the file's ISO
+    #[arg(long, value_name = "AMOUNT", value_parser = parse_strength)]
+    pub denoise_luma: Option<f32>,
+
+    /// Colour noise reduction, 0-100, scaled to the file's ISO
+    #[arg(long, value_name = "AMOUNT", value_parser = parse_strength)]
+    pub denoise_chroma: Option<f32>,
+
+    /// White balance: asshot, daylight, cloudy, shade, tungsten, fluorescent,
+    /// flash, or KELVIN[,TINT] such as 5600,+10
+    #[arg(long, value_name = "WB", allow_hyphen_values = true, value_parser = parse_white_balance)]
+    pub white_balance: Option<WhiteBalance>,
+
+    /// Exposure compensation in stops, e.g. +0.7 or -1
+    #[arg(long, value_name = "EV", allow_hyphen_values = true, value_parser = parse_exposure)]
+    pub exposure: Option<f32>,
+
+    /// Linear level that becomes black, as a fraction of white (0-0.9)
+    #[arg(long, value_name = "LEVEL")]
+    pub black_point: Option<f32>,
+
+    /// Linear level that becomes white, as a fraction of white (0.1-1)
+    #[arg(long, value_name = "LEVEL")]
+    pub white_point: Option<f32>,
+
+    /// Tone curve: parametric (clips at white) or filmic (rolls off highlights)
+    #[arg(long, value_name = "CURVE", value_parser = parse_tone_curve)]
+    pub tone_curve: Option<ToneCurve>,
+
+    /// Contrast, -100 to 100
+    #[arg(long, value_name = "AMOUNT", allow_hyphen_values = true, value_parser = parse_slider)]
+    pub contrast: Option<f32>,
+
+    /// Highlights, -100 to 100; negative values recover detail above white
+    #[arg(long, value_name = "AMOUNT", allow_hyphen_values = true, value_parser = parse_slider)]
+    pub highlights: Option<f32>,
+
+    /// Shadows, -100 to 100; positive values lift dark areas
+    #[arg(long, value_name = "AMOUNT", allow_hyphen_values = true, value_parser = parse_slider)]
+    pub shadows: Option<f32>,
+
+    /// Rebuild clipped channels instead of letting blown highlights turn magenta
+    #[arg(long)]
+    pub reconstruct_highlights: bool,
+
+    /// Output colour space, embedded as an ICC profile: srgb, p3, adobergb,
+    /// rec2020 or prophoto
+    #[arg(long, value_name = "SPACE", value_parser = parse_color_space)]
+    pub color_space: Option<ColorSpace>,
+}
+
+impl DevelopArgs {
+    pub fn settings(&self) -> DevelopSettings {
+        let mut settings = DevelopSettings::default();
+        if let Some(demosaic) = self.demosaic {
+            settings.demosaic = demosaic;
+        }
+        let denoise = &mut settings.denoise;
+        if let Some(method) = self.denoise {
+            denoise.method = method;
+        }
+        denoise.luma = self.denoise_luma.unwrap_or(denoise.luma);
+        denoise.chroma = self.denoise_chroma.unwrap_or(denoise.chroma);
+        if let Some(white_balance) = self.white_balance {
+            settings.white_balance = white_balance;
+        }
+        if let Some(exposure) = self.exposure {
+            settings.exposure_ev = exposure;
+        }
+        settings.set_levels(
+            self.black_point.unwrap_or(settings.black_point),
+            self.white_point.unwrap_or(settings.white_point),
+        );
+
+        let tone = &mut settings.tone;
+        if let Some(curve) = self.tone_curve {
+            tone.curve = curve;
+        }
+        tone.contrast = self.contrast.unwrap_or(tone.contrast);
+        tone.highlights = self.highlights.unwrap_or(tone.highlights);
+        tone.shadows = self.shadows.unwrap_or(tone.shadows);
+        tone.reconstruct_highlights |= self.reconstruct_highlights;
+
+        if let Some(color_space) = self.color_space {
+            settings.color_space = color_space;
+        }
+        settings
+    }
+}
+
+/// Rotation, flips, straightening and crop, applied to every file after
+/// the camera orientation
+#[derive(Debug, Args)]
+pub struct GeometryArgs {
+    /// Crop to an aspect ratio W:H (1:1, 4:5, 16:9, 3:2...), a rectangle
+    /// X,Y,W,H as fractions of the image, or both as W:H@X,Y,W,H
+    #[arg(long, value_name = "CROP", value_parser = parse_crop)]
+    pub crop: Option<(CropAspect, CropRect)>,
+
+    /// Rotate clockwise by 90, 180 or 270 degrees
+    #[arg(long, value_name = "DEGREES", allow_hyphen_values = true, value_parser = parse_rotation)]
+    pub rotate: Option<Rotation>,
+
+    /// Mirror the image: h (left to right), v (top to bottom) or hv
+    #[arg(long, value_name = "AXIS", value_parser = parse_flip)]
+    pub flip: Option<(bool, bool)>,
+
+    /// Level by turning clockwise this many degrees (-45 to 45); the empty
+    /// corners are cropped away
+    #[arg(long, value_name = "DEGREES", allow_hyphen_values = true, value_parser = parse_straighten)]
+    pub straighten: Option<f32>,
+
+    /// Level the horizon by the camera's roll at capture, read from the
+    /// GPMF accelerometer, and crop away the empty corners
+    #[arg(long, conflicts_with = "straighten")]
+    pub auto_level: bool,
+}
+
+impl GeometryArgs {
+    pub fn geometry(&self) -> Geometry {
+        let (aspect, crop) = self.crop.unwrap_or_default();
+        let (flip_horizontal, flip_vertical) = self.flip.unwrap_or_default();
+        Geometry {
+            rotation: self.rotate.unwrap_or_default(),
+            flip_horizontal,
+            flip_vertical,
+            straighten: self.straighten.unwrap_or(0.0),
+            auto_level: self.auto_level,
+            aspect,
+            crop,
+        }
+    }
+}
+
+/// Descriptive metadata added to outputs; the source files are not changed
+#[derive(Debug, Args)]
+pub struct MetadataEditArgs {
+    /// Creator / artist name
+    #[arg(long, visible_alias = "artist")]
+    pub creator: Option<String>,
+
+    /// Copyright notice
+    #[arg(long)]
+    pub copyright: Option<String>,
+
+    /// Caption written as the image description
+    #[arg(long)]
+    pub caption: Option<String>,
+
+    /// Replace the recorded position, as LAT,LON in decimal degrees
+    #[arg(long, value_name = "LAT,LON", allow_hyphen_values = true, value_parser = parse_position)]
+    pub set_gps: Option<(f64, f64)>,
+
+    /// Keywords, comma separated or repeated
+    #[arg(long = "keyword", value_delimiter = ',')]
+    pub keywords: Vec<String>,
+
+    /// Star rating (0-5)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=5))]
+    pub rating: Option<u8>,
+}
+
+impl MetadataEditArgs {
+    pub fn edits(&self) -> MetadataEdits {
+        MetadataEdits {
+            creator: self.creator.clone(),
+            copyright: self.copyright.clone(),
+            caption: self.caption.clone(),
+            keywords: MetadataEdits::parse_keywords(&self.keywords.join(",")),
+            rating: self.rating,
+            gps_position: self.set_gps,
+        }
+    }
+}
+
+/// Location and identity scrubbing flags shared by commands that export metadata
+#[derive(Debug, Args)]
+pub struct PrivacyArgs {
+    /// GPS handling in exported metadata: keep, drop or round:N (N decimals)
+    #[arg(long = "gps", default_value = "keep", value_parser = parse_gps_policy)]
+    pub gps: GpsPolicy,
+
+    /// Remove camera serial numbers and owner names from exported metadata
+    #[arg(long)]
+    pub drop_identifiers: bool,
+}
+
+impl PrivacyArgs {
+    pub fn policy(&self) -> PrivacyPolicy {
+        PrivacyPolicy {
+            gps: self.gps,
+            drop_identifiers: self.drop_identifiers,
+        }
+    }
+}
+
+/// Privacy flags for commands that print metadata rather than write files,
+/// which redact location and identifiers unless asked not to
+#[derive(Debug, Args)]
+pub struct ReportPrivacyArgs {
+    /// GPS handling in the printed metadata: keep, drop or round:N (N decimals)
+    #[arg(long = "gps", default_value = "drop", value_parser = parse_gps_policy)]
+    pub gps: GpsPolicy,
+
+    /// Show camera serial numbers and owner names
+    #[arg(long)]
+    pub show_identifiers: bool,
+}
+
+impl ReportPrivacyArgs {
+    pub fn policy(&self) -> PrivacyPolicy {
+        PrivacyPolicy {
+            gps: self.gps,
+            drop_identifiers: !self.show_identifiers,
+        }
+    }
+}
+
+fn parse_position(value: &str) -> Result<(f64, f64), String> {
+    MetadataEdits::parse_position(value)
+        .ok_or_else(|| format!("expected LAT,LON in decimal degrees, got '{}'", value))
+}
+
+pub fn parse_filter(value: &str) -> Result<Filter, String> {
+    Filter::parse(value).map_err(|e| e.to_string())
+}
+
+fn parse_white_balance(value: &str) -> Result<WhiteBalance, String> {
+    WhiteBalance::parse(value).ok_or_else(|| {
+        format!(
+            "expected asshot, a preset name or KELVIN[,TINT] ({}-{} K, tint -100..100), got '{}'",
+            WhiteBalance::MIN_KELVIN,
+            WhiteBalance::MAX_KELVIN,
+            value
+        )
+    })
+}
+
+fn parse_exposure(value: &str) -> Result<f32, String> {
+    value
+        .trim()
+        .trim_end_matches("EV")
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|ev| ev.abs() <= DevelopSettings::MAX_EXPOSURE_EV)
+        .ok_or_else(|| {
+            format!(
+                "expected stops between -{0} and +{0}, got '{1}'",
+                DevelopSettings::MAX_EXPOSURE_EV,
+                value
+            )
+        })
+}
+
+fn parse_color_space(value: &str) -> Result<ColorSpace, String> {
+    ColorSpace::parse(value).ok_or_else(|| {
+        format!("expected srgb, p3, adobergb, rec2020 or prophoto, got '{}'", value)
+    })
+}
+
+fn parse_demosaic(value: &str) -> Result<DemosaicMethod, String> {
+    DemosaicMethod::parse(value)
+        .ok_or_else(|| format!("expected sdk, bilinear, ppg or ahd, got '{}'", value))
+}
+
+fn parse_denoise_method(value: &str) -> Result<DenoiseMethod, String> {
+    DenoiseMethod::parse(value).ok_or_else(|| format!("expected bilateral or wavelet, got '{}'", value))
+}
+
+fn parse_tone_curve(value: &str) -> Result<ToneCurve, String> {
+    ToneCurve::parse(value).ok_or_else(|| format!("expected parametric or filmic, got '{}'", value))
+}
+
+/// Slider from -100 to 100, stored as -1 to 1
+fn parse_slider(value: &str) -> Result<f32, String> {
+    value
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|amount| amount.abs() <= 100.0)
+        .map(|amount| amount / 100.0)
+        .ok_or_else(|| format!("expected a number from -100 to 100, got '{}'", value))
+}
+
+fn parse_strength(value: &str) -> Result<f32, String> {
+    value
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|amount| (0.0..=100.0).contains(amount))
+        .map(|amount| amount / 100.0)
+        .ok_or_else(|| format!("expected a number from 0 to 100, got '{}'", value))
+}
+
+fn parse_resize(value: &str) -> Result<ResizeMode, String> {
+    ResizeMode::parse(value).ok_or_else(|| {
+        format!("expected off, a long edge such as 2048, or [fit|fill|exact:]WIDTHxHEIGHT, got '{}'", value)
+    })
+}
+
+fn parse_resize_filter(value: &str) -> Result<ResizeFilter, String> {
+    ResizeFilter::parse(value).ok_or_else(|| format!("expected lanczos or mitchell, got '{}'", value))
+}
+
+fn parse_sharpen_amount(value: &str) -> Result<f32, String> {
+    value
+        .trim()
+        .trim_end_matches('%')
+        .parse::<f32>()
+        .ok()
+        .filter(|amount| (0.0..=Sharpening::MAX_AMOUNT * 100.0).contains(amount))
+        .map(|amount| amount / 100.0)
+        .ok_or_else(|| format!("expected a percentage from 0 to 500, got '{}'", value))
+}
+
+fn parse_sharpen_radius(value: &str) -> Result<f32, String> {
+    value
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|radius| (Sharpening::MIN_RADIUS..=Sharpening::MAX_RADIUS).contains(radius))
+        .ok_or_else(|| format!("expected a radius from 0.2 to 5, got '{}'", value))
+}
+
+fn parse_crop(value: &str) -> Result<(CropAspect, CropRect), String> {
+    let (aspect, rect) = match value.split_once('@') {
+        Some((aspect, rect)) => (CropAspect::parse(aspect), CropRect::parse(rect)),
+        None if value.contains(':') => (CropAspect::parse(value), Some(CropRect::FULL)),
+        None => (Some(CropAspect::Free), CropRect::parse(value)),
+    };
+    aspect.zip(rect).ok_or_else(|| {
+        format!("expected W:H, X,Y,W,H fractions inside the image, or W:H@X,Y,W,H, got '{}'", value)
+    })
+}
+
+fn parse_rotation(value: &str) -> Result<Rotation, String> {
+    Rotation::parse(value).ok_or_else(|| format!("expected 0, 90, 180 or 270, got '{}'", value))
+}
+
+fn parse_flip(value: &str) -> Result<(bool, bool), String> {
+    match value.to_ascii_lowercase().as_str() {
+        "h" => Ok((true, false)),
+        "v" => Ok((false, true)),
+        "hv" | "vh" => Ok((true, true)),
+        _ => Err(format!("expected h, v or hv, got '{}'", value)),
+    }
+}
+
+fn parse_straighten(value: &str) -> Result<f32, String> {
+    value
+        .trim()
+        .trim_end_matches('°')
+        .parse::<f32>()
+        .ok()
+        .filter(|degrees| degrees.abs() <= Geometry::MAX_STRAIGHTEN)
+        .ok_or_else(|| format!("expected degrees from -45 to 45, got '{}'", value))
+}
+
+fn parse_lens_correction(value: &str) -> Result<LensCorrection, String> {
+    LensCorrection::parse(value).ok_or_else(|| {
+        format!("expected off or K1[,K2] within +-2, got '{}'", value)
+    })
+}
+
+fn parse_edge_mode(value: &str) -> Result<EdgeMode, String> {
+    EdgeMode::parse(value).ok_or_else(|| format!("expected crop or fill, got '{}'", value))
+}
+
+fn parse_orientation_mode(value: &str) -> Result<OrientationMode, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "ignore" => Ok(OrientationMode::Ignore),
+        "bake" => Ok(OrientationMode::Bake),
+        "tag" => Ok(OrientationMode::Tag),
+        _ => Err(format!("expected ignore, bake or tag, got '{}'", value)),
+    }
+}
+
+fn parse_gps_policy(value: &str) -> Result<GpsPolicy, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "keep" => Ok(GpsPolicy::Keep),
+        "drop" => Ok(GpsPolicy::Drop),
+        other => other
+            .strip_prefix("round:")
+            .and_then(|n| n.parse::<u8>().ok())
+            .filter(|n| *n <= 8)
+            .map(GpsPolicy::Round)
+            .ok_or_else(|| format!("expected keep, drop or round:N (N <= 8), got '{}'", value)),
+    }
+}
+
+impl ConvertArgs {
+    /// Build the conversion configuration described by the flags
+    pub fn to_config(&self) -> Result<ConversionConfig> {
+        let defaults = ConversionConfig::default();
+        let mut config = ConversionConfig {
+            output_format: self.format.unwrap_or(defaults.output_format),
+            quality: self.quality.unwrap_or(defaults.quality),
+            output_directory: self
+                .output_dir
+                .as_ref()
+                .map(|dir| dir.display().to_string()),
+            preserve_metadata: !self.strip_metadata,
+            filename_template: self.name_template.clone(),
+            privacy: self.privacy.policy(),
+            write_xmp_sidecar: self.xmp,
+            orientation_mode: self.orientation,
+            metadata_edits: self.edits.edits(),
+            isolate_decoder: self.isolate_decoder,
+            develop: self.develop.settings(),
+            lens: LensSettings {
+                correction: self.lens_correction.unwrap_or_default(),
+                edges: self.lens_edges,
+            },
+            stitch_fusion: self.stitch_fusion,
+            resize: ResizeSettings {
+                mode: self.resize.unwrap_or_default(),
+                filter: self.resize_filter,
+            },
+            sharpen: Sharpening {
+                amount: self.sharpen.unwrap_or(0.0),
+                radius: self.sharpen_radius,
+                threshold: self.sharpen_threshold,
+            },
+            geometry: self.geometry.geometry(),
+            camera_presets: self.format.is_none() && self.quality.is_none(),
+            ..defaults
+        };
+
+        config.time_shift = self.time_shift()?;
+        Ok(config)
+    }
+
+    fn time_shift(&self) -> Result<TimeShift> {
+        let mut shift = if let Some(ref value) = self.time_shift {
+            TimeShift {
+                offset_seconds: parse_offset(value)
+                    .ok_or_else(|| anyhow!("Invalid --time-shift: {}", value))?,
+                utc_offset_seconds: None,
+            }
+        } else if let (Some(ref reference), Some(ref true_time)) =
+            (&self.time_reference, &self.true_time)
+        {
+            let metadata = gpr::read_metadata(reference)?;
+            let camera_time = metadata.date_taken.ok_or_else(|| {
+                anyhow!("{} has no capture date", reference.display())
+            })?;
+            let shift = TimeShift::from_reference(&camera_time, true_time)?;
+            println!(
+                "Camera clock offset derived from {}: {}",
+                reference.display(),
+                shift.offset_display()
+            );
+            shift
+        } else {
+            TimeShift::default()
+        };
+
+        if let Some(ref value) = self.utc_offset {
+            shift.utc_offset_seconds =
+                Some(parse_zone_offset(value).ok_or_else(|| {
+                    anyhow!("Invalid --utc-offset: {} (expected whole minutes, e.g. +05:30)", value)
+                })?);
+        }
+
+        Ok(shift)
+    }
+}
+
+fn parse_format(value: &str) -> Result<OutputFormat, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "jpeg" | "jpg" => Ok(OutputFormat::Jpeg),
+        "png" => Ok(OutputFormat::Png),
+        "tiff" | "tif" => Ok(OutputFormat::Tiff),
+        other => Err(format!("unsupported format '{}'", other)),
+    }
+}
+
+/// Keep the files matching `filter`; their metadata must already be loaded
+pub fn apply_filter(files: &mut Vec<GprFile>, filter: Option<&Filter>) {
+    let Some(filter) = filter else {
+        return;
+    };
+    let total = files.len();
+    files.retain(|file| filter.matches(file));
+    eprintln!("{} of {} files match \"{}\"", files.len(), total, filter);
+}
+
+/// Expand the input arguments into the list of GPR files to process
+pub fn collect_inputs(inputs: &[PathBuf]) -> Result<Vec<GprFile>> {
+    let mut files = Vec::new();
+
+    for input in inputs {
+        if input.is_dir() {
+            let mut found = find_gpr_files(input);
+            found.sort();
+            files.extend(found.into_iter().map(GprFile::new));
+        } else if input.is_file() && is_gpr_file(input) {
+            files.push(GprFile::new(input.clone()));
+        } else if input.exists() {
+            bail!("Not a GPR file: {}", input.display());
+        } else {
+            return Err(anyhow!("No such file or directory: {}", input.display()));
+        }
+    }
+
+    Ok(files)
+}
+
+pub fn run(args: ConvertArgs) -> Result<()> {
+    let config = args.to_config()?;
+    let mut files = collect_inputs(&args.inputs)?;
+
+    if files.is_empty() {
+        bail!("No .gpr files found");
+    }
+
+    let mut catalog = Catalog::open_default();
+    catalog.set_isolated(args.isolate_decoder);
+    catalog.load_all(&mut files);
+    apply_filter(&mut files, args.filter.as_ref());
+    if files.is_empty() {
+        bail!("No files match the filter");
+    }
+
+    if args.geotag.is_enabled() {
+        args.geotag.apply(&mut files, &config.time_shift, &config.privacy)?;
+    }
+
+    if !config.geometry.is_identity() {
+        eprintln!("Geometry for all outputs: {}", config.geometry.summary());
+    }
+
+    if !config.metadata_edits.is_empty() {
+        eprintln!("Metadata edits for all outputs: {}", config.metadata_edits.summary());
+    }
+
+    if let Some(ref dir) = config.output_directory {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create output directory: {}", dir))?;
+    }
+
+    let outputs = GprConverter::batch_convert(
+        &files,
+        &config,
+        Some(Box::new(|done, total| {
+            if done < total {
+                eprintln!("[{}/{}] Converting...", done + 1, total);
+            }
+        })),
+    )?;
+
+    for (source, output) in &outputs {
+        catalog.record_conversion(source, output, &config);
+        println!("{}", output.display());
+    }
+    if let Err(e) = catalog.save() {
+        log::warn!("{:#}", e);
+    }
+    println!("Converted {} of {} files", outputs.len(), files.len());
+
+    if outputs.len() < files.len() {
+        bail!("{} files failed to convert", files.len() - outputs.len());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use super::convert::ReportPrivacyArgs;
+use crate::gpr::inspect;
+use anyhow::{Context, Result};
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+    /// First GPR file
+    pub left: PathBuf,
+
+    /// Second GPR file
+    pub right: PathBuf,
+
+    /// Only list fields whose values differ
+    #[arg(long)]
+    pub changed: bool,
+
+    /// Print the comparison as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub privacy: ReportPrivacyArgs,
+}
+
+pub fn run(args: DiffArgs) -> Result<()> {
+    let diffs = inspect::diff_files(&args.left, &args.right, &args.privacy.policy(), false)?;
+    let shown: Vec<&inspect::FieldDiff> = diffs
+        .iter()
+        .filter(|d| !args.changed || d.is_different())
+        .collect();
+
+    if args.json {
+        let json = serde_json::to_string_pretty(&shown).context("Failed to serialize diff")?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    let label_width = shown.iter().map(|d| d.label().chars().count()).max().unwrap_or(0);
+    println!("  A: {}", args.left.display());
+    println!("  B: {}", args.right.display());
+    println!();
+
+    for diff in &shown {
+        let marker = if diff.is_different() { '*' } else { ' ' };
+        let left = diff.left.as_deref().unwrap_or("-");
+        let right = diff.right.as_deref().unwrap_or("-");
+        if diff.is_different() {
+            println!("{} {:width$}  A: {}", marker, diff.label(), left, width = label_width);
+            println!("  {:width$}  B: {}", "", right, width = label_width);
+        } else {
+            println!("{} {:width$}  {}", marker, diff.label(), left, width = label_width);
+        }
+    }
+
+    let changed = diffs.iter().filter(|d| d.is_different()).count();
+    println!();
+    println!("{} of {} fields differ", changed, diffs.len());
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use super::convert::{collect_inputs, PrivacyArgs};
+use crate::models::{GprFile, PrivacyPolicy, TimeShift};
+use crate::telemetry::{self, GeotagOptions, GeotagReport, Track};
+use crate::utils::datetime::{parse_offset, ExifDateTime};
+use crate::utils::file_utils::find_video_files;
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use std::path::PathBuf;
+
+/// Track matching flags shared by `convert` and `geotag`
+#[derive(Debug, Args)]
+pub struct GeotagArgs {
+    /// GPX track(s) used to add GPS coordinates to photos without a fix
+    #[arg(long = "gpx", value_name = "FILE")]
+    pub gpx: Vec<PathBuf>,
+
+    /// GoPro MP4 video(s), or directories of them, whose GPS telemetry is used
+    /// like a GPX track
+    #[arg(long = "video", value_name = "FILE|DIR")]
+    pub video: Vec<PathBuf>,
+
+    /// Maximum time in seconds between a photo and the track fixes used for it
+    #[arg(long, default_value_t = 60)]
+    pub gpx_max_gap: i64,
+
+    /// Offset added to capture times to get UTC, e.g. +07:00 for a camera on PDT
+    #[arg(long, allow_hyphen_values = true)]
+    pub gpx_time_offset: Option<String>,
+
+    /// Replace GPS coordinates the camera already recorded
+    #[arg(long)]
+    pub gpx_overwrite: bool,
+
+    /// Write the matched/unmatched report as JSON to this file
+    #[arg(long, value_name = "FILE")]
+    pub geotag_report: Option<PathBuf>,
+}
+
+impl GeotagArgs {
+    pub fn is_enabled(&self) -> bool {
+        !self.gpx.is_empty() || !self.video.is_empty()
+    }
+
+    fn options(&self) -> Result<GeotagOptions> {
+        let time_offset_seconds = match self.gpx_time_offset {
+            Some(ref value) => Some(
+                parse_offset(value)
+                    .ok_or_else(|| anyhow!("Invalid --gpx-time-offset: {}", value))?,
+            ),
+            None => None,
+        };
+
+        Ok(GeotagOptions {
+            max_gap_seconds: self.gpx_max_gap,
+            time_offset_seconds,
+            overwrite: self.gpx_overwrite,
+        })
+    }
+
+    fn load_track(&self) -> Result<Track> {
+        let mut track = Track::default();
+        for path in &self.gpx {
+            track.merge(telemetry::load_gpx(path)?);
+        }
+        for input in &self.video {
+            if input.is_dir() {
+                // Clips without a GPS lock are common; skip them
+                for path in find_video_files(input) {
+                    match telemetry::load_mp4(&path) {
+                        Ok(video) => track.merge(video),
+                        Err(e) => log::warn!("{:#}", e),
+                    }
+                }
+            } else {
+                track.merge(telemetry::load_mp4(input)?);
+            }
+        }
+        Ok(track)
+    }
+
+    /// Match `files` against the GPX and video tracks, print a summary and write the
+    /// JSON report if requested
+    pub fn apply(
+        &self,
+        files: &mut [GprFile],
+        time_shift: &TimeShift,
+        privacy: &PrivacyPolicy,
+    ) -> Result<GeotagReport> {
+        let track = self.load_track()?;
+        if let Some((start, end)) = track.time_range() {
+            println!(
+                "Track: {} points from {} to {} UTC",
+                track.points().len(),
+                ExifDateTime::from_timestamp(start),
+                ExifDateTime::from_timestamp(end)
+            );
+        }
+        let mut report = telemetry::geotag_files(files, &track, time_shift, &self.options()?);
+        report.apply_privacy(privacy);
+
+        for entry in &report.entries {
+            let name = entry
+                .path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            println!("{:<24} {}", name, entry.outcome.describe());
+        }
+        println!(
+            "Geotagged {} of {} files ({} unmatched)",
+            report.matched(),
+            report.entries.len(),
+            report.unmatched()
+        );
+
+        if let Some(ref path) = self.geotag_report {
+            let json = serde_json::to_string_pretty(&report)?;
+            std::fs::write(path, json)
+                .with_context(|| format!("Failed to write report: {}", path.display()))?;
+        }
+
+        Ok(report)
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct GeotagCommandArgs {
+    /// GPR files or directories (searched recursively)
+    #[arg(required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Camera clock correction applied before matching, e.g. +02:00
+    #[arg(long, allow_hyphen_values = true)]
+    pub time_shift: Option<String>,
+
+    #[command(flatten)]
+    pub privacy: PrivacyArgs,
+
+    #[command(flatten)]
+    pub geotag: GeotagArgs,
+}
+
+/// Report how photos would be geotagged without converting anything
+pub fn run(args: GeotagCommandArgs) -> Result<()> {
+    if !args.geotag.is_enabled() {
+        return Err(anyhow!("At least one --gpx track or --video is required"));
+    }
+
+    let time_shift = TimeShift {
+        offset_seconds: match args.time_shift {
+            Some(ref value) => parse_offset(value)
+                .ok_or_else(|| anyhow!("Invalid --time-shift: {}", value))?,
+            None => 0,
+        },
+        utc_offset_seconds: None,
+    };
+
+    let mut files = collect_inputs(&args.inputs)?;
+    args.geotag
+        .apply(&mut files, &time_shift, &args.privacy.policy())?;
+    Ok(())
+}
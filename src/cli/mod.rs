@@ -0,0 +1,80 @@
+pub mod catalog;
+pub mod convert;
+pub mod diff;
+pub mod geotag;
+pub mod stats;
+pub mod verify;
+
+use crate::gpr::converter::{WorkerTask, DECODE_WORKER_COMMAND};
+use crate::gpr::GprConverter;
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+/// Command-line entry point. Without a subcommand the TUI is started.
+#[derive(Debug, Parser)]
+#[command(name = "gprprotool", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Convert GPR files or whole directories without opening the TUI
+    Convert(Box<convert::ConvertArgs>),
+    /// Match photos against GPX tracks and report which would be geotagged
+    Geotag(geotag::GeotagCommandArgs),
+    /// Compare the metadata of two GPR files field by field
+    Diff(diff::DiffArgs),
+    /// Summarize cameras, exposure, dates, locations and storage of a shoot
+    Stats(stats::StatsArgs),
+    /// Maintain the on-disk catalog of metadata, thumbnails and conversions
+    Catalog(catalog::CatalogArgs),
+    /// Check files for truncation and structural damage before converting
+    Verify(verify::VerifyArgs),
+    /// Decode one file for an isolated conversion (internal)
+    #[command(name = DECODE_WORKER_COMMAND, hide = true)]
+    DecodeWorker {
+        input: PathBuf,
+        /// Development settings as JSON
+        #[arg(long)]
+        develop: Option<String>,
+        /// Noise profile to denoise for, as JSON
+        #[arg(long, requires = "develop")]
+        noise: Option<String>,
+        /// Print the file's metadata as JSON instead of decoding it
+        #[arg(long, conflicts_with = "develop")]
+        metadata: bool,
+        /// Decode a 1/8 resolution preview instead
+        #[arg(long, conflicts_with_all = ["develop", "metadata"])]
+        preview: bool,
+        /// Only check that the SDK can parse the file
+        #[arg(long, conflicts_with_all = ["develop", "metadata", "preview"])]
+        check: bool,
+    },
+}
+
+/// Run a non-interactive command
+pub fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Convert(args) => convert::run(*args),
+        Command::Geotag(args) => geotag::run(args),
+        Command::Diff(args) => diff::run(args),
+        Command::Stats(args) => stats::run(args),
+        Command::Catalog(args) => catalog::run(args),
+        Command::Verify(args) => verify::run(args),
+        Command::DecodeWorker { input, develop, noise, metadata, preview, check } => {
+            let task = if metadata {
+                WorkerTask::Metadata
+            } else if preview {
+                WorkerTask::Preview
+            } else if check {
+                WorkerTask::Check
+            } else {
+                WorkerTask::Decode { develop: develop.as_deref(), noise: noise.as_deref() }
+            };
+            GprConverter::run_decode_worker(&input, task)
+        }
+    }
+}
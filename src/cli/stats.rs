@@ -0,0 +1,98 @@
+use super::convert::{apply_filter, collect_inputs, parse_filter, PrivacyArgs};
+use crate::catalog::Catalog;
+use crate::models::shoot_stats::HistogramBin;
+use crate::models::{Filter, ShootStats};
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct StatsArgs {
+    /// GPR files or directories (searched recursively)
+    #[arg(required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Only count files matching this query, e.g. "camera:HERO7 date:2024-06"
+    #[arg(long, value_name = "QUERY", value_parser = parse_filter)]
+    pub filter: Option<Filter>,
+
+    /// Write the statistics as JSON to this file ("-" for stdout)
+    #[arg(long, value_name = "FILE")]
+    pub json: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub privacy: PrivacyArgs,
+}
+
+pub fn run(args: StatsArgs) -> Result<()> {
+    let mut files = collect_inputs(&args.inputs)?;
+    if files.is_empty() {
+        bail!("No .gpr files found");
+    }
+
+    let mut catalog = Catalog::open_default();
+    catalog.load_all(&mut files);
+    if let Err(e) = catalog.save() {
+        log::warn!("{:#}", e);
+    }
+    apply_filter(&mut files, args.filter.as_ref());
+
+    let mut stats = ShootStats::collect(&files);
+    stats.apply_privacy(&args.privacy.policy());
+
+    if let Some(ref path) = args.json {
+        let json = serde_json::to_string_pretty(&stats).context("Failed to serialize statistics")?;
+        if path.as_os_str() == "-" {
+            println!("{}", json);
+            return Ok(());
+        }
+        std::fs::write(path, json)
+            .with_context(|| format!("Failed to write statistics: {}", path.display()))?;
+        eprintln!("Wrote statistics to {}", path.display());
+    }
+
+    print_report(&stats);
+    Ok(())
+}
+
+fn print_report(stats: &ShootStats) {
+    println!("Files:      {} ({} unreadable)", stats.total_files, stats.unreadable);
+    println!("Storage:    {}", stats.storage_display());
+    println!("Dates:      {}", stats.date_range_display());
+    println!("Geotagged:  {} ({})", stats.geotagged, stats.gps_bounds_display());
+
+    let cameras: Vec<HistogramBin> = stats
+        .cameras
+        .iter()
+        .map(|(camera, count)| HistogramBin {
+            label: camera.clone(),
+            count: *count,
+        })
+        .collect();
+    let days: Vec<HistogramBin> = stats
+        .photos_per_day
+        .iter()
+        .map(|(day, count)| HistogramBin {
+            label: day.clone(),
+            count: *count,
+        })
+        .collect();
+
+    print_histogram("Cameras", &cameras);
+    print_histogram("ISO", &stats.iso);
+    print_histogram("Shutter speed", &stats.shutter_speed);
+    print_histogram("Photos per day", &days);
+}
+
+fn print_histogram(title: &str, bins: &[HistogramBin]) {
+    const BAR_WIDTH: usize = 40;
+
+    println!();
+    println!("{}:", title);
+    let label_width = bins.iter().map(|b| b.label.chars().count()).max().unwrap_or(0);
+    let max = bins.iter().map(|b| b.count).max().unwrap_or(0).max(1);
+    for bin in bins {
+        let bar = "█".repeat(bin.count * BAR_WIDTH / max);
+        println!("  {:width$}  {:>5}  {}", bin.label, bin.count, bar, width = label_width);
+    }
+}
@@ -0,0 +1,64 @@
+use super::convert::collect_inputs;
+use crate::gpr::verify::{self, Verdict, VerifyResult};
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    /// GPR files or directories (searched recursively)
+    #[arg(required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Print the results as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// List every issue found, not just the main reason
+    #[arg(long, short)]
+    pub verbose: bool,
+
+    /// Run the SDK check in a child process, so a file that crashes the SDK
+    /// is reported as corrupt instead of ending the run
+    #[arg(long)]
+    pub isolate_decoder: bool,
+}
+
+/// Check every file and fail if any is corrupt, so transfers can be
+/// verified from scripts
+pub fn run(args: VerifyArgs) -> Result<()> {
+    let files = collect_inputs(&args.inputs)?;
+    if files.is_empty() {
+        bail!("No .gpr files found");
+    }
+
+    let results: Vec<VerifyResult> = files.iter().map(|f| verify::verify_file(&f.path, args.isolate_decoder)).collect();
+    let count = |verdict: Verdict| results.iter().filter(|r| r.verdict == verdict).count();
+
+    if args.json {
+        let json = serde_json::to_string_pretty(&results).context("Failed to serialize results")?;
+        println!("{}", json);
+    } else {
+        for result in &results {
+            println!("{:<8} {}  {}", result.verdict.as_str(), result.path.display(), result.reason);
+            if args.verbose && result.issues.len() > 1 {
+                for issue in &result.issues {
+                    println!("         - {}: {}", issue.severity.as_str(), issue.message);
+                }
+            }
+        }
+        println!();
+        println!(
+            "{} OK, {} with warnings, {} corrupt",
+            count(Verdict::Ok),
+            count(Verdict::Warning),
+            count(Verdict::Corrupt)
+        );
+    }
+
+    let corrupt = count(Verdict::Corrupt);
+    if corrupt > 0 {
+        bail!("{} of {} files are corrupt", corrupt, results.len());
+    }
+    Ok(())
+}
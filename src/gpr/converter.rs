@@ -1,20 +1,142 @@
-use crate::models::{ConversionConfig, GprFile, OutputFormat};
+use crate::models::{
+    CameraProfile, ConversionConfig, DevelopSettings, GprFile, OrientationMode, OutputFormat,
+};
+use crate::models::camera_profile::{NoiseProfile, NOISE_12MP};
+use crate::models::fusion::{self, Panorama};
+use crate::models::gpr_file::GprMetadata;
 use crate::gpr::ffi::*;
+use crate::gpr::validate::{check_dimensions, validate_input};
+use crate::gpr::develop::denoise;
+use crate::gpr::metadata_reader::sdk_can_parse;
+use crate::gpr::{
+    develop, exif_writer, geometry, icc, lens, parse_metadata, read_metadata, resize, stitch, tiff_writer,
+    xmp_writer,
+};
+use crate::utils::datetime::ExifDateTime;
 use anyhow::{anyhow, Context, Result};
-use image::{ImageBuffer, Rgb};
-use std::path::PathBuf;
+use image::{ImageBuffer, ImageEncoder, Rgb};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::ptr;
+use std::time::{Duration, Instant};
+
+/// Hidden subcommand that runs [`GprConverter::run_decode_worker`]
+pub const DECODE_WORKER_COMMAND: &str = "decode-worker";
+
+/// Longest an isolated decode may take before the child is killed
+const DECODE_TIMEOUT: Duration = Duration::from_secs(120);
 
 pub struct GprConverter;
 
+/// What the decoder process does with its file
+pub enum WorkerTask<'a> {
+    /// Decode at full resolution; `develop` is JSON [`DevelopSettings`], the
+    /// defaults when absent, and `noise` the JSON [`NoiseProfile`] to denoise
+    /// for, the 12MP sensor's at ISO 100 when absent
+    Decode { develop: Option<&'a str>, noise: Option<&'a str> },
+    /// Decode at 1/8 resolution with the SDK
+    Preview,
+    /// Write the file's [`GprMetadata`] as JSON
+    Metadata,
+    /// Only run the SDK's metadata parser over the file
+    Check,
+}
+
 impl GprConverter {
     /// Convert a GPR file to the specified output format using the official GoPro GPR library
     pub fn convert(gpr_file: &GprFile, config: &ConversionConfig) -> Result<PathBuf> {
         log::info!("Starting conversion of {} using GoPro GPR library", gpr_file.filename);
+        let config = &config.for_file(&gpr_file.path);
+
+        let (gpr_data, metadata) = Self::load(gpr_file, config)?;
+        let config = &config.for_camera(&metadata);
 
         // Determine output path
-        let output_path = Self::determine_output_path(gpr_file, config)?;
+        let stem = Self::file_stem(gpr_file)?;
+        let output_path = Self::determine_output_path(gpr_file, stem, config, Some(&metadata))?;
+
+        let rgb_image = Self::decode_for_output(gpr_file, &gpr_data, &metadata, config)?;
+
+        let rgb_image = match lens::distortion_for(&config.lens) {
+            Some(distortion) => {
+                log::info!("Correcting lens distortion ({})", config.lens.correction.display());
+                lens::correct(&rgb_image, distortion, config.lens.edges)
+            }
+            None => rgb_image,
+        };
+
+        let orientation = config.baked_orientation(&metadata);
+        let rgb_image = if orientation != 1 {
+            log::info!("Applying orientation {}", orientation);
+            geometry::apply_orientation(rgb_image, orientation)
+        } else {
+            rgb_image
+        };
+
+        if config.geometry.auto_level {
+            match metadata.roll_degrees() {
+                Some(roll) => log::info!("Levelling horizon: camera rolled {:+.1}°", roll),
+                None => log::warn!("{} has no usable GPMF gravity; not levelled", gpr_file.filename),
+            }
+        }
+        let geometry = config.geometry.levelled(&metadata, orientation);
+        let rgb_image = if geometry.is_identity() {
+            rgb_image
+        } else {
+            log::info!("Applying geometry: {}", geometry.summary());
+            geometry::apply(rgb_image, &geometry)
+        };
+
+        // A tagged 90 degree rotation turns the output box around
+        let transposed = config.output_orientation(&metadata) >= 5;
+        let rgb_image = Self::finish(rgb_image, config, transposed);
+
+        Self::write_output(&rgb_image, gpr_file, &metadata, config, &output_path, None)?;
+
+        log::info!("Conversion complete: {}", output_path.display());
+        Ok(output_path)
+    }
+
+    /// Stitch the front (GPFR) and back (GPBK) halves of a Fusion shot into
+    /// one equirectangular 360 panorama, named `GP360_<sequence>` and tagged
+    /// with GPano XMP. Metadata and metadata edits are taken from the front
+    /// file; crop and rotation settings do not apply to panoramas.
+    pub fn convert_pair(front: &GprFile, back: &GprFile, config: &ConversionConfig) -> Result<PathBuf> {
+        log::info!("Stitching Fusion pair {} + {}", front.filename, back.filename);
+        let config = &ConversionConfig { metadata_edits: config.edits_for(&front.path), ..config.clone() };
+
+        let (front_data, mut metadata) = Self::load(front, config)?;
+        let (back_data, back_metadata) = Self::load(back, config)?;
+        let config = &config.for_camera(&metadata);
+        // Each lens is oriented as it would be converted on its own before
+        // stitching; a tag cannot describe a panorama, so tagging bakes
+        let lens_config = ConversionConfig { orientation_mode: config.orientation_mode.with_geometry(), ..config.clone() };
+        let front_orientation = lens_config.baked_orientation(&metadata);
+        let back_orientation = lens_config.baked_orientation(&back_metadata);
+        metadata.orientation = Some(1);
+
+        let stem = fusion::panorama_stem(&front.path)
+            .ok_or_else(|| anyhow!("Not a Fusion front lens file: {}", front.filename))?;
+        let output_path = Self::determine_output_path(front, &stem, config, Some(&metadata))?;
+
+        let front_image = Self::decode_for_output(front, &front_data, &metadata, config)?;
+        let front_image = geometry::apply_orientation(front_image, front_orientation);
+        let back_image = Self::decode_for_output(back, &back_data, &back_metadata, config)?;
+        let back_image = geometry::apply_orientation(back_image, back_orientation);
+        let panorama = stitch::stitch(&front_image, &back_image);
+        log::info!("Stitched {}x{} panorama", panorama.width(), panorama.height());
+        let panorama = Self::finish(panorama, config, false);
+
+        let size = Panorama { width: panorama.width(), height: panorama.height() };
+        Self::write_output(&panorama, front, &metadata, config, &output_path, Some(size))?;
+
+        log::info!("Conversion complete: {}", output_path.display());
+        Ok(output_path)
+    }
 
+    /// Read a GPR file and the metadata to write with its output
+    fn load(gpr_file: &GprFile, config: &ConversionConfig) -> Result<(Vec<u8>, GprMetadata)> {
         // Read GPR file into memory
         log::debug!("Reading GPR file: {}", gpr_file.path.display());
         let gpr_data = std::fs::read(&gpr_file.path)
@@ -22,35 +144,168 @@ impl GprConverter {
 
         log::debug!("GPR file size: {} bytes", gpr_data.len());
 
-        // Create allocator
-        let allocator = create_allocator();
-
-        // Create GPR buffer
-        let mut inp_buffer = gpr_buffer {
-            buffer: gpr_data.as_ptr() as *mut std::os::raw::c_void,
-            size: gpr_data.len(),
+        // Parse metadata (EXIF and GPR SDK), unless the file already has it;
+        // the SDK parser runs in the decoder process when that is isolated
+        log::debug!("Parsing GPR metadata...");
+        let source_metadata = match gpr_file.metadata {
+            Some(ref metadata) => metadata.clone(),
+            None if config.isolate_decoder => Self::read_metadata_isolated(&gpr_file.path)?,
+            None => parse_metadata(&gpr_data).context("Failed to parse GPR metadata")?,
         };
 
-        // Parse metadata
-        log::debug!("Parsing GPR metadata...");
-        let mut parameters: gpr_parameters = unsafe { std::mem::zeroed() };
-        unsafe {
-            gpr_parameters_set_defaults(&mut parameters);
+        log::info!(
+            "Parsed metadata: sensor {}, output {} ({})",
+            source_metadata.sensor_size_display(),
+            source_metadata.output_size_display(),
+            source_metadata.source.as_str()
+        );
+
+        if CameraProfile::lookup(&source_metadata.camera_model).is_none() {
+            log::warn!(
+                "{} claims an unknown camera model: {}",
+                gpr_file.filename,
+                source_metadata.camera_model
+            );
         }
 
-        let parse_result = unsafe {
-            gpr_parse_metadata(&allocator, &mut inp_buffer, &mut parameters)
+        // Metadata as it will be written to the output
+        Ok((gpr_data, Self::prepare_output_metadata(source_metadata, config)))
+    }
+
+    /// Full resolution RGB for a file, decoded in this process or a child
+    fn decode_for_output(
+        gpr_file: &GprFile,
+        gpr_data: &[u8],
+        metadata: &GprMetadata,
+        config: &ConversionConfig,
+    ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>> {
+        let noise = denoise::noise_profile(metadata);
+        let rgb_image = if config.isolate_decoder {
+            Self::decode_rgb_isolated(&gpr_file.path, &config.develop, noise)?
+        } else {
+            Self::decode_full(gpr_data, &config.develop, noise)?
         };
+        log::info!(
+            "Decoded {}x{} (sensor {}, output {})",
+            rgb_image.width(),
+            rgb_image.height(),
+            metadata.sensor_size_display(),
+            metadata.output_size_display()
+        );
+        Ok(rgb_image)
+    }
 
-        if !parse_result {
-            return Err(anyhow!("Failed to parse GPR metadata"));
+    /// Resize to the output size, then sharpen. `transposed` when the
+    /// image will be displayed rotated by 90 degrees.
+    fn finish(
+        image: ImageBuffer<Rgb<u8>, Vec<u8>>,
+        config: &ConversionConfig,
+        transposed: bool,
+    ) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let mut settings = config.resize;
+        if transposed {
+            settings.mode = settings.mode.transposed();
+        }
+        let mut image = resize::resize(image, &settings);
+        if !config.sharpen.is_off() {
+            log::info!("Sharpening ({}, radius {})", config.sharpen.amount_display(), config.sharpen.radius);
+            resize::sharpen(&mut image, &config.sharpen);
         }
+        image
+    }
 
+    /// Encode the finished image with its EXIF, and the XMP sidecar when
+    /// enabled. Panoramas also get their XMP, with the GPano tags, embedded.
+    fn write_output(
+        image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+        gpr_file: &GprFile,
+        metadata: &GprMetadata,
+        config: &ConversionConfig,
+        output_path: &PathBuf,
+        panorama: Option<Panorama>,
+    ) -> Result<()> {
+        // Save to output format
         log::info!(
-            "Parsed metadata: {}x{} pixels",
-            parameters.input_width,
-            parameters.input_height
+            "Encoding to {} (quality: {})...",
+            config.output_format.as_str(),
+            config.quality_display()
         );
+        let exif = if config.preserve_metadata {
+            Some(exif_writer::build_exif(metadata, config)?)
+        } else {
+            None
+        };
+        let xmp = match panorama {
+            Some(_) => Some(xmp_writer::build_xmp(gpr_file, Some(metadata), config, output_path, panorama)?),
+            None => None,
+        };
+        Self::save_image(image, output_path, config, exif, xmp.as_deref())?;
+
+        if config.write_xmp_sidecar {
+            let sidecar = xmp_writer::write_sidecar(gpr_file, Some(metadata), config, output_path, panorama)?;
+            log::info!("Wrote XMP sidecar: {}", sidecar.display());
+        }
+        Ok(())
+    }
+
+    /// Small preview of a GPR file, decoded at 1/8 resolution and oriented
+    /// as a conversion with `orientation_mode` would show it. With
+    /// `isolated`, the SDK runs in a child process as in conversions.
+    pub fn preview(
+        gpr_file: &GprFile,
+        orientation_mode: OrientationMode,
+        isolated: bool,
+    ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>> {
+        let orientation = match (orientation_mode, &gpr_file.metadata) {
+            (OrientationMode::Bake, Some(metadata)) => metadata.effective_orientation(),
+            (OrientationMode::Bake, None) if isolated => Self::read_metadata_isolated(&gpr_file.path)
+                .map(|metadata| metadata.effective_orientation())
+                .unwrap_or(1),
+            (OrientationMode::Bake, None) => read_metadata(&gpr_file.path)
+                .map(|metadata| metadata.effective_orientation())
+                .unwrap_or(1),
+            (OrientationMode::Ignore | OrientationMode::Tag, _) => 1,
+        };
+
+        let image = if isolated {
+            Self::image_from_worker(&Self::run_worker(&gpr_file.path, &["--preview"])?)?
+        } else {
+            let gpr_data = std::fs::read(&gpr_file.path)
+                .with_context(|| format!("Failed to read GPR file: {}", gpr_file.path.display()))?;
+            Self::decode_rgb(&gpr_data, GPR_RGB_RESOLUTION::GPR_RGB_RESOLUTION_EIGHTH)?
+        };
+        Ok(geometry::apply_orientation(image, orientation))
+    }
+
+    /// Full resolution RGB: the SDK's conversion, or the raw development
+    /// pipeline once any development setting is changed
+    fn decode_full(
+        gpr_data: &[u8],
+        develop: &DevelopSettings,
+        noise: NoiseProfile,
+    ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>> {
+        if develop.is_neutral() {
+            Self::decode_rgb(gpr_data, GPR_RGB_RESOLUTION::GPR_RGB_RESOLUTION_FULL)
+        } else {
+            develop::develop(gpr_data, develop, noise)
+        }
+    }
+
+    /// Decode GPR file contents to 8-bit RGB with the GPR SDK
+    fn decode_rgb(
+        gpr_data: &[u8],
+        resolution: GPR_RGB_RESOLUTION,
+    ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>> {
+        validate_input(gpr_data)?;
+
+        // Create allocator
+        let allocator = create_allocator();
+
+        // Create GPR buffer
+        let mut inp_buffer = gpr_buffer {
+            buffer: gpr_data.as_ptr() as *mut std::os::raw::c_void,
+            size: gpr_data.len(),
+        };
 
         // Convert GPR to RGB
         log::debug!("Converting GPR to RGB...");
@@ -64,7 +319,7 @@ impl GprConverter {
         let rgb_result = unsafe {
             gpr_convert_gpr_to_rgb(
                 &allocator,
-                GPR_RGB_RESOLUTION::GPR_RGB_RESOLUTION_FULL,
+                resolution,
                 8, // 8-bit per channel
                 &mut inp_buffer,
                 &mut out_rgb_buffer,
@@ -75,35 +330,123 @@ impl GprConverter {
             return Err(anyhow!("Failed to convert GPR to RGB"));
         }
 
-        log::info!(
-            "RGB conversion successful - buffer: {} bytes, dimensions: {}x{} (metadata was: {}x{})",
+        log::debug!(
+            "RGB conversion successful - buffer: {} bytes, dimensions: {}x{}",
             out_rgb_buffer.size,
             out_rgb_buffer.width,
-            out_rgb_buffer.height,
-            parameters.input_width,
-            parameters.input_height
+            out_rgb_buffer.height
         );
 
         // Use the actual dimensions from the RGB buffer, not the metadata
         let width = out_rgb_buffer.width as u32;
         let height = out_rgb_buffer.height as u32;
-        let rgb_image = Self::rgb_buffer_to_image(&out_rgb_buffer, width, height)?;
+        let rgb_image = Self::rgb_buffer_to_image(&out_rgb_buffer, width, height);
 
         // Free RGB buffer
         if let Some(free_fn) = allocator.mem_free {
             free_fn(out_rgb_buffer.buffer);
         }
 
-        // Save to output format
-        log::info!(
-            "Encoding to {} (quality: {})...",
-            config.output_format.as_str(),
-            config.quality_display()
-        );
-        Self::save_image(&rgb_image, &output_path, config)?;
+        rgb_image
+    }
 
-        log::info!("Conversion complete: {}", output_path.display());
-        Ok(output_path)
+    /// Decode a GPR file in a child process running [`run_decode_worker`],
+    /// so an SDK crash or hang fails this file instead of the whole program
+    fn decode_rgb_isolated(
+        path: &Path,
+        develop: &DevelopSettings,
+        noise: NoiseProfile,
+    ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>> {
+        let develop = serde_json::to_string(develop)?;
+        let noise = serde_json::to_string(&noise)?;
+        Self::image_from_worker(&Self::run_worker(path, &["--develop", &develop, "--noise", &noise])?)
+    }
+
+    /// The image a decoder process wrote: width and height, then RGB bytes
+    fn image_from_worker(data: &[u8]) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>> {
+        let header = data.get(..8).ok_or_else(|| anyhow!("Decoder output is empty"))?;
+        let width = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let height = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        check_dimensions(width, height)?;
+        ImageBuffer::from_raw(width, height, data[8..].to_vec())
+            .ok_or_else(|| anyhow!("Decoder output does not match {}x{}", width, height))
+    }
+
+    /// Parse a file's metadata in a child process, for the same reason as
+    /// [`decode_rgb_isolated`]: the SDK's metadata parser reads the whole
+    /// container too
+    pub fn read_metadata_isolated(path: &Path) -> Result<GprMetadata> {
+        let data = Self::run_worker(path, &["--metadata"])?;
+        serde_json::from_slice(&data).context("Invalid metadata from the decoder process")
+    }
+
+    /// Run the SDK's metadata parser over a file in a child process, for
+    /// checking files that may be damaged
+    pub fn check_isolated(path: &Path) -> Result<()> {
+        Self::run_worker(path, &["--check"]).map(|_| ())
+    }
+
+    /// Run the hidden decoder subcommand on `path` and return its output
+    fn run_worker(path: &Path, args: &[&str]) -> Result<Vec<u8>> {
+        let exe = std::env::current_exe().context("Cannot locate the gprprotool executable")?;
+        let mut child = Command::new(exe)
+            .arg(DECODE_WORKER_COMMAND)
+            .arg(path)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to start the decoder process")?;
+
+        // Drain both pipes while waiting, or a full pipe blocks the child
+        let mut stdout = child.stdout.take().ok_or_else(|| anyhow!("Decoder has no stdout"))?;
+        let mut stderr = child.stderr.take().ok_or_else(|| anyhow!("Decoder has no stderr"))?;
+        let stdout_reader = std::thread::spawn(move || {
+            let mut data = Vec::new();
+            stdout.read_to_end(&mut data).map(|_| data)
+        });
+        let stderr_reader = std::thread::spawn(move || {
+            let mut text = String::new();
+            let _ = stderr.read_to_string(&mut text);
+            text
+        });
+
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if started.elapsed() > DECODE_TIMEOUT {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(anyhow!(
+                    "Decoder did not finish within {} seconds",
+                    DECODE_TIMEOUT.as_secs()
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        };
+
+        let data = stdout_reader
+            .join()
+            .map_err(|_| anyhow!("Decoder output reader panicked"))?
+            .context("Failed to read decoder output")?;
+        let errors = stderr_reader.join().unwrap_or_default();
+
+        if !status.success() {
+            return Err(match status.code() {
+                Some(_) => {
+                    let message = errors
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Error: "))
+                        .unwrap_or("unknown error");
+                    anyhow!("Decoder failed: {}", message)
+                }
+                None => anyhow!("Decoder crashed ({}); the file is likely malformed", status),
+            });
+        }
+        Ok(data)
     }
 
     /// Convert GPR RGB buffer to ImageBuffer
@@ -115,20 +458,22 @@ impl GprConverter {
         // The buffer size tells us how much data we actually have
         let actual_size = rgb_buffer.size;
 
+        let pixels = width as usize * height as usize;
+
         log::debug!(
             "RGB buffer info - size: {} bytes, expected for {}x{}: {} bytes",
             actual_size,
             width,
             height,
-            width * height * 3
+            pixels * 3
         );
 
         // Determine actual dimensions from buffer size
         // Buffer could be RGB (3 bytes) or RGBA (4 bytes) per pixel
-        let bytes_per_pixel = if actual_size == (width * height * 4) as usize {
+        let bytes_per_pixel = if actual_size == pixels * 4 {
             log::info!("Detected RGBA format (4 bytes per pixel)");
             4
-        } else if actual_size == (width * height * 3) as usize {
+        } else if actual_size == pixels * 3 {
             log::info!("Detected RGB format (3 bytes per pixel)");
             3
         } else {
@@ -138,7 +483,7 @@ impl GprConverter {
             // Assume RGB format and calculate actual dimensions
             let total_pixels = actual_size / 3;
             let inferred_width = (total_pixels as f64).sqrt() as u32;
-            let inferred_height = total_pixels as u32 / inferred_width;
+            let inferred_height = (total_pixels as u32).checked_div(inferred_width).unwrap_or(0);
 
             log::info!(
                 "Inferred dimensions: {}x{} (from {} pixels)",
@@ -160,7 +505,8 @@ impl GprConverter {
         height: u32,
         bytes_per_pixel: usize,
     ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>> {
-        let data_size = (width * height * bytes_per_pixel as u32) as usize;
+        check_dimensions(width, height)?;
+        let data_size = width as usize * height as usize * bytes_per_pixel;
 
         if rgb_buffer.size < data_size {
             return Err(anyhow!(
@@ -179,7 +525,7 @@ impl GprConverter {
 
         for y in 0..height {
             for x in 0..width {
-                let idx = ((y * width + x) * bytes_per_pixel as u32) as usize;
+                let idx = (y as usize * width as usize + x as usize) * bytes_per_pixel;
                 let r = rgb_data[idx];
                 let g = rgb_data[idx + 1];
                 let b = rgb_data[idx + 2];
@@ -191,63 +537,137 @@ impl GprConverter {
         Ok(img_buffer)
     }
 
+    /// Metadata as it should appear in the converted output, with the
+    /// configured clock correction, edits and privacy policy applied
+    fn prepare_output_metadata(mut metadata: GprMetadata, config: &ConversionConfig) -> GprMetadata {
+        if let Some(shifted) = metadata
+            .date_taken
+            .as_deref()
+            .and_then(|date| config.time_shift.apply(date))
+        {
+            metadata.date_taken = Some(shifted.to_string());
+        }
+
+        config.metadata_edits.apply(&mut metadata);
+        config.privacy.apply(&mut metadata);
+
+        metadata
+    }
+
     /// Save image to file
     fn save_image(
         image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
         path: &PathBuf,
         config: &ConversionConfig,
+        exif: Option<Vec<u8>>,
+        xmp: Option<&str>,
     ) -> Result<()> {
+        let mut file = std::fs::File::create(path)
+            .with_context(|| format!("Failed to create output file: {}", path.display()))?;
+        // The SDK conversion is sRGB, the default colour space
+        let icc = icc::profile(config.develop.color_space);
+        // JPEG and PNG are encoded in memory when XMP is to be spliced in
+        let mut encoded = Vec::new();
+
         match config.output_format {
             OutputFormat::Jpeg => {
-                let file = std::fs::File::create(path)
-                    .with_context(|| format!("Failed to create output file: {}", path.display()))?;
-
                 let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(
-                    file,
+                    &mut encoded,
                     config.quality,
                 );
+                if let Some(exif) = exif {
+                    encoder.set_exif_metadata(exif)?;
+                }
+                encoder.set_icc_profile(icc)?;
 
                 encoder
-                    .encode(
+                    .write_image(
                         image.as_raw(),
                         image.width(),
                         image.height(),
                         image::ExtendedColorType::Rgb8,
                     )
                     .context("Failed to encode JPEG")?;
+                if let Some(xmp) = xmp {
+                    encoded = xmp_writer::embed_in_jpeg(&encoded, xmp)?;
+                }
             }
             OutputFormat::Png => {
-                image
-                    .save(path)
+                let mut encoder = image::codecs::png::PngEncoder::new(&mut encoded);
+                if let Some(exif) = exif {
+                    encoder.set_exif_metadata(exif)?;
+                }
+                encoder.set_icc_profile(icc)?;
+
+                encoder
+                    .write_image(
+                        image.as_raw(),
+                        image.width(),
+                        image.height(),
+                        image::ExtendedColorType::Rgb8,
+                    )
                     .with_context(|| format!("Failed to save PNG: {}", path.display()))?;
+                if let Some(xmp) = xmp {
+                    encoded = xmp_writer::embed_in_png(&encoded, xmp)?;
+                }
+            }
+            OutputFormat::Tiff => {
+                let xmp = xmp.map(str::as_bytes);
+                return tiff_writer::write_tiff(std::io::BufWriter::new(file), image, exif, &icc, xmp)
+                    .with_context(|| format!("Failed to save TIFF: {}", path.display()));
             }
         }
-        Ok(())
+        file.write_all(&encoded)
+            .with_context(|| format!("Failed to write output file: {}", path.display()))
     }
 
-    /// Convert multiple GPR files in batch
+    /// Convert multiple GPR files in batch, returning the source and output
+    /// path of every file that converted. With Fusion stitching on, paired
+    /// front and back files share one output and are both listed with it.
     #[allow(dead_code)]
     pub fn batch_convert(
         files: &[GprFile],
         config: &ConversionConfig,
         progress_callback: Option<Box<dyn Fn(usize, usize)>>,
-    ) -> Result<Vec<PathBuf>> {
+    ) -> Result<Vec<(PathBuf, PathBuf)>> {
         let mut output_paths = Vec::new();
         let mut errors = Vec::new();
 
-        for (i, file) in files.iter().enumerate() {
+        // Each job is a single file or a Fusion pair
+        let jobs: Vec<Vec<&GprFile>> = if config.stitch_fusion {
+            let (pairs, unpaired) = fusion::find_pairs(files);
+            let mut jobs: Vec<Vec<&GprFile>> = pairs
+                .iter()
+                .map(|pair| vec![&files[pair.front], &files[pair.back]])
+                .collect();
+            jobs.extend(unpaired.into_iter().map(|index| vec![&files[index]]));
+            jobs
+        } else {
+            files.iter().map(|file| vec![file]).collect()
+        };
+
+        let mut done = 0;
+        for job in jobs {
             if let Some(ref callback) = progress_callback {
-                callback(i, files.len());
+                callback(done, files.len());
             }
-
-            match Self::convert(file, config) {
+            done += job.len();
+
+            let (result, name) = match job[..] {
+                [front, back] => (
+                    Self::convert_pair(front, back, config),
+                    format!("{} + {}", front.filename, back.filename),
+                ),
+                _ => (Self::convert(job[0], config), job[0].filename.clone()),
+            };
+            match result {
                 Ok(path) => {
-                    log::info!("Successfully converted: {}", file.filename);
-                    output_paths.push(path);
+                    log::info!("Successfully converted: {}", name);
+                    output_paths.extend(job.iter().map(|file| (file.path.clone(), path.clone())));
                 }
                 Err(e) => {
-                    log::error!("Failed to convert {}: {}", file.filename, e);
-                    errors.push((file.filename.clone(), e));
+                    log::error!("Failed to convert {}: {}", name, e);
+                    errors.push((name, e));
                 }
             }
         }
@@ -263,9 +683,48 @@ impl GprConverter {
         Ok(output_paths)
     }
 
+    /// Body of the hidden decoder subcommand: run `task` on `path`. Images
+    /// are written to stdout as the width and height (u32 little-endian)
+    /// followed by the RGB bytes.
+    pub fn run_decode_worker(path: &Path, task: WorkerTask) -> Result<()> {
+        let gpr_data = std::fs::read(path)
+            .with_context(|| format!("Failed to read GPR file: {}", path.display()))?;
+
+        let mut stdout = std::io::stdout().lock();
+        let image = match task {
+            WorkerTask::Decode { develop, noise } => {
+                let develop: DevelopSettings = match develop {
+                    Some(develop) => serde_json::from_str(develop).context("Invalid development settings")?,
+                    None => DevelopSettings::default(),
+                };
+                let noise: NoiseProfile = match noise {
+                    Some(noise) => serde_json::from_str(noise).context("Invalid noise profile")?,
+                    None => NOISE_12MP,
+                };
+                Self::decode_full(&gpr_data, &develop, noise)?
+            }
+            WorkerTask::Preview => Self::decode_rgb(&gpr_data, GPR_RGB_RESOLUTION::GPR_RGB_RESOLUTION_EIGHTH)?,
+            WorkerTask::Metadata => {
+                let metadata = parse_metadata(&gpr_data).context("Failed to parse GPR metadata")?;
+                serde_json::to_writer(&mut stdout, &metadata)?;
+                stdout.flush()?;
+                return Ok(());
+            }
+            WorkerTask::Check => return sdk_can_parse(&gpr_data),
+        };
+        stdout.write_all(&image.width().to_le_bytes())?;
+        stdout.write_all(&image.height().to_le_bytes())?;
+        stdout.write_all(image.as_raw())?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    /// Output path for `gpr_file`, named from `stem` (normally the source's)
     fn determine_output_path(
         gpr_file: &GprFile,
+        stem: &str,
         config: &ConversionConfig,
+        metadata: Option<&GprMetadata>,
     ) -> Result<PathBuf> {
         let output_dir = if let Some(ref dir) = config.output_directory {
             PathBuf::from(dir)
@@ -277,19 +736,43 @@ impl GprConverter {
                 .to_path_buf()
         };
 
-        let stem = gpr_file
-            .path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .ok_or_else(|| anyhow!("Could not extract filename stem"))?;
+        let extension = config.output_format.extension();
 
-        let extension = match config.output_format {
-            OutputFormat::Jpeg => "jpg",
-            OutputFormat::Png => "png",
+        let name = match config.filename_template {
+            Some(ref template) => Self::render_filename_template(template, stem, metadata),
+            None => stem.to_string(),
         };
-
-        let filename = format!("{}.{}", stem, extension);
+        let filename = format!("{}{}.{}", name, config.resize.mode.suffix(), extension);
 
         Ok(output_dir.join(filename))
     }
+
+    fn file_stem(gpr_file: &GprFile) -> Result<&str> {
+        gpr_file
+            .path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| anyhow!("Could not extract filename stem"))
+    }
+
+    /// Expand `{stem}`, `{date}`, `{time}` and `{camera}` in a file name template
+    fn render_filename_template(
+        template: &str,
+        stem: &str,
+        metadata: Option<&GprMetadata>,
+    ) -> String {
+        let taken = metadata
+            .and_then(|m| m.date_taken.as_deref())
+            .and_then(ExifDateTime::parse);
+        let camera = metadata
+            .map(|m| m.camera_model.replace(' ', "-"))
+            .unwrap_or_else(|| "unknown".to_string());
+
+        template
+            .replace("{stem}", stem)
+            .replace("{date}", &taken.map(|t| t.date_compact()).unwrap_or_else(|| "nodate".to_string()))
+            .replace("{time}", &taken.map(|t| t.time_compact()).unwrap_or_else(|| "notime".to_string()))
+            .replace("{camera}", &camera)
+            .replace(['/', '\\'], "_")
+    }
 }
@@ -0,0 +1,203 @@
+use super::raw::{Matrix3, RawImage};
+use super::white_balance::{self, multiply};
+use crate::models::{ColorSpace, WhiteBalance};
+
+/// ICC profile connection space white
+pub const D50: (f32, f32) = (0.3457, 0.3585);
+
+/// Bradford cone response matrix
+const BRADFORD: Matrix3 = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+/// Matrix from white-balanced camera RGB to linear RGB in `space`, scaled
+/// so a neutral camera value of 1 stays 1. Camera RGB goes to D50 XYZ with
+/// the file's ForwardMatrix, or without one through the inverse of its
+/// ColorMatrix for the scene light and a Bradford adaptation to D50.
+pub fn camera_to_output(
+    raw: &RawImage,
+    white_balance: &WhiteBalance,
+    gains: [f32; 3],
+    space: ColorSpace,
+) -> Matrix3 {
+    let kelvin = match white_balance.kelvin_tint() {
+        Some((kelvin, _)) => kelvin as f32,
+        None => as_shot_temperature(raw),
+    };
+    let camera_to_d50 = match white_balance::interpolate(&raw.forward_matrices, kelvin) {
+        Some(forward) => forward,
+        None => {
+            let xyz_to_camera = white_balance::color_matrix(raw, kelvin);
+            let Some(camera_to_xyz) = invert(&xyz_to_camera) else {
+                log::warn!("ColorMatrix is singular; leaving camera colours unconverted");
+                return identity();
+            };
+            // Undo the white balance gains to get back to the camera's response
+            let unbalance = diagonal(gains.map(|g| 1.0 / g));
+            let camera_to_xyz = multiply_matrices(&camera_to_xyz, &unbalance);
+            let scene_white = multiply(&camera_to_xyz, [1.0; 3]);
+            multiply_matrices(&bradford(scene_white, xy_to_xyz(D50)), &camera_to_xyz)
+        }
+    };
+    let Some(xyz_to_output) = invert(&rgb_to_xyz(space)) else {
+        return identity();
+    };
+
+    let matrix = multiply_matrices(&xyz_to_output, &camera_to_d50);
+    let white = multiply(&matrix, [1.0; 3])[1];
+    if white <= 0.0 {
+        return identity();
+    }
+    matrix.map(|row| row.map(|v| v / white))
+}
+
+/// Linear RGB in `space` to D50 XYZ, with the primaries Bradford-adapted
+/// from the space's white; the columns are the ICC colorant tags
+pub fn rgb_to_xyz(space: ColorSpace) -> Matrix3 {
+    let primaries = space.primaries().map(|(x, y)| xy_to_xyz((x, y)));
+    let columns = transpose(&primaries);
+    let white = xy_to_xyz(space.white());
+    let scale = invert(&columns).map_or([1.0; 3], |inverse| multiply(&inverse, white));
+    let native = multiply_matrices(&columns, &diagonal(scale));
+    multiply_matrices(&bradford(white, xy_to_xyz(D50)), &native)
+}
+
+/// Colour temperature matching the camera's AsShotNeutral, found by
+/// iterating between the interpolated ColorMatrix and McCamy's formula
+fn as_shot_temperature(raw: &RawImage) -> f32 {
+    let Some(neutral) = raw.as_shot_neutral else {
+        return 5500.0;
+    };
+    let mut kelvin = 5000.0;
+    for _ in 0..5 {
+        let Some(camera_to_xyz) = invert(&white_balance::color_matrix(raw, kelvin)) else {
+            break;
+        };
+        let [x, y, z] = multiply(&camera_to_xyz, neutral);
+        let sum = x + y + z;
+        if sum <= 0.0 {
+            break;
+        }
+        let n = (x / sum - 0.3320) / (0.1858 - y / sum);
+        kelvin = (449.0 * n.powi(3) + 3525.0 * n.powi(2) + 6823.3 * n + 5520.33).clamp(
+            WhiteBalance::MIN_KELVIN as f32,
+            WhiteBalance::MAX_KELVIN as f32,
+        );
+    }
+    kelvin
+}
+
+/// Von Kries adaptation in Bradford cone space from one white to another
+fn bradford(from: [f32; 3], to: [f32; 3]) -> Matrix3 {
+    let Some(inverse) = invert(&BRADFORD) else {
+        return identity();
+    };
+    let (from, to) = (multiply(&BRADFORD, from), multiply(&BRADFORD, to));
+    let scale = diagonal([to[0] / from[0], to[1] / from[1], to[2] / from[2]]);
+    multiply_matrices(&inverse, &multiply_matrices(&scale, &BRADFORD))
+}
+
+pub fn xy_to_xyz((x, y): (f32, f32)) -> [f32; 3] {
+    [x / y, 1.0, (1.0 - x - y) / y]
+}
+
+fn identity() -> Matrix3 {
+    diagonal([1.0; 3])
+}
+
+fn diagonal(values: [f32; 3]) -> Matrix3 {
+    let mut matrix = [[0.0; 3]; 3];
+    for (i, value) in values.into_iter().enumerate() {
+        matrix[i][i] = value;
+    }
+    matrix
+}
+
+fn transpose(m: &Matrix3) -> Matrix3 {
+    let mut t = [[0.0; 3]; 3];
+    for (row, values) in m.iter().enumerate() {
+        for (col, value) in values.iter().enumerate() {
+            t[col][row] = *value;
+        }
+    }
+    t
+}
+
+pub fn multiply_matrices(a: &Matrix3, b: &Matrix3) -> Matrix3 {
+    let mut product = [[0.0; 3]; 3];
+    for (row, out) in product.iter_mut().enumerate() {
+        for (col, value) in out.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[row][k] * b[k][col]).sum();
+        }
+    }
+    product
+}
+
+/// Inverse by cofactors, computed in f64; `None` if singular
+pub fn invert(m: &Matrix3) -> Option<Matrix3> {
+    let m = m.map(|row| row.map(|v| v as f64));
+    let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let adjugate = [
+        [cofactor(1, 2, 1, 2), -cofactor(0, 2, 1, 2), cofactor(0, 1, 1, 2)],
+        [-cofactor(1, 2, 0, 2), cofactor(0, 2, 0, 2), -cofactor(0, 1, 0, 2)],
+        [cofactor(1, 2, 0, 1), -cofactor(0, 2, 0, 1), cofactor(0, 1, 0, 1)],
+    ];
+    let determinant = m[0][0] * adjugate[0][0] + m[0][1] * adjugate[1][0] + m[0][2] * adjugate[2][0];
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    Some(adjugate.map(|row| row.map(|v| (v / determinant) as f32)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_matrices() {
+        // The well-known D50-adapted sRGB matrix
+        let srgb = rgb_to_xyz(ColorSpace::Srgb);
+        let expected = [
+            [0.4361, 0.3851, 0.1431],
+            [0.2225, 0.7169, 0.0606],
+            [0.0139, 0.0971, 0.7141],
+        ];
+        for (row, expected_row) in srgb.iter().zip(expected) {
+            for (value, expected) in row.iter().zip(expected_row) {
+                assert!((value - expected).abs() < 0.002, "{:?}", srgb);
+            }
+        }
+
+        // Every space maps RGB white to D50
+        for space in ColorSpace::all() {
+            let white = multiply(&rgb_to_xyz(space), [1.0; 3]);
+            let d50 = xy_to_xyz(D50);
+            assert!(white.iter().zip(d50).all(|(a, b)| (a - b).abs() < 1e-3), "{}", space.as_str());
+        }
+
+        // With the sRGB fallback camera matrix, camera neutral stays neutral
+        // and camera RGB passes through to sRGB
+        let raw = RawImage {
+            width: 2,
+            height: 2,
+            data: vec![0; 4],
+            cfa: crate::models::camera_profile::CfaPattern::Rggb,
+            black_level: 0.0,
+            white_level: 1.0,
+            as_shot_neutral: None,
+            color_matrices: Vec::new(),
+            forward_matrices: Vec::new(),
+        };
+        let custom = WhiteBalance::Custom { kelvin: 6504, tint: 0 };
+        let gains = white_balance::multipliers(&custom, &raw);
+        let matrix = camera_to_output(&raw, &custom, gains, ColorSpace::Srgb);
+        for (row, values) in matrix.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                let expected = if row == col { 1.0 } else { 0.0 };
+                assert!((value - expected).abs() < 0.02, "{:?}", matrix);
+            }
+        }
+    }
+}
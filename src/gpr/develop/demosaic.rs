@@ -0,0 +1,393 @@
+use super::raw::RawImage;
+use super::LinearImage;
+use crate::models::DemosaicMethod;
+
+const RED: usize = 0;
+const GREEN: usize = 1;
+const BLUE: usize = 2;
+
+/// Output tile size for AHD, which keeps its per-direction buffers small
+const AHD_TILE: usize = 256;
+
+/// Pixels around an AHD tile computed only as context for its edges
+const AHD_MARGIN: usize = 4;
+
+/// Interpolate the full RGB image from the colour filter mosaic. `Sdk` has
+/// no Rust counterpart here and falls back to AHD.
+pub fn demosaic(raw: &RawImage, method: DemosaicMethod) -> LinearImage {
+    let started = std::time::Instant::now();
+    let image = match method {
+        DemosaicMethod::Bilinear => bilinear(raw),
+        DemosaicMethod::Ppg => ppg(raw),
+        DemosaicMethod::Sdk | DemosaicMethod::Ahd => ahd(raw),
+    };
+    log::debug!("{} demosaic took {:?}", method.as_str(), started.elapsed());
+    image
+}
+
+/// Normalized photosites with mirrored access past the edges. Reflecting
+/// about the first and last row/column keeps the parity of the coordinate,
+/// so a mirrored photosite has the colour of the one it stands in for.
+struct Mosaic<'a> {
+    raw: &'a RawImage,
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl<'a> Mosaic<'a> {
+    fn new(raw: &'a RawImage) -> Self {
+        let (width, height) = (raw.width as usize, raw.height as usize);
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                data.push(raw.normalized(x, y));
+            }
+        }
+        Self { raw, width, height, data }
+    }
+
+    fn get(&self, x: isize, y: isize) -> f32 {
+        self.data[mirror(y, self.height) * self.width + mirror(x, self.width)]
+    }
+
+    fn color(&self, x: isize, y: isize) -> usize {
+        self.raw.cfa.color_at(mirror(x, self.width), mirror(y, self.height))
+    }
+}
+
+pub fn mirror(i: isize, n: usize) -> usize {
+    let last = n as isize - 1;
+    let i = if i < 0 { -i } else { i };
+    let i = if i > last { 2 * last - i } else { i };
+    i.clamp(0, last) as usize
+}
+
+/// Fill in the two missing colours of every photosite with the mean of the
+/// neighbours of that colour in its 3x3 window
+pub fn bilinear(raw: &RawImage) -> LinearImage {
+    let (width, height) = (raw.width as usize, raw.height as usize);
+    let mut image = LinearImage::new(raw.width, raw.height);
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut sums = [0.0f32; 3];
+            let mut counts = [0u32; 3];
+            for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                    let color = raw.cfa.color_at(nx, ny);
+                    sums[color] += raw.normalized(nx, ny);
+                    counts[color] += 1;
+                }
+            }
+
+            let own = raw.cfa.color_at(x, y);
+            let pixel = image.pixel_mut(x, y);
+            for color in 0..3 {
+                pixel[color] = if color == own {
+                    raw.normalized(x, y)
+                } else if counts[color] > 0 {
+                    sums[color] / counts[color] as f32
+                } else {
+                    0.0
+                };
+            }
+        }
+    }
+
+    image
+}
+
+/// Patterned Pixel Grouping (Chuan-kai Lin): green along the flattest of
+/// the four directions, then red and blue from colour differences to green,
+/// along the flatter diagonal where neither is a neighbour
+pub fn ppg(raw: &RawImage) -> LinearImage {
+    let mosaic = Mosaic::new(raw);
+    let (width, height) = (mosaic.width, mosaic.height);
+
+    let mut green = vec![0.0f32; width * height];
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let center = mosaic.get(x, y);
+            green[y as usize * width + x as usize] = if mosaic.color(x, y) == GREEN {
+                center
+            } else {
+                ppg_green(&mosaic, x, y, center)
+            };
+        }
+    }
+    let g = |x: isize, y: isize| green[mirror(y, height) * width + mirror(x, width)];
+
+    let mut image = LinearImage::new(raw.width, raw.height);
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let own = mosaic.color(x, y);
+            let center = g(x, y);
+            // Colour at (x, y) from the colour differences of two neighbours
+            let transit = |(ax, ay): (isize, isize), (bx, by): (isize, isize)| {
+                center + ((mosaic.get(ax, ay) - g(ax, ay)) + (mosaic.get(bx, by) - g(bx, by))) / 2.0
+            };
+
+            let mut rgb = [0.0f32; 3];
+            rgb[GREEN] = center;
+            if own == GREEN {
+                let horizontal = mosaic.color(x + 1, y);
+                rgb[horizontal] = transit((x - 1, y), (x + 1, y));
+                rgb[RED + BLUE - horizontal] = transit((x, y - 1), (x, y + 1));
+            } else {
+                let value = mosaic.get(x, y);
+                rgb[own] = value;
+                let diagonal = |dx: isize| {
+                    let (a, b) = ((x + dx, y - 1), (x - dx, y + 1));
+                    let gradient = (mosaic.get(a.0, a.1) - mosaic.get(b.0, b.1)).abs()
+                        + (mosaic.get(x + 2 * dx, y - 2) - value).abs()
+                        + (value - mosaic.get(x - 2 * dx, y + 2)).abs()
+                        + (g(a.0, a.1) - center).abs()
+                        + (center - g(b.0, b.1)).abs();
+                    (gradient, transit(a, b))
+                };
+                let (north_east, north_west) = (diagonal(1), diagonal(-1));
+                rgb[RED + BLUE - own] = if north_east.0 < north_west.0 {
+                    north_east.1
+                } else if north_west.0 < north_east.0 {
+                    north_west.1
+                } else {
+                    (north_east.1 + north_west.1) / 2.0
+                };
+            }
+            image.pixel_mut(x as usize, y as usize).copy_from_slice(&rgb);
+        }
+    }
+
+    image
+}
+
+/// Green at a red or blue photosite, interpolated along the direction with
+/// the smallest gradient and kept between the two greens it came from
+fn ppg_green(mosaic: &Mosaic, x: isize, y: isize, center: f32) -> f32 {
+    let (north, south) = (mosaic.get(x, y - 1), mosaic.get(x, y + 1));
+    let (west, east) = (mosaic.get(x - 1, y), mosaic.get(x + 1, y));
+    let (north2, south2) = (mosaic.get(x, y - 2), mosaic.get(x, y + 2));
+    let (west2, east2) = (mosaic.get(x - 2, y), mosaic.get(x + 2, y));
+
+    let candidates = [
+        ((center - north2).abs() * 2.0 + (north - south).abs(), (north * 3.0 + south + center - north2) / 4.0, (north, south)),
+        ((center - east2).abs() * 2.0 + (west - east).abs(), (east * 3.0 + west + center - east2) / 4.0, (west, east)),
+        ((center - west2).abs() * 2.0 + (west - east).abs(), (west * 3.0 + east + center - west2) / 4.0, (west, east)),
+        ((center - south2).abs() * 2.0 + (north - south).abs(), (south * 3.0 + north + center - south2) / 4.0, (north, south)),
+    ];
+    let (_, value, (a, b)) = candidates
+        .into_iter()
+        .min_by(|x, y| x.0.total_cmp(&y.0))
+        .expect("four candidates");
+    value.clamp(a.min(b), a.max(b))
+}
+
+/// Adaptive Homogeneity-Directed demosaicing (Hirakawa and Parks): the
+/// image is interpolated once horizontally and once vertically, and each
+/// pixel takes the direction whose CIELAB neighbourhood is more uniform
+pub fn ahd(raw: &RawImage) -> LinearImage {
+    let mosaic = Mosaic::new(raw);
+    let mut image = LinearImage::new(raw.width, raw.height);
+
+    for top in (0..mosaic.height).step_by(AHD_TILE) {
+        for left in (0..mosaic.width).step_by(AHD_TILE) {
+            ahd_tile(&mosaic, &mut image, left, top);
+        }
+    }
+
+    image
+}
+
+/// One direction of an AHD tile: interpolated RGB and its CIELAB values
+/// over the tile and its margin
+struct Directional {
+    rgb: Vec<[f32; 3]>,
+    lab: Vec<[f32; 3]>,
+}
+
+fn ahd_tile(mosaic: &Mosaic, image: &mut LinearImage, left: usize, top: usize) {
+    let tile_width = AHD_TILE.min(mosaic.width - left);
+    let tile_height = AHD_TILE.min(mosaic.height - top);
+    let (width, height) = (tile_width + 2 * AHD_MARGIN, tile_height + 2 * AHD_MARGIN);
+    // Mosaic coordinates of padded tile position (i, j)
+    let origin = (left as isize - AHD_MARGIN as isize, top as isize - AHD_MARGIN as isize);
+
+    let directions = [(1, 0), (0, 1)].map(|(dx, dy)| {
+        // Green along the direction, corrected by the local curvature of
+        // the photosite's own colour and kept between its two neighbours
+        let mut green = vec![0.0f32; width * height];
+        for j in 0..height {
+            for i in 0..width {
+                let (x, y) = (origin.0 + i as isize, origin.1 + j as isize);
+                let center = mosaic.get(x, y);
+                green[j * width + i] = if mosaic.color(x, y) == GREEN {
+                    center
+                } else {
+                    let (a, b) = (mosaic.get(x - dx, y - dy), mosaic.get(x + dx, y + dy));
+                    let curvature = 2.0 * center - mosaic.get(x - 2 * dx, y - 2 * dy) - mosaic.get(x + 2 * dx, y + 2 * dy);
+                    ((a + b) / 2.0 + curvature / 4.0).clamp(a.min(b), a.max(b))
+                };
+            }
+        }
+
+        // Red and blue from colour differences to that green
+        let difference = |i: isize, j: isize| {
+            let (i, j) = (i.clamp(0, width as isize - 1), j.clamp(0, height as isize - 1));
+            mosaic.get(origin.0 + i, origin.1 + j) - green[j as usize * width + i as usize]
+        };
+        let mut rgb = vec![[0.0f32; 3]; width * height];
+        for j in 0..height as isize {
+            for i in 0..width as isize {
+                let (x, y) = (origin.0 + i, origin.1 + j);
+                let center = green[j as usize * width + i as usize];
+                let own = mosaic.color(x, y);
+                let pixel = &mut rgb[j as usize * width + i as usize];
+                pixel[GREEN] = center;
+                if own == GREEN {
+                    let horizontal = mosaic.color(x + 1, y);
+                    pixel[horizontal] = center + (difference(i - 1, j) + difference(i + 1, j)) / 2.0;
+                    pixel[RED + BLUE - horizontal] = center + (difference(i, j - 1) + difference(i, j + 1)) / 2.0;
+                } else {
+                    pixel[own] = mosaic.get(x, y);
+                    let diagonals = difference(i - 1, j - 1)
+                        + difference(i + 1, j - 1)
+                        + difference(i - 1, j + 1)
+                        + difference(i + 1, j + 1);
+                    pixel[RED + BLUE - own] = center + diagonals / 4.0;
+                }
+            }
+        }
+
+        let lab = rgb.iter().map(|&pixel| lab(pixel)).collect();
+        Directional { rgb, lab }
+    });
+
+    // Homogeneity: neighbours within the smaller of the two directions'
+    // own luminance and chroma variation
+    let mut homogeneity = [vec![0u8; width * height], vec![0u8; width * height]];
+    for j in 1..height - 1 {
+        for i in 1..width - 1 {
+            let at = j * width + i;
+            let [horizontal, vertical] = &directions;
+            let spread = |d: &Directional, a: usize, b: usize| {
+                let (center, a, b) = (d.lab[at], d.lab[a], d.lab[b]);
+                (
+                    (a[0] - center[0]).abs().max((b[0] - center[0]).abs()),
+                    chroma_distance(a, center).max(chroma_distance(b, center)),
+                )
+            };
+            let (luma_h, chroma_h) = spread(horizontal, at - 1, at + 1);
+            let (luma_v, chroma_v) = spread(vertical, at - width, at + width);
+            let (luma_limit, chroma_limit) = (luma_h.min(luma_v), chroma_h.min(chroma_v));
+
+            for (direction, counts) in directions.iter().zip(homogeneity.iter_mut()) {
+                let center = direction.lab[at];
+                counts[at] = [at - 1, at + 1, at - width, at + width]
+                    .into_iter()
+                    .filter(|&n| {
+                        let neighbour = direction.lab[n];
+                        (neighbour[0] - center[0]).abs() <= luma_limit
+                            && chroma_distance(neighbour, center) <= chroma_limit
+                    })
+                    .count() as u8;
+            }
+        }
+    }
+
+    for j in AHD_MARGIN..AHD_MARGIN + tile_height {
+        for i in AHD_MARGIN..AHD_MARGIN + tile_width {
+            let score = |counts: &Vec<u8>| -> u32 {
+                (j - 1..=j + 1)
+                    .flat_map(|nj| (i - 1..=i + 1).map(move |ni| nj * width + ni))
+                    .map(|n| counts[n] as u32)
+                    .sum()
+            };
+            let (horizontal, vertical) = (score(&homogeneity[0]), score(&homogeneity[1]));
+            let at = j * width + i;
+            let (h, v) = (directions[0].rgb[at], directions[1].rgb[at]);
+            let pixel = if horizontal > vertical {
+                h
+            } else if vertical > horizontal {
+                v
+            } else {
+                [(h[0] + v[0]) / 2.0, (h[1] + v[1]) / 2.0, (h[2] + v[2]) / 2.0]
+            };
+            image
+                .pixel_mut(left + i - AHD_MARGIN, top + j - AHD_MARGIN)
+                .copy_from_slice(&pixel);
+        }
+    }
+}
+
+fn chroma_distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+}
+
+/// CIELAB (D65) of camera RGB taken as linear sRGB; AHD only needs a
+/// roughly perceptual space to compare neighbours in
+fn lab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(|v| v.max(0.0));
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.9505;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.0890;
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::camera_profile::CfaPattern;
+
+    #[test]
+    fn test_demosaic_methods() {
+        // A flat colour, and a vertical edge between two greys, on a GRBG
+        // mosaic so no method relies on red being at the origin
+        let (width, height) = (20u32, 12u32);
+        let cfa = CfaPattern::Grbg;
+        let flat = [4000u16, 8000, 12000];
+        let mut data = Vec::new();
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                data.push(flat[cfa.color_at(x, y)]);
+            }
+        }
+        let mut raw = RawImage {
+            width,
+            height,
+            data,
+            cfa,
+            black_level: 0.0,
+            white_level: 16000.0,
+            as_shot_neutral: None,
+            color_matrices: Vec::new(),
+            forward_matrices: Vec::new(),
+        };
+
+        for method in [DemosaicMethod::Bilinear, DemosaicMethod::Ppg, DemosaicMethod::Ahd] {
+            let image = demosaic(&raw, method);
+            for pixel in image.data.chunks_exact(3) {
+                assert_eq!(pixel, [0.25, 0.5, 0.75], "{}", method.as_str());
+            }
+        }
+
+        for (i, value) in raw.data.iter_mut().enumerate() {
+            *value = if i % width as usize >= 10 { 12000 } else { 4000 };
+        }
+        for method in [DemosaicMethod::Ppg, DemosaicMethod::Ahd] {
+            // Directional methods keep a grey edge free of colour fringes
+            let image = demosaic(&raw, method);
+            for pixel in image.data.chunks_exact(3) {
+                assert!(pixel[0] == pixel[1] && pixel[1] == pixel[2], "{} {:?}", method.as_str(), pixel);
+            }
+        }
+    }
+}
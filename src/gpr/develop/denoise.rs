@@ -0,0 +1,286 @@
+use super::demosaic::mirror;
+use super::tone::CLIP_LEVEL;
+use super::LinearImage;
+use crate::models::camera_profile::{NoiseProfile, NOISE_12MP};
+use crate::models::gpr_file::GprMetadata;
+use crate::models::{CameraProfile, DenoiseMethod, DenoiseSettings};
+
+/// Noise multiples removed at full strength: differences up to this many
+/// standard deviations count as noise
+const STRENGTH_SIGMAS: f32 = 3.0;
+
+/// Bilateral spatial reach for luma, in pixels, and its kernel radius
+const LUMA_SPATIAL_SIGMA: f32 = 1.0;
+const LUMA_RADIUS: isize = 2;
+
+/// Colour noise is coarser: the chroma kernel spans 9x9 pixels, sampled
+/// every other pixel
+const CHROMA_SPATIAL_SIGMA: f32 = 2.5;
+const CHROMA_RADIUS: isize = 2;
+const CHROMA_STEP: isize = 2;
+
+/// Range sigmas at which a neighbour's bilateral weight reaches zero
+const RANGE_CUTOFF: f32 = 3.0;
+
+/// Wavelet scales shrunk
+const WAVELET_LEVELS: usize = 5;
+
+/// The [1 4 6 4 1] / 16 B3-spline kernel
+const B3: [f32; 5] = [0.0625, 0.25, 0.375, 0.25, 0.0625];
+
+/// Standard deviation of unit white noise in each scale of the B3-spline
+/// a trous transform
+const WAVELET_NOISE: [f32; WAVELET_LEVELS] = [0.8907, 0.2007, 0.0856, 0.0413, 0.0205];
+
+/// Noise of the file's camera at its ISO, from the camera profile (or the
+/// 12MP sensor's when the camera has none) scaled by `GprMetadata::iso`
+pub fn noise_profile(metadata: &GprMetadata) -> NoiseProfile {
+    let base = CameraProfile::lookup(&metadata.camera_model)
+        .and_then(|camera| camera.noise)
+        .unwrap_or(NOISE_12MP);
+    let iso = metadata.iso.unwrap_or_else(|| {
+        log::warn!("No ISO in file; denoising for ISO 100");
+        100
+    });
+    log::debug!("Denoising for ISO {}", iso);
+    base.at_iso(iso)
+}
+
+/// Reduce noise in demosaiced camera RGB.
+///
+/// Each channel is first variance-stabilized with the generalized Anscombe
+/// transform for `noise` (variance = scale * signal + offset), which makes
+/// the noise the same everywhere regardless of brightness and ISO. The
+/// result is split into luma and two colour differences that are filtered
+/// with their own strength. Pixels with a clipped channel are left alone
+/// for highlight reconstruction.
+pub fn apply(image: &mut LinearImage, settings: &DenoiseSettings, noise: NoiseProfile) {
+    if settings.is_off() {
+        return;
+    }
+    let (width, height) = (image.width as usize, image.height as usize);
+    let (a, b) = (noise.scale as f32, noise.offset as f32);
+    let stabilize = |v: f32| 2.0 / a * (a * v + b).max(0.0).sqrt();
+    let restore = |t: f32| ((a * t / 2.0).powi(2) - b) / a;
+
+    let pixels = width * height;
+    let mut luma = Vec::with_capacity(pixels);
+    let mut chroma = [Vec::with_capacity(pixels), Vec::with_capacity(pixels)];
+    for pixel in image.data.chunks_exact(3) {
+        let [r, g, b] = [0, 1, 2].map(|c| stabilize(pixel[c]));
+        luma.push((r + g + b) / 3.0);
+        chroma[0].push(b - g);
+        chroma[1].push(r - g);
+    }
+
+    // Unit noise per channel: the mean of three has 1/sqrt(3), a difference
+    // sqrt(2)
+    let luma_threshold = settings.luma * STRENGTH_SIGMAS / 3f32.sqrt();
+    let chroma_threshold = settings.chroma * STRENGTH_SIGMAS * 2f32.sqrt();
+    match settings.method {
+        DenoiseMethod::Bilateral => {
+            if luma_threshold > 0.0 {
+                let planes = [luma];
+                [luma] = bilateral(&planes, width, height, luma_threshold, LUMA_SPATIAL_SIGMA, LUMA_RADIUS, 1);
+            }
+            if chroma_threshold > 0.0 {
+                chroma = bilateral(
+                    &chroma,
+                    width,
+                    height,
+                    chroma_threshold,
+                    CHROMA_SPATIAL_SIGMA,
+                    CHROMA_RADIUS,
+                    CHROMA_STEP,
+                );
+            }
+        }
+        DenoiseMethod::Wavelet => {
+            if luma_threshold > 0.0 {
+                wavelet_shrink(&mut luma, width, height, luma_threshold);
+            }
+            if chroma_threshold > 0.0 {
+                for plane in chroma.iter_mut() {
+                    wavelet_shrink(plane, width, height, chroma_threshold);
+                }
+            }
+        }
+    }
+
+    for (i, pixel) in image.data.chunks_exact_mut(3).enumerate() {
+        if pixel.iter().any(|&v| v >= CLIP_LEVEL) {
+            continue;
+        }
+        let g = luma[i] - (chroma[0][i] + chroma[1][i]) / 3.0;
+        let stabilized = [g + chroma[1][i], g, g + chroma[0][i]];
+        for (value, t) in pixel.iter_mut().zip(stabilized) {
+            *value = restore(t);
+        }
+    }
+}
+
+/// Joint bilateral filter of `N` planes: neighbours `step` pixels apart
+/// within `radius` steps, weighted by a Gaussian of their distance and by
+/// how far their values are from the centre's, in units of `range_sigma`.
+/// The range weight `(1 - d^2 / (3 range_sigma)^2)^2` is a cheap stand-in
+/// for a Gaussian.
+fn bilateral<const N: usize>(
+    planes: &[Vec<f32>; N],
+    width: usize,
+    height: usize,
+    range_sigma: f32,
+    spatial_sigma: f32,
+    radius: isize,
+    step: isize,
+) -> [Vec<f32>; N] {
+    // Offsets with their index delta and spatial weight
+    let mut offsets = Vec::new();
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let distance2 = ((dx * dx + dy * dy) * step * step) as f32;
+            let delta = (dy * width as isize + dx) * step;
+            offsets.push((dx * step, dy * step, delta, (-distance2 / (2.0 * spatial_sigma * spatial_sigma)).exp()));
+        }
+    }
+    let reach = (radius * step) as usize;
+    let cutoff = RANGE_CUTOFF * range_sigma;
+    let range_scale = 1.0 / (cutoff * cutoff);
+
+    let mut output: [Vec<f32>; N] = std::array::from_fn(|_| vec![0.0; width * height]);
+    for y in 0..height {
+        for x in 0..width {
+            let at = y * width + x;
+            // Only pixels near the border need mirrored neighbours
+            let inside = x >= reach && y >= reach && x + reach < width && y + reach < height;
+            let centre: [f32; N] = std::array::from_fn(|p| planes[p][at]);
+            let mut sums = [0.0f32; N];
+            let mut total = 0.0f32;
+            for &(dx, dy, delta, spatial) in &offsets {
+                let neighbour = if inside {
+                    (at as isize + delta) as usize
+                } else {
+                    mirror(y as isize + dy, height) * width + mirror(x as isize + dx, width)
+                };
+                let values: [f32; N] = std::array::from_fn(|p| planes[p][neighbour]);
+                let distance2: f32 = values.iter().zip(centre).map(|(v, c)| (v - c) * (v - c)).sum();
+                let range = (1.0 - distance2 * range_scale).max(0.0);
+                let weight = spatial * range * range;
+                total += weight;
+                for (sum, value) in sums.iter_mut().zip(values) {
+                    *sum += weight * value;
+                }
+            }
+            for (plane, sum) in output.iter_mut().zip(sums) {
+                plane[at] = sum / total;
+            }
+        }
+    }
+    output
+}
+
+/// Soft-threshold the detail of each a trous wavelet scale at `threshold`
+/// times the noise that scale holds
+fn wavelet_shrink(plane: &mut [f32], width: usize, height: usize, threshold: f32) {
+    let mut coarse = plane.to_vec();
+    let mut result = vec![0.0f32; plane.len()];
+    for (level, noise) in WAVELET_NOISE.iter().enumerate() {
+        let smoothed = b3_smooth(&coarse, width, height, 1 << level);
+        let limit = threshold * noise;
+        for ((out, fine), smooth) in result.iter_mut().zip(&coarse).zip(&smoothed) {
+            let detail = fine - smooth;
+            *out += detail.signum() * (detail.abs() - limit).max(0.0);
+        }
+        coarse = smoothed;
+    }
+    for ((value, detail), base) in plane.iter_mut().zip(result).zip(coarse) {
+        *value = base + detail;
+    }
+}
+
+/// Separable [1 4 6 4 1] / 16 smoothing with taps `spacing` pixels apart
+fn b3_smooth(plane: &[f32], width: usize, height: usize, spacing: usize) -> Vec<f32> {
+    let mut rows = vec![0.0f32; plane.len()];
+    for (row, out) in plane.chunks_exact(width).zip(rows.chunks_exact_mut(width)) {
+        smooth_line(row, out, spacing);
+    }
+
+    // Vertical pass a whole row at a time
+    let mut output = vec![0.0f32; plane.len()];
+    for (y, out) in output.chunks_exact_mut(width).enumerate() {
+        for (t, weight) in B3.iter().enumerate() {
+            let source = mirror(y as isize + (t as isize - 2) * spacing as isize, height);
+            for (value, row) in out.iter_mut().zip(&rows[source * width..(source + 1) * width]) {
+                *value += weight * row;
+            }
+        }
+    }
+    output
+}
+
+/// Horizontal pass of the B3 kernel over a row, mirrored at the ends
+fn smooth_line(line: &[f32], out: &mut [f32], spacing: usize) {
+    let n = line.len();
+    let reach = 2 * spacing;
+    for (i, value) in out.iter_mut().enumerate() {
+        *value = if i >= reach && i + reach < n {
+            B3[0] * line[i - reach]
+                + B3[1] * line[i - spacing]
+                + B3[2] * line[i]
+                + B3[3] * line[i + spacing]
+                + B3[4] * line[i + reach]
+        } else {
+            B3
+                .iter()
+                .enumerate()
+                .map(|(t, w)| w * line[mirror(i as isize + (t as isize - 2) * spacing as isize, n)])
+                .sum()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_denoise() {
+        // The profile comes from the caller's metadata, ISO 100 without one
+        let metadata = GprMetadata { camera_model: "GoPro HERO7 Black".to_string(), iso: Some(1600), ..Default::default() };
+        let noise = noise_profile(&metadata);
+        assert_eq!(noise, NOISE_12MP.at_iso(1600));
+        assert_eq!(noise_profile(&GprMetadata { iso: None, ..metadata }), NOISE_12MP);
+        let level = 0.18f32;
+        let sigma = (noise.scale as f32 * level + noise.offset as f32).sqrt();
+
+        // Flat grey with repeatable pseudo-random noise of the profile's size
+        let mut seed = 12345u32;
+        let mut image = LinearImage::new(64, 64);
+        for value in image.data.iter_mut() {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let uniform = (seed >> 8) as f32 / (1u32 << 24) as f32 - 0.5;
+            // Uniform noise with a standard deviation of `sigma`
+            *value = level + uniform * sigma * 12f32.sqrt();
+        }
+        let deviation = |image: &LinearImage| {
+            let n = image.data.len() as f32;
+            let mean = image.data.iter().sum::<f32>() / n;
+            let variance = image.data.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+            (mean, variance.sqrt())
+        };
+        let (_, before) = deviation(&image);
+
+        for method in [DenoiseMethod::Bilateral, DenoiseMethod::Wavelet] {
+            let mut denoised = image.clone();
+            let settings = DenoiseSettings { method, luma: 1.0, chroma: 1.0 };
+            apply(&mut denoised, &settings, noise);
+            let (mean, after) = deviation(&denoised);
+            assert!((mean - level).abs() < level * 0.01, "{:?} mean {}", method, mean);
+            assert!(after < before / 2.0, "{:?} {} -> {}", method, before, after);
+        }
+
+        // Nothing changes with both strengths at zero
+        let mut untouched = image.clone();
+        apply(&mut untouched, &DenoiseSettings::OFF, noise);
+        assert_eq!(untouched.data, image.data);
+    }
+}
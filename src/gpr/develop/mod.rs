@@ -0,0 +1,96 @@
+pub mod color;
+pub mod demosaic;
+pub mod denoise;
+pub mod raw;
+pub mod tone;
+pub mod white_balance;
+
+use crate::models::camera_profile::NoiseProfile;
+use crate::models::color_space::Transfer;
+use crate::models::DevelopSettings;
+use anyhow::Result;
+use image::{ImageBuffer, Rgb};
+pub use raw::RawImage;
+
+/// Linear RGB image, three samples per pixel, where 1.0 is the sensor's
+/// white level after black subtraction
+#[derive(Debug, Clone)]
+pub struct LinearImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<f32>,
+}
+
+impl LinearImage {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0.0; width as usize * height as usize * 3],
+        }
+    }
+
+    pub fn pixel_mut(&mut self, x: usize, y: usize) -> &mut [f32] {
+        let at = (y * self.width as usize + x) * 3;
+        &mut self.data[at..at + 3]
+    }
+
+    /// Clip to 0-1 and encode with a transfer curve
+    pub fn to_rgb8(&self, transfer: Transfer) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        // 16-bit table: pure power curves are too steep near black for less
+        let table: Vec<u8> = (0..=u16::MAX)
+            .map(|i| (transfer.encode(i as f32 / 65535.0) * 255.0 + 0.5) as u8)
+            .collect();
+        let data = self
+            .data
+            .iter()
+            .map(|&v| table[(v.clamp(0.0, 1.0) * 65535.0 + 0.5) as usize])
+            .collect();
+        ImageBuffer::from_raw(self.width, self.height, data)
+            .expect("LinearImage holds three samples per pixel")
+    }
+}
+
+/// Develop GPR contents from the sensor mosaic instead of the SDK's RGB
+/// conversion, denoising for `noise` (see [`denoise::noise_profile`])
+pub fn develop(
+    gpr_data: &[u8],
+    settings: &DevelopSettings,
+    noise: NoiseProfile,
+) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>> {
+    let raw = RawImage::decode(gpr_data)?;
+    let mut image = demosaic::demosaic(&raw, settings.demosaic);
+    if !settings.denoise.is_off() {
+        denoise::apply(&mut image, &settings.denoise, noise);
+    }
+    apply_adjustments(&mut image, &raw, settings);
+    tone::apply(&mut image, &settings.tone);
+    Ok(image.to_rgb8(settings.color_space.transfer()))
+}
+
+/// Highlight reconstruction, white balance, exposure and black/white point,
+/// in that order, on linear camera RGB, then conversion to the output
+/// colour space
+pub fn apply_adjustments(image: &mut LinearImage, raw: &RawImage, settings: &DevelopSettings) {
+    let balance = white_balance::multipliers(&settings.white_balance, raw);
+    if settings.tone.reconstruct_highlights {
+        tone::reconstruct_highlights(image, balance);
+    }
+
+    let exposure = 2f32.powf(settings.exposure_ev);
+    let gains = balance.map(|g| g * exposure);
+    log::debug!("White balance gains with exposure: {:?}", gains);
+
+    let matrix = color::camera_to_output(raw, &settings.white_balance, balance, settings.color_space);
+    log::debug!("Camera to {} matrix: {:?}", settings.color_space.as_str(), matrix);
+
+    let black = settings.black_point;
+    let range = (settings.white_point - black).max(f32::EPSILON);
+    for pixel in image.data.chunks_exact_mut(3) {
+        let mut camera = [0.0; 3];
+        for ((out, value), gain) in camera.iter_mut().zip(pixel.iter()).zip(gains) {
+            *out = (value * gain - black) / range;
+        }
+        pixel.copy_from_slice(&white_balance::multiply(&matrix, camera));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::gpr::ffi::*;
+use crate::gpr::validate::{check_dimensions, validate_input};
+use crate::models::camera_profile::CfaPattern;
+use anyhow::{anyhow, bail, Context, Result};
+use exif::{In, Tag, Value};
+
+const TILE_WIDTH: u16 = 322;
+const TILE_LENGTH: u16 = 323;
+const CFA_REPEAT_PATTERN_DIM: u16 = 33421;
+const CFA_PATTERN: u16 = 33422;
+const BLACK_LEVEL: u16 = 50714;
+const WHITE_LEVEL: u16 = 50717;
+const COLOR_MATRIX_1: u16 = 50721;
+const COLOR_MATRIX_2: u16 = 50722;
+const AS_SHOT_NEUTRAL: u16 = 50728;
+const FORWARD_MATRIX_1: u16 = 50964;
+const FORWARD_MATRIX_2: u16 = 50965;
+const CALIBRATION_ILLUMINANT_1: u16 = 50778;
+const CALIBRATION_ILLUMINANT_2: u16 = 50779;
+
+/// Row-major 3x3 matrix
+pub type Matrix3 = [[f32; 3]; 3];
+
+/// The sensor mosaic of a GPR file with the DNG tags needed to develop it
+#[derive(Debug, Clone)]
+pub struct RawImage {
+    pub width: u32,
+    pub height: u32,
+    /// One sample per photosite, row-major
+    pub data: Vec<u16>,
+    pub cfa: CfaPattern,
+    pub black_level: f32,
+    pub white_level: f32,
+    /// Camera RGB of a neutral surface under the capture light
+    pub as_shot_neutral: Option<[f32; 3]>,
+    /// XYZ-to-camera matrices, with the colour temperature each was
+    /// calibrated under
+    pub color_matrices: Vec<(f32, Matrix3)>,
+    /// White-balanced camera to D50 XYZ matrices, by calibration
+    /// temperature. GoPro files usually have none.
+    pub forward_matrices: Vec<(f32, Matrix3)>,
+}
+
+impl RawImage {
+    /// Expand GPR contents to an uncompressed DNG with the SDK and read its
+    /// mosaic
+    pub fn decode(gpr_data: &[u8]) -> Result<RawImage> {
+        validate_input(gpr_data)?;
+        let dng = convert_to_dng(gpr_data)?;
+        Self::from_dng(&dng).context("Failed to read the DNG produced by the GPR SDK")
+    }
+
+    /// Read an uncompressed 16-bit CFA DNG
+    pub fn from_dng(dng: &[u8]) -> Result<RawImage> {
+        let exif_data = exif::Reader::new()
+            .read_from_container(&mut std::io::Cursor::new(dng))
+            .context("Failed to read DNG tags")?;
+        let field = |tag: u16| exif_data.get_field(Tag(exif::Context::Tiff, tag), In::PRIMARY);
+        let numbers = |tag: u16| field(tag).map(|f| numbers(&f.value)).unwrap_or_default();
+        let first = |tag: u16| numbers(tag).first().copied();
+
+        let width = first(Tag::ImageWidth.number()).ok_or_else(|| anyhow!("No image width"))? as u32;
+        let height = first(Tag::ImageLength.number()).ok_or_else(|| anyhow!("No image height"))? as u32;
+        check_dimensions(width, height)?;
+
+        if first(Tag::Compression.number()).unwrap_or(1.0) != 1.0 {
+            bail!("Raw data is compressed");
+        }
+        if first(Tag::BitsPerSample.number()) != Some(16.0) {
+            bail!("Raw data is not 16 bits per sample");
+        }
+        if numbers(CFA_REPEAT_PATTERN_DIM) != [2.0, 2.0] {
+            bail!("Only 2x2 colour filter arrays are supported");
+        }
+        let cfa = match field(CFA_PATTERN).map(|f| &f.value) {
+            Some(Value::Byte(pattern)) => CfaPattern::from_dng(pattern),
+            _ => None,
+        }
+        .ok_or_else(|| anyhow!("Unsupported or missing CFAPattern"))?;
+
+        let black = numbers(BLACK_LEVEL);
+        let black_level = match black.len() {
+            0 => 0.0,
+            n => black.iter().sum::<f64>() as f32 / n as f32,
+        };
+        let white_level = first(WHITE_LEVEL).unwrap_or(65535.0) as f32;
+        if white_level <= black_level {
+            bail!("White level {} is not above black level {}", white_level, black_level);
+        }
+
+        let as_shot_neutral = match numbers(AS_SHOT_NEUTRAL).as_slice() {
+            [r, g, b] if *r > 0.0 && *g > 0.0 && *b > 0.0 => Some([*r as f32, *g as f32, *b as f32]),
+            _ => None,
+        };
+
+        let calibrated = |tags: [u16; 2]| {
+            tags.into_iter()
+                .zip([CALIBRATION_ILLUMINANT_1, CALIBRATION_ILLUMINANT_2])
+                .filter_map(|(matrix_tag, illuminant_tag)| {
+                    let temperature = illuminant_temperature(first(illuminant_tag).unwrap_or(0.0) as u16);
+                    matrix3(&numbers(matrix_tag)).map(|matrix| (temperature, matrix))
+                })
+                .collect::<Vec<_>>()
+        };
+        let color_matrices = calibrated([COLOR_MATRIX_1, COLOR_MATRIX_2]);
+        let forward_matrices = calibrated([FORWARD_MATRIX_1, FORWARD_MATRIX_2]);
+
+        let data = read_samples(dng, &exif_data, width, height, exif_data.little_endian())?;
+
+        Ok(RawImage {
+            width,
+            height,
+            data,
+            cfa,
+            black_level,
+            white_level,
+            as_shot_neutral,
+            color_matrices,
+            forward_matrices,
+        })
+    }
+
+    /// Sample at (x, y) with the black level removed, scaled so the white
+    /// level is 1.0
+    pub fn normalized(&self, x: usize, y: usize) -> f32 {
+        let value = self.data[y * self.width as usize + x] as f32;
+        (value - self.black_level) / (self.white_level - self.black_level)
+    }
+}
+
+/// Run the SDK's GPR to DNG conversion
+fn convert_to_dng(gpr_data: &[u8]) -> Result<Vec<u8>> {
+    let allocator = create_allocator();
+
+    // The SDK takes a mutable pointer but only reads the input buffer
+    let mut inp_buffer = gpr_buffer {
+        buffer: gpr_data.as_ptr() as *mut std::os::raw::c_void,
+        size: gpr_data.len(),
+    };
+    let mut out_buffer = gpr_buffer {
+        buffer: std::ptr::null_mut(),
+        size: 0,
+    };
+
+    let mut parameters: gpr_parameters = unsafe { std::mem::zeroed() };
+    unsafe {
+        gpr_parameters_set_defaults(&mut parameters);
+    }
+    if !unsafe { gpr_parse_metadata(&allocator, &mut inp_buffer, &mut parameters) } {
+        bail!("GPR SDK failed to parse metadata");
+    }
+
+    let converted =
+        unsafe { gpr_convert_gpr_to_dng(&allocator, &parameters, &mut inp_buffer, &mut out_buffer) };
+    if !converted || out_buffer.buffer.is_null() {
+        bail!("GPR SDK failed to convert to DNG");
+    }
+
+    let dng = unsafe { std::slice::from_raw_parts(out_buffer.buffer as *const u8, out_buffer.size) }.to_vec();
+    if let Some(free_fn) = allocator.mem_free {
+        free_fn(out_buffer.buffer);
+    }
+    Ok(dng)
+}
+
+/// Strip or tile data of the primary image as 16-bit samples
+fn read_samples(
+    dng: &[u8],
+    exif_data: &exif::Exif,
+    width: u32,
+    height: u32,
+    little_endian: bool,
+) -> Result<Vec<u16>> {
+    let field_numbers = |tag: Tag| {
+        exif_data
+            .get_field(tag, In::PRIMARY)
+            .map(|f| numbers(&f.value))
+            .unwrap_or_default()
+    };
+    let (width, height) = (width as usize, height as usize);
+    let mut samples = vec![0u16; width * height];
+
+    let read_segment = |offset: f64, count: f64| -> Result<&[u8]> {
+        let (offset, count) = (offset as usize, count as usize);
+        dng.get(offset..offset + count)
+            .ok_or_else(|| anyhow!("Raw data at byte {} runs past the end of the file", offset))
+    };
+    let sample = |bytes: &[u8]| {
+        if little_endian {
+            u16::from_le_bytes([bytes[0], bytes[1]])
+        } else {
+            u16::from_be_bytes([bytes[0], bytes[1]])
+        }
+    };
+
+    let tile_offsets = field_numbers(Tag::TileOffsets);
+    if !tile_offsets.is_empty() {
+        let tile_width = field_numbers(Tag(exif::Context::Tiff, TILE_WIDTH)).first().copied().unwrap_or(0.0) as usize;
+        let tile_length = field_numbers(Tag(exif::Context::Tiff, TILE_LENGTH)).first().copied().unwrap_or(0.0) as usize;
+        if tile_width == 0 || tile_length == 0 {
+            bail!("Tiles without a tile size");
+        }
+        let tiles_across = width.div_ceil(tile_width);
+        let counts = field_numbers(Tag::TileByteCounts);
+
+        for (index, (&offset, &count)) in tile_offsets.iter().zip(&counts).enumerate() {
+            let tile = read_segment(offset, count)?;
+            let (left, top) = ((index % tiles_across) * tile_width, (index / tiles_across) * tile_length);
+            for row in 0..tile_length.min(height.saturating_sub(top)) {
+                for col in 0..tile_width.min(width - left) {
+                    let at = (row * tile_width + col) * 2;
+                    let bytes = tile.get(at..at + 2).ok_or_else(|| anyhow!("Tile {} is short", index))?;
+                    samples[(top + row) * width + left + col] = sample(bytes);
+                }
+            }
+        }
+    } else {
+        let offsets = field_numbers(Tag::StripOffsets);
+        let counts = field_numbers(Tag::StripByteCounts);
+        if offsets.is_empty() {
+            bail!("No strip or tile offsets");
+        }
+        let mut data = Vec::with_capacity(width * height * 2);
+        for (&offset, &count) in offsets.iter().zip(&counts) {
+            data.extend_from_slice(read_segment(offset, count)?);
+        }
+        if data.len() < samples.len() * 2 {
+            bail!("Raw data is {} bytes, expected {}", data.len(), samples.len() * 2);
+        }
+        for (value, bytes) in samples.iter_mut().zip(data.chunks_exact(2)) {
+            *value = sample(bytes);
+        }
+    }
+
+    Ok(samples)
+}
+
+/// Numeric tag values as f64, with rationals divided out
+fn numbers(value: &Value) -> Vec<f64> {
+    match value {
+        Value::Byte(v) => v.iter().map(|&x| x as f64).collect(),
+        Value::Short(v) => v.iter().map(|&x| x as f64).collect(),
+        Value::Long(v) => v.iter().map(|&x| x as f64).collect(),
+        Value::SLong(v) => v.iter().map(|&x| x as f64).collect(),
+        Value::Rational(v) => v.iter().map(|x| x.to_f64()).collect(),
+        Value::SRational(v) => v.iter().map(|x| x.to_f64()).collect(),
+        Value::Float(v) => v.iter().map(|&x| x as f64).collect(),
+        Value::Double(v) => v.clone(),
+        _ => Vec::new(),
+    }
+}
+
+fn matrix3(values: &[f64]) -> Option<Matrix3> {
+    (values.len() == 9).then(|| {
+        let mut matrix = [[0.0; 3]; 3];
+        for (i, value) in values.iter().enumerate() {
+            matrix[i / 3][i % 3] = *value as f32;
+        }
+        matrix
+    })
+}
+
+/// Colour temperature of an EXIF LightSource / DNG CalibrationIlluminant
+fn illuminant_temperature(illuminant: u16) -> f32 {
+    match illuminant {
+        3 => 2850.0,         // Tungsten
+        17 => 2856.0,        // Standard light A
+        24 => 3200.0,        // ISO studio tungsten
+        15 => 3450.0,        // White fluorescent
+        2 | 14 => 4150.0,    // Fluorescent, cool white fluorescent
+        18 => 4874.0,        // Standard light B
+        13 => 5000.0,        // Day white fluorescent
+        23 => 5003.0,        // D50
+        1 | 4 | 9 => 5500.0, // Daylight, flash, fine weather
+        20 => 5503.0,        // D55
+        12 => 6430.0,        // Daylight fluorescent
+        10 => 6500.0,        // Cloudy
+        21 => 6504.0,        // D65
+        19 => 6774.0,        // Standard light C
+        11 => 7500.0,        // Shade
+        22 => 7504.0,        // D75
+        _ => 5000.0,
+    }
+}
@@ -0,0 +1,133 @@
+use super::LinearImage;
+use crate::models::{ToneCurve, ToneSettings};
+
+/// Normalized raw level from which a channel counts as clipped
+pub const CLIP_LEVEL: f32 = 0.99;
+
+/// Linear level where the filmic shoulder starts to bend
+const FILMIC_KNEE: f32 = 0.6;
+
+/// Exponent of the perceptual space the sliders work in
+const PERCEPTUAL_GAMMA: f32 = 2.2;
+
+/// Scale of the shadows and highlights bumps: a peak of 0.14 times the
+/// 27/4 that normalizes `v * (1 - v)^2`
+const SLIDER_STRENGTH: f32 = 0.14 * 27.0 / 4.0;
+
+/// Raise clipped channels of demosaiced camera RGB so that, once `gains`
+/// are applied, they are at least as bright as the brightest channel. This
+/// turns blown highlights neutral and keeps detail above white for the
+/// highlights slider and the filmic curve, where plain clipping would leave
+/// them magenta.
+pub fn reconstruct_highlights(image: &mut LinearImage, gains: [f32; 3]) {
+    for pixel in image.data.chunks_exact_mut(3) {
+        if pixel.iter().all(|&v| v < CLIP_LEVEL) {
+            continue;
+        }
+        let brightest = pixel
+            .iter()
+            .zip(gains)
+            .map(|(v, gain)| v * gain)
+            .fold(0.0, f32::max);
+        for (value, gain) in pixel.iter_mut().zip(gains) {
+            if *value >= CLIP_LEVEL {
+                *value = value.max(brightest / gain);
+            }
+        }
+    }
+}
+
+/// Map white-balanced linear RGB through the curve and sliders. The curve
+/// acts on the largest channel and every channel is scaled by the same
+/// factor, so hues are kept.
+pub fn apply(image: &mut LinearImage, tone: &ToneSettings) {
+    if *tone == ToneSettings::NEUTRAL {
+        return;
+    }
+    for pixel in image.data.chunks_exact_mut(3) {
+        let norm = pixel.iter().copied().fold(0.0, f32::max);
+        if norm <= 0.0 {
+            continue;
+        }
+        let scale = map(norm, tone) / norm;
+        for value in pixel.iter_mut() {
+            *value *= scale;
+        }
+    }
+}
+
+/// Tone curve for a single linear value, which may be above 1
+pub fn map(linear: f32, tone: &ToneSettings) -> f32 {
+    let mut value = match tone.curve {
+        ToneCurve::Parametric => linear,
+        ToneCurve::Filmic => shoulder(linear, FILMIC_KNEE),
+    };
+    if tone.highlights < 0.0 {
+        // Compress everything above a knee that drops as the slider goes down
+        value = shoulder(value, 1.0 + tone.highlights * 0.5);
+    }
+
+    let mut v = value.clamp(0.0, 1.0).powf(1.0 / PERCEPTUAL_GAMMA);
+    // Bumps that peak in the dark (1/3) and bright (2/3) tones and leave
+    // black and white fixed, kept small enough that the curve stays monotonic
+    v += tone.shadows * SLIDER_STRENGTH * v * (1.0 - v) * (1.0 - v);
+    if tone.highlights > 0.0 {
+        v += tone.highlights * SLIDER_STRENGTH * v * v * (1.0 - v);
+    }
+    // S-curve around the middle, monotonic for contrast within -1 to 1
+    v -= tone.contrast * (2.0 * std::f32::consts::PI * v).sin() / (2.0 * std::f32::consts::PI);
+
+    v.clamp(0.0, 1.0).powf(PERCEPTUAL_GAMMA)
+}
+
+/// Linear below `knee`, then an exponential roll-off that approaches 1 and
+/// keeps the slope continuous
+fn shoulder(value: f32, knee: f32) -> f32 {
+    if value <= knee {
+        value
+    } else {
+        let range = 1.0 - knee;
+        knee + range * (1.0 - (-(value - knee) / range).exp())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tone_map() {
+        let neutral = ToneSettings::NEUTRAL;
+        for value in [0.0, 0.01, 0.18, 0.5, 1.0] {
+            assert!((map(value, &neutral) - value).abs() < 1e-4);
+        }
+
+        // The filmic curve keeps shadows, stays below white and is monotonic
+        let filmic = ToneSettings {
+            curve: ToneCurve::Filmic,
+            ..neutral
+        };
+        assert!((map(0.1, &filmic) - 0.1).abs() < 1e-4);
+        let samples: Vec<f32> = (0..30).map(|i| map(i as f32 * 0.1, &filmic)).collect();
+        assert!(samples.windows(2).all(|w| w[1] > w[0]));
+        assert!(samples.iter().all(|&v| v < 1.0));
+
+        // Pulling highlights down separates values that would clip
+        let recover = ToneSettings {
+            highlights: -1.0,
+            ..neutral
+        };
+        assert!(map(1.0, &recover) < map(2.0, &recover));
+        let lift = ToneSettings {
+            shadows: 1.0,
+            ..neutral
+        };
+        assert!(map(0.05, &lift) > 0.1);
+
+        // A pixel with green clipped becomes neutral instead of magenta
+        let mut image = LinearImage::new(1, 1);
+        image.data = vec![0.6, 1.0, 0.7];
+        reconstruct_highlights(&mut image, [2.0, 1.0, 1.5]);
+        assert_eq!(image.data, vec![0.6, 1.2, 0.7]);
+    }
+}
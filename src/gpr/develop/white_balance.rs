@@ -0,0 +1,131 @@
+use super::raw::{Matrix3, RawImage};
+use crate::models::WhiteBalance;
+
+/// Standard XYZ (D65) to linear sRGB, used as the camera matrix for files
+/// without a ColorMatrix tag
+const XYZ_TO_SRGB: Matrix3 = [
+    [3.2406, -1.5372, -0.4986],
+    [-0.9689, 1.8758, 0.0415],
+    [0.0557, -0.2040, 1.0570],
+];
+
+/// Per-channel gains that make a neutral surface under the chosen light
+/// equal in camera RGB, scaled so the smallest gain is 1 and clipped
+/// highlights stay white
+pub fn multipliers(white_balance: &WhiteBalance, raw: &RawImage) -> [f32; 3] {
+    let neutral = match white_balance.kelvin_tint() {
+        None => raw.as_shot_neutral.unwrap_or_else(|| {
+            log::warn!("No AsShotNeutral tag; leaving the raw colour unbalanced");
+            [1.0; 3]
+        }),
+        Some((kelvin, _)) => {
+            let (x, y) = kelvin_to_xy(kelvin as f32);
+            let white = [x / y, 1.0, (1.0 - x - y) / y];
+            multiply(&color_matrix(raw, kelvin as f32), white)
+        }
+    };
+
+    let mut gains = neutral.map(|n| if n > 0.0 { 1.0 / n } else { 1.0 });
+    if let Some((_, tint)) = white_balance.kelvin_tint() {
+        // Positive tint removes green, shifting towards magenta
+        gains[1] *= 1.0 - tint as f32 * 0.003;
+    }
+
+    let smallest = gains.iter().copied().fold(f32::INFINITY, f32::min);
+    gains.map(|g| g / smallest)
+}
+
+/// The file's XYZ-to-camera matrix for a colour temperature
+pub fn color_matrix(raw: &RawImage, kelvin: f32) -> Matrix3 {
+    interpolate(&raw.color_matrices, kelvin).unwrap_or(XYZ_TO_SRGB)
+}
+
+/// Calibrated matrices interpolated in inverse temperature between the
+/// lowest and highest calibration, as the DNG specification describes
+pub fn interpolate(matrices: &[(f32, Matrix3)], kelvin: f32) -> Option<Matrix3> {
+    let mut matrices = matrices.to_vec();
+    matrices.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    match matrices.as_slice() {
+        [] => None,
+        [(_, only)] => Some(*only),
+        [(low_kelvin, low), .., (high_kelvin, high)] => {
+            let weight = if low_kelvin == high_kelvin {
+                0.5
+            } else {
+                ((1.0 / kelvin - 1.0 / high_kelvin) / (1.0 / low_kelvin - 1.0 / high_kelvin)).clamp(0.0, 1.0)
+            };
+            let mut matrix = [[0.0; 3]; 3];
+            for (row, out) in matrix.iter_mut().enumerate() {
+                for (col, value) in out.iter_mut().enumerate() {
+                    *value = weight * low[row][col] + (1.0 - weight) * high[row][col];
+                }
+            }
+            Some(matrix)
+        }
+    }
+}
+
+pub fn multiply(matrix: &Matrix3, vector: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
+
+/// CIE 1931 chromaticity of a light source: the daylight locus from 4000 K
+/// and the Planckian locus (Kim et al. approximation) below
+pub fn kelvin_to_xy(kelvin: f32) -> (f32, f32) {
+    let t = kelvin.clamp(1667.0, 25000.0) as f64;
+
+    let (x, y) = if t >= 4000.0 {
+        let x = if t <= 7000.0 {
+            -4.6070e9 / t.powi(3) + 2.9678e6 / t.powi(2) + 0.09911e3 / t + 0.244063
+        } else {
+            -2.0064e9 / t.powi(3) + 1.9018e6 / t.powi(2) + 0.24748e3 / t + 0.237040
+        };
+        (x, -3.000 * x * x + 2.870 * x - 0.275)
+    } else {
+        let x = -0.2661239e9 / t.powi(3) - 0.2343589e6 / t.powi(2) + 0.8776956e3 / t + 0.179910;
+        let y = if t <= 2222.0 {
+            -1.1063814 * x.powi(3) - 1.34811020 * x.powi(2) + 2.18555832 * x - 0.20219683
+        } else {
+            -0.9549476 * x.powi(3) - 1.37418593 * x.powi(2) + 2.09137015 * x - 0.16748867
+        };
+        (x, y)
+    };
+
+    (x as f32, y as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::camera_profile::CfaPattern;
+    use crate::models::develop::WhiteBalancePreset;
+
+    #[test]
+    fn test_multipliers() {
+        // D65 is close to (0.3127, 0.3290)
+        let (x, y) = kelvin_to_xy(6504.0);
+        assert!((x - 0.3127).abs() < 0.001 && (y - 0.3290).abs() < 0.001);
+
+        let mut raw = RawImage {
+            width: 2,
+            height: 2,
+            data: vec![0; 4],
+            cfa: CfaPattern::Rggb,
+            black_level: 0.0,
+            white_level: 1.0,
+            as_shot_neutral: Some([0.5, 1.0, 0.8]),
+            color_matrices: Vec::new(),
+            forward_matrices: Vec::new(),
+        };
+        assert_eq!(multipliers(&WhiteBalance::AsShot, &raw), [2.0, 1.0, 1.25]);
+
+        // With the sRGB fallback matrix, D65 is neutral and tungsten needs
+        // much more blue than red
+        raw.color_matrices.clear();
+        let d65 = multipliers(&WhiteBalance::Custom { kelvin: 6504, tint: 0 }, &raw);
+        assert!(d65.iter().all(|g| (g - 1.0).abs() < 0.02));
+        let tungsten = multipliers(&WhiteBalance::Preset(WhiteBalancePreset::Tungsten), &raw);
+        assert!(tungsten[2] > 2.0 * tungsten[0]);
+    }
+}
@@ -0,0 +1,281 @@
+use crate::models::gpr_file::GprMetadata;
+use crate::models::ConversionConfig;
+use anyhow::{Context, Result};
+use exif::experimental::Writer;
+use exif::{Context as TagContext, Field, In, Rational, Tag, Value};
+use std::io::Cursor;
+
+/// Windows star rating (0-5), read by Explorer and most photo managers
+const RATING: Tag = Tag(TagContext::Tiff, 0x4746);
+
+/// Windows keywords, UTF-16LE separated by semicolons
+const XP_KEYWORDS: Tag = Tag(TagContext::Tiff, 0x9c9e);
+
+/// Windows comment and author, UTF-16LE like the keywords
+const XP_COMMENT: Tag = Tag(TagContext::Tiff, 0x9c9c);
+const XP_AUTHOR: Tag = Tag(TagContext::Tiff, 0x9c9d);
+
+/// Build a TIFF-structured EXIF block for embedding in converted outputs
+///
+/// `metadata` is expected to already carry any output adjustments (such as
+/// the corrected capture time); this function only serializes it.
+pub fn build_exif(metadata: &GprMetadata, config: &ConversionConfig) -> Result<Vec<u8>> {
+    let mut fields = vec![
+        ascii_field(Tag::Model, In::PRIMARY, &metadata.camera_model),
+        ascii_field(
+            Tag::Software,
+            In::PRIMARY,
+            concat!("gprprotool ", env!("CARGO_PKG_VERSION")),
+        ),
+    ];
+
+    if let Some(ref date) = metadata.date_taken {
+        fields.push(ascii_field(Tag::DateTime, In::PRIMARY, date));
+        fields.push(ascii_field(Tag::DateTimeOriginal, In::PRIMARY, date));
+    }
+
+    if let Some(offset) = config.time_shift.offset_time_original() {
+        fields.push(ascii_field(Tag::OffsetTime, In::PRIMARY, &offset));
+        fields.push(ascii_field(Tag::OffsetTimeOriginal, In::PRIMARY, &offset));
+    }
+
+    if let Some(ref serial) = metadata.serial_number {
+        fields.push(ascii_field(Tag::BodySerialNumber, In::PRIMARY, serial));
+    }
+
+    if let Some(ref owner) = metadata.owner_name {
+        fields.push(ascii_field(Tag::CameraOwnerName, In::PRIMARY, owner));
+    }
+
+    // The ASCII tags get a 7-bit rendering of edited text; anything they
+    // cannot hold is kept in the Unicode tags (copyright has none, so it
+    // relies on the transliteration and the XMP sidecar)
+    let edits = &config.metadata_edits;
+    if let Some(ref caption) = edits.caption {
+        fields.push(ascii_field(Tag::ImageDescription, In::PRIMARY, &ascii_text(caption)));
+        if !caption.is_ascii() {
+            fields.push(utf16_field(XP_COMMENT, caption));
+            fields.push(Field {
+                tag: Tag::UserComment,
+                ifd_num: In::PRIMARY,
+                value: Value::Undefined(user_comment_bytes(caption), 0),
+            });
+        }
+    }
+    if let Some(ref creator) = edits.creator {
+        fields.push(ascii_field(Tag::Artist, In::PRIMARY, &ascii_text(creator)));
+        if !creator.is_ascii() {
+            fields.push(utf16_field(XP_AUTHOR, creator));
+        }
+    }
+    if let Some(ref copyright) = edits.copyright {
+        fields.push(ascii_field(Tag::Copyright, In::PRIMARY, &ascii_text(copyright)));
+    }
+    if let Some(rating) = edits.rating {
+        fields.push(Field {
+            tag: RATING,
+            ifd_num: In::PRIMARY,
+            value: Value::Short(vec![rating.min(5) as u16]),
+        });
+    }
+    if !edits.keywords.is_empty() {
+        fields.push(utf16_field(XP_KEYWORDS, &edits.keywords.join(";")));
+    }
+
+    fields.push(Field {
+        tag: Tag::Orientation,
+        ifd_num: In::PRIMARY,
+        value: Value::Short(vec![config.output_orientation(metadata)]),
+    });
+
+    if let Some(iso) = metadata.iso {
+        fields.push(Field {
+            tag: Tag::PhotographicSensitivity,
+            ifd_num: In::PRIMARY,
+            value: Value::Short(vec![iso.min(u16::MAX as u32) as u16]),
+        });
+    }
+
+    let rationals = [
+        (Tag::ExposureTime, metadata.exposure_time.as_deref()),
+        (Tag::FNumber, metadata.f_number.as_deref()),
+        (Tag::FocalLength, metadata.focal_length.as_deref()),
+    ];
+    for (tag, display) in rationals {
+        if let Some(rational) = display.and_then(parse_rational) {
+            fields.push(Field {
+                tag,
+                ifd_num: In::PRIMARY,
+                value: Value::Rational(vec![rational]),
+            });
+        }
+    }
+
+    if let (Some(lat), Some(lon)) = (metadata.gps_latitude, metadata.gps_longitude) {
+        fields.extend(gps_fields(lat, lon));
+    }
+
+    let mut writer = Writer::new();
+    for field in &fields {
+        writer.push_field(field);
+    }
+
+    let mut buffer = Cursor::new(Vec::new());
+    writer
+        .write(&mut buffer, false)
+        .context("Failed to encode EXIF data")?;
+
+    Ok(buffer.into_inner())
+}
+
+fn ascii_field(tag: Tag, ifd_num: In, value: &str) -> Field {
+    Field {
+        tag,
+        ifd_num,
+        value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+    }
+}
+
+/// Text for an EXIF ASCII field, which only holds 7-bit characters: the
+/// common legal symbols are spelled out and anything else becomes `?`
+fn ascii_text(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            c if c.is_ascii() => text.push(c),
+            '©' => text.push_str("(C)"),
+            '®' => text.push_str("(R)"),
+            '™' => text.push_str("(TM)"),
+            _ => text.push('?'),
+        }
+    }
+    text
+}
+
+fn utf16_field(tag: Tag, value: &str) -> Field {
+    Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value: Value::Byte(utf16_bytes(value)),
+    }
+}
+
+/// NUL-terminated UTF-16LE, as the Windows `XP*` tags expect
+fn utf16_bytes(value: &str) -> Vec<u8> {
+    value
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect()
+}
+
+/// `UserComment` in the Unicode character code: an 8 byte `UNICODE` header
+/// and UTF-16 in the byte order of the EXIF block, which is big-endian here
+fn user_comment_bytes(value: &str) -> Vec<u8> {
+    b"UNICODE\0"
+        .iter()
+        .copied()
+        .chain(value.encode_utf16().flat_map(|unit| unit.to_be_bytes()))
+        .collect()
+}
+
+fn gps_fields(latitude: f64, longitude: f64) -> Vec<Field> {
+    let lat_ref = if latitude < 0.0 { "S" } else { "N" };
+    let lon_ref = if longitude < 0.0 { "W" } else { "E" };
+
+    vec![
+        Field {
+            tag: Tag::GPSVersionID,
+            ifd_num: In::PRIMARY,
+            value: Value::Byte(vec![2, 3, 0, 0]),
+        },
+        ascii_field(Tag::GPSLatitudeRef, In::PRIMARY, lat_ref),
+        Field {
+            tag: Tag::GPSLatitude,
+            ifd_num: In::PRIMARY,
+            value: Value::Rational(degrees_to_dms(latitude.abs())),
+        },
+        ascii_field(Tag::GPSLongitudeRef, In::PRIMARY, lon_ref),
+        Field {
+            tag: Tag::GPSLongitude,
+            ifd_num: In::PRIMARY,
+            value: Value::Rational(degrees_to_dms(longitude.abs())),
+        },
+    ]
+}
+
+/// Split decimal degrees into degree/minute/second rationals
+fn degrees_to_dms(value: f64) -> Vec<Rational> {
+    // Round once, in units of 1/10000 s, so seconds never come out as 60
+    let total = (value * 3600.0 * 10_000.0).round() as u64;
+    let degrees = total / (3600 * 10_000);
+    let minutes = total / (60 * 10_000) % 60;
+    let seconds = total % (60 * 10_000);
+
+    vec![
+        Rational::from((degrees as u32, 1)),
+        Rational::from((minutes as u32, 1)),
+        Rational::from((seconds as u32, 10_000)),
+    ]
+}
+
+/// Parse the display strings kept in [`GprMetadata`] (`1/576`, `f/2.8`,
+/// `2.97 mm`) back into an EXIF rational
+fn parse_rational(display: &str) -> Option<Rational> {
+    let value = display
+        .trim()
+        .trim_start_matches("f/")
+        .trim_end_matches("mm")
+        .trim();
+
+    if let Some((num, denom)) = value.split_once('/') {
+        let num = num.trim().parse().ok()?;
+        let denom = denom.trim().parse().ok()?;
+        return Some(Rational::from((num, denom)));
+    }
+
+    let decimal: f64 = value.parse().ok()?;
+    if !decimal.is_finite() || decimal < 0.0 {
+        return None;
+    }
+    Some(Rational::from(((decimal * 1000.0).round() as u32, 1000)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MetadataEdits;
+
+    #[test]
+    fn test_non_ascii_edits() {
+        let config = ConversionConfig {
+            metadata_edits: MetadataEdits {
+                creator: Some("Zoë Lefèvre".to_string()),
+                copyright: Some("© 2024 Crew".to_string()),
+                caption: Some("Dawn patrol, Ōarai".to_string()),
+                ..MetadataEdits::default()
+            },
+            ..ConversionConfig::default()
+        };
+        let block = build_exif(&GprMetadata::default(), &config).unwrap();
+        let (fields, _) = exif::parse_exif(&block).unwrap();
+        let bytes = |tag| match fields.iter().find(|f| f.tag == tag).map(|f| &f.value) {
+            Some(Value::Ascii(v)) => v[0].clone(),
+            Some(Value::Byte(v)) | Some(Value::Undefined(v, _)) => v.clone(),
+            other => panic!("unexpected {:?} for {}", other, tag),
+        };
+
+        // ASCII fields stay 7-bit
+        assert_eq!(bytes(Tag::Artist), b"Zo? Lef?vre");
+        assert_eq!(bytes(Tag::Copyright), b"(C) 2024 Crew");
+        assert_eq!(bytes(Tag::ImageDescription), b"Dawn patrol, ?arai");
+
+        // The full text is in the Unicode tags
+        assert_eq!(bytes(XP_AUTHOR), utf16_bytes("Zoë Lefèvre"));
+        assert_eq!(bytes(XP_COMMENT), utf16_bytes("Dawn patrol, Ōarai"));
+        let comment = bytes(Tag::UserComment);
+        let units: Vec<u16> = comment[8..].chunks(2).map(|b| u16::from_be_bytes([b[0], b[1]])).collect();
+        assert_eq!(&comment[..8], b"UNICODE\0");
+        assert_eq!(String::from_utf16(&units).unwrap(), "Dawn patrol, Ōarai");
+    }
+}
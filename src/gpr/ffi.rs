@@ -5,7 +5,7 @@
 #![allow(non_snake_case)]
 #![allow(dead_code)]
 
-use std::os::raw::{c_int, c_uint, c_void};
+use std::os::raw::{c_char, c_int, c_uint, c_void};
 
 // GPR buffer structure
 #[repr(C)]
@@ -44,12 +44,47 @@ pub enum GPR_RGB_RESOLUTION {
     GPR_RGB_RESOLUTION_FULL = 4,
 }
 
+// Rational as used in the SDK EXIF structures
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct gpr_unsigned_rational {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+// Date and time as used in the SDK EXIF structures
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct gpr_date_and_time {
+    pub year: u32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
 // EXIF info structure (simplified)
+//
+// Only the leading fields of gpr_exif_info.h that we read are declared; the
+// remainder (exposure bias onwards, GPS info) is covered by the padding.
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct gpr_exif_info {
-    // Add EXIF fields as needed
-    pub _placeholder: [u8; 1024],
+    pub camera_make: [c_char; 32],
+    pub camera_model: [c_char; 32],
+    pub camera_serial: [c_char; 32],
+    pub software_version: [c_char; 32],
+    pub user_comment: [c_char; 64],
+    pub image_description: [c_char; 64],
+    pub exposure_time: gpr_unsigned_rational,
+    pub f_stop_number: gpr_unsigned_rational,
+    pub aperture: gpr_unsigned_rational,
+    pub exposure_program: c_int,
+    pub iso_speed_rating: u16,
+    pub date_time_original: gpr_date_and_time,
+    pub date_time_digitized: gpr_date_and_time,
+    pub _remainder: [u8; 1024 - 336],
 }
 
 // Profile info structure (simplified)
@@ -137,6 +172,21 @@ pub extern "C" fn gpr_free(ptr: *mut c_void) {
     }
 }
 
+// Read a fixed-size, NUL-padded C string field
+pub fn c_string_field(field: &[c_char]) -> Option<String> {
+    let bytes: Vec<u8> = field
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+    let value = String::from_utf8_lossy(&bytes).trim().to_string();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
 // Create default allocator
 pub fn create_allocator() -> gpr_allocator {
     gpr_allocator {
@@ -0,0 +1,163 @@
+use crate::gpr::remap::remap;
+use crate::models::{Geometry, Rotation};
+use image::imageops;
+use image::{ImageBuffer, Rgb};
+
+/// Apply rotation, flips, straightening and crop to an upright image
+pub fn apply(image: ImageBuffer<Rgb<u8>, Vec<u8>>, geometry: &Geometry) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    if geometry.is_identity() {
+        return image;
+    }
+    let image = orient(image, geometry);
+
+    let (width, height) = straightened_size(image.width(), image.height(), geometry.straighten);
+    let (left, top, crop_width, crop_height) = geometry.crop_pixels(width, height);
+    if geometry.straighten == 0.0 {
+        return imageops::crop_imm(&image, left, top, crop_width, crop_height).to_image();
+    }
+
+    // Rotate each output position back into the source about the centres
+    let (sin, cos) = geometry.straighten.to_radians().sin_cos();
+    let (cx, cy) = (image.width() as f32 / 2.0, image.height() as f32 / 2.0);
+    let (ox, oy) = (left as f32 - width as f32 / 2.0, top as f32 - height as f32 / 2.0);
+    remap(&image, crop_width, crop_height, |x, y| {
+        let (dx, dy) = (x + ox, y + oy);
+        (cx + cos * dx + sin * dy, cy - sin * dx + cos * dy)
+    })
+}
+
+/// Rotate/flip a decoded image by an EXIF Orientation value
+pub fn apply_orientation(image: ImageBuffer<Rgb<u8>, Vec<u8>>, orientation: u16) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    match image::metadata::Orientation::from_exif(orientation as u8) {
+        Some(orientation) => {
+            let mut image = image::DynamicImage::ImageRgb8(image);
+            image.apply_orientation(orientation);
+            image.into_rgb8()
+        }
+        None => image,
+    }
+}
+
+/// Quarter turns and flips, which only move pixels
+pub fn orient(image: ImageBuffer<Rgb<u8>, Vec<u8>>, geometry: &Geometry) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let mut image = match geometry.rotation {
+        Rotation::None => image,
+        Rotation::Cw90 => imageops::rotate90(&image),
+        Rotation::Half => imageops::rotate180(&image),
+        Rotation::Ccw90 => imageops::rotate270(&image),
+    };
+    if geometry.flip_horizontal {
+        imageops::flip_horizontal_in_place(&mut image);
+    }
+    if geometry.flip_vertical {
+        imageops::flip_vertical_in_place(&mut image);
+    }
+    image
+}
+
+/// Largest rectangle with the image's own aspect ratio that fits inside it
+/// once turned `degrees`, so a straightened image has no empty corners
+pub fn straightened_size(width: u32, height: u32, degrees: f32) -> (u32, u32) {
+    if degrees == 0.0 {
+        return (width, height);
+    }
+    let (sin, cos) = degrees.to_radians().abs().sin_cos();
+    let (w, h) = (width as f32, height as f32);
+    let scale = (w / (w * cos + h * sin)).min(h / (w * sin + h * cos));
+    (((w * scale).floor() as u32).max(1), ((h * scale).floor() as u32).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::gpr_file::GprMetadata;
+    use crate::models::{ConversionConfig, CropAspect, CropRect, OrientationMode};
+
+    #[test]
+    fn test_geometry() {
+        // Left half red, right half blue
+        let image = ImageBuffer::from_fn(200, 100, |x, _| if x < 100 { Rgb([255u8, 0, 0]) } else { Rgb([0, 0, 255]) });
+
+        let turned = apply(image.clone(), &Geometry { rotation: Rotation::Cw90, ..Geometry::default() });
+        assert_eq!(turned.dimensions(), (100, 200));
+        assert_eq!(turned.get_pixel(50, 0).0, [255, 0, 0]);
+        let flipped = apply(image.clone(), &Geometry { flip_horizontal: true, ..Geometry::default() });
+        assert_eq!(flipped.get_pixel(0, 0).0, [0, 0, 255]);
+
+        // Straightening crops the corners away: nothing black is left
+        let straightened = apply(image.clone(), &Geometry { straighten: 10.0, ..Geometry::default() });
+        assert_eq!(straightened.dimensions(), straightened_size(200, 100, 10.0));
+        assert!(straightened.width() < 200 && straightened.width() > 140);
+        assert!(straightened.pixels().all(|p| p.0[0] as u32 + p.0[2] as u32 > 200));
+
+        // Turned clockwise, the red/blue edge leans right at the top
+        let (w, h) = straightened.dimensions();
+        assert_eq!(straightened.get_pixel(w / 2 + 4, 0).0, [255, 0, 0]);
+        assert_eq!(straightened.get_pixel(w / 2 - 4, h - 1).0, [0, 0, 255]);
+
+        // Square crop of the right half
+        let geometry = Geometry {
+            aspect: CropAspect::Ratio(1, 1),
+            crop: CropRect { x: 0.5, y: 0.0, width: 0.5, height: 1.0 },
+            ..Geometry::default()
+        };
+        let cropped = apply(image, &geometry);
+        assert_eq!(cropped.dimensions(), (100, 100));
+        assert!(cropped.pixels().all(|p| p.0 == [0, 0, 255]));
+    }
+
+    #[test]
+    fn test_orientation() {
+        // Decoded pixels a b c / d e f, and how each EXIF Orientation shows them
+        let letters = |image: &ImageBuffer<Rgb<u8>, Vec<u8>>| -> String {
+            let rows: Vec<String> = (0..image.height())
+                .map(|y| (0..image.width()).map(|x| image.get_pixel(x, y).0[0] as char).collect())
+                .collect();
+            rows.join("/")
+        };
+        let decoded = ImageBuffer::from_fn(3, 2, |x, y| Rgb([b"abcdef"[(y * 3 + x) as usize], 0, 0]));
+        let reference = ["abc/def", "cba/fed", "fed/cba", "def/abc", "ad/be/cf", "da/eb/fc", "fc/eb/da", "cf/be/ad"];
+        for (orientation, expected) in (1..=8).zip(reference) {
+            assert_eq!(letters(&apply_orientation(decoded.clone(), orientation)), expected, "orientation {}", orientation);
+        }
+
+        // Every sample is tagged mirrored; only an explicit bake mirrors it
+        let metadata = crate::gpr::read_metadata(std::path::Path::new("sample-data/Hero6/GOPR0024.GPR")).unwrap();
+        assert_eq!(metadata.effective_orientation(), 2);
+        let config = ConversionConfig::default();
+        assert_eq!((config.baked_orientation(&metadata), config.output_orientation(&metadata)), (1, 1));
+        let baked = ConversionConfig { orientation_mode: OrientationMode::Bake, ..config };
+        let output = apply_orientation(decoded, baked.baked_orientation(&metadata));
+        assert_eq!((letters(&output), baked.output_orientation(&metadata)), ("cba/fed".to_string(), 1));
+    }
+
+    #[test]
+    fn test_level_mirrored() {
+        // Camera rolled 5° clockwise: the horizon it records rises to the right
+        let roll = 5f64.to_radians();
+        let metadata = GprMetadata {
+            orientation: Some(2),
+            gravity: Some([9.8 * roll.cos(), -9.8 * roll.sin(), 0.0]),
+            ..GprMetadata::default()
+        };
+        let slope = (roll as f32).tan();
+        let decoded = ImageBuffer::from_fn(400, 300, |x, y| {
+            let above = (y as f32 + 0.5 - 150.0) + slope * (x as f32 + 0.5 - 200.0);
+            if above.abs() < 2.0 { Rgb([0u8, 0, 0]) } else { Rgb([255, 255, 255]) }
+        });
+
+        // Mirrored by the baked orientation, the horizon falls to the right
+        // instead, and levelling must turn the other way
+        let config = ConversionConfig { orientation_mode: OrientationMode::Bake, ..ConversionConfig::default() };
+        let orientation = config.baked_orientation(&metadata);
+        let geometry = Geometry { auto_level: true, ..Geometry::default() }.levelled(&metadata, orientation);
+        let levelled = apply(apply_orientation(decoded, orientation), &geometry);
+
+        let horizon = |x: u32| {
+            let rows: Vec<u32> = (0..levelled.height()).filter(|&y| levelled.get_pixel(x, y).0[0] < 128).collect();
+            rows.iter().sum::<u32>() as f32 / rows.len() as f32
+        };
+        let (left, right) = (horizon(10), horizon(levelled.width() - 10));
+        assert!((left - right).abs() < 2.0, "horizon at {} and {}", left, right);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::gpr::develop::color::{rgb_to_xyz, xy_to_xyz, D50};
+use crate::models::color_space::Transfer;
+use crate::models::ColorSpace;
+
+/// Entries in tabulated tone curves, enough for smooth 16-bit lookups
+const CURVE_POINTS: usize = 1024;
+
+/// ICC v2.1 display profile (matrix/TRC) for an output colour space. v2 is
+/// used over v4 because every viewer and OS colour engine reads it.
+pub fn profile(space: ColorSpace) -> Vec<u8> {
+    let colorants = rgb_to_xyz(space);
+    let column = |i: usize| xyz_tag([colorants[0][i], colorants[1][i], colorants[2][i]]);
+    let curve = curve_tag(space.transfer());
+
+    let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"desc", desc_tag(space.as_str())),
+        (b"cprt", text_tag("No copyright, use freely")),
+        (b"wtpt", xyz_tag(xy_to_xyz(D50))),
+        (b"rXYZ", column(0)),
+        (b"gXYZ", column(1)),
+        (b"bXYZ", column(2)),
+        (b"rTRC", curve.clone()),
+        (b"gTRC", curve.clone()),
+        (b"bTRC", curve),
+    ];
+
+    let table_size = 4 + tags.len() * 12;
+    let mut data = Vec::new();
+    let mut table = Vec::with_capacity(table_size);
+    table.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    for (signature, tag) in &tags {
+        let offset = 128 + table_size + data.len();
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&(offset as u32).to_be_bytes());
+        table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+        data.extend_from_slice(tag);
+        // Tag data starts on a four-byte boundary
+        data.resize(data.len().next_multiple_of(4), 0);
+    }
+
+    let size = 128 + table.len() + data.len();
+    let mut profile = header(size as u32);
+    profile.extend_from_slice(&table);
+    profile.extend_from_slice(&data);
+    profile
+}
+
+fn header(size: u32) -> Vec<u8> {
+    let mut header = vec![0u8; 128];
+    header[0..4].copy_from_slice(&size.to_be_bytes());
+    header[8..12].copy_from_slice(&0x0210_0000u32.to_be_bytes());
+    header[12..16].copy_from_slice(b"mntr");
+    header[16..20].copy_from_slice(b"RGB ");
+    header[20..24].copy_from_slice(b"XYZ ");
+    // Creation date/time left at zero
+    header[36..40].copy_from_slice(b"acsp");
+    // Perceptual rendering intent (bytes 64-67) is zero
+    let illuminant = xy_to_xyz(D50);
+    for (i, value) in illuminant.iter().enumerate() {
+        header[68 + i * 4..72 + i * 4].copy_from_slice(&s15_fixed16(*value));
+    }
+    header
+}
+
+fn s15_fixed16(value: f32) -> [u8; 4] {
+    ((value as f64 * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn xyz_tag(xyz: [f32; 3]) -> Vec<u8> {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    for value in xyz {
+        tag.extend_from_slice(&s15_fixed16(value));
+    }
+    tag
+}
+
+fn text_tag(text: &str) -> Vec<u8> {
+    let mut tag = b"text\0\0\0\0".to_vec();
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    tag
+}
+
+/// textDescriptionType: ASCII, with empty Unicode and ScriptCode parts
+fn desc_tag(text: &str) -> Vec<u8> {
+    let mut tag = b"desc\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    // Unicode language code and count
+    tag.extend_from_slice(&[0; 8]);
+    // ScriptCode code, count and its fixed 67-byte field
+    tag.extend_from_slice(&[0; 3]);
+    tag.extend_from_slice(&[0; 67]);
+    tag
+}
+
+/// curveType: a single u8Fixed8 exponent for pure power laws, otherwise a
+/// table of linear values at evenly spaced encoded values
+fn curve_tag(transfer: Transfer) -> Vec<u8> {
+    let mut tag = b"curv\0\0\0\0".to_vec();
+    match transfer {
+        Transfer::Gamma(gamma) => {
+            tag.extend_from_slice(&1u32.to_be_bytes());
+            tag.extend_from_slice(&((gamma * 256.0).round() as u16).to_be_bytes());
+        }
+        _ => {
+            tag.extend_from_slice(&(CURVE_POINTS as u32).to_be_bytes());
+            for i in 0..CURVE_POINTS {
+                let linear = transfer.decode(i as f32 / (CURVE_POINTS - 1) as f32);
+                tag.extend_from_slice(&((linear.clamp(0.0, 1.0) * 65535.0).round() as u16).to_be_bytes());
+            }
+        }
+    }
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_icc_profile() {
+        for space in ColorSpace::all() {
+            let icc = profile(space);
+            assert_eq!(u32::from_be_bytes(icc[0..4].try_into().unwrap()) as usize, icc.len());
+            assert_eq!(&icc[36..40], b"acsp");
+
+            // Every tag lies inside the profile, four-byte aligned
+            let count = u32::from_be_bytes(icc[128..132].try_into().unwrap()) as usize;
+            assert_eq!(count, 9);
+            for entry in icc[132..132 + count * 12].chunks_exact(12) {
+                let offset = u32::from_be_bytes(entry[4..8].try_into().unwrap()) as usize;
+                let size = u32::from_be_bytes(entry[8..12].try_into().unwrap()) as usize;
+                assert_eq!(offset % 4, 0);
+                assert!(offset + size <= icc.len());
+            }
+        }
+    }
+}
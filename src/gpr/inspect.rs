@@ -0,0 +1,227 @@
+use crate::gpr::{parse_metadata, GprConverter};
+use crate::models::gpr_file::GprMetadata;
+use crate::models::PrivacyPolicy;
+use crate::telemetry::gpmf;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+
+/// One named metadata value, as listed in a file comparison
+#[derive(Debug, Clone, Serialize)]
+pub struct MetadataField {
+    pub group: &'static str,
+    pub name: String,
+    pub value: String,
+}
+
+impl MetadataField {
+    fn new(group: &'static str, name: &str, value: impl Into<String>) -> Self {
+        Self {
+            group,
+            name: name.to_string(),
+            value: value.into(),
+        }
+    }
+}
+
+/// DNG tags that decide how the raw data turns into colour
+const DNG_COLOR_TAGS: &[(u16, &str)] = &[
+    (50706, "DNGVersion"),
+    (50708, "UniqueCameraModel"),
+    (33422, "CFAPattern"),
+    (50714, "BlackLevel"),
+    (50717, "WhiteLevel"),
+    (50721, "ColorMatrix1"),
+    (50722, "ColorMatrix2"),
+    (50723, "CameraCalibration1"),
+    (50724, "CameraCalibration2"),
+    (50727, "AnalogBalance"),
+    (50728, "AsShotNeutral"),
+    (50730, "BaselineExposure"),
+    (50731, "BaselineNoise"),
+    (50732, "BaselineSharpness"),
+    (50778, "CalibrationIlluminant1"),
+    (50779, "CalibrationIlluminant2"),
+    (50964, "ForwardMatrix1"),
+    (50965, "ForwardMatrix2"),
+    (50936, "ProfileName"),
+    (51041, "NoiseProfile"),
+];
+
+/// DNGPrivateData, which holds GPMF on cameras that embed it
+const DNG_PRIVATE_DATA: u16 = 50740;
+
+/// List a file's metadata as comparable fields: the reconciled camera
+/// metadata, the DNG colour tags and a summary of any embedded GPMF
+///
+/// `privacy` is applied to the camera metadata before it is listed, as it
+/// would be when writing output EXIF. With `isolated`, the SDK parses the
+/// metadata in a child process.
+pub fn inspect(path: &Path, privacy: &PrivacyPolicy, isolated: bool) -> Result<Vec<MetadataField>> {
+    let data = std::fs::read(path)
+        .with_context(|| format!("Failed to read GPR file: {}", path.display()))?;
+
+    let mut fields = vec![MetadataField::new("File", "Size", format!("{} bytes", data.len()))];
+
+    let metadata = if isolated { GprConverter::read_metadata_isolated(path) } else { parse_metadata(&data) };
+    match metadata {
+        Ok(mut metadata) => {
+            privacy.apply(&mut metadata);
+            fields.extend(camera_fields(&metadata));
+        }
+        Err(e) => fields.push(MetadataField::new("Camera", "Error", format!("{:#}", e))),
+    }
+
+    // Raw tags are only available when the EXIF can be read
+    if let Ok(exif_data) = exif::Reader::new().read_from_container(&mut std::io::Cursor::new(&data)) {
+        for &(tag, name) in DNG_COLOR_TAGS {
+            if let Some(field) = exif_data.get_field(exif::Tag(exif::Context::Tiff, tag), exif::In::PRIMARY) {
+                fields.push(MetadataField::new("DNG", name, format_value(&field.value)));
+            }
+        }
+
+        let private_data = exif_data
+            .get_field(exif::Tag(exif::Context::Tiff, DNG_PRIVATE_DATA), exif::In::PRIMARY)
+            .and_then(|f| match f.value {
+                exif::Value::Byte(ref v) => Some(v.clone()),
+                _ => None,
+            });
+        fields.extend(gpmf_fields(private_data.as_deref()));
+    }
+
+    Ok(fields)
+}
+
+fn camera_fields(metadata: &GprMetadata) -> Vec<MetadataField> {
+    let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+
+    let mut fields = vec![
+        MetadataField::new("Camera", "Model", metadata.camera_model.clone()),
+        MetadataField::new("Camera", "Serial", optional(&metadata.serial_number)),
+        MetadataField::new("Camera", "Owner", optional(&metadata.owner_name)),
+        MetadataField::new("Camera", "Read Via", metadata.source.as_str()),
+        MetadataField::new("Image", "Sensor Size", metadata.sensor_size_display()),
+        MetadataField::new("Image", "Active Area", metadata.active_area_display()),
+        MetadataField::new("Image", "Output Size", metadata.output_size_display()),
+        MetadataField::new(
+            "Image",
+            "CFA",
+            metadata.cfa_pattern.map(|c| c.as_str().to_string()).unwrap_or_default(),
+        ),
+        MetadataField::new("Exposure", "Date Taken", optional(&metadata.date_taken)),
+        MetadataField::new(
+            "Exposure",
+            "ISO",
+            metadata.iso.map(|iso| iso.to_string()).unwrap_or_default(),
+        ),
+        MetadataField::new("Exposure", "Exposure", optional(&metadata.exposure_time)),
+        MetadataField::new("Exposure", "F-Number", optional(&metadata.f_number)),
+        MetadataField::new("Exposure", "Focal Length", optional(&metadata.focal_length)),
+    ];
+
+    if let (Some(lat), Some(lon)) = (metadata.gps_latitude, metadata.gps_longitude) {
+        fields.push(MetadataField::new("GPS", "Position", format!("{:.6}, {:.6}", lat, lon)));
+    }
+
+    fields
+}
+
+fn gpmf_fields(private_data: Option<&[u8]>) -> Vec<MetadataField> {
+    let Some(payload) = private_data.and_then(gpmf::from_dng_private_data) else {
+        return vec![MetadataField::new("GPMF", "Payload", "None")];
+    };
+
+    let summary = match gpmf::summarize(payload) {
+        Ok(summary) => summary,
+        Err(e) => return vec![MetadataField::new("GPMF", "Error", format!("{:#}", e))],
+    };
+
+    let mut fields = vec![MetadataField::new("GPMF", "Payload", format!("{} bytes", payload.len()))];
+    for device in &summary.devices {
+        let streams: Vec<String> = device.streams.iter().map(|s| s.display()).collect();
+        fields.push(MetadataField::new("GPMF", &device.name, streams.join("; ")));
+    }
+    fields
+}
+
+/// Values as plain numbers, with rationals reduced to decimals
+fn format_value(value: &exif::Value) -> String {
+    fn decimal(v: f64) -> String {
+        let s = format!("{:.4}", v);
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+
+    let parts: Vec<String> = match value {
+        exif::Value::Byte(v) => v.iter().map(|x| x.to_string()).collect(),
+        exif::Value::Short(v) => v.iter().map(|x| x.to_string()).collect(),
+        exif::Value::Long(v) => v.iter().map(|x| x.to_string()).collect(),
+        exif::Value::SLong(v) => v.iter().map(|x| x.to_string()).collect(),
+        exif::Value::Rational(v) => v.iter().map(|x| decimal(x.to_f64())).collect(),
+        exif::Value::SRational(v) => v.iter().map(|x| decimal(x.to_f64())).collect(),
+        exif::Value::Double(v) => v.iter().map(|x| format!("{:.4e}", x)).collect(),
+        exif::Value::Ascii(v) => v
+            .iter()
+            .map(|s| String::from_utf8_lossy(s).trim().to_string())
+            .collect(),
+        other => vec![format!("{:?}", other)],
+    };
+    parts.join(" ")
+}
+
+/// A field present in either file, with both values
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldDiff {
+    pub group: &'static str,
+    pub name: String,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+impl FieldDiff {
+    pub fn is_different(&self) -> bool {
+        self.left != self.right
+    }
+
+    pub fn label(&self) -> String {
+        format!("{} {}", self.group, self.name)
+    }
+}
+
+/// Pair up the fields of two files, in the order of the first file with
+/// fields only the second file has appended
+pub fn diff_fields(left: &[MetadataField], right: &[MetadataField]) -> Vec<FieldDiff> {
+    let find = |fields: &[MetadataField], field: &MetadataField| {
+        fields
+            .iter()
+            .find(|f| f.group == field.group && f.name == field.name)
+            .map(|f| f.value.clone())
+    };
+
+    let mut diffs: Vec<FieldDiff> = left
+        .iter()
+        .map(|field| FieldDiff {
+            group: field.group,
+            name: field.name.clone(),
+            left: Some(field.value.clone()),
+            right: find(right, field),
+        })
+        .collect();
+
+    for field in right {
+        if find(left, field).is_none() {
+            diffs.push(FieldDiff {
+                group: field.group,
+                name: field.name.clone(),
+                left: None,
+                right: Some(field.value.clone()),
+            });
+        }
+    }
+
+    diffs
+}
+
+/// Compare the metadata of two GPR files
+pub fn diff_files(left: &Path, right: &Path, privacy: &PrivacyPolicy, isolated: bool) -> Result<Vec<FieldDiff>> {
+    Ok(diff_fields(&inspect(left, privacy, isolated)?, &inspect(right, privacy, isolated)?))
+}
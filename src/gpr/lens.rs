@@ -0,0 +1,160 @@
+use crate::gpr::remap::remap;
+use crate::models::lens::Distortion;
+use crate::models::{EdgeMode, LensCorrection, LensSettings};
+use image::{ImageBuffer, Rgb};
+
+/// Points checked along each frame edge when looking for the crop zoom
+const EDGE_SAMPLES: usize = 64;
+
+/// The distortion to remove, if any
+pub fn distortion_for(settings: &LensSettings) -> Option<Distortion> {
+    match settings.correction {
+        LensCorrection::Off => None,
+        LensCorrection::Custom(distortion) => Some(distortion),
+    }
+}
+
+/// Remove radial distortion, keeping the image size
+pub fn correct(
+    image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    distortion: Distortion,
+    edges: EdgeMode,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    if distortion.k1 == 0.0 && distortion.k2 == 0.0 {
+        return image.clone();
+    }
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let (cx, cy) = (width / 2.0, height / 2.0);
+    let half_diagonal = cx.hypot(cy);
+
+    let zoom = match edges {
+        EdgeMode::Fill => 1.0,
+        EdgeMode::Crop => crop_zoom(distortion, cx / half_diagonal, cy / half_diagonal),
+    };
+    log::debug!("Lens correction {:?}, zoom {:.3}", distortion, zoom);
+
+    remap(image, image.width(), image.height(), |x, y| {
+        let (u, v) = ((x - cx) / half_diagonal / zoom, (y - cy) / half_diagonal / zoom);
+        let scale = source_scale(distortion, u, v);
+        (cx + u * scale * half_diagonal, cy + v * scale * half_diagonal)
+    })
+}
+
+/// Ratio of source to corrected radius at normalized position (u, v)
+fn source_scale(distortion: Distortion, u: f32, v: f32) -> f32 {
+    let r = u.hypot(v);
+    if r == 0.0 {
+        1.0
+    } else {
+        distortion.source_radius(r) / r
+    }
+}
+
+/// Smallest enlargement at which every point on the frame edge (half
+/// extents `half_width`, `half_height` in half-diagonals) maps inside the
+/// source, found by bisection
+fn crop_zoom(distortion: Distortion, half_width: f32, half_height: f32) -> f32 {
+    let inside = |zoom: f32| {
+        (0..=EDGE_SAMPLES).all(|i| {
+            let t = i as f32 / EDGE_SAMPLES as f32 * 2.0 - 1.0;
+            [(t * half_width, half_height), (half_width, t * half_height)]
+                .into_iter()
+                .all(|(u, v)| {
+                    let (u, v) = (u / zoom, v / zoom);
+                    let scale = source_scale(distortion, u, v);
+                    (u * scale).abs() <= half_width && (v * scale).abs() <= half_height
+                })
+        })
+    };
+
+    let (mut low, mut high) = (0.25f32, 4.0f32);
+    if !inside(high) {
+        return high;
+    }
+    for _ in 0..30 {
+        let middle = (low + high) / 2.0;
+        if inside(middle) {
+            high = middle;
+        } else {
+            low = middle;
+        }
+    }
+    high
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lens_correction() {
+        let barrel = Distortion { k1: 0.3, k2: 0.1 };
+        let (half_width, half_height) = (0.8, 0.6);
+        // Cropping enlarges just enough that the corner samples the corner
+        let zoom = crop_zoom(barrel, half_width, half_height);
+        assert!((barrel.source_radius(1.0 / zoom) - 1.0).abs() < 1e-3, "{}", zoom);
+
+        // The centre stays put and a flat image stays flat once cropped
+        let image = ImageBuffer::from_pixel(40, 30, Rgb([10u8, 20, 30]));
+        let cropped = correct(&image, barrel, EdgeMode::Crop);
+        assert!(cropped.pixels().all(|p| p.0 == [10, 20, 30]));
+        // Filling keeps the scale, leaving black corners
+        let filled = correct(&image, barrel, EdgeMode::Fill);
+        assert_eq!(filled.get_pixel(0, 0).0, [0, 0, 0]);
+        assert_eq!(filled.get_pixel(20, 15).0, [10, 20, 30]);
+    }
+
+    #[test]
+    fn test_calibration_target() {
+        // A grid of straight lines as a barrel-distorted lens images it:
+        // each pixel shows the target point whose distorted radius lands
+        // there. This checks the remap, not any particular lens.
+        let distortion = Distortion { k1: 0.22, k2: 0.05 };
+        let (width, height) = (400u32, 300u32);
+        let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+        let half_diagonal = cx.hypot(cy);
+        let on_grid = |x: f32, y: f32| x.rem_euclid(40.0) < 3.0 || y.rem_euclid(40.0) < 3.0;
+        let target = ImageBuffer::from_fn(width, height, |x, y| {
+            if on_grid(x as f32, y as f32) { Rgb([0u8, 0, 0]) } else { Rgb([255, 255, 255]) }
+        });
+        let photographed = ImageBuffer::from_fn(width, height, |x, y| {
+            let (u, v) = ((x as f32 + 0.5 - cx) / half_diagonal, (y as f32 + 0.5 - cy) / half_diagonal);
+            let source = u.hypot(v);
+            // Invert the lens model by Newton's method
+            let mut r = source;
+            for _ in 0..20 {
+                let (r2, r4) = (r * r, r * r * r * r);
+                let slope = 1.0 + 3.0 * distortion.k1 * r2 + 5.0 * distortion.k2 * r4;
+                r -= (distortion.source_radius(r) - source) / slope;
+            }
+            let scale = if source == 0.0 { 1.0 } else { r / source };
+            let (tx, ty) = (cx + u * scale * half_diagonal - 0.5, cy + v * scale * half_diagonal - 0.5);
+            if on_grid(tx.round(), ty.round()) { Rgb([0u8, 0, 0]) } else { Rgb([255, 255, 255]) }
+        });
+
+        // Share of pixels that differ from the flat target, where the source
+        // covers the corrected frame
+        let mismatch = |image: &ImageBuffer<Rgb<u8>, Vec<u8>>| {
+            let covered: Vec<(u32, u32)> = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .filter(|&(x, y)| {
+                    let (u, v) = ((x as f32 - cx) / half_diagonal, (y as f32 - cy) / half_diagonal);
+                    let scale = source_scale(distortion, u, v);
+                    (u * scale).abs() < 0.78 && (v * scale).abs() < 0.58
+                })
+                .collect();
+            let wrong = covered
+                .iter()
+                .filter(|&&(x, y)| (image.get_pixel(x, y).0[0] < 128) != (target.get_pixel(x, y).0[0] < 128))
+                .count();
+            wrong as f32 / covered.len() as f32
+        };
+
+        assert!(mismatch(&photographed) > 0.1, "{}", mismatch(&photographed));
+        let corrected = correct(&photographed, distortion, EdgeMode::Fill);
+        assert!(mismatch(&corrected) < 0.02, "{}", mismatch(&corrected));
+        // Coefficients that do not match the lens leave the lines bent
+        let wrong = correct(&photographed, Distortion { k1: 0.08, k2: 0.0 }, EdgeMode::Fill);
+        assert!(mismatch(&wrong) > 0.05, "{}", mismatch(&wrong));
+    }
+}
@@ -1,24 +1,132 @@
-use crate::models::gpr_file::GprMetadata;
-use anyhow::{Context, Result};
+use crate::gpr::ffi::*;
+use crate::gpr::validate::validate_input;
+use crate::telemetry::gpmf;
+use crate::models::camera_profile::CfaPattern;
+use crate::models::gpr_file::{GprMetadata, ImageArea, MetadataSource, ThumbnailInfo};
+use crate::utils::datetime::ExifDateTime;
+use anyhow::{anyhow, Context, Result};
 use std::path::Path;
 
-/// Read metadata from a GPR file using EXIF data
+/// Values the GoPro GPR SDK reports from `gpr_parse_metadata`
+#[derive(Debug, Clone)]
+struct SdkMetadata {
+    width: u32,
+    height: u32,
+    make: Option<String>,
+    model: Option<String>,
+    serial_number: Option<String>,
+    iso: Option<u32>,
+    exposure_time: Option<String>,
+    f_number: Option<String>,
+    date_taken: Option<String>,
+}
+
+/// Read metadata from a GPR file
 ///
 /// GPR files are based on Adobe DNG format with VC-5 compression.
-/// This function reads EXIF metadata from the GPR/DNG container.
+/// See [`parse_metadata`] for how the two metadata sources are combined.
 pub fn read_metadata(path: &Path) -> Result<GprMetadata> {
-    use std::fs::File;
-    use std::io::BufReader;
+    let data = std::fs::read(path)
+        .with_context(|| format!("Failed to open file for metadata reading: {}", path.display()))?;
+    parse_metadata(&data)
+}
 
-    let file = File::open(path)
-        .with_context(|| format!("Failed to open file for EXIF reading: {}", path.display()))?;
-    let mut reader = BufReader::new(file);
+/// Read metadata from GPR file contents in one pass over both parsers
+///
+/// The EXIF/DNG tags are read with kamadak-exif and the file is also run
+/// through the SDK's `gpr_parse_metadata`. EXIF values win where both have
+/// one; the SDK fills the gaps and supplies the sensor size the decoder will
+/// use. Files whose EXIF cannot be read fall back to the SDK values alone.
+pub fn parse_metadata(data: &[u8]) -> Result<GprMetadata> {
+    let exif_data = exif::Reader::new()
+        .read_from_container(&mut std::io::Cursor::new(data))
+        .context("Failed to read EXIF data from GPR file");
+    let sdk = read_sdk_metadata(data);
 
-    let exif_reader = exif::Reader::new();
-    let exif_data = exif_reader
-        .read_from_container(&mut reader)
-        .context("Failed to read EXIF data from GPR file")?;
+    match (exif_data, sdk) {
+        (Ok(exif_data), Ok(sdk)) => {
+            let mut metadata = metadata_from_exif(&exif_data);
+            reconcile(&mut metadata, &sdk);
+            Ok(metadata)
+        }
+        (Ok(exif_data), Err(e)) => {
+            log::warn!("GPR SDK could not parse metadata, using EXIF only: {}", e);
+            let mut metadata = metadata_from_exif(&exif_data);
+            metadata.source = MetadataSource::Exif;
+            Ok(metadata)
+        }
+        (Err(e), Ok(sdk)) => {
+            log::warn!("{:#}, using GPR SDK metadata", e);
+            Ok(metadata_from_sdk(sdk))
+        }
+        (Err(exif_error), Err(sdk_error)) => Err(anyhow!(
+            "Failed to read metadata: {:#}; {:#}",
+            exif_error,
+            sdk_error
+        )),
+    }
+}
+
+/// Whether the SDK's metadata parser accepts the file contents
+pub fn sdk_can_parse(data: &[u8]) -> Result<()> {
+    read_sdk_metadata(data).map(|_| ())
+}
 
+/// Run the SDK metadata parser over the file contents
+fn read_sdk_metadata(data: &[u8]) -> Result<SdkMetadata> {
+    validate_input(data)?;
+    let allocator = create_allocator();
+
+    // The SDK takes a mutable pointer but only reads the input buffer
+    let mut inp_buffer = gpr_buffer {
+        buffer: data.as_ptr() as *mut std::os::raw::c_void,
+        size: data.len(),
+    };
+
+    let mut parameters: gpr_parameters = unsafe { std::mem::zeroed() };
+    unsafe {
+        gpr_parameters_set_defaults(&mut parameters);
+    }
+
+    let parse_result = unsafe { gpr_parse_metadata(&allocator, &mut inp_buffer, &mut parameters) };
+    if !parse_result {
+        return Err(anyhow!("GPR SDK failed to parse metadata"));
+    }
+
+    let exif_info = &parameters.exif_info;
+    let iso = match exif_info.iso_speed_rating {
+        0 => None,
+        iso => Some(iso as u32),
+    };
+
+    let date = exif_info.date_time_original;
+    let date_taken = (date.year > 0).then(|| {
+        ExifDateTime {
+            year: date.year as i32,
+            month: date.month,
+            day: date.day,
+            hour: date.hour,
+            minute: date.minute,
+            second: date.second,
+        }
+        .to_string()
+    });
+
+    Ok(SdkMetadata {
+        width: parameters.input_width,
+        height: parameters.input_height,
+        make: c_string_field(&exif_info.camera_make),
+        model: c_string_field(&exif_info.camera_model),
+        serial_number: c_string_field(&exif_info.camera_serial),
+        iso,
+        exposure_time: exposure_display(exif_info.exposure_time),
+        f_number: rational_value(exif_info.f_stop_number)
+            .map(|f| format!("f/{}", (f * 10.0).round() / 10.0)),
+        date_taken,
+    })
+}
+
+fn metadata_from_exif(exif_data: &exif::Exif) -> GprMetadata {
     // Extract camera make and model
     let make = exif_data
         .get_field(exif::Tag::Make, exif::In::PRIMARY)
@@ -28,31 +136,73 @@ pub fn read_metadata(path: &Path) -> Result<GprMetadata> {
         .get_field(exif::Tag::Model, exif::In::PRIMARY)
         .map(|f| f.display_value().to_string());
 
-    let camera_model = match (make, model) {
-        (Some(make), Some(model)) => format!("{} {}", make.trim(), model.trim()),
-        (None, Some(model)) => model.trim().to_string(),
-        (Some(make), None) => make.trim().to_string(),
-        (None, None) => "Unknown Camera".to_string(),
-    };
+    let make = make.map(|m| m.trim().trim_matches('"').trim().to_string());
+    let model = model.map(|m| m.trim().trim_matches('"').trim().to_string());
+
+    let camera_model = camera_name(make, model);
+
+    // Sensor size is the raw image in IFD0
+    let sensor_width = uint_values(exif_data, exif::Tag::ImageWidth)
+        .and_then(|v| v.first().copied())
+        .unwrap_or(0);
 
-    // Get image dimensions
-    let width = exif_data
-        .get_field(exif::Tag::ImageWidth, exif::In::PRIMARY)
+    let sensor_height = uint_values(exif_data, exif::Tag::ImageLength)
+        .and_then(|v| v.first().copied())
+        .unwrap_or(0);
+
+    // DNG ActiveArea is top, left, bottom, right
+    let active_area = uint_values(exif_data, exif::Tag(exif::Context::Tiff, 50829))
+        .filter(|v| v.len() >= 4 && v[2] > v[0] && v[3] > v[1])
+        .map(|v| ImageArea {
+            top: v[0],
+            left: v[1],
+            width: v[3] - v[1],
+            height: v[2] - v[0],
+        });
+
+    // Output size is the DNG DefaultCropSize, else the active area, else the sensor
+    let (width, height) = uint_values(exif_data, exif::Tag(exif::Context::Tiff, 50720))
+        .filter(|v| v.len() >= 2 && v[0] > 0 && v[1] > 0)
+        .map(|v| (v[0], v[1]))
+        .or_else(|| active_area.map(|area| (area.width, area.height)))
+        .unwrap_or((sensor_width, sensor_height));
+
+    // Only 2x2 patterns are used by GoPro sensors
+    let cfa_pattern = exif_data
+        .get_field(exif::Tag(exif::Context::Tiff, 33422), exif::In::PRIMARY)
         .and_then(|f| match f.value {
-            exif::Value::Short(ref v) if !v.is_empty() => Some(v[0] as u32),
-            exif::Value::Long(ref v) if !v.is_empty() => Some(v[0]),
+            exif::Value::Byte(ref v) => CfaPattern::from_dng(v),
             _ => None,
-        })
-        .unwrap_or(0);
+        });
 
-    let height = exif_data
-        .get_field(exif::Tag::ImageLength, exif::In::PRIMARY)
+    let orientation = uint_values(exif_data, exif::Tag::Orientation)
+        .and_then(|v| v.first().map(|&o| o as u16));
+
+    // Thumbnail IFD (IFD1); GPR files normally have none
+    let thumbnail = exif_data
+        .fields()
+        .any(|f| f.ifd_num == exif::In::THUMBNAIL)
+        .then(|| {
+            let thumbnail_value = |tag| {
+                exif_data
+                    .get_field(tag, exif::In::THUMBNAIL)
+                    .and_then(|f| f.value.get_uint(0))
+            };
+            ThumbnailInfo {
+                width: thumbnail_value(exif::Tag::ImageWidth),
+                height: thumbnail_value(exif::Tag::ImageLength),
+                orientation: thumbnail_value(exif::Tag::Orientation).map(|o| o as u16),
+                length: thumbnail_value(exif::Tag::JPEGInterchangeFormatLength),
+            }
+        });
+
+    // GPMF carried in DNGPrivateData (Fusion) holds the accelerometer
+    let gravity = exif_data
+        .get_field(exif::Tag(exif::Context::Tiff, 50740), exif::In::PRIMARY)
         .and_then(|f| match f.value {
-            exif::Value::Short(ref v) if !v.is_empty() => Some(v[0] as u32),
-            exif::Value::Long(ref v) if !v.is_empty() => Some(v[0]),
+            exif::Value::Byte(ref v) => gpmf::from_dng_private_data(v).and_then(gpmf::gravity),
             _ => None,
-        })
-        .unwrap_or(0);
+        });
 
     // Extract ISO
     let iso = exif_data
@@ -97,14 +247,24 @@ pub fn read_metadata(path: &Path) -> Result<GprMetadata> {
             display.trim_matches('"').to_string()
         });
 
+    // Extract camera serial number (EXIF 2.3 tag, or the DNG one GoPro writes)
+    let serial_number = ascii_value(exif_data, exif::Tag::BodySerialNumber)
+        .or_else(|| ascii_value(exif_data, exif::Tag(exif::Context::Tiff, 50735)));
+
+    let owner_name = ascii_value(exif_data, exif::Tag::CameraOwnerName);
+
     // Extract GPS coordinates
-    let gps_latitude = extract_gps_coordinate(&exif_data, exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef);
-    let gps_longitude = extract_gps_coordinate(&exif_data, exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef);
+    let gps_latitude = extract_gps_coordinate(exif_data, exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef);
+    let gps_longitude = extract_gps_coordinate(exif_data, exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef);
 
-    Ok(GprMetadata {
+    GprMetadata {
         camera_model,
         width,
         height,
+        sensor_width,
+        sensor_height,
+        active_area,
+        cfa_pattern,
         iso,
         exposure_time,
         f_number,
@@ -112,7 +272,131 @@ pub fn read_metadata(path: &Path) -> Result<GprMetadata> {
         date_taken,
         gps_latitude,
         gps_longitude,
-    })
+        serial_number,
+        owner_name,
+        orientation,
+        thumbnail,
+        gravity,
+        source: MetadataSource::ExifAndSdk,
+    }
+}
+
+fn metadata_from_sdk(sdk: SdkMetadata) -> GprMetadata {
+    GprMetadata {
+        camera_model: camera_name(sdk.make, sdk.model),
+        width: sdk.width,
+        height: sdk.height,
+        sensor_width: sdk.width,
+        sensor_height: sdk.height,
+        active_area: None,
+        cfa_pattern: None,
+        iso: sdk.iso,
+        exposure_time: sdk.exposure_time,
+        f_number: sdk.f_number,
+        focal_length: None,
+        date_taken: sdk.date_taken,
+        gps_latitude: None,
+        gps_longitude: None,
+        serial_number: sdk.serial_number,
+        owner_name: None,
+        orientation: None,
+        thumbnail: None,
+        gravity: None,
+        source: MetadataSource::Sdk,
+    }
+}
+
+/// Fill gaps in the EXIF values from the SDK and take the SDK sensor size,
+/// which is what the decoder works from
+fn reconcile(metadata: &mut GprMetadata, sdk: &SdkMetadata) {
+    if sdk.width > 0 && sdk.height > 0 {
+        if (metadata.sensor_width, metadata.sensor_height) != (sdk.width, sdk.height) {
+            log::debug!(
+                "Sensor size differs: EXIF {}x{}, GPR SDK {}x{}",
+                metadata.sensor_width,
+                metadata.sensor_height,
+                sdk.width,
+                sdk.height
+            );
+            if metadata.width == metadata.sensor_width && metadata.height == metadata.sensor_height {
+                metadata.width = sdk.width;
+                metadata.height = sdk.height;
+            }
+        }
+        metadata.sensor_width = sdk.width;
+        metadata.sensor_height = sdk.height;
+    }
+
+    if metadata.camera_model == UNKNOWN_CAMERA {
+        metadata.camera_model = camera_name(sdk.make.clone(), sdk.model.clone());
+    }
+    if metadata.iso.is_none() {
+        metadata.iso = sdk.iso;
+    }
+    if metadata.exposure_time.is_none() {
+        metadata.exposure_time = sdk.exposure_time.clone();
+    }
+    if metadata.f_number.is_none() {
+        metadata.f_number = sdk.f_number.clone();
+    }
+    if metadata.date_taken.is_none() {
+        metadata.date_taken = sdk.date_taken.clone();
+    }
+    if metadata.serial_number.is_none() {
+        metadata.serial_number = sdk.serial_number.clone();
+    }
+}
+
+const UNKNOWN_CAMERA: &str = "Unknown Camera";
+
+fn camera_name(make: Option<String>, model: Option<String>) -> String {
+    match (make, model) {
+        (Some(make), Some(model)) => format!("{} {}", make, model),
+        (None, Some(model)) => model,
+        (Some(make), None) => make,
+        (None, None) => UNKNOWN_CAMERA.to_string(),
+    }
+}
+
+fn rational_value(value: gpr_unsigned_rational) -> Option<f64> {
+    if value.denominator == 0 || value.numerator == 0 {
+        None
+    } else {
+        Some(value.numerator as f64 / value.denominator as f64)
+    }
+}
+
+/// Exposure times are shown as fractions, like kamadak-exif does
+fn exposure_display(value: gpr_unsigned_rational) -> Option<String> {
+    let seconds = rational_value(value)?;
+    if seconds < 1.0 {
+        Some(format!("1/{}", (1.0 / seconds).round()))
+    } else {
+        Some(format!("{}", seconds))
+    }
+}
+
+/// Read an integer (or rational, rounded) field as a list of values
+fn uint_values(exif_data: &exif::Exif, tag: exif::Tag) -> Option<Vec<u32>> {
+    let field = exif_data.get_field(tag, exif::In::PRIMARY)?;
+    match field.value {
+        exif::Value::Short(ref v) => Some(v.iter().map(|&x| x as u32).collect()),
+        exif::Value::Long(ref v) => Some(v.clone()),
+        exif::Value::Rational(ref v) => Some(v.iter().map(|r| r.to_f64().round() as u32).collect()),
+        _ => None,
+    }
+}
+
+/// Read an ASCII field as a trimmed string, ignoring empty values
+fn ascii_value(exif_data: &exif::Exif, tag: exif::Tag) -> Option<String> {
+    let field = exif_data.get_field(tag, exif::In::PRIMARY)?;
+    match field.value {
+        exif::Value::Ascii(ref parts) => parts
+            .first()
+            .map(|v| String::from_utf8_lossy(v).trim().to_string())
+            .filter(|v| !v.is_empty()),
+        _ => None,
+    }
 }
 
 /// Extract GPS coordinate from EXIF data
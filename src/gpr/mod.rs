@@ -1,6 +1,19 @@
 pub mod ffi;
 pub mod converter;
+pub mod develop;
+pub mod exif_writer;
+pub mod geometry;
+pub mod icc;
+pub mod inspect;
+pub mod lens;
 pub mod metadata_reader;
+pub mod remap;
+pub mod resize;
+pub mod stitch;
+pub mod tiff_writer;
+pub mod validate;
+pub mod verify;
+pub mod xmp_writer;
 
 pub use converter::GprConverter;
-pub use metadata_reader::read_metadata;
+pub use metadata_reader::{parse_metadata, read_metadata};
@@ -0,0 +1,57 @@
+use image::{ImageBuffer, Rgb};
+
+/// Build a `width` x `height` image whose pixel centres are taken from the
+/// source positions `source(x, y)` returns, with bicubic (Catmull-Rom)
+/// interpolation. Positions outside the source are black.
+pub fn remap<F>(
+    image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    width: u32,
+    height: u32,
+    source: F,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>>
+where
+    F: Fn(f32, f32) -> (f32, f32),
+{
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let (sx, sy) = source(x as f32 + 0.5, y as f32 + 0.5);
+        Rgb(sample_bicubic(image, sx - 0.5, sy - 0.5).unwrap_or([0; 3]))
+    })
+}
+
+/// Catmull-Rom interpolation at pixel coordinates (integer values are pixel
+/// centres), or `None` more than half a pixel outside the image
+pub fn sample_bicubic(image: &ImageBuffer<Rgb<u8>, Vec<u8>>, x: f32, y: f32) -> Option<[u8; 3]> {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    if !(x >= -0.5 && y >= -0.5 && x <= width as f32 - 0.5 && y <= height as f32 - 0.5) {
+        return None;
+    }
+
+    let (x0, y0) = (x.floor(), y.floor());
+    let (wx, wy) = (catmull_rom(x - x0), catmull_rom(y - y0));
+    let (x0, y0) = (x0 as i64, y0 as i64);
+
+    let mut sum = [0.0f32; 3];
+    for (j, weight_y) in wy.iter().enumerate() {
+        let sy = (y0 + j as i64 - 1).clamp(0, height - 1) as u32;
+        for (i, weight_x) in wx.iter().enumerate() {
+            let sx = (x0 + i as i64 - 1).clamp(0, width - 1) as u32;
+            let pixel = image.get_pixel(sx, sy).0;
+            let weight = weight_x * weight_y;
+            for (total, value) in sum.iter_mut().zip(pixel) {
+                *total += weight * value as f32;
+            }
+        }
+    }
+    Some(sum.map(|v| v.round().clamp(0.0, 255.0) as u8))
+}
+
+/// Weights of the four taps around a sample `t` (0-1) past the second tap
+fn catmull_rom(t: f32) -> [f32; 4] {
+    let (t2, t3) = (t * t, t * t * t);
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    ]
+}
@@ -0,0 +1,225 @@
+use crate::models::resize::{ResizeFilter, ResizeSettings, Sharpening};
+use image::{ImageBuffer, Rgb};
+use std::f32::consts::PI;
+
+/// Resize to the configured output size, or return the image unchanged
+pub fn resize(
+    image: ImageBuffer<Rgb<u8>, Vec<u8>>,
+    settings: &ResizeSettings,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let target = settings.mode.target(image.width(), image.height());
+    let Some(((width, height), (scaled_width, scaled_height))) = target else {
+        return image;
+    };
+    log::info!(
+        "Resizing {}x{} to {}x{} ({}, {})",
+        image.width(),
+        image.height(),
+        width,
+        height,
+        settings.mode.display(),
+        settings.filter.as_str()
+    );
+    let scaled = resample(&image, scaled_width, scaled_height, settings.filter);
+    if (scaled_width, scaled_height) == (width, height) {
+        return scaled;
+    }
+    // Fill: crop the overflow evenly from both sides
+    let (left, top) = ((scaled_width - width) / 2, (scaled_height - height) / 2);
+    image::imageops::crop_imm(&scaled, left, top, width, height).to_image()
+}
+
+/// Separable resampling to `width` x `height`. When shrinking, the filter is
+/// stretched over the source pixels each output pixel covers.
+pub fn resample(
+    image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    width: u32,
+    height: u32,
+    filter: ResizeFilter,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let (source_width, source_height) = (image.width() as usize, image.height() as usize);
+    let (width, height) = (width as usize, height as usize);
+    let source: Vec<f32> = image.as_raw().iter().map(|&v| v as f32).collect();
+
+    // Horizontal pass into source_height rows of the new width
+    let columns = weights(source_width, width, filter);
+    let mut rows = vec![0.0f32; width * source_height * 3];
+    for y in 0..source_height {
+        let line = &source[y * source_width * 3..(y + 1) * source_width * 3];
+        for (x, (first, taps)) in columns.iter().enumerate() {
+            let out = &mut rows[(y * width + x) * 3..(y * width + x) * 3 + 3];
+            for (i, weight) in taps.iter().enumerate() {
+                let at = (first + i) * 3;
+                for c in 0..3 {
+                    out[c] += weight * line[at + c];
+                }
+            }
+        }
+    }
+
+    // Vertical pass, a whole row at a time
+    let lines = weights(source_height, height, filter);
+    let mut output = vec![0.0f32; width * height * 3];
+    for (y, (first, taps)) in lines.iter().enumerate() {
+        let out = &mut output[y * width * 3..(y + 1) * width * 3];
+        for (i, weight) in taps.iter().enumerate() {
+            let row = &rows[(first + i) * width * 3..(first + i + 1) * width * 3];
+            for (value, source) in out.iter_mut().zip(row) {
+                *value += weight * source;
+            }
+        }
+    }
+
+    let data = output.iter().map(|v| v.round().clamp(0.0, 255.0) as u8).collect();
+    ImageBuffer::from_raw(width as u32, height as u32, data).expect("three samples per pixel")
+}
+
+/// For every output position, the first source index it reads and the
+/// normalized weights of consecutive source pixels from there
+fn weights(source: usize, target: usize, filter: ResizeFilter) -> Vec<(usize, Vec<f32>)> {
+    let ratio = source as f32 / target as f32;
+    let stretch = ratio.max(1.0);
+    let support = filter.support() * stretch;
+    (0..target)
+        .map(|i| {
+            let centre = (i as f32 + 0.5) * ratio - 0.5;
+            let first = (centre - support).ceil().max(0.0) as usize;
+            let last = ((centre + support).floor() as usize).min(source - 1);
+            let mut taps: Vec<f32> = (first..=last)
+                .map(|j| filter.kernel((j as f32 - centre) / stretch))
+                .collect();
+            let total: f32 = taps.iter().sum();
+            if total.abs() > f32::EPSILON {
+                taps.iter_mut().for_each(|w| *w /= total);
+            }
+            (first, taps)
+        })
+        .collect()
+}
+
+impl ResizeFilter {
+    /// Half-width of the kernel in source pixels, before stretching
+    fn support(&self) -> f32 {
+        match self {
+            ResizeFilter::Lanczos => 3.0,
+            ResizeFilter::Mitchell => 2.0,
+        }
+    }
+
+    fn kernel(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            ResizeFilter::Lanczos => {
+                if x < 1e-6 {
+                    1.0
+                } else if x < 3.0 {
+                    let px = PI * x;
+                    3.0 * px.sin() * (px / 3.0).sin() / (px * px)
+                } else {
+                    0.0
+                }
+            }
+            ResizeFilter::Mitchell => {
+                const B: f32 = 1.0 / 3.0;
+                const C: f32 = 1.0 / 3.0;
+                let (x2, x3) = (x * x, x * x * x);
+                let value = if x < 1.0 {
+                    (12.0 - 9.0 * B - 6.0 * C) * x3 + (-18.0 + 12.0 * B + 6.0 * C) * x2 + (6.0 - 2.0 * B)
+                } else if x < 2.0 {
+                    (-B - 6.0 * C) * x3 + (6.0 * B + 30.0 * C) * x2 + (-12.0 * B - 48.0 * C) * x + (8.0 * B + 24.0 * C)
+                } else {
+                    0.0
+                };
+                value / 6.0
+            }
+        }
+    }
+}
+
+/// Unsharp mask on brightness: the difference between each pixel's luma
+/// and a Gaussian blur of it is scaled by the amount and added to all three
+/// channels, so edges gain contrast without colour fringes. Differences
+/// under the threshold are left alone.
+pub fn sharpen(image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, settings: &Sharpening) {
+    if settings.is_off() {
+        return;
+    }
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let luma: Vec<f32> = image
+        .pixels()
+        .map(|p| 0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32)
+        .collect();
+    let blurred = gaussian_blur(&luma, width, height, settings.radius);
+
+    let threshold = settings.threshold as f32;
+    for ((pixel, luma), blurred) in image.pixels_mut().zip(&luma).zip(&blurred) {
+        let detail = luma - blurred;
+        if detail.abs() < threshold {
+            continue;
+        }
+        let boost = detail * settings.amount;
+        for value in pixel.0.iter_mut() {
+            *value = (*value as f32 + boost).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// Separable Gaussian blur of a single plane, clamped at the borders
+fn gaussian_blur(plane: &[f32], width: usize, height: usize, sigma: f32) -> Vec<f32> {
+    let radius = (sigma * 3.0).ceil().max(1.0) as isize;
+    let kernel: Vec<f32> = (-radius..=radius)
+        .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = kernel.iter().sum();
+    let kernel: Vec<f32> = kernel.iter().map(|w| w / total).collect();
+    let clamp = |i: isize, n: usize| i.clamp(0, n as isize - 1) as usize;
+
+    let mut rows = vec![0.0f32; plane.len()];
+    for y in 0..height {
+        for x in 0..width {
+            rows[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, w)| w * plane[y * width + clamp(x as isize + k as isize - radius, width)])
+                .sum();
+        }
+    }
+    let mut output = vec![0.0f32; plane.len()];
+    for (y, out) in output.chunks_exact_mut(width).enumerate() {
+        for (k, weight) in kernel.iter().enumerate() {
+            let source = clamp(y as isize + k as isize - radius, height);
+            for (value, row) in out.iter_mut().zip(&rows[source * width..(source + 1) * width]) {
+                *value += weight * row;
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::resize::ResizeMode;
+
+    #[test]
+    fn test_resize_and_sharpen() {
+        // Flat areas stay flat with either filter, at the requested size
+        let flat = ImageBuffer::from_pixel(400, 300, Rgb([90u8, 140, 200]));
+        for filter in [ResizeFilter::Lanczos, ResizeFilter::Mitchell] {
+            let settings = ResizeSettings { mode: ResizeMode::Fill { width: 100, height: 100 }, filter };
+            let resized = resize(flat.clone(), &settings);
+            assert_eq!(resized.dimensions(), (100, 100));
+            assert!(resized.pixels().all(|p| p.0 == [90, 140, 200]));
+        }
+
+        // Sharpening steepens an edge, and a high threshold leaves it alone
+        let edge = ImageBuffer::from_fn(20, 4, |x, _| if x < 10 { Rgb([100u8; 3]) } else { Rgb([150u8; 3]) });
+        let mut sharpened = edge.clone();
+        sharpen(&mut sharpened, &Sharpening { amount: 1.0, radius: 1.0, threshold: 0 });
+        assert!(sharpened.get_pixel(9, 0)[0] < 100 && sharpened.get_pixel(10, 0)[0] > 150);
+        assert_eq!(sharpened.get_pixel(0, 0)[0], 100);
+        let mut untouched = edge.clone();
+        sharpen(&mut untouched, &Sharpening { amount: 1.0, radius: 1.0, threshold: 60 });
+        assert_eq!(untouched, edge);
+    }
+}
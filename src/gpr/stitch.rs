@@ -0,0 +1,214 @@
+use crate::gpr::remap::sample_bicubic;
+use image::{ImageBuffer, Rgb};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+/// Field of view of each Fusion lens. The 10 degrees beyond a hemisphere
+/// are seen by both lenses and hold the seam.
+const LENS_FOV_DEGREES: f32 = 190.0;
+
+/// Width of the band either side of the seam in which the lenses are
+/// cross-faded; at most the 10 degree overlap
+const BLEND_DEGREES: f32 = 8.0;
+
+/// Points sampled around the seam when matching the lens exposures
+const SEAM_SAMPLES: usize = 720;
+
+/// Largest exposure correction applied to the back lens, either way
+const MAX_GAIN: f32 = 2.0;
+
+/// An equidistant fisheye (radius proportional to the angle from the axis)
+/// whose image circle is centred in the frame and fills its shorter side
+struct Fisheye<'a> {
+    image: &'a ImageBuffer<Rgb<u8>, Vec<u8>>,
+    cx: f32,
+    cy: f32,
+    /// Image circle radius per radian off axis
+    pixels_per_radian: f32,
+    half_fov: f32,
+}
+
+impl<'a> Fisheye<'a> {
+    fn new(image: &'a ImageBuffer<Rgb<u8>, Vec<u8>>) -> Self {
+        let half_fov = LENS_FOV_DEGREES.to_radians() / 2.0;
+        let radius = image.width().min(image.height()) as f32 / 2.0;
+        Self {
+            image,
+            cx: image.width() as f32 / 2.0,
+            cy: image.height() as f32 / 2.0,
+            pixels_per_radian: radius / half_fov,
+            half_fov,
+        }
+    }
+
+    /// Colour seen in `direction` (x right, y up, z along the lens axis),
+    /// or `None` outside the field of view
+    fn sample(&self, [x, y, z]: [f32; 3]) -> Option<[f32; 3]> {
+        let theta = z.clamp(-1.0, 1.0).acos();
+        if theta > self.half_fov {
+            return None;
+        }
+        let rho = x.hypot(y);
+        let r = theta * self.pixels_per_radian;
+        let (dx, dy) = if rho > 1e-6 { (x / rho, y / rho) } else { (0.0, 0.0) };
+        // Pixel centres are at integer coordinates for `sample_bicubic`
+        let (px, py) = (self.cx + r * dx - 0.5, self.cy - r * dy - 0.5);
+        sample_bicubic(self.image, px, py).map(|p| p.map(f32::from))
+    }
+}
+
+/// Unit direction for a panorama position: longitude 0 faces the front
+/// lens, latitude is positive upwards
+fn direction(longitude: f32, latitude: f32) -> [f32; 3] {
+    let (sin_lon, cos_lon) = longitude.sin_cos();
+    let (sin_lat, cos_lat) = latitude.sin_cos();
+    [cos_lat * sin_lon, sin_lat, cos_lat * cos_lon]
+}
+
+/// The same direction seen from the back lens, which faces the other way
+fn back_direction([x, y, z]: [f32; 3]) -> [f32; 3] {
+    [-x, y, -z]
+}
+
+/// Stitch the front and back fisheye images of a Fusion shot into an
+/// equirectangular panorama, twice as wide as it is high, at the
+/// resolution of the lens centres. The back lens is matched to the front's
+/// exposure along the seam, and the two are cross-faded across it.
+pub fn stitch(
+    front: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    back: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let front = Fisheye::new(front);
+    let back = Fisheye::new(back);
+
+    let height = ((PI * front.pixels_per_radian).round() as u32).max(1);
+    let width = height * 2;
+    let gains = seam_gains(&front, &back);
+    let blend = BLEND_DEGREES.to_radians();
+    log::debug!("Stitching {}x{} panorama, back lens gains {:?}", width, height, gains);
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let longitude = (x as f32 + 0.5) / width as f32 * 2.0 * PI - PI;
+        let latitude = FRAC_PI_2 - (y as f32 + 0.5) / height as f32 * PI;
+        let d = direction(longitude, latitude);
+
+        // Weight of the front lens: 1 well inside its hemisphere, fading to
+        // 0 across the seam at 90 degrees off axis
+        let off_axis = d[2].clamp(-1.0, 1.0).acos();
+        let t = ((FRAC_PI_2 - off_axis) / blend + 0.5).clamp(0.0, 1.0);
+        let weight = t * t * (3.0 - 2.0 * t);
+
+        let front_colour = if weight > 0.0 { front.sample(d) } else { None };
+        let back_colour = if weight < 1.0 { back.sample(back_direction(d)) } else { None }
+            .map(|colour| [0, 1, 2].map(|c| colour[c] * gains[c]));
+
+        let colour = match (front_colour, back_colour) {
+            (Some(f), Some(b)) => [0, 1, 2].map(|c| f[c] * weight + b[c] * (1.0 - weight)),
+            (Some(colour), None) | (None, Some(colour)) => colour,
+            (None, None) => [0.0; 3],
+        };
+        Rgb(colour.map(|v| v.round().clamp(0.0, 255.0) as u8))
+    })
+}
+
+/// Per-channel gains bringing the back lens to the front lens's brightness,
+/// from the mean of each around the seam where both see the same scene
+fn seam_gains(front: &Fisheye, back: &Fisheye) -> [f32; 3] {
+    let mut front_sum = [0.0f64; 3];
+    let mut back_sum = [0.0f64; 3];
+    for i in 0..SEAM_SAMPLES {
+        // Points on the seam circle, perpendicular to the lens axes
+        let angle = i as f32 / SEAM_SAMPLES as f32 * 2.0 * PI;
+        let d = [angle.cos(), angle.sin(), 0.0];
+        if let (Some(f), Some(b)) = (front.sample(d), back.sample(back_direction(d))) {
+            for c in 0..3 {
+                front_sum[c] += f[c] as f64;
+                back_sum[c] += b[c] as f64;
+            }
+        }
+    }
+    [0, 1, 2].map(|c| {
+        if back_sum[c] > 0.0 && front_sum[c] > 0.0 {
+            ((front_sum[c] / back_sum[c]) as f32).clamp(1.0 / MAX_GAIN, MAX_GAIN)
+        } else {
+            1.0
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stitch() {
+        // Flat grey lenses, the back one a stop darker
+        let front = ImageBuffer::from_pixel(64, 60, Rgb([120u8, 120, 120]));
+        let back = ImageBuffer::from_pixel(64, 60, Rgb([60u8, 60, 60]));
+        let panorama = stitch(&front, &back);
+
+        assert_eq!(panorama.width(), panorama.height() * 2);
+        // Exposure matching leaves no seam and the whole sphere is covered
+        assert!(panorama.pixels().all(|p| p.0.iter().all(|&v| v.abs_diff(120) <= 1)));
+
+        // The centre faces the front lens, the edges the back lens
+        let mut front = front;
+        for (x, y) in [(31, 29), (32, 29), (31, 30), (32, 30)] {
+            front.put_pixel(x, y, Rgb([255, 0, 0]));
+        }
+        let panorama = stitch(&front, &back);
+        let (width, height) = panorama.dimensions();
+        assert!(panorama.get_pixel(width / 2, height / 2).0[0] > 150);
+        assert!(panorama.get_pixel(0, height / 2).0[0] <= 121);
+    }
+
+    #[test]
+    fn test_stitch_positions() {
+        // 100 px lens circles cover 95 degrees, so a feature 45 degrees off
+        // axis sits 47.4 px from the centre and one 30 degrees off 31.6 px
+        let mut front = ImageBuffer::from_pixel(200, 200, Rgb([128u8, 128, 128]));
+        let mut back = front.clone();
+        let mark = |image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, cx: u32, cy: u32, colour: [u8; 3]| {
+            for y in cy - 3..=cy + 3 {
+                for x in cx - 3..=cx + 3 {
+                    image.put_pixel(x, y, Rgb(colour));
+                }
+            }
+        };
+        // Front: 45 degrees right on the horizon, and 30 degrees up ahead
+        mark(&mut front, 147, 100, [255, 0, 0]);
+        mark(&mut front, 100, 68, [0, 255, 0]);
+        // Back: 45 degrees to its right, which faces the panorama's left
+        mark(&mut back, 147, 100, [0, 0, 255]);
+        let panorama = stitch(&front, &back);
+        let (width, height) = panorama.dimensions();
+
+        // Longitude runs -180..180 degrees across, latitude 90..-90 down
+        let at = |longitude: f32, latitude: f32| {
+            let x = ((longitude + 180.0) / 360.0 * width as f32) as u32;
+            let y = ((90.0 - latitude) / 180.0 * height as f32) as u32;
+            panorama.get_pixel(x, y).0
+        };
+        assert!(at(45.0, 0.0)[0] > 200, "{:?}", at(45.0, 0.0));
+        assert!(at(-45.0, 0.0)[0] < 140);
+        assert!(at(0.0, 30.0)[1] > 200, "{:?}", at(0.0, 30.0));
+        assert!(at(0.0, -30.0)[1] < 140);
+        assert!(at(-135.0, 0.0)[2] > 200, "{:?}", at(-135.0, 0.0));
+        assert!(at(135.0, 0.0)[2] < 140);
+
+        // The seam is at 90 degrees: a front lens that sees white only up to
+        // it and a black back lens meet there
+        let mut white_front = ImageBuffer::from_pixel(200, 200, Rgb([0u8, 0, 0]));
+        for (_, _, pixel) in white_front.enumerate_pixels_mut().filter(|&(x, y, _)| {
+            (x as f32 + 0.5 - 100.0).hypot(y as f32 + 0.5 - 100.0) < 100.0 * 90.0 / 95.0
+        }) {
+            *pixel = Rgb([255, 255, 255]);
+        }
+        let black_back = ImageBuffer::from_pixel(200, 200, Rgb([0u8, 0, 0]));
+        let panorama = stitch(&white_front, &black_back);
+        let row = height / 2;
+        let bright = |x: u32| panorama.get_pixel(x, row).0[0] > 128;
+        assert!((0..width / 4 - 8).all(|x| !bright(x)));
+        assert!((width / 4 + 8..width * 3 / 4 - 8).all(bright));
+        assert!((width * 3 / 4 + 8..width).all(|x| !bright(x)));
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::{Context, Result};
+use exif::{In, Value};
+use image::{ImageBuffer, Rgb};
+use std::io::{Seek, Write};
+use tiff::encoder::{colortype, DirectoryEncoder, Rational, SRational, TiffEncoder, TiffKind};
+use tiff::tags::Tag;
+
+/// Image structure tags the encoder writes itself
+const STRUCTURE_TAGS: &[u16] = &[256, 257, 258, 259, 262, 273, 277, 278, 279, 282, 283, 284, 296, 317, 339];
+
+/// Pointers to the EXIF, GPS and Interoperability IFDs, rewritten for the
+/// new layout
+const POINTER_TAGS: &[u16] = &[34665, 34853, 40965];
+
+/// XMP packet tag (XMLPacket)
+const XMP_TAG: u16 = 700;
+
+/// Write an 8-bit RGB TIFF with an embedded ICC profile and optional XMP
+/// packet. The fields of `exif` (a TIFF-structured block from
+/// `exif_writer`) are copied into the image directory and its EXIF and GPS
+/// sub-directories.
+pub fn write_tiff<W: Write + Seek>(
+    writer: W,
+    image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    exif: Option<Vec<u8>>,
+    icc: &[u8],
+    xmp: Option<&[u8]>,
+) -> Result<()> {
+    let fields = match exif {
+        Some(exif) => exif::Reader::new()
+            .read_raw(exif)
+            .context("Failed to read EXIF block for TIFF output")?
+            .fields()
+            .filter(|field| field.ifd_num == In::PRIMARY)
+            .filter(|field| !POINTER_TAGS.contains(&field.tag.number()))
+            .cloned()
+            .collect(),
+        None => Vec::new(),
+    };
+    let in_context = |context: exif::Context| {
+        fields
+            .iter()
+            .filter(move |field| field.tag.context() == context)
+            .map(|field| (field.tag.number(), &field.value))
+    };
+
+    let mut encoder = TiffEncoder::new(writer)?;
+
+    let mut sub_directories = Vec::new();
+    for (context, pointer) in [(exif::Context::Exif, Tag::ExifDirectory), (exif::Context::Gps, Tag::GpsDirectory)] {
+        let mut entries = in_context(context).peekable();
+        if entries.peek().is_none() {
+            continue;
+        }
+        let mut directory = encoder.extra_directory()?;
+        for (tag, value) in entries {
+            write_field(&mut directory, tag, value)?;
+        }
+        sub_directories.push((pointer, directory.finish_with_offsets()?.offset));
+    }
+
+    let mut tiff_image = encoder.new_image::<colortype::RGB8>(image.width(), image.height())?;
+    let directory = tiff_image.encoder();
+    for (tag, value) in in_context(exif::Context::Tiff) {
+        if !STRUCTURE_TAGS.contains(&tag) {
+            write_field(directory, tag, value)?;
+        }
+    }
+    for (pointer, offset) in sub_directories {
+        directory.write_tag(pointer, offset)?;
+    }
+    directory.write_tag(Tag::IccProfile, icc)?;
+    if let Some(xmp) = xmp {
+        directory.write_tag(Tag::Unknown(XMP_TAG), xmp)?;
+    }
+    tiff_image.write_data(image.as_raw())?;
+    Ok(())
+}
+
+fn write_field<W: Write + Seek, K: TiffKind>(
+    directory: &mut DirectoryEncoder<'_, W, K>,
+    tag: u16,
+    value: &Value,
+) -> Result<()> {
+    let tag = Tag::from_u16_exhaustive(tag);
+    match value {
+        Value::Ascii(strings) => {
+            let text = strings
+                .first()
+                .map(|s| String::from_utf8_lossy(s).into_owned())
+                .unwrap_or_default();
+            directory.write_tag(tag, text.as_str())?
+        }
+        Value::Byte(bytes) | Value::Undefined(bytes, _) => directory.write_tag(tag, bytes.as_slice())?,
+        Value::Short(values) => directory.write_tag(tag, values.as_slice())?,
+        Value::Long(values) => directory.write_tag(tag, values.as_slice())?,
+        Value::SLong(values) => directory.write_tag(tag, values.as_slice())?,
+        Value::Rational(values) => {
+            let values: Vec<Rational> = values.iter().map(|r| Rational { n: r.num, d: r.denom }).collect();
+            directory.write_tag(tag, values.as_slice())?
+        }
+        Value::SRational(values) => {
+            let values: Vec<SRational> = values.iter().map(|r| SRational { n: r.num, d: r.denom }).collect();
+            directory.write_tag(tag, values.as_slice())?
+        }
+        other => log::debug!("Skipping EXIF tag {:?} of unsupported type {:?}", tag, other),
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use crate::gpr::verify::{check_structure, Verdict};
+use anyhow::{bail, Result};
+
+/// Largest input handed to the SDK; GoPro's own files are under 10 MB
+pub const MAX_INPUT_SIZE: usize = 256 * 1024 * 1024;
+
+/// Largest width or height accepted from a file or the decoder
+pub const MAX_DIMENSION: u32 = 16384;
+
+/// Largest pixel count accepted; the biggest GoPro sensor is 27 MP
+pub const MAX_PIXELS: u64 = 100_000_000;
+
+/// Gate every SDK call: the input must be a size the SDK can take and its
+/// container must pass the structure checks, so the SDK never follows an
+/// offset or size out of the buffer
+pub fn validate_input(data: &[u8]) -> Result<()> {
+    if data.len() > MAX_INPUT_SIZE {
+        bail!("File is {} bytes, more than the {} byte limit", data.len(), MAX_INPUT_SIZE);
+    }
+
+    if let Some(issue) = check_structure(data)
+        .into_iter()
+        .find(|issue| issue.severity == Verdict::Corrupt)
+    {
+        bail!("Rejected before decoding: {}", issue.message);
+    }
+    Ok(())
+}
+
+/// Image dimensions within [`MAX_DIMENSION`] and [`MAX_PIXELS`]
+pub fn check_dimensions(width: u32, height: u32) -> Result<()> {
+    if width == 0 || height == 0 {
+        bail!("Image has no pixels ({}x{})", width, height);
+    }
+    if width > MAX_DIMENSION || height > MAX_DIMENSION || width as u64 * height as u64 > MAX_PIXELS {
+        bail!("Image size {}x{} exceeds the supported maximum", width, height);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Truncated and byte-mutated copies of the sample files must be
+    /// rejected cleanly or pass with in-bounds structure; nothing may panic
+    #[test]
+    fn test_fuzz_corpus() {
+        let samples = ["sample-data/Hero6/GOPR0024.GPR", "sample-data/Fusion/GPBK7066.GPR"];
+        for sample in samples {
+            let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(sample);
+            let data = std::fs::read(&path).unwrap_or_else(|e| panic!("{} is missing: {}", sample, e));
+            validate_input(&data).unwrap();
+
+            // Truncation at every header byte and at points through the payload
+            for len in (0..512).chain((512..data.len()).step_by(data.len() / 64)) {
+                assert!(validate_input(&data[..len]).is_err(), "{} truncated to {}", sample, len);
+            }
+
+            // Deterministic byte mutations, every other one in the header and
+            // first IFD and the rest anywhere in the file
+            let mut mutated = data.clone();
+            let mut seed: u32 = 0x2545_f491;
+            for i in 0..2000 {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let span = if i % 2 == 0 { 512.min(data.len()) } else { data.len() };
+                let offset = seed as usize % span;
+                let original = mutated[offset];
+                mutated[offset] = (seed >> 24) as u8;
+                let _ = validate_input(&mutated);
+                mutated[offset] = original;
+            }
+        }
+    }
+}
@@ -0,0 +1,488 @@
+use crate::gpr::metadata_reader::sdk_can_parse;
+use crate::gpr::GprConverter;
+use crate::gpr::validate::check_dimensions;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// TIFF compression value GoPro uses for VC-5 encoded raw data
+const COMPRESSION_VC5: u32 = 9;
+
+/// Every VC-5 bitstream starts with this marker
+const VC5_MARKER: &[u8] = b"VC-5";
+
+/// IFDs followed before a file is assumed to contain a loop
+const MAX_IFDS: usize = 32;
+
+/// Zero bytes at the end of the raw data that suggest an interrupted copy
+const ZERO_TAIL: usize = 4096;
+
+const NEW_SUBFILE_TYPE: u16 = 254;
+const IMAGE_WIDTH: u16 = 256;
+const IMAGE_LENGTH: u16 = 257;
+const COMPRESSION: u16 = 259;
+const STRIP_OFFSETS: u16 = 273;
+const ROWS_PER_STRIP: u16 = 278;
+const STRIP_BYTE_COUNTS: u16 = 279;
+const TILE_WIDTH: u16 = 322;
+const TILE_LENGTH: u16 = 323;
+const TILE_OFFSETS: u16 = 324;
+const TILE_BYTE_COUNTS: u16 = 325;
+const SUB_IFDS: u16 = 330;
+const EXIF_IFD: u16 = 34665;
+const GPS_IFD: u16 = 34853;
+
+/// Overall state of a file, ordered from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Ok,
+    /// Readable, but something looks off
+    Warning,
+    /// Conversion will fail or cannot be trusted
+    Corrupt,
+}
+
+impl Verdict {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Verdict::Ok => "OK",
+            Verdict::Warning => "WARNING",
+            Verdict::Corrupt => "CORRUPT",
+        }
+    }
+}
+
+/// One problem found in a file
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub severity: Verdict,
+    pub message: String,
+}
+
+impl Issue {
+    fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Verdict::Warning,
+            message: message.into(),
+        }
+    }
+
+    fn corrupt(message: impl Into<String>) -> Self {
+        Self {
+            severity: Verdict::Corrupt,
+            message: message.into(),
+        }
+    }
+}
+
+/// Result of checking one file
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyResult {
+    pub path: PathBuf,
+    pub verdict: Verdict,
+    /// The first of the most severe issues, or a note that none were found
+    pub reason: String,
+    pub issues: Vec<Issue>,
+}
+
+impl VerifyResult {
+    fn new(path: &Path, issues: Vec<Issue>) -> Self {
+        let verdict = issues.iter().map(|i| i.severity).max().unwrap_or(Verdict::Ok);
+        let reason = issues
+            .iter()
+            .find(|i| i.severity == verdict)
+            .map(|i| i.message.clone())
+            .unwrap_or_else(|| "No problems found".to_string());
+        Self {
+            path: path.to_path_buf(),
+            verdict,
+            reason,
+            issues,
+        }
+    }
+}
+
+/// Check a GPR file's container and raw data, then run the SDK's metadata
+/// parser over it, in a child process when `isolated`
+///
+/// The SDK is only called once the structure checks found nothing corrupt,
+/// since truncated files are what make it misbehave.
+pub fn verify_file(path: &Path, isolated: bool) -> VerifyResult {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) => return VerifyResult::new(path, vec![Issue::corrupt(format!("Cannot read file: {}", e))]),
+    };
+
+    let mut issues = check_structure(&data);
+    if issues.iter().all(|i| i.severity != Verdict::Corrupt) {
+        let parsed = if isolated { GprConverter::check_isolated(path) } else { sdk_can_parse(&data) };
+        if let Err(e) = parsed {
+            issues.push(Issue::corrupt(format!("{:#}", e)));
+        }
+        if let Err(e) = exif::Reader::new().read_from_container(&mut std::io::Cursor::new(&data)) {
+            issues.push(Issue::warning(format!("EXIF cannot be read: {}", e)));
+        }
+    }
+
+    VerifyResult::new(path, issues)
+}
+
+/// Walk the TIFF/DNG structure without calling into the SDK: IFDs and tag
+/// data within the file, tile/strip layout consistent with the image size,
+/// and a VC-5 raw image present and complete
+pub fn check_structure(data: &[u8]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    let Some(tiff) = Tiff::new(data) else {
+        issues.push(Issue::corrupt("Not a TIFF/DNG file"));
+        return issues;
+    };
+
+    let mut images = Vec::new();
+    let mut queue = vec![(tiff.read_u32(4).unwrap_or(0), "IFD0".to_string(), true)];
+    let mut visited = HashSet::new();
+    let mut main_index = 0;
+
+    while let Some((offset, name, is_main)) = queue.pop() {
+        if !visited.insert(offset) {
+            issues.push(Issue::corrupt(format!("{} at byte {} is referenced twice", name, offset)));
+            continue;
+        }
+        if visited.len() > MAX_IFDS {
+            issues.push(Issue::corrupt(format!("More than {} IFDs", MAX_IFDS)));
+            break;
+        }
+
+        let ifd = match tiff.read_ifd(offset as usize, &name) {
+            Ok(ifd) => ifd,
+            Err(issue) => {
+                issues.push(issue);
+                continue;
+            }
+        };
+        issues.extend(ifd.issues.iter().cloned());
+
+        if is_main && ifd.next != 0 {
+            main_index += 1;
+            queue.push((ifd.next, format!("IFD{}", main_index), true));
+        }
+        for (i, &sub) in ifd.values(SUB_IFDS).iter().enumerate() {
+            queue.push((sub, format!("{} SubIFD{}", name, i), false));
+        }
+        if let Some(&exif) = ifd.values(EXIF_IFD).first() {
+            queue.push((exif, "EXIF IFD".to_string(), false));
+        }
+        if let Some(&gps) = ifd.values(GPS_IFD).first() {
+            queue.push((gps, "GPS IFD".to_string(), false));
+        }
+
+        if ifd.first(IMAGE_WIDTH).is_some() {
+            issues.extend(check_segments(&ifd, &name, data.len()));
+            images.push(ifd);
+        }
+    }
+
+    let raw = images.iter().find(|ifd| {
+        ifd.first(COMPRESSION) == Some(COMPRESSION_VC5) && ifd.first(NEW_SUBFILE_TYPE).unwrap_or(0) == 0
+    });
+    match raw {
+        Some(ifd) => issues.extend(check_vc5(ifd, data)),
+        None => issues.push(Issue::corrupt("No VC-5 compressed raw image")),
+    }
+
+    issues
+}
+
+/// Tile or strip offsets and sizes of an image IFD against its dimensions
+fn check_segments(ifd: &Ifd, name: &str, file_len: usize) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let width = ifd.first(IMAGE_WIDTH).unwrap_or(0);
+    let height = ifd.first(IMAGE_LENGTH).unwrap_or(0);
+    if let Err(e) = check_dimensions(width, height) {
+        issues.push(Issue::corrupt(format!("{}: {}", name, e)));
+        return issues;
+    }
+
+    let (kind, offsets, counts, expected) = if ifd.first(TILE_OFFSETS).is_some() {
+        let tile_width = ifd.first(TILE_WIDTH).unwrap_or(0);
+        let tile_length = ifd.first(TILE_LENGTH).unwrap_or(0);
+        if tile_width == 0 || tile_length == 0 {
+            issues.push(Issue::corrupt(format!("{} has tiles without a tile size", name)));
+            return issues;
+        }
+        let expected = width.div_ceil(tile_width) as usize * height.div_ceil(tile_length) as usize;
+        ("tile", ifd.values(TILE_OFFSETS), ifd.values(TILE_BYTE_COUNTS), expected)
+    } else if ifd.first(STRIP_OFFSETS).is_some() {
+        let rows = ifd.first(ROWS_PER_STRIP).unwrap_or(height).clamp(1, height);
+        ("strip", ifd.values(STRIP_OFFSETS), ifd.values(STRIP_BYTE_COUNTS), height.div_ceil(rows) as usize)
+    } else {
+        issues.push(Issue::corrupt(format!("{} has no tile or strip offsets", name)));
+        return issues;
+    };
+
+    if offsets.len() != expected || counts.len() != expected {
+        issues.push(Issue::corrupt(format!(
+            "{} has {} {} offsets and {} sizes for a {}x{} image, expected {}",
+            name,
+            offsets.len(),
+            kind,
+            counts.len(),
+            width,
+            height,
+            expected
+        )));
+    }
+
+    for (i, (&offset, &count)) in offsets.iter().zip(counts).enumerate() {
+        let end = offset as u64 + count as u64;
+        if count == 0 {
+            issues.push(Issue::corrupt(format!("{} {} {} is empty", name, kind, i)));
+        } else if end > file_len as u64 {
+            issues.push(Issue::corrupt(format!(
+                "{} {} {} ends at byte {} but the file is {} bytes; it is truncated",
+                name, kind, i, end, file_len
+            )));
+        }
+    }
+
+    issues
+}
+
+/// The raw image's payload: the VC-5 marker, a matching encoded size and no
+/// run of zeros where the copy may have stopped
+fn check_vc5(ifd: &Ifd, data: &[u8]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let (offsets, counts) = if ifd.first(TILE_OFFSETS).is_some() {
+        (ifd.values(TILE_OFFSETS), ifd.values(TILE_BYTE_COUNTS))
+    } else {
+        (ifd.values(STRIP_OFFSETS), ifd.values(STRIP_BYTE_COUNTS))
+    };
+
+    for (&offset, &count) in offsets.iter().zip(counts) {
+        let Some(payload) = data.get(offset as usize..offset as usize + count as usize) else {
+            // Already reported by check_segments
+            continue;
+        };
+
+        if !payload.starts_with(VC5_MARKER) {
+            issues.push(Issue::corrupt(format!("Raw data at byte {} has no VC-5 marker", offset)));
+            continue;
+        }
+
+        // Image width and height follow the marker as big-endian tag/value
+        // pairs (tags 0x14 and 0x15)
+        let tag_value = |tag: u16| {
+            payload[4..]
+                .chunks_exact(4)
+                .take(16)
+                .find(|pair| u16::from_be_bytes([pair[0], pair[1]]) == tag)
+                .map(|pair| u16::from_be_bytes([pair[2], pair[3]]) as u32)
+        };
+        let width = ifd.first(TILE_WIDTH).or(ifd.first(IMAGE_WIDTH));
+        let height = ifd.first(TILE_LENGTH).or(ifd.first(IMAGE_LENGTH));
+        if let (Some(encoded_width), Some(encoded_height)) = (tag_value(0x14), tag_value(0x15)) {
+            if Some(encoded_width) != width || Some(encoded_height) != height {
+                issues.push(Issue::warning(format!(
+                    "VC-5 stream is {}x{} but the DNG says {}x{}",
+                    encoded_width,
+                    encoded_height,
+                    width.unwrap_or(0),
+                    height.unwrap_or(0)
+                )));
+            }
+        }
+
+        let zeros = payload.iter().rev().take_while(|&&b| b == 0).count();
+        if zeros >= ZERO_TAIL.min(payload.len() - VC5_MARKER.len()) {
+            issues.push(Issue::warning(format!(
+                "Raw data ends in {} zero bytes; the copy may be incomplete",
+                zeros
+            )));
+        }
+    }
+
+    issues
+}
+
+/// Byte order aware access to a TIFF file
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+/// Tag values of one IFD, with problems found while reading it
+struct Ifd {
+    tags: Vec<(u16, Vec<u32>)>,
+    next: u32,
+    issues: Vec<Issue>,
+}
+
+impl Ifd {
+    fn values(&self, tag: u16) -> &[u32] {
+        self.tags
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, values)| values.as_slice())
+            .unwrap_or(&[])
+    }
+
+    fn first(&self, tag: u16) -> Option<u32> {
+        self.values(tag).first().copied()
+    }
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..4)? {
+            b"II*\0" => true,
+            b"MM\0*" => false,
+            _ => return None,
+        };
+        Some(Self { data, little_endian })
+    }
+
+    fn read_u16(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn read_u32(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    /// Read one IFD. Entries whose data lies outside the file are reported;
+    /// SHORT, LONG and IFD values are kept for the layout checks.
+    fn read_ifd(&self, offset: usize, name: &str) -> Result<Ifd, Issue> {
+        let truncated = || {
+            Issue::corrupt(format!(
+                "{} at byte {} is past the end of the file ({} bytes)",
+                name,
+                offset,
+                self.data.len()
+            ))
+        };
+        let count = self.read_u16(offset).ok_or_else(truncated)? as usize;
+        if count == 0 {
+            return Err(Issue::corrupt(format!("{} has no entries", name)));
+        }
+        let next = self.read_u32(offset + 2 + count * 12).ok_or_else(truncated)?;
+
+        let mut ifd = Ifd {
+            tags: Vec::new(),
+            next,
+            issues: Vec::new(),
+        };
+        for entry in (0..count).map(|i| offset + 2 + i * 12) {
+            let (Some(tag), Some(kind), Some(values)) =
+                (self.read_u16(entry), self.read_u16(entry + 2), self.read_u32(entry + 4))
+            else {
+                return Err(truncated());
+            };
+            let width: u64 = match kind {
+                1 | 2 | 6 | 7 => 1,
+                3 | 8 => 2,
+                4 | 9 | 11 | 13 => 4,
+                5 | 10 | 12 => 8,
+                _ => {
+                    ifd.issues.push(Issue::warning(format!(
+                        "{} tag {} has unknown type {}",
+                        name, tag, kind
+                    )));
+                    continue;
+                }
+            };
+
+            let size = width * values as u64;
+            let data_offset = if size <= 4 {
+                entry as u64 + 8
+            } else {
+                self.read_u32(entry + 8).unwrap_or(0) as u64
+            };
+            if data_offset + size > self.data.len() as u64 {
+                ifd.issues.push(Issue::corrupt(format!(
+                    "{} tag {} data at byte {} runs past the end of the file",
+                    name, tag, data_offset
+                )));
+                continue;
+            }
+
+            let read = |i: usize| match kind {
+                3 => self.read_u16(data_offset as usize + i * 2).map(u32::from),
+                _ => self.read_u32(data_offset as usize + i * 4),
+            };
+            if matches!(kind, 3 | 4 | 13) {
+                let values = (0..values as usize).filter_map(read).collect();
+                ifd.tags.push((tag, values));
+            }
+        }
+
+        Ok(ifd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little-endian TIFF with one VC-5 tile of `payload`
+    fn gpr(payload: &[u8]) -> Vec<u8> {
+        let entries: [(u16, u16, u32); 8] = [
+            (NEW_SUBFILE_TYPE, 4, 0),
+            (IMAGE_WIDTH, 4, 16),
+            (IMAGE_LENGTH, 4, 8),
+            (COMPRESSION, 3, COMPRESSION_VC5),
+            (TILE_WIDTH, 4, 16),
+            (TILE_LENGTH, 4, 8),
+            (TILE_OFFSETS, 4, 8 + 2 + 8 * 12 + 4),
+            (TILE_BYTE_COUNTS, 4, payload.len() as u32),
+        ];
+        let mut data = b"II*\0".to_vec();
+        data.extend(8u32.to_le_bytes());
+        data.extend((entries.len() as u16).to_le_bytes());
+        for (tag, kind, value) in entries {
+            data.extend(tag.to_le_bytes());
+            data.extend(kind.to_le_bytes());
+            data.extend(1u32.to_le_bytes());
+            data.extend(value.to_le_bytes());
+        }
+        data.extend(0u32.to_le_bytes());
+        data.extend(payload);
+        data
+    }
+
+    #[test]
+    fn test_check_structure() {
+        let mut payload = b"VC-5".to_vec();
+        payload.extend([0x00, 0x14, 0x00, 0x10, 0x00, 0x15, 0x00, 0x08]);
+        payload.extend([0x5a; 5000]);
+        let data = gpr(&payload);
+        assert!(check_structure(&data).is_empty());
+
+        let truncated = check_structure(&data[..data.len() - 10]);
+        assert_eq!(truncated.len(), 1);
+        assert!(truncated[0].message.contains("truncated"));
+
+        let mut zeroed = data.clone();
+        let len = zeroed.len();
+        zeroed[len - ZERO_TAIL..].fill(0);
+        let issues = check_structure(&zeroed);
+        assert_eq!(issues[0].severity, Verdict::Warning);
+
+        let mut unmarked = data.clone();
+        unmarked[8 + 2 + 8 * 12 + 4] = b'X';
+        assert_eq!(check_structure(&unmarked)[0].severity, Verdict::Corrupt);
+
+        assert_eq!(check_structure(b"JFIF")[0].message, "Not a TIFF/DNG file");
+        assert_eq!(check_structure(&data[..20])[0].severity, Verdict::Corrupt);
+    }
+}
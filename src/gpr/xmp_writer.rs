@@ -0,0 +1,305 @@
+use crate::models::fusion::Panorama;
+use crate::models::gpr_file::GprMetadata;
+use crate::models::{ConversionConfig, GprFile};
+use crate::utils::datetime::ExifDateTime;
+use anyhow::{bail, Context, Result};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Namespace for the processing provenance recorded by gprprotool
+const GPRPROTOOL_NS: &str = "https://github.com/keenanjohnson/gprprotool/ns/1.0/";
+
+/// Identifier that starts a JPEG APP1 segment holding XMP
+const JPEG_XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// Keyword of the PNG iTXt chunk holding XMP
+const PNG_XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+
+/// Sidecar path for a converted output: same name, `.xmp` extension
+pub fn sidecar_path(output_path: &Path) -> PathBuf {
+    output_path.with_extension("xmp")
+}
+
+/// Write the XMP sidecar for `output_path` and return its path
+pub fn write_sidecar(
+    gpr_file: &GprFile,
+    metadata: Option<&GprMetadata>,
+    config: &ConversionConfig,
+    output_path: &Path,
+    panorama: Option<Panorama>,
+) -> Result<PathBuf> {
+    let path = sidecar_path(output_path);
+    let packet = build_xmp(gpr_file, metadata, config, output_path, panorama)?;
+    std::fs::write(&path, packet)
+        .with_context(|| format!("Failed to write XMP sidecar: {}", path.display()))?;
+    Ok(path)
+}
+
+/// Build an XMP packet with the source metadata, descriptive edits and
+/// processing provenance (source file, processing settings and tool
+/// version), plus the GPano tags for a stitched panorama
+pub fn build_xmp(
+    gpr_file: &GprFile,
+    metadata: Option<&GprMetadata>,
+    config: &ConversionConfig,
+    output_path: &Path,
+    panorama: Option<Panorama>,
+) -> Result<String> {
+    let edits = &config.metadata_edits;
+    let settings = serde_json::to_string(&config.processing_settings()).context("Failed to serialize settings")?;
+
+    // Simple properties are written as attributes of the description
+    let mut attributes: Vec<(&str, String)> = vec![
+        ("xmp:CreatorTool", concat!("gprprotool ", env!("CARGO_PKG_VERSION")).to_string()),
+        ("dc:format", config.output_format.mime_type().to_string()),
+        ("gprpro:SourceFile", gpr_file.path.display().to_string()),
+        ("gprpro:OutputFile", output_path.display().to_string()),
+        ("gprpro:Version", env!("CARGO_PKG_VERSION").to_string()),
+        ("gprpro:Settings", settings),
+    ];
+
+    if let Some(metadata) = metadata {
+        attributes.push(("tiff:Model", metadata.camera_model.clone()));
+        attributes.push(("tiff:Orientation", config.output_orientation(metadata).to_string()));
+        if let Some(ref serial) = metadata.serial_number {
+            attributes.push(("exifEX:BodySerialNumber", serial.clone()));
+        }
+        if let Some(ref owner) = metadata.owner_name {
+            attributes.push(("exifEX:CameraOwnerName", owner.clone()));
+        }
+        if let Some(ref exposure) = metadata.exposure_time {
+            attributes.push(("exif:ExposureTime", exposure.clone()));
+        }
+        if let Some(ref f_number) = metadata.f_number {
+            attributes.push(("exif:FNumber", f_number.trim_start_matches("f/").to_string()));
+        }
+        if let Some(ref focal_length) = metadata.focal_length {
+            attributes.push(("exif:FocalLength", focal_length.trim_end_matches("mm").trim().to_string()));
+        }
+        if let Some(taken) = metadata.date_taken.as_deref().and_then(ExifDateTime::parse) {
+            let date = xmp_date(&taken, config.time_shift.offset_time_original().as_deref());
+            attributes.push(("exif:DateTimeOriginal", date.clone()));
+            attributes.push(("photoshop:DateCreated", date));
+        }
+        if let (Some(lat), Some(lon)) = (metadata.gps_latitude, metadata.gps_longitude) {
+            attributes.push(("exif:GPSLatitude", xmp_coordinate(lat, 'N', 'S')));
+            attributes.push(("exif:GPSLongitude", xmp_coordinate(lon, 'E', 'W')));
+        }
+    }
+
+    if let Some(panorama) = panorama {
+        // A full sphere: the image is the whole panorama, nothing cropped
+        let (width, height) = (panorama.width.to_string(), panorama.height.to_string());
+        attributes.push(("GPano:ProjectionType", "equirectangular".to_string()));
+        attributes.push(("GPano:UsePanoramaViewer", "True".to_string()));
+        attributes.push(("GPano:StitchingSoftware", concat!("gprprotool ", env!("CARGO_PKG_VERSION")).to_string()));
+        attributes.push(("GPano:FullPanoWidthPixels", width.clone()));
+        attributes.push(("GPano:FullPanoHeightPixels", height.clone()));
+        attributes.push(("GPano:CroppedAreaImageWidthPixels", width));
+        attributes.push(("GPano:CroppedAreaImageHeightPixels", height));
+        attributes.push(("GPano:CroppedAreaLeftPixels", "0".to_string()));
+        attributes.push(("GPano:CroppedAreaTopPixels", "0".to_string()));
+    }
+
+    if let Some(rating) = edits.rating {
+        attributes.push(("xmp:Rating", rating.min(5).to_string()));
+    }
+
+    let mut xmp = String::new();
+    xmp.push_str("<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n");
+    xmp.push_str("<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n");
+    xmp.push_str(" <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n");
+    xmp.push_str("  <rdf:Description rdf:about=\"\"\n");
+    for (prefix, namespace) in [
+        ("xmp", "http://ns.adobe.com/xap/1.0/"),
+        ("dc", "http://purl.org/dc/elements/1.1/"),
+        ("tiff", "http://ns.adobe.com/tiff/1.0/"),
+        ("exif", "http://ns.adobe.com/exif/1.0/"),
+        ("exifEX", "http://cipa.jp/exif/1.0/"),
+        ("photoshop", "http://ns.adobe.com/photoshop/1.0/"),
+        ("GPano", "http://ns.google.com/photos/1.0/panorama/"),
+        ("gprpro", GPRPROTOOL_NS),
+    ] {
+        writeln!(xmp, "    xmlns:{}=\"{}\"", prefix, namespace)?;
+    }
+    for (name, value) in &attributes {
+        writeln!(xmp, "    {}=\"{}\"", name, escape_xml(value))?;
+    }
+    xmp.push_str("   >\n");
+
+    if let Some(ref creator) = edits.creator {
+        xmp.push_str("   <dc:creator>\n    <rdf:Seq>\n");
+        writeln!(xmp, "     <rdf:li>{}</rdf:li>", escape_xml(creator))?;
+        xmp.push_str("    </rdf:Seq>\n   </dc:creator>\n");
+    }
+    if let Some(ref copyright) = edits.copyright {
+        xmp.push_str("   <dc:rights>\n    <rdf:Alt>\n");
+        writeln!(xmp, "     <rdf:li xml:lang=\"x-default\">{}</rdf:li>", escape_xml(copyright))?;
+        xmp.push_str("    </rdf:Alt>\n   </dc:rights>\n");
+    }
+    if let Some(ref caption) = edits.caption {
+        xmp.push_str("   <dc:description>\n    <rdf:Alt>\n");
+        writeln!(xmp, "     <rdf:li xml:lang=\"x-default\">{}</rdf:li>", escape_xml(caption))?;
+        xmp.push_str("    </rdf:Alt>\n   </dc:description>\n");
+    }
+    if !edits.keywords.is_empty() {
+        xmp.push_str("   <dc:subject>\n    <rdf:Bag>\n");
+        for keyword in &edits.keywords {
+            writeln!(xmp, "     <rdf:li>{}</rdf:li>", escape_xml(keyword))?;
+        }
+        xmp.push_str("    </rdf:Bag>\n   </dc:subject>\n");
+    }
+
+    xmp.push_str("  </rdf:Description>\n");
+    xmp.push_str(" </rdf:RDF>\n");
+    xmp.push_str("</x:xmpmeta>\n");
+    xmp.push_str("<?xpacket end=\"w\"?>\n");
+
+    Ok(xmp)
+}
+
+/// Splice an XMP packet into an encoded JPEG as an APP1 segment, after the
+/// existing application segments (JFIF, EXIF, ICC)
+pub fn embed_in_jpeg(jpeg: &[u8], packet: &str) -> Result<Vec<u8>> {
+    let length = 2 + JPEG_XMP_HEADER.len() + packet.len();
+    if length > u16::MAX as usize {
+        bail!("XMP packet too large for a JPEG segment ({} bytes)", packet.len());
+    }
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        bail!("Not a JPEG stream");
+    }
+
+    let mut position = 2;
+    while position + 4 <= jpeg.len() && jpeg[position] == 0xFF && (0xE0..=0xEF).contains(&jpeg[position + 1]) {
+        let segment = u16::from_be_bytes([jpeg[position + 2], jpeg[position + 3]]) as usize;
+        position += 2 + segment;
+    }
+    if position > jpeg.len() {
+        bail!("Truncated JPEG segment");
+    }
+
+    let mut output = Vec::with_capacity(jpeg.len() + length + 2);
+    output.extend_from_slice(&jpeg[..position]);
+    output.extend_from_slice(&[0xFF, 0xE1]);
+    output.extend_from_slice(&(length as u16).to_be_bytes());
+    output.extend_from_slice(JPEG_XMP_HEADER);
+    output.extend_from_slice(packet.as_bytes());
+    output.extend_from_slice(&jpeg[position..]);
+    Ok(output)
+}
+
+/// Splice an XMP packet into an encoded PNG as an uncompressed iTXt chunk
+/// ahead of the image data
+pub fn embed_in_png(png: &[u8], packet: &str) -> Result<Vec<u8>> {
+    const SIGNATURE: usize = 8;
+    let mut position = SIGNATURE;
+    loop {
+        if position + 8 > png.len() {
+            bail!("PNG stream has no image data");
+        }
+        if &png[position + 4..position + 8] == b"IDAT" {
+            break;
+        }
+        let length = u32::from_be_bytes(png[position..position + 4].try_into()?) as usize;
+        // Length, type, data and CRC
+        position += 12 + length;
+    }
+
+    // Keyword, then no compression, and empty language and translated keyword
+    let mut chunk = b"iTXt".to_vec();
+    chunk.extend_from_slice(PNG_XMP_KEYWORD);
+    chunk.extend_from_slice(&[0, 0, 0, 0, 0]);
+    chunk.extend_from_slice(packet.as_bytes());
+
+    let mut output = Vec::with_capacity(png.len() + chunk.len() + 8);
+    output.extend_from_slice(&png[..position]);
+    output.extend_from_slice(&((chunk.len() - 4) as u32).to_be_bytes());
+    output.extend_from_slice(&chunk);
+    output.extend_from_slice(&crc32fast::hash(&chunk).to_be_bytes());
+    output.extend_from_slice(&png[position..]);
+    Ok(output)
+}
+
+/// XMP dates are ISO 8601, with the zone when it is known
+fn xmp_date(taken: &ExifDateTime, offset: Option<&str>) -> String {
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+        taken.year,
+        taken.month,
+        taken.day,
+        taken.hour,
+        taken.minute,
+        taken.second,
+        offset.unwrap_or("")
+    )
+}
+
+/// XMP GPS coordinates use the `DDD,MM.mmmmmmK` form
+fn xmp_coordinate(value: f64, positive: char, negative: char) -> String {
+    let direction = if value < 0.0 { negative } else { positive };
+    let value = value.abs();
+    let degrees = value.trunc();
+    let minutes = (value - degrees) * 60.0;
+    format!("{},{:.6}{}", degrees as u32, minutes, direction)
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GpsPolicy, MetadataEdits, PrivacyPolicy};
+
+    #[test]
+    fn test_gps_edit_follows_privacy() {
+        // A position typed in by hand, converted with GPS dropped
+        let config = ConversionConfig {
+            metadata_edits: MetadataEdits { gps_position: Some((47.60621, -122.33207)), ..MetadataEdits::default() },
+            privacy: PrivacyPolicy { gps: GpsPolicy::Drop, drop_identifiers: true },
+            ..ConversionConfig::default()
+        };
+        let mut metadata = GprMetadata { camera_model: "GoPro HERO7 Black".to_string(), ..GprMetadata::default() };
+        config.metadata_edits.apply(&mut metadata);
+        config.privacy.apply(&mut metadata);
+
+        let gpr_file = GprFile::new(PathBuf::from("GOPR0001.GPR"));
+        let xmp = build_xmp(&gpr_file, Some(&metadata), &config, Path::new("GOPR0001.jpg"), None).unwrap();
+        assert!(xmp.contains("gprpro:Settings="));
+        for coordinate in ["47.6", "47,", "122.3", "122,", "GPS"] {
+            assert!(!xmp.contains(coordinate), "{} in {}", coordinate, xmp);
+        }
+    }
+
+    #[test]
+    fn test_file_edits() {
+        // A caption typed for one file stays on that file
+        let (tagged, other) = (PathBuf::from("GOPR0001.GPR"), PathBuf::from("GOPR0002.GPR"));
+        let mut config = ConversionConfig {
+            metadata_edits: MetadataEdits { creator: Some("Jane Doe".to_string()), ..MetadataEdits::default() },
+            ..ConversionConfig::default()
+        };
+        let caption = MetadataEdits { caption: Some("First light".to_string()), ..MetadataEdits::default() };
+        config.file_edits.insert(tagged.clone(), caption);
+
+        let xmp = |path: &PathBuf| {
+            let gpr_file = GprFile::new(path.clone());
+            build_xmp(&gpr_file, None, &config.for_file(path), &path.with_extension("jpg"), None).unwrap()
+        };
+        let (tagged, other) = (xmp(&tagged), xmp(&other));
+        assert!(tagged.contains("First light") && tagged.contains("Jane Doe"));
+        assert!(!other.contains("First light") && other.contains("Jane Doe"));
+    }
+}
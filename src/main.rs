@@ -1,9 +1,13 @@
+mod catalog;
+mod cli;
 mod gpr;
 mod models;
+mod telemetry;
 mod ui;
 mod utils;
 
 use anyhow::Result;
+use clap::Parser;
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
     execute,
@@ -14,6 +18,7 @@ use ratatui::{
     Terminal,
 };
 use std::io;
+use std::time::Duration;
 
 use ui::app::{App, AppState};
 
@@ -21,6 +26,12 @@ fn main() -> Result<()> {
     // Initialize logger
     env_logger::init();
 
+    // Subcommands run without the TUI
+    let cli = cli::Cli::parse();
+    if let Some(command) = cli.command {
+        return cli::run(command);
+    }
+
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -33,6 +44,7 @@ fn main() -> Result<()> {
 
     // Run the application
     let res = run_app(&mut terminal, &mut app);
+    app.save_catalog();
 
     // Restore terminal
     disable_raw_mode()?;
@@ -57,6 +69,16 @@ fn run_app<B: ratatui::backend::Backend>(
     loop {
         terminal.draw(|f| ui::render::render(f, app))?;
 
+        // Keep redrawing while a batch converts or a directory is checked in
+        // the background
+        if app.batch.is_some() || app.verify_job.is_some() {
+            app.poll_batch();
+            app.poll_verify();
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+        }
+
         if let Event::Key(key) = event::read()? {
             if key.kind == KeyEventKind::Press {
                 match app.state {
@@ -69,6 +91,15 @@ fn run_app<B: ratatui::backend::Backend>(
                             _ => {}
                         }
                     }
+                    AppState::FileBrowser if app.text_input.is_some() => {
+                        match key.code {
+                            KeyCode::Esc => app.cancel_text_edit(),
+                            KeyCode::Enter => app.commit_filter_edit(),
+                            KeyCode::Backspace => app.text_input_backspace(),
+                            KeyCode::Char(c) => app.text_input_char(c),
+                            _ => {}
+                        }
+                    }
                     AppState::FileBrowser => {
                         match key.code {
                             KeyCode::Char('q') | KeyCode::Esc => app.back_to_main_menu(),
@@ -76,6 +107,7 @@ fn run_app<B: ratatui::backend::Backend>(
                             KeyCode::Down | KeyCode::Char('j') => app.next_file(),
                             KeyCode::Enter => app.select_file(),
                             KeyCode::Backspace => app.go_to_parent_directory(),
+                            KeyCode::Char('/') => app.begin_filter_edit(),
                             _ => {}
                         }
                     }
@@ -83,6 +115,87 @@ fn run_app<B: ratatui::backend::Backend>(
                         match key.code {
                             KeyCode::Char('q') | KeyCode::Esc => app.back_to_file_browser(),
                             KeyCode::Char('c') => app.go_to_conversion_config(),
+                            KeyCode::Char('e') => app.open_metadata_editor(),
+                            KeyCode::Char('r') => app.open_crop_editor(),
+                            KeyCode::Char('l') => app.toggle_file_auto_level(),
+                            KeyCode::Char('m') => app.mark_for_compare(),
+                            KeyCode::Char('d') => app.compare_with_marked(),
+                            _ => {}
+                        }
+                    }
+                    AppState::Stats => {
+                        match key.code {
+                            KeyCode::Char('q') | KeyCode::Esc => app.back_to_main_menu(),
+                            KeyCode::Char('s') => app.export_shoot_stats(),
+                            _ => {}
+                        }
+                    }
+                    AppState::Verify => {
+                        match key.code {
+                            KeyCode::Char('q') | KeyCode::Esc => app.back_to_main_menu(),
+                            KeyCode::Up | KeyCode::Char('k') => app.scroll_verify(-1),
+                            KeyCode::Down | KeyCode::Char('j') => app.scroll_verify(1),
+                            KeyCode::PageUp => app.scroll_verify(-10),
+                            KeyCode::PageDown => app.scroll_verify(10),
+                            KeyCode::Char('s') => app.export_verify_results(),
+                            _ => {}
+                        }
+                    }
+                    AppState::Compare => {
+                        match key.code {
+                            KeyCode::Char('q') | KeyCode::Esc => app.back_to_file_info(),
+                            KeyCode::Up | KeyCode::Char('k') => app.scroll_compare(-1),
+                            KeyCode::Down | KeyCode::Char('j') => app.scroll_compare(1),
+                            KeyCode::PageUp => app.scroll_compare(-10),
+                            KeyCode::PageDown => app.scroll_compare(10),
+                            KeyCode::Char('f') => app.toggle_compare_filter(),
+                            _ => {}
+                        }
+                    }
+                    AppState::EditMetadata if app.text_input.is_some() => {
+                        match key.code {
+                            KeyCode::Esc => app.cancel_text_edit(),
+                            KeyCode::Enter => app.commit_text_edit(),
+                            KeyCode::Backspace => app.text_input_backspace(),
+                            KeyCode::Char(c) => app.text_input_char(c),
+                            _ => {}
+                        }
+                    }
+                    AppState::EditMetadata => {
+                        match key.code {
+                            KeyCode::Char('q') | KeyCode::Esc => app.close_metadata_editor(),
+                            KeyCode::Up | KeyCode::Char('k') => app.previous_edit_field(),
+                            KeyCode::Down | KeyCode::Char('j') => app.next_edit_field(),
+                            KeyCode::Left | KeyCode::Char('h') => app.adjust_edit_field(-1),
+                            KeyCode::Right | KeyCode::Char('l') => app.adjust_edit_field(1),
+                            KeyCode::Char('e') | KeyCode::Enter => app.begin_text_edit(),
+                            KeyCode::Char('x') | KeyCode::Delete => app.clear_edit_field(),
+                            _ => {}
+                        }
+                    }
+                    AppState::Crop => {
+                        match key.code {
+                            KeyCode::Char('q') | KeyCode::Esc => app.close_crop_editor(),
+                            KeyCode::Enter => app.save_crop(),
+                            KeyCode::Left | KeyCode::Char('h') => app.move_crop(-1, 0),
+                            KeyCode::Right | KeyCode::Char('l') => app.move_crop(1, 0),
+                            KeyCode::Up | KeyCode::Char('k') => app.move_crop(0, -1),
+                            KeyCode::Down | KeyCode::Char('j') => app.move_crop(0, 1),
+                            KeyCode::Char('+') | KeyCode::Char('=') => app.resize_crop(1),
+                            KeyCode::Char('-') => app.resize_crop(-1),
+                            KeyCode::Char('a') => app.cycle_crop_aspect(1),
+                            KeyCode::Char('A') => app.cycle_crop_aspect(-1),
+                            KeyCode::Char('o') => app.swap_crop_aspect(),
+                            KeyCode::Char('r') => app.turn_crop(1),
+                            KeyCode::Char('R') => app.turn_crop(-1),
+                            KeyCode::Char('f') => app.flip_crop(true),
+                            KeyCode::Char('v') => app.flip_crop(false),
+                            KeyCode::Char('[') => app.straighten_crop(-5),
+                            KeyCode::Char(']') => app.straighten_crop(5),
+                            KeyCode::Char('{') => app.straighten_crop(-1),
+                            KeyCode::Char('}') => app.straighten_crop(1),
+                            KeyCode::Char('L') => app.toggle_crop_auto_level(),
+                            KeyCode::Char('x') => app.reset_crop(),
                             _ => {}
                         }
                     }
@@ -93,6 +206,7 @@ fn run_app<B: ratatui::backend::Backend>(
                             KeyCode::Down | KeyCode::Char('j') => app.next_config_option(),
                             KeyCode::Left | KeyCode::Char('h') => app.adjust_config_option(-1),
                             KeyCode::Right | KeyCode::Char('l') => app.adjust_config_option(1),
+                            KeyCode::Char('e') => app.begin_config_edit(),
                             KeyCode::Enter => app.start_conversion(),
                             _ => {}
                         }
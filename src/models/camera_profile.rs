@@ -0,0 +1,286 @@
+use super::{ConversionConfig, OutputFormat};
+use serde::{Deserialize, Serialize};
+
+/// Colour filter array layout, top-left 2x2 block read row by row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CfaPattern {
+    Rggb,
+    Bggr,
+    Grbg,
+    Gbrg,
+}
+
+impl CfaPattern {
+    /// Decode a 2x2 DNG CFAPattern (0 = red, 1 = green, 2 = blue)
+    pub fn from_dng(pattern: &[u8]) -> Option<CfaPattern> {
+        match pattern {
+            [0, 1, 1, 2] => Some(CfaPattern::Rggb),
+            [2, 1, 1, 0] => Some(CfaPattern::Bggr),
+            [1, 0, 2, 1] => Some(CfaPattern::Grbg),
+            [1, 2, 0, 1] => Some(CfaPattern::Gbrg),
+            _ => None,
+        }
+    }
+
+    /// Colour (0 = red, 1 = green, 2 = blue) of the photosite at (x, y)
+    pub fn color_at(&self, x: usize, y: usize) -> usize {
+        let pattern = match self {
+            CfaPattern::Rggb => [0, 1, 1, 2],
+            CfaPattern::Bggr => [2, 1, 1, 0],
+            CfaPattern::Grbg => [1, 0, 2, 1],
+            CfaPattern::Gbrg => [1, 2, 0, 1],
+        };
+        pattern[(y % 2) * 2 + x % 2]
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            CfaPattern::Rggb => "RGGB",
+            CfaPattern::Bggr => "BGGR",
+            CfaPattern::Grbg => "GRBG",
+            CfaPattern::Gbrg => "GBRG",
+        }
+    }
+}
+
+/// DNG NoiseProfile coefficients (noise variance = scale * signal + offset)
+/// at ISO 100. GoPro files scale both linearly with ISO.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NoiseProfile {
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl NoiseProfile {
+    pub fn at_iso(&self, iso: u32) -> NoiseProfile {
+        let gain = iso.max(1) as f64 / 100.0;
+        NoiseProfile {
+            scale: self.scale * gain,
+            offset: self.offset * gain,
+        }
+    }
+}
+
+/// Output defaults suggested for a camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPreset {
+    pub output_format: OutputFormat,
+    pub quality: u8,
+}
+
+impl CameraPreset {
+    pub fn apply(&self, config: &mut ConversionConfig) {
+        config.output_format = self.output_format;
+        config.quality = self.quality;
+    }
+}
+
+/// What a GoPro model can do, keyed on its EXIF Make/Model
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraProfile {
+    pub make: &'static str,
+    pub model: &'static str,
+    pub sensor_width: u32,
+    pub sensor_height: u32,
+    /// Lens / field of view modes available for photos
+    pub fov_modes: &'static [&'static str],
+    pub cfa_pattern: CfaPattern,
+    /// Typical noise at ISO 100; files also carry their own NoiseProfile tag
+    pub noise: Option<NoiseProfile>,
+    /// Photo modes and lenses in which the camera does not write GPR
+    pub raw_disabled_by: &'static [&'static str],
+    pub preset: CameraPreset,
+}
+
+const JPEG_PRESET: CameraPreset = CameraPreset {
+    output_format: OutputFormat::Jpeg,
+    quality: 95,
+};
+
+/// The HERO5 to HERO8 share the 12MP sensor of the HERO6/HERO7 samples
+pub const NOISE_12MP: NoiseProfile = NoiseProfile {
+    scale: 1.562e-4,
+    offset: 4.88e-8,
+};
+
+pub const CAMERAS: &[CameraProfile] = &[
+    CameraProfile {
+        make: "GoPro",
+        model: "FUSION",
+        // Each lens is recorded as its own GPR (GPFR front, GPBK back)
+        sensor_width: 3104,
+        sensor_height: 3000,
+        fov_modes: &["Spherical"],
+        // Dual fisheye; see Fusion stitching instead
+        cfa_pattern: CfaPattern::Rggb,
+        noise: Some(NoiseProfile {
+            scale: 7.81e-5,
+            offset: 2.44e-8,
+        }),
+        raw_disabled_by: &["Burst"],
+        // Lossless output so front/back pairs stitch without recompression
+        preset: CameraPreset {
+            output_format: OutputFormat::Png,
+            quality: 95,
+        },
+    },
+    CameraProfile {
+        make: "GoPro",
+        model: "HERO5 Black",
+        sensor_width: 4000,
+        sensor_height: 3000,
+        fov_modes: &["Wide", "Linear", "Medium", "Narrow"],
+        cfa_pattern: CfaPattern::Rggb,
+        noise: Some(NOISE_12MP),
+        raw_disabled_by: &["WDR", "Burst", "Linear", "Medium", "Narrow"],
+        preset: JPEG_PRESET,
+    },
+    CameraProfile {
+        make: "GoPro",
+        model: "HERO6 Black",
+        sensor_width: 4000,
+        sensor_height: 3000,
+        fov_modes: &["Wide", "Linear"],
+        cfa_pattern: CfaPattern::Rggb,
+        noise: Some(NOISE_12MP),
+        raw_disabled_by: &["HDR", "Burst", "Linear"],
+        preset: JPEG_PRESET,
+    },
+    CameraProfile {
+        make: "GoPro",
+        model: "HERO7 Black",
+        sensor_width: 4000,
+        sensor_height: 3000,
+        fov_modes: &["Wide", "Linear"],
+        cfa_pattern: CfaPattern::Rggb,
+        noise: Some(NOISE_12MP),
+        raw_disabled_by: &["SuperPhoto", "HDR", "Burst", "Linear"],
+        preset: JPEG_PRESET,
+    },
+    CameraProfile {
+        make: "GoPro",
+        model: "HERO8 Black",
+        sensor_width: 4000,
+        sensor_height: 3000,
+        fov_modes: &["Wide", "Linear", "Narrow"],
+        cfa_pattern: CfaPattern::Rggb,
+        noise: Some(NOISE_12MP),
+        raw_disabled_by: &["SuperPhoto", "HDR", "LiveBurst", "Burst", "Linear", "Narrow"],
+        preset: JPEG_PRESET,
+    },
+    CameraProfile {
+        make: "GoPro",
+        model: "HERO9 Black",
+        sensor_width: 5184,
+        sensor_height: 3888,
+        fov_modes: &["Wide", "Linear", "Narrow"],
+        cfa_pattern: CfaPattern::Rggb,
+        noise: None,
+        raw_disabled_by: &["SuperPhoto", "HDR", "LiveBurst", "Burst", "Linear", "Narrow"],
+        preset: JPEG_PRESET,
+    },
+    CameraProfile {
+        make: "GoPro",
+        model: "HERO10 Black",
+        sensor_width: 5568,
+        sensor_height: 4176,
+        fov_modes: &["Wide", "Linear", "Narrow"],
+        cfa_pattern: CfaPattern::Rggb,
+        noise: None,
+        raw_disabled_by: &["SuperPhoto", "HDR", "Burst", "Linear", "Narrow"],
+        preset: JPEG_PRESET,
+    },
+    CameraProfile {
+        make: "GoPro",
+        model: "HERO11 Black",
+        sensor_width: 5568,
+        sensor_height: 4872,
+        fov_modes: &["Wide", "Linear", "Narrow"],
+        cfa_pattern: CfaPattern::Rggb,
+        noise: None,
+        raw_disabled_by: &["SuperPhoto", "HDR", "Burst", "Linear", "Narrow"],
+        preset: JPEG_PRESET,
+    },
+    CameraProfile {
+        make: "GoPro",
+        model: "HERO12 Black",
+        sensor_width: 5568,
+        sensor_height: 4872,
+        fov_modes: &["Wide", "Linear", "Narrow"],
+        cfa_pattern: CfaPattern::Rggb,
+        noise: None,
+        raw_disabled_by: &["SuperPhoto", "HDR", "Burst", "Linear", "Narrow"],
+        preset: JPEG_PRESET,
+    },
+];
+
+impl CameraProfile {
+    /// Find the profile for a camera name as stored in metadata,
+    /// either "Make Model" or the model alone
+    pub fn lookup(camera_model: &str) -> Option<&'static CameraProfile> {
+        let wanted = normalize(camera_model);
+        CAMERAS.iter().find(|camera| {
+            normalize(camera.model) == wanted
+                || normalize(&format!("{} {}", camera.make, camera.model)) == wanted
+        })
+    }
+
+    pub fn name(&self) -> String {
+        format!("{} {}", self.make, self.model)
+    }
+
+    pub fn sensor_size_display(&self) -> String {
+        format!(
+            "{}x{} ({:.0} MP)",
+            self.sensor_width,
+            self.sensor_height,
+            (self.sensor_width * self.sensor_height) as f64 / 1_000_000.0
+        )
+    }
+
+    pub fn raw_disabled_display(&self) -> String {
+        self.raw_disabled_by.join(", ")
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace() && *c != '"')
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(CameraProfile::lookup("GoPro HERO7 Black").unwrap().model, "HERO7 Black");
+        assert_eq!(CameraProfile::lookup("GoPro FUSION").unwrap().sensor_width, 3104);
+        assert_eq!(CameraProfile::lookup("hero12 black").unwrap().sensor_height, 4872);
+        assert!(CameraProfile::lookup("GoPro HERO7 Silver").is_none());
+        assert!(CameraProfile::lookup("Unknown Camera").is_none());
+    }
+
+    #[test]
+    fn test_noise_at_iso() {
+        // Matches the NoiseProfile the HERO7 sample carries at ISO 252
+        let noise = NOISE_12MP.at_iso(252);
+        assert!((noise.scale - 3.936e-4).abs() < 1e-6);
+        assert!((noise.offset - 1.2297e-7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_preset_per_file() {
+        // A mixed batch: each file gets its own camera's preset
+        let config = ConversionConfig { camera_presets: true, ..ConversionConfig::default() };
+        let camera = |model: &str| crate::models::gpr_file::GprMetadata { camera_model: model.to_string(), ..Default::default() };
+        assert_eq!(config.for_camera(&camera("GoPro FUSION")).output_format, OutputFormat::Png);
+        assert_eq!(config.for_camera(&camera("GoPro HERO7 Black")).output_format, OutputFormat::Jpeg);
+        assert_eq!(config.for_camera(&camera("Unknown Camera")).output_format, OutputFormat::Jpeg);
+        // Settings chosen by hand are kept
+        let chosen = ConversionConfig { output_format: OutputFormat::Tiff, ..ConversionConfig::default() };
+        assert_eq!(chosen.for_camera(&camera("GoPro FUSION")).output_format, OutputFormat::Tiff);
+    }
+}
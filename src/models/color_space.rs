@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+
+/// Transfer curve between linear light and encoded values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transfer {
+    /// IEC 61966-2-1 piecewise curve, also used by Display P3
+    Srgb,
+    /// ITU-R BT.709 / BT.2020 camera curve
+    Bt709,
+    /// Pure power law
+    Gamma(f32),
+}
+
+impl Transfer {
+    /// Encode a linear value in 0-1
+    pub fn encode(&self, linear: f32) -> f32 {
+        match self {
+            Transfer::Srgb => {
+                if linear <= 0.0031308 {
+                    linear * 12.92
+                } else {
+                    1.055 * linear.powf(1.0 / 2.4) - 0.055
+                }
+            }
+            Transfer::Bt709 => {
+                if linear < 0.018 {
+                    linear * 4.5
+                } else {
+                    1.099 * linear.powf(0.45) - 0.099
+                }
+            }
+            Transfer::Gamma(gamma) => linear.powf(1.0 / gamma),
+        }
+    }
+
+    /// Linear value of an encoded value in 0-1
+    pub fn decode(&self, encoded: f32) -> f32 {
+        match self {
+            Transfer::Srgb => {
+                if encoded <= 0.04045 {
+                    encoded / 12.92
+                } else {
+                    ((encoded + 0.055) / 1.055).powf(2.4)
+                }
+            }
+            Transfer::Bt709 => {
+                if encoded < 0.081 {
+                    encoded / 4.5
+                } else {
+                    ((encoded + 0.099) / 1.099).powf(1.0 / 0.45)
+                }
+            }
+            Transfer::Gamma(gamma) => encoded.powf(*gamma),
+        }
+    }
+}
+
+/// RGB colour space of converted outputs, embedded as an ICC profile
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    DisplayP3,
+    AdobeRgb,
+    Rec2020,
+    ProPhoto,
+}
+
+impl ColorSpace {
+    pub fn all() -> Vec<ColorSpace> {
+        vec![
+            ColorSpace::Srgb,
+            ColorSpace::DisplayP3,
+            ColorSpace::AdobeRgb,
+            ColorSpace::Rec2020,
+            ColorSpace::ProPhoto,
+        ]
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ColorSpace::Srgb => "sRGB",
+            ColorSpace::DisplayP3 => "Display P3",
+            ColorSpace::AdobeRgb => "Adobe RGB (1998)",
+            ColorSpace::Rec2020 => "Rec. 2020",
+            ColorSpace::ProPhoto => "ProPhoto RGB",
+        }
+    }
+
+    pub fn cycle(&self, delta: i32) -> ColorSpace {
+        let all = ColorSpace::all();
+        let current = all.iter().position(|s| s == self).unwrap_or(0) as i32;
+        all[(current + delta).rem_euclid(all.len() as i32) as usize]
+    }
+
+    /// Parse `srgb`, `p3`, `adobergb`, `rec2020` or `prophoto`
+    pub fn parse(value: &str) -> Option<ColorSpace> {
+        let value: String = value
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        match value.as_str() {
+            "srgb" => Some(ColorSpace::Srgb),
+            "p3" | "displayp3" => Some(ColorSpace::DisplayP3),
+            "adobergb" | "adobergb1998" => Some(ColorSpace::AdobeRgb),
+            "rec2020" | "bt2020" => Some(ColorSpace::Rec2020),
+            "prophoto" | "prophotorgb" | "romm" => Some(ColorSpace::ProPhoto),
+            _ => None,
+        }
+    }
+
+    /// CIE xy chromaticities of the red, green and blue primaries
+    pub fn primaries(&self) -> [(f32, f32); 3] {
+        match self {
+            ColorSpace::Srgb => [(0.64, 0.33), (0.30, 0.60), (0.15, 0.06)],
+            ColorSpace::DisplayP3 => [(0.680, 0.320), (0.265, 0.690), (0.150, 0.060)],
+            ColorSpace::AdobeRgb => [(0.64, 0.33), (0.21, 0.71), (0.15, 0.06)],
+            ColorSpace::Rec2020 => [(0.708, 0.292), (0.170, 0.797), (0.131, 0.046)],
+            ColorSpace::ProPhoto => [(0.7347, 0.2653), (0.1596, 0.8404), (0.0366, 0.0001)],
+        }
+    }
+
+    /// CIE xy chromaticity of the white point: D50 for ProPhoto, D65 for
+    /// the rest
+    pub fn white(&self) -> (f32, f32) {
+        match self {
+            ColorSpace::ProPhoto => (0.3457, 0.3585),
+            _ => (0.3127, 0.3290),
+        }
+    }
+
+    pub fn transfer(&self) -> Transfer {
+        match self {
+            ColorSpace::Srgb | ColorSpace::DisplayP3 => Transfer::Srgb,
+            ColorSpace::AdobeRgb => Transfer::Gamma(563.0 / 256.0),
+            ColorSpace::Rec2020 => Transfer::Bt709,
+            ColorSpace::ProPhoto => Transfer::Gamma(1.8),
+        }
+    }
+}
@@ -1,9 +1,18 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::gpr_file::GprMetadata;
+use super::{
+    CameraProfile, DevelopSettings, Geometry, LensSettings, MetadataEdits, OrientationMode, PrivacyPolicy, ResizeSettings,
+    Sharpening, TimeShift,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OutputFormat {
     Jpeg,
     Png,
+    Tiff,
 }
 
 impl OutputFormat {
@@ -11,14 +20,33 @@ impl OutputFormat {
         match self {
             OutputFormat::Jpeg => "JPEG",
             OutputFormat::Png => "PNG",
+            OutputFormat::Tiff => "TIFF",
         }
     }
 
-    #[allow(dead_code)]
     pub fn extension(&self) -> &str {
         match self {
             OutputFormat::Jpeg => "jpg",
             OutputFormat::Png => "png",
+            OutputFormat::Tiff => "tif",
+        }
+    }
+
+    /// Format of an output file, from its extension
+    pub fn from_path(path: &Path) -> Option<OutputFormat> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+            "png" => Some(OutputFormat::Png),
+            "tif" | "tiff" => Some(OutputFormat::Tiff),
+            _ => None,
+        }
+    }
+
+    pub fn mime_type(&self) -> &str {
+        match self {
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Png => "image/png",
+            OutputFormat::Tiff => "image/tiff",
         }
     }
 }
@@ -26,9 +54,57 @@ impl OutputFormat {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConversionConfig {
     pub output_format: OutputFormat,
-    pub quality: u8, // 1-100 for JPEG, ignored for PNG
+    pub quality: u8, // 1-100 for JPEG, ignored for PNG and TIFF
     pub output_directory: Option<String>,
     pub preserve_metadata: bool,
+    pub time_shift: TimeShift,
+    pub privacy: PrivacyPolicy,
+    /// Output file name without extension, e.g. `{date}_{time}_{stem}`
+    pub filename_template: Option<String>,
+    /// Write an XMP sidecar next to each output
+    pub write_xmp_sidecar: bool,
+    /// Metadata edits for every file
+    pub metadata_edits: MetadataEdits,
+    /// Metadata edits made for individual files, over `metadata_edits`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub file_edits: HashMap<PathBuf, MetadataEdits>,
+    pub orientation_mode: OrientationMode,
+    /// Decode in a child process so an SDK crash only fails that file
+    pub isolate_decoder: bool,
+    /// White balance, exposure and levels applied to the linear raw data
+    pub develop: DevelopSettings,
+    /// Lens distortion removed from the decoded image
+    pub lens: LensSettings,
+    /// Stitch Fusion front/back (GPFR/GPBK) pairs into one 360 panorama
+    pub stitch_fusion: bool,
+    /// Output size, applied last before sharpening
+    pub resize: ResizeSettings,
+    /// Unsharp mask applied to the final size
+    pub sharpen: Sharpening,
+    /// Rotation, flips, straightening and crop for every file
+    pub geometry: Geometry,
+    /// Geometry set for individual files, replacing `geometry` for them
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub file_geometry: HashMap<PathBuf, Geometry>,
+    /// Take the output format and quality from the preset of each file's
+    /// camera instead of the settings above
+    pub camera_presets: bool,
+}
+
+/// The settings that decide an output's pixels, recorded with it as
+/// provenance. Descriptive edits, paths and the privacy policy are left out,
+/// so that nothing the policy scrubs from the metadata comes back this way.
+#[derive(Debug, Serialize)]
+pub struct ProcessingSettings<'a> {
+    pub output_format: OutputFormat,
+    pub quality: u8,
+    pub orientation_mode: OrientationMode,
+    pub develop: &'a DevelopSettings,
+    pub lens: &'a LensSettings,
+    pub stitch_fusion: bool,
+    pub resize: &'a ResizeSettings,
+    pub sharpen: &'a Sharpening,
+    pub geometry: &'a Geometry,
 }
 
 impl Default for ConversionConfig {
@@ -38,15 +114,106 @@ impl Default for ConversionConfig {
             quality: 95,
             output_directory: None,
             preserve_metadata: true,
+            time_shift: TimeShift::default(),
+            privacy: PrivacyPolicy::default(),
+            filename_template: None,
+            write_xmp_sidecar: false,
+            metadata_edits: MetadataEdits::default(),
+            file_edits: HashMap::new(),
+            orientation_mode: OrientationMode::default(),
+            isolate_decoder: false,
+            develop: DevelopSettings::default(),
+            lens: LensSettings::default(),
+            stitch_fusion: false,
+            resize: ResizeSettings::default(),
+            sharpen: Sharpening::OFF,
+            geometry: Geometry::default(),
+            file_geometry: HashMap::new(),
+            camera_presets: false,
         }
     }
 }
 
 impl ConversionConfig {
+    pub fn processing_settings(&self) -> ProcessingSettings<'_> {
+        ProcessingSettings {
+            output_format: self.output_format,
+            quality: self.quality,
+            orientation_mode: self.orientation_mode,
+            develop: &self.develop,
+            lens: &self.lens,
+            stitch_fusion: self.stitch_fusion,
+            resize: &self.resize,
+            sharpen: &self.sharpen,
+            geometry: &self.geometry,
+        }
+    }
+
+    /// Geometry that applies to `path`
+    pub fn geometry_for(&self, path: &Path) -> Geometry {
+        self.file_geometry.get(path).copied().unwrap_or(self.geometry)
+    }
+
+    /// Metadata edits that apply to `path`: its own, then those for every file
+    pub fn edits_for(&self, path: &Path) -> MetadataEdits {
+        match self.file_edits.get(path) {
+            Some(own) => self.metadata_edits.merged(own),
+            None => self.metadata_edits.clone(),
+        }
+    }
+
+    /// Settings for converting `path`, with its own geometry and metadata
+    /// edits in place of the defaults. Geometry is defined on the image as shown, so a tagged
+    /// orientation is baked into the pixels whenever there is any.
+    pub fn for_file(&self, path: &Path) -> ConversionConfig {
+        let geometry = self.geometry_for(path);
+        let orientation_mode = if geometry.is_identity() {
+            self.orientation_mode
+        } else {
+            self.orientation_mode.with_geometry()
+        };
+        ConversionConfig {
+            geometry,
+            orientation_mode,
+            file_geometry: HashMap::new(),
+            metadata_edits: self.edits_for(path),
+            file_edits: HashMap::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Settings for a file from the camera in `metadata`: with
+    /// `camera_presets`, that camera's preset replaces the output format
+    /// and quality
+    pub fn for_camera(&self, metadata: &GprMetadata) -> ConversionConfig {
+        let mut config = self.clone();
+        if self.camera_presets {
+            if let Some(camera) = CameraProfile::lookup(&metadata.camera_model) {
+                log::debug!("Using the {} preset", camera.model);
+                camera.preset.apply(&mut config);
+            }
+        }
+        config
+    }
+
+    /// Orientation to rotate and flip the decoded pixels by
+    pub fn baked_orientation(&self, metadata: &GprMetadata) -> u16 {
+        self.orientation_mode.baked_orientation(metadata)
+    }
+
+    /// Orientation tag for the output: the source orientation when tagging,
+    /// otherwise upright
+    pub fn output_orientation(&self, metadata: &GprMetadata) -> u16 {
+        match self.orientation_mode {
+            OrientationMode::Tag => metadata.effective_orientation(),
+            OrientationMode::Ignore | OrientationMode::Bake => 1,
+        }
+    }
+
     pub fn quality_display(&self) -> String {
         match self.output_format {
             OutputFormat::Jpeg => format!("{}%", self.quality),
-            OutputFormat::Png => "N/A".to_string(),
+            OutputFormat::Png | OutputFormat::Tiff => "N/A".to_string(),
         }
     }
 }
@@ -0,0 +1,384 @@
+use super::ColorSpace;
+use serde::{Deserialize, Serialize};
+
+/// Named lighting conditions with their colour temperature and tint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WhiteBalancePreset {
+    Daylight,
+    Cloudy,
+    Shade,
+    Tungsten,
+    Fluorescent,
+    Flash,
+}
+
+impl WhiteBalancePreset {
+    pub fn all() -> Vec<WhiteBalancePreset> {
+        vec![
+            WhiteBalancePreset::Daylight,
+            WhiteBalancePreset::Cloudy,
+            WhiteBalancePreset::Shade,
+            WhiteBalancePreset::Tungsten,
+            WhiteBalancePreset::Fluorescent,
+            WhiteBalancePreset::Flash,
+        ]
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            WhiteBalancePreset::Daylight => "Daylight",
+            WhiteBalancePreset::Cloudy => "Cloudy",
+            WhiteBalancePreset::Shade => "Shade",
+            WhiteBalancePreset::Tungsten => "Tungsten",
+            WhiteBalancePreset::Fluorescent => "Fluorescent",
+            WhiteBalancePreset::Flash => "Flash",
+        }
+    }
+
+    /// Colour temperature in Kelvin and tint
+    pub fn kelvin_tint(&self) -> (u32, i32) {
+        match self {
+            WhiteBalancePreset::Daylight => (5500, 0),
+            WhiteBalancePreset::Cloudy => (6500, 5),
+            WhiteBalancePreset::Shade => (7500, 5),
+            WhiteBalancePreset::Tungsten => (2850, 0),
+            WhiteBalancePreset::Fluorescent => (3800, 20),
+            WhiteBalancePreset::Flash => (5900, 5),
+        }
+    }
+}
+
+/// White balance applied to the linear camera data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WhiteBalance {
+    /// The camera's AsShotNeutral
+    #[default]
+    AsShot,
+    Preset(WhiteBalancePreset),
+    /// Colour temperature in Kelvin and green-magenta tint, -100 to 100
+    /// (positive is more magenta)
+    Custom { kelvin: u32, tint: i32 },
+}
+
+impl WhiteBalance {
+    pub const MIN_KELVIN: u32 = 2000;
+    pub const MAX_KELVIN: u32 = 12000;
+
+    pub fn display(&self) -> String {
+        match self {
+            WhiteBalance::AsShot => "As Shot".to_string(),
+            WhiteBalance::Preset(preset) => preset.as_str().to_string(),
+            WhiteBalance::Custom { kelvin, tint } => format!("{}K, tint {:+}", kelvin, tint),
+        }
+    }
+
+    /// Colour temperature and tint, unless the camera's neutral is used
+    pub fn kelvin_tint(&self) -> Option<(u32, i32)> {
+        match self {
+            WhiteBalance::AsShot => None,
+            WhiteBalance::Preset(preset) => Some(preset.kelvin_tint()),
+            WhiteBalance::Custom { kelvin, tint } => Some((*kelvin, *tint)),
+        }
+    }
+
+    /// Step through As Shot, the presets and a custom value that starts from
+    /// the current temperature
+    pub fn cycle(&self, delta: i32) -> WhiteBalance {
+        let custom = match self {
+            WhiteBalance::Custom { .. } => *self,
+            _ => self.adjusted(0, 0),
+        };
+        let mut options = vec![WhiteBalance::AsShot];
+        options.extend(WhiteBalancePreset::all().into_iter().map(WhiteBalance::Preset));
+        options.push(custom);
+
+        let current = options.iter().position(|o| o == self).unwrap_or(0) as i32;
+        options[(current + delta).rem_euclid(options.len() as i32) as usize]
+    }
+
+    /// Shift the temperature (or tint), switching to a custom value; As Shot
+    /// starts from daylight
+    pub fn adjusted(&self, kelvin_delta: i32, tint_delta: i32) -> WhiteBalance {
+        let (kelvin, tint) = self.kelvin_tint().unwrap_or((5500, 0));
+        WhiteBalance::Custom {
+            kelvin: (kelvin as i32 + kelvin_delta)
+                .clamp(Self::MIN_KELVIN as i32, Self::MAX_KELVIN as i32) as u32,
+            tint: (tint + tint_delta).clamp(-100, 100),
+        }
+    }
+
+    /// Parse `asshot`, a preset name, or `KELVIN[,TINT]` such as `5600,+10`
+    pub fn parse(value: &str) -> Option<WhiteBalance> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("asshot") || value.eq_ignore_ascii_case("as-shot") {
+            return Some(WhiteBalance::AsShot);
+        }
+        if let Some(preset) = WhiteBalancePreset::all()
+            .into_iter()
+            .find(|p| p.as_str().eq_ignore_ascii_case(value))
+        {
+            return Some(WhiteBalance::Preset(preset));
+        }
+
+        let (kelvin, tint) = match value.split_once(',') {
+            Some((kelvin, tint)) => (kelvin, tint.trim().parse().ok()?),
+            None => (value, 0),
+        };
+        let kelvin: u32 = kelvin.trim().trim_end_matches(['K', 'k']).parse().ok()?;
+        ((Self::MIN_KELVIN..=Self::MAX_KELVIN).contains(&kelvin) && (-100..=100).contains(&tint))
+            .then_some(WhiteBalance::Custom { kelvin, tint })
+    }
+}
+
+/// How the two missing colours of each photosite are interpolated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DemosaicMethod {
+    /// The GPR SDK's RGB conversion while nothing else is adjusted, AHD
+    /// otherwise
+    #[default]
+    Sdk,
+    /// Neighbour average: fastest, soft with colour fringes on edges
+    Bilinear,
+    /// Patterned Pixel Grouping: gradient-directed, a good speed/detail
+    /// trade-off
+    Ppg,
+    /// Adaptive Homogeneity-Directed: slowest, fewest zipper artefacts
+    Ahd,
+}
+
+impl DemosaicMethod {
+    pub fn all() -> Vec<DemosaicMethod> {
+        vec![
+            DemosaicMethod::Sdk,
+            DemosaicMethod::Bilinear,
+            DemosaicMethod::Ppg,
+            DemosaicMethod::Ahd,
+        ]
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            DemosaicMethod::Sdk => "GPR SDK",
+            DemosaicMethod::Bilinear => "Bilinear",
+            DemosaicMethod::Ppg => "PPG",
+            DemosaicMethod::Ahd => "AHD",
+        }
+    }
+
+    pub fn cycle(&self, delta: i32) -> DemosaicMethod {
+        let all = DemosaicMethod::all();
+        let current = all.iter().position(|m| m == self).unwrap_or(0) as i32;
+        all[(current + delta).rem_euclid(all.len() as i32) as usize]
+    }
+
+    /// Parse `sdk`, `bilinear`, `ppg` or `ahd`
+    pub fn parse(value: &str) -> Option<DemosaicMethod> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "sdk" => Some(DemosaicMethod::Sdk),
+            "bilinear" => Some(DemosaicMethod::Bilinear),
+            "ppg" => Some(DemosaicMethod::Ppg),
+            "ahd" => Some(DemosaicMethod::Ahd),
+            _ => None,
+        }
+    }
+}
+
+/// Base curve mapping scene-linear values to the output range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ToneCurve {
+    /// Straight line shaped only by the contrast, highlights and shadows
+    /// sliders; anything above white clips
+    #[default]
+    Parametric,
+    /// Rolls values above mid-tones off towards white instead of clipping
+    Filmic,
+}
+
+impl ToneCurve {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ToneCurve::Parametric => "Parametric",
+            ToneCurve::Filmic => "Filmic",
+        }
+    }
+
+    pub fn toggle(&self) -> ToneCurve {
+        match self {
+            ToneCurve::Parametric => ToneCurve::Filmic,
+            ToneCurve::Filmic => ToneCurve::Parametric,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<ToneCurve> {
+        [ToneCurve::Parametric, ToneCurve::Filmic]
+            .into_iter()
+            .find(|curve| curve.as_str().eq_ignore_ascii_case(value.trim()))
+    }
+}
+
+/// Tone mapping after white balance and exposure. The sliders run from
+/// -1 to 1 and are shown as -100 to +100.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ToneSettings {
+    pub curve: ToneCurve,
+    pub contrast: f32,
+    /// Negative values pull bright areas down, recovering detail above white
+    pub highlights: f32,
+    /// Positive values lift dark areas
+    pub shadows: f32,
+    /// Rebuild clipped channels from the unclipped ones instead of letting
+    /// blown highlights turn magenta
+    pub reconstruct_highlights: bool,
+}
+
+impl ToneSettings {
+    /// Leaves the image untouched
+    pub const NEUTRAL: ToneSettings = ToneSettings {
+        curve: ToneCurve::Parametric,
+        contrast: 0.0,
+        highlights: 0.0,
+        shadows: 0.0,
+        reconstruct_highlights: false,
+    };
+
+    /// Slider value as shown, e.g. `+25`
+    pub fn slider_display(value: f32) -> String {
+        format!("{:+.0}", value * 100.0)
+    }
+
+    /// Move a slider by `delta` hundredths, keeping it within -1 to 1
+    pub fn step_slider(value: f32, delta: i32) -> f32 {
+        ((value * 100.0).round() + delta as f32).clamp(-100.0, 100.0) / 100.0
+    }
+}
+
+impl Default for ToneSettings {
+    fn default() -> Self {
+        Self::NEUTRAL
+    }
+}
+
+/// Noise reduction algorithm
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DenoiseMethod {
+    /// Edge-preserving neighbourhood average
+    #[default]
+    Bilateral,
+    /// Shrinks the noise out of each wavelet scale: keeps fine texture and
+    /// also cleans blotchy low-frequency colour noise
+    Wavelet,
+}
+
+impl DenoiseMethod {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DenoiseMethod::Bilateral => "Bilateral",
+            DenoiseMethod::Wavelet => "Wavelet",
+        }
+    }
+
+    pub fn toggle(&self) -> DenoiseMethod {
+        match self {
+            DenoiseMethod::Bilateral => DenoiseMethod::Wavelet,
+            DenoiseMethod::Wavelet => DenoiseMethod::Bilateral,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<DenoiseMethod> {
+        [DenoiseMethod::Bilateral, DenoiseMethod::Wavelet]
+            .into_iter()
+            .find(|method| method.as_str().eq_ignore_ascii_case(value.trim()))
+    }
+}
+
+/// Noise reduction on linear camera data, before white balance and tone
+/// mapping. Strengths run from 0 (off) to 1 and are shown as 0 to 100; the
+/// amount of noise they remove follows the camera's noise at the file's ISO.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DenoiseSettings {
+    pub method: DenoiseMethod,
+    /// Brightness noise (grain)
+    pub luma: f32,
+    /// Colour noise (blotches)
+    pub chroma: f32,
+}
+
+impl DenoiseSettings {
+    pub const OFF: DenoiseSettings = DenoiseSettings {
+        method: DenoiseMethod::Bilateral,
+        luma: 0.0,
+        chroma: 0.0,
+    };
+
+    pub fn is_off(&self) -> bool {
+        self.luma <= 0.0 && self.chroma <= 0.0
+    }
+
+    /// Strength as shown, e.g. `40`
+    pub fn strength_display(value: f32) -> String {
+        format!("{:.0}", value * 100.0)
+    }
+
+    /// Move a strength by `delta` hundredths, keeping it within 0 to 1
+    pub fn step_strength(value: f32, delta: i32) -> f32 {
+        ((value * 100.0).round() + delta as f32).clamp(0.0, 100.0) / 100.0
+    }
+}
+
+impl Default for DenoiseSettings {
+    fn default() -> Self {
+        Self::OFF
+    }
+}
+
+/// Adjustments made on linear raw data. With the defaults the GPR SDK's own
+/// RGB conversion is used instead.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DevelopSettings {
+    pub demosaic: DemosaicMethod,
+    pub denoise: DenoiseSettings,
+    pub white_balance: WhiteBalance,
+    /// Exposure compensation in stops
+    pub exposure_ev: f32,
+    /// Linear level that becomes black, as a fraction of the white level
+    pub black_point: f32,
+    /// Linear level that becomes white, as a fraction of the white level
+    pub white_point: f32,
+    pub tone: ToneSettings,
+    /// Output colour space the camera colours are converted to
+    pub color_space: ColorSpace,
+}
+
+impl Default for DevelopSettings {
+    fn default() -> Self {
+        Self {
+            demosaic: DemosaicMethod::Sdk,
+            denoise: DenoiseSettings::OFF,
+            white_balance: WhiteBalance::AsShot,
+            exposure_ev: 0.0,
+            black_point: 0.0,
+            white_point: 1.0,
+            tone: ToneSettings::NEUTRAL,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+}
+
+impl DevelopSettings {
+    pub const MAX_EXPOSURE_EV: f32 = 5.0;
+
+    /// Whether nothing has been changed, so the SDK conversion can be used
+    pub fn is_neutral(&self) -> bool {
+        *self == DevelopSettings::default()
+    }
+
+    pub fn exposure_display(&self) -> String {
+        format!("{:+.1} EV", self.exposure_ev)
+    }
+
+    /// Keep the black point below the white point, both within 0-1
+    pub fn set_levels(&mut self, black_point: f32, white_point: f32) {
+        self.white_point = white_point.clamp(0.1, 1.0);
+        self.black_point = black_point.clamp(0.0, self.white_point - 0.05);
+    }
+}
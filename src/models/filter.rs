@@ -0,0 +1,454 @@
+use super::gpr_file::{GprFile, GprMetadata};
+use super::shoot_stats::parse_exposure;
+use crate::utils::datetime::ExifDateTime;
+use anyhow::{anyhow, bail, Result};
+use std::fmt;
+
+/// Mean Earth radius used for `within:` distances
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// A library query such as `iso>800 camera:HERO7 date:2024-06`
+///
+/// Terms are separated by spaces and must all match. Each term is
+/// `field<op>value` with `:` (match), `=`, `<`, `<=`, `>` or `>=`, or a bare
+/// word matched against the file name and camera. A leading `-` negates a
+/// term and double quotes allow spaces in values.
+///
+/// | Field | Values |
+/// |---|---|
+/// | `iso`, `shutter`, `f`, `focal`, `width`, `height` | numbers (`shutter<1/500`) |
+/// | `camera`, `serial`, `name` | case-insensitive substring |
+/// | `date` | `2024`, `2024-06`, `2024-06-01` or `2024-06-01..2024-06-10` |
+/// | `within` | `lat,lon,radius` with `km`, `m` or `mi` |
+/// | `gps` | `yes` or `no` |
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    source: String,
+    terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone)]
+struct Term {
+    negated: bool,
+    condition: Condition,
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Number {
+        field: NumericField,
+        comparison: Comparison,
+        value: f64,
+    },
+    Text {
+        field: TextField,
+        value: String,
+    },
+    /// Capture time against the span `[start, end)` the query date covers
+    Date {
+        comparison: Comparison,
+        start: i64,
+        end: i64,
+    },
+    Within {
+        latitude: f64,
+        longitude: f64,
+        radius_km: f64,
+    },
+    HasGps(bool),
+    /// Bare word: file name or camera
+    Any(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NumericField {
+    Iso,
+    Shutter,
+    Aperture,
+    FocalLength,
+    Width,
+    Height,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextField {
+    Camera,
+    Serial,
+    Name,
+}
+
+impl Filter {
+    pub fn parse(query: &str) -> Result<Filter> {
+        let terms = tokenize(query)?
+            .iter()
+            .map(|token| parse_term(token))
+            .collect::<Result<Vec<Term>>>()?;
+        Ok(Filter {
+            source: query.trim().to_string(),
+            terms,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Whether `file` satisfies every term. Files without metadata only
+    /// match terms about their name.
+    pub fn matches(&self, file: &GprFile) -> bool {
+        self.terms.iter().all(|term| {
+            term.condition.matches(&file.filename, file.metadata.as_ref()) != term.negated
+        })
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Condition {
+    fn matches(&self, filename: &str, metadata: Option<&GprMetadata>) -> bool {
+        match self {
+            Condition::Text {
+                field: TextField::Name,
+                value,
+            } => contains(filename, value),
+            Condition::Any(value) => {
+                contains(filename, value) || metadata.is_some_and(|m| contains(&m.camera_model, value))
+            }
+            _ => metadata.is_some_and(|metadata| self.matches_metadata(metadata)),
+        }
+    }
+
+    fn matches_metadata(&self, metadata: &GprMetadata) -> bool {
+        match self {
+            Condition::Number {
+                field,
+                comparison,
+                value,
+            } => field
+                .value(metadata)
+                .is_some_and(|actual| comparison.compare(actual, *value)),
+            Condition::Text { field, value } => {
+                let actual = match field {
+                    TextField::Camera => Some(metadata.camera_model.as_str()),
+                    TextField::Serial => metadata.serial_number.as_deref(),
+                    TextField::Name => None,
+                };
+                actual.is_some_and(|actual| contains(actual, value))
+            }
+            Condition::Date {
+                comparison,
+                start,
+                end,
+            } => {
+                let Some(taken) = metadata.date_taken.as_deref().and_then(ExifDateTime::parse) else {
+                    return false;
+                };
+                let ts = taken.to_timestamp();
+                match comparison {
+                    Comparison::Equal => ts >= *start && ts < *end,
+                    Comparison::Less => ts < *start,
+                    Comparison::LessOrEqual => ts < *end,
+                    Comparison::Greater => ts >= *end,
+                    Comparison::GreaterOrEqual => ts >= *start,
+                }
+            }
+            Condition::Within {
+                latitude,
+                longitude,
+                radius_km,
+            } => match (metadata.gps_latitude, metadata.gps_longitude) {
+                (Some(lat), Some(lon)) => distance_km(*latitude, *longitude, lat, lon) <= *radius_km,
+                _ => false,
+            },
+            Condition::HasGps(wanted) => {
+                (metadata.gps_latitude.is_some() && metadata.gps_longitude.is_some()) == *wanted
+            }
+            Condition::Any(_) => false,
+        }
+    }
+}
+
+impl Comparison {
+    fn compare(&self, actual: f64, wanted: f64) -> bool {
+        match self {
+            Comparison::Equal => (actual - wanted).abs() <= 1e-9 * wanted.abs().max(1.0),
+            Comparison::Less => actual < wanted,
+            Comparison::LessOrEqual => actual <= wanted,
+            Comparison::Greater => actual > wanted,
+            Comparison::GreaterOrEqual => actual >= wanted,
+        }
+    }
+}
+
+impl NumericField {
+    fn value(&self, metadata: &GprMetadata) -> Option<f64> {
+        match self {
+            NumericField::Iso => metadata.iso.map(f64::from),
+            NumericField::Shutter => metadata.exposure_time.as_deref().and_then(parse_exposure),
+            NumericField::Aperture => metadata.f_number.as_deref().and_then(leading_number),
+            NumericField::FocalLength => metadata.focal_length.as_deref().and_then(leading_number),
+            NumericField::Width => Some(metadata.width as f64).filter(|w| *w > 0.0),
+            NumericField::Height => Some(metadata.height as f64).filter(|h| *h > 0.0),
+        }
+    }
+
+    fn parse_value(&self, value: &str) -> Option<f64> {
+        match self {
+            NumericField::Shutter => parse_exposure(value),
+            _ => leading_number(value),
+        }
+    }
+}
+
+/// Split on spaces outside double quotes, dropping the quotes
+fn tokenize(query: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in query.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if quoted {
+        bail!("Unterminated quote in filter");
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+fn parse_term(token: &str) -> Result<Term> {
+    let (negated, token) = match token.strip_prefix(['-', '!']) {
+        Some(rest) if !rest.is_empty() => (true, rest),
+        _ => (false, token),
+    };
+
+    let Some(split) = token.find([':', '=', '<', '>']) else {
+        return Ok(Term {
+            negated,
+            condition: Condition::Any(token.to_string()),
+        });
+    };
+    let field = token[..split].to_ascii_lowercase();
+    let rest = &token[split..];
+    let (comparison, value) = if let Some(value) = rest.strip_prefix("<=") {
+        (Comparison::LessOrEqual, value)
+    } else if let Some(value) = rest.strip_prefix(">=") {
+        (Comparison::GreaterOrEqual, value)
+    } else if let Some(value) = rest.strip_prefix('<') {
+        (Comparison::Less, value)
+    } else if let Some(value) = rest.strip_prefix('>') {
+        (Comparison::Greater, value)
+    } else {
+        (Comparison::Equal, &rest[1..])
+    };
+    if value.is_empty() {
+        bail!("Missing value for '{}'", field);
+    }
+
+    let numeric = match field.as_str() {
+        "iso" => Some(NumericField::Iso),
+        "shutter" | "exposure" | "ss" => Some(NumericField::Shutter),
+        "f" | "aperture" | "fnumber" => Some(NumericField::Aperture),
+        "focal" => Some(NumericField::FocalLength),
+        "width" => Some(NumericField::Width),
+        "height" => Some(NumericField::Height),
+        _ => None,
+    };
+    let text = match field.as_str() {
+        "camera" | "model" => Some(TextField::Camera),
+        "serial" => Some(TextField::Serial),
+        "name" | "file" => Some(TextField::Name),
+        _ => None,
+    };
+
+    let condition = if let Some(field) = numeric {
+        Condition::Number {
+            field,
+            comparison,
+            value: field
+                .parse_value(value)
+                .ok_or_else(|| anyhow!("Invalid number in '{}'", token))?,
+        }
+    } else if let Some(field) = text {
+        if comparison != Comparison::Equal {
+            bail!("'{}' only supports ':'", token);
+        }
+        Condition::Text {
+            field,
+            value: value.to_string(),
+        }
+    } else {
+        match field.as_str() {
+            "date" => parse_date_condition(comparison, value)
+                .ok_or_else(|| anyhow!("Invalid date in '{}' (use YYYY, YYYY-MM or YYYY-MM-DD)", token))?,
+            "within" | "near" => parse_within(value)
+                .ok_or_else(|| anyhow!("Invalid area in '{}' (use lat,lon,radius)", token))?,
+            "gps" => match value.to_ascii_lowercase().as_str() {
+                "yes" | "true" => Condition::HasGps(true),
+                "no" | "false" => Condition::HasGps(false),
+                _ => bail!("Invalid value in '{}' (use yes or no)", token),
+            },
+            _ => bail!("Unknown filter field '{}'", field),
+        }
+    };
+
+    Ok(Term { negated, condition })
+}
+
+fn parse_date_condition(comparison: Comparison, value: &str) -> Option<Condition> {
+    let (start, end) = match value.split_once("..") {
+        Some((from, to)) if comparison == Comparison::Equal => (date_span(from)?.0, date_span(to)?.1),
+        Some(_) => return None,
+        None => date_span(value)?,
+    };
+    Some(Condition::Date {
+        comparison,
+        start,
+        end,
+    })
+}
+
+/// Timestamps bounding a partial date: the year, month or day it names
+fn date_span(value: &str) -> Option<(i64, i64)> {
+    let parts: Vec<&str> = value.split(['-', ':']).collect();
+    let year: i32 = parts.first()?.parse().ok()?;
+    let month: Option<u32> = parts.get(1).map(|m| m.parse()).transpose().ok()?;
+    let day: Option<u32> = parts.get(2).map(|d| d.parse()).transpose().ok()?;
+    if parts.len() > 3 {
+        return None;
+    }
+
+    let date = |y: i32, m: u32, d: u32| {
+        ExifDateTime::parse(&format!("{:04}:{:02}:{:02} 00:00:00", y, m, d)).map(|d| d.to_timestamp())
+    };
+    let start = date(year, month.unwrap_or(1), day.unwrap_or(1))?;
+    let end = match (month, day) {
+        (Some(_), Some(_)) => start + 86_400,
+        (Some(12), None) => date(year + 1, 1, 1)?,
+        (Some(m), None) => date(year, m + 1, 1)?,
+        (None, _) => date(year + 1, 1, 1)?,
+    };
+    Some((start, end))
+}
+
+fn parse_within(value: &str) -> Option<Condition> {
+    let mut parts = value.split(',');
+    let latitude: f64 = parts.next()?.trim().parse().ok()?;
+    let longitude: f64 = parts.next()?.trim().parse().ok()?;
+    let radius = parts.next()?.trim().to_ascii_lowercase();
+    if parts.next().is_some() || latitude.abs() > 90.0 || longitude.abs() > 180.0 {
+        return None;
+    }
+
+    let (number, scale) = if let Some(n) = radius.strip_suffix("km") {
+        (n, 1.0)
+    } else if let Some(n) = radius.strip_suffix("mi") {
+        (n, 1.609_344)
+    } else if let Some(n) = radius.strip_suffix('m') {
+        (n, 0.001)
+    } else {
+        (radius.as_str(), 1.0)
+    };
+    let radius_km = number.trim().parse::<f64>().ok()? * scale;
+    (radius_km >= 0.0).then_some(Condition::Within {
+        latitude,
+        longitude,
+        radius_km,
+    })
+}
+
+/// Great-circle distance between two points, in kilometres
+fn distance_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// First number in a display value such as "f/2.8" or "3 mm"
+fn leading_number(value: &str) -> Option<f64> {
+    let value = value.trim().trim_start_matches(['f', 'F', '/']);
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    value[..end].parse().ok()
+}
+
+fn contains(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn file(name: &str, camera: &str, iso: u32, date: &str, gps: Option<(f64, f64)>) -> GprFile {
+        GprFile {
+            path: PathBuf::from(name),
+            filename: name.to_string(),
+            size: 1000,
+            metadata: Some(GprMetadata {
+                camera_model: camera.to_string(),
+                iso: Some(iso),
+                exposure_time: Some("1/240".to_string()),
+                f_number: Some("f/2.8".to_string()),
+                date_taken: Some(date.to_string()),
+                gps_latitude: gps.map(|g| g.0),
+                gps_longitude: gps.map(|g| g.1),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        let seattle = file("GOPR0001.GPR", "GoPro HERO7 Black", 1600, "2024:06:15 10:00:00", Some((47.61, -122.33)));
+        let fusion = file("GPFR0002.GPR", "GoPro FUSION", 100, "2024-07-01 09:00:00", None);
+
+        let matching = |query: &str| -> Vec<&str> {
+            let filter = Filter::parse(query).unwrap();
+            [&seattle, &fusion]
+                .into_iter()
+                .filter(|f| filter.matches(f))
+                .map(|f| f.filename.as_str())
+                .collect()
+        };
+
+        assert_eq!(matching("iso>800 camera:HERO7 date:2024-06 within:47.6,-122.3,5km").len(), 1);
+        assert_eq!(matching("iso<=100"), vec!["GPFR0002.GPR"]);
+        assert_eq!(matching("-camera:hero7"), vec!["GPFR0002.GPR"]);
+        assert_eq!(matching("camera:\"FUSION\" gps:no f:2.8"), vec!["GPFR0002.GPR"]);
+        assert_eq!(matching("date>2024-06").len(), 1);
+        assert_eq!(matching("date:2024-06-01..2024-07-01").len(), 2);
+        assert_eq!(matching("shutter<1/200 0001").len(), 1);
+        assert_eq!(matching("within:47.6,-122.3,500m").len(), 0);
+        assert!(Filter::parse("colour:red").is_err());
+        assert!(Filter::parse("date:2024-13").is_err());
+        assert!(Filter::parse("").unwrap().is_empty());
+    }
+}
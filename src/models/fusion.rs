@@ -0,0 +1,122 @@
+use super::GprFile;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Which of the two Fusion lenses a file was taken with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FusionLens {
+    /// GPFR files
+    Front,
+    /// GPBK files
+    Back,
+}
+
+impl FusionLens {
+    /// The lens and sequence number of a Fusion file name such as
+    /// `GPFR7066.GPR`
+    pub fn from_path(path: &Path) -> Option<(FusionLens, String)> {
+        let stem = path.file_stem()?.to_str()?;
+        let prefix = stem.get(..4)?;
+        let lens = if prefix.eq_ignore_ascii_case("GPFR") {
+            FusionLens::Front
+        } else if prefix.eq_ignore_ascii_case("GPBK") {
+            FusionLens::Back
+        } else {
+            return None;
+        };
+        let sequence = &stem[4..];
+        (!sequence.is_empty() && sequence.chars().all(|c| c.is_ascii_digit()))
+            .then(|| (lens, sequence.to_string()))
+    }
+}
+
+/// Front and back halves of one Fusion shot, as indices into a file list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FusionPair {
+    pub front: usize,
+    pub back: usize,
+}
+
+/// Size of a stitched equirectangular panorama, for the GPano XMP tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Panorama {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Match GPFR and GPBK files in the same directory by sequence number.
+/// Returns the pairs, in the order of their front files, and the indices of
+/// every file left unpaired.
+pub fn find_pairs(files: &[GprFile]) -> (Vec<FusionPair>, Vec<usize>) {
+    let mut backs: HashMap<(Option<&Path>, String), usize> = HashMap::new();
+    for (index, file) in files.iter().enumerate() {
+        if let Some((FusionLens::Back, sequence)) = FusionLens::from_path(&file.path) {
+            backs.entry((file.path.parent(), sequence)).or_insert(index);
+        }
+    }
+
+    let mut pairs = Vec::new();
+    for (index, file) in files.iter().enumerate() {
+        if let Some((FusionLens::Front, sequence)) = FusionLens::from_path(&file.path) {
+            if let Some(back) = backs.remove(&(file.path.parent(), sequence)) {
+                pairs.push(FusionPair { front: index, back });
+            }
+        }
+    }
+
+    let unpaired = (0..files.len())
+        .filter(|&index| !pairs.iter().any(|pair| pair.front == index || pair.back == index))
+        .collect();
+    (pairs, unpaired)
+}
+
+/// Output stem for a stitched pair: `GPFR7066` becomes `GP360_7066`
+pub fn panorama_stem(front: &Path) -> Option<String> {
+    let (_, sequence) = FusionLens::from_path(front)?;
+    Some(format!("GP360_{}", sequence))
+}
+
+/// Front and back paths of the shot `path` belongs to, when both halves
+/// exist on disk
+pub fn pair_paths(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    let (lens, _) = FusionLens::from_path(path)?;
+    let name = path.file_name()?.to_str()?;
+    let prefix = match lens {
+        FusionLens::Front => "GPBK",
+        FusionLens::Back => "GPFR",
+    };
+    let partner = path.with_file_name(format!("{}{}", prefix, &name[4..]));
+    if !partner.is_file() {
+        return None;
+    }
+    match lens {
+        FusionLens::Front => Some((path.to_path_buf(), partner)),
+        FusionLens::Back => Some((partner, path.to_path_buf())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_pairs() {
+        let files: Vec<GprFile> = [
+            "a/GPFR7066.GPR",
+            "a/GOPR0001.GPR",
+            "a/gpbk7066.gpr",
+            "b/GPBK7067.GPR",
+            "a/GPFR7067.GPR",
+        ]
+        .iter()
+        .map(|path| GprFile::new(PathBuf::from(path)))
+        .collect();
+
+        let (pairs, unpaired) = find_pairs(&files);
+        assert_eq!(pairs, vec![FusionPair { front: 0, back: 2 }]);
+        // Different directories never pair
+        assert_eq!(unpaired, vec![1, 3, 4]);
+        assert_eq!(panorama_stem(Path::new("a/GPFR7066.GPR")).as_deref(), Some("GP360_7066"));
+        assert_eq!(FusionLens::from_path(Path::new("GPFRX.GPR")), None);
+    }
+}
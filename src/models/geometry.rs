@@ -0,0 +1,359 @@
+use super::gpr_file::GprMetadata;
+use super::orientation;
+use serde::{Deserialize, Serialize};
+
+/// Quarter turns applied to the upright image
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rotation {
+    #[default]
+    None,
+    Cw90,
+    Half,
+    Ccw90,
+}
+
+impl Rotation {
+    const ALL: [Rotation; 4] = [Rotation::None, Rotation::Cw90, Rotation::Half, Rotation::Ccw90];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Rotation::None => "None",
+            Rotation::Cw90 => "90° CW",
+            Rotation::Half => "180°",
+            Rotation::Ccw90 => "90° CCW",
+        }
+    }
+
+    /// A further `quarters` clockwise quarter turns
+    pub fn turned(&self, quarters: i32) -> Rotation {
+        let current = Rotation::ALL.iter().position(|r| r == self).unwrap_or(0) as i32;
+        Rotation::ALL[(current + quarters).rem_euclid(4) as usize]
+    }
+
+    /// Parse clockwise degrees: 0, 90, 180, 270 or -90
+    pub fn parse(value: &str) -> Option<Rotation> {
+        let degrees: i32 = value.trim().trim_end_matches('°').parse().ok()?;
+        (degrees % 90 == 0).then(|| Rotation::None.turned(degrees / 90))
+    }
+}
+
+/// Shape of the crop rectangle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CropAspect {
+    #[default]
+    Free,
+    /// Width to height, e.g. 16:9 or 4:5
+    Ratio(u32, u32),
+}
+
+impl CropAspect {
+    /// Aspect presets offered in the crop screen
+    pub const PRESETS: &'static [CropAspect] = &[
+        CropAspect::Free,
+        CropAspect::Ratio(1, 1),
+        CropAspect::Ratio(4, 5),
+        CropAspect::Ratio(16, 9),
+        CropAspect::Ratio(3, 2),
+    ];
+
+    pub fn display(&self) -> String {
+        match self {
+            CropAspect::Free => "Free".to_string(),
+            CropAspect::Ratio(w, h) => format!("{}:{}", w, h),
+        }
+    }
+
+    /// Step through the presets, keeping a portrait or landscape choice
+    pub fn cycle(&self, delta: i32) -> CropAspect {
+        let presets = CropAspect::PRESETS;
+        let portrait = matches!(*self, CropAspect::Ratio(w, h) if w < h);
+        let current = presets
+            .iter()
+            .position(|p| p == self || p.swapped() == *self)
+            .unwrap_or(0) as i32;
+        let next = presets[(current + delta).rem_euclid(presets.len() as i32) as usize];
+        let next_portrait = matches!(next, CropAspect::Ratio(w, h) if w < h);
+        if portrait != next_portrait {
+            next.swapped()
+        } else {
+            next
+        }
+    }
+
+    /// The same ratio turned between portrait and landscape
+    pub fn swapped(&self) -> CropAspect {
+        match *self {
+            CropAspect::Free => CropAspect::Free,
+            CropAspect::Ratio(w, h) => CropAspect::Ratio(h, w),
+        }
+    }
+
+    /// Width divided by height
+    pub fn ratio(&self) -> Option<f64> {
+        match *self {
+            CropAspect::Free => None,
+            CropAspect::Ratio(w, h) => Some(w as f64 / h as f64),
+        }
+    }
+
+    /// Parse `free` or `W:H`
+    pub fn parse(value: &str) -> Option<CropAspect> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("free") {
+            return Some(CropAspect::Free);
+        }
+        let (w, h) = value.split_once(':')?;
+        let (w, h) = (w.trim().parse::<u32>().ok()?, h.trim().parse::<u32>().ok()?);
+        (w > 0 && h > 0).then_some(CropAspect::Ratio(w, h))
+    }
+}
+
+/// Crop rectangle as fractions of the rotated and straightened image
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CropRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl CropRect {
+    pub const FULL: CropRect = CropRect {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+
+    /// Smallest side of a crop, as a fraction of the image
+    pub const MIN_SIZE: f32 = 0.05;
+
+    /// Parse `X,Y,W,H` fractions (0-1) of the image
+    pub fn parse(value: &str) -> Option<CropRect> {
+        let parts: Vec<f32> = value
+            .split(',')
+            .map(|part| part.trim().parse::<f32>().ok())
+            .collect::<Option<_>>()?;
+        let [x, y, width, height] = parts[..] else {
+            return None;
+        };
+        let rect = CropRect { x, y, width, height };
+        (x >= 0.0
+            && y >= 0.0
+            && width >= CropRect::MIN_SIZE
+            && height >= CropRect::MIN_SIZE
+            && x + width <= 1.0 + f32::EPSILON
+            && y + height <= 1.0 + f32::EPSILON)
+            .then_some(rect)
+    }
+
+    /// Moved by a fraction of the image, staying inside it
+    pub fn moved(&self, dx: f32, dy: f32) -> CropRect {
+        CropRect {
+            x: (self.x + dx).clamp(0.0, 1.0 - self.width),
+            y: (self.y + dy).clamp(0.0, 1.0 - self.height),
+            ..*self
+        }
+    }
+
+    /// Grown or shrunk about its centre by `amount` of the image on each
+    /// side, staying inside it
+    pub fn resized(&self, amount: f32) -> CropRect {
+        let width = (self.width + 2.0 * amount).clamp(CropRect::MIN_SIZE, 1.0);
+        let height = (self.height + 2.0 * amount).clamp(CropRect::MIN_SIZE, 1.0);
+        CropRect {
+            x: self.x + (self.width - width) / 2.0,
+            y: self.y + (self.height - height) / 2.0,
+            width,
+            height,
+        }
+        .moved(0.0, 0.0)
+    }
+}
+
+impl Default for CropRect {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+/// Crop, straightening, rotation and flips of one output. Flips and quarter
+/// turns apply to the upright image first, then the straightening, whose
+/// empty corners are always cropped away, then the crop rectangle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Geometry {
+    pub rotation: Rotation,
+    /// Mirror left to right
+    pub flip_horizontal: bool,
+    /// Mirror top to bottom
+    pub flip_vertical: bool,
+    /// Degrees clockwise, within `MAX_STRAIGHTEN`
+    pub straighten: f32,
+    /// Straighten by the camera's roll at capture, from GPMF gravity,
+    /// instead of by `straighten`
+    #[serde(default)]
+    pub auto_level: bool,
+    pub aspect: CropAspect,
+    pub crop: CropRect,
+}
+
+impl Geometry {
+    pub const MAX_STRAIGHTEN: f32 = 45.0;
+
+    pub fn is_identity(&self) -> bool {
+        *self == Geometry::default()
+    }
+
+    /// Turn the result `quarters` times clockwise, keeping the crop on the
+    /// same part of the picture. With one flip set the underlying rotation
+    /// runs the other way.
+    pub fn turn(&mut self, quarters: i32) {
+        let mirrored = self.flip_horizontal != self.flip_vertical;
+        self.rotation = self.rotation.turned(if mirrored { -quarters } else { quarters });
+        for _ in 0..quarters.rem_euclid(4) {
+            let crop = self.crop;
+            self.crop = CropRect {
+                x: 1.0 - crop.y - crop.height,
+                y: crop.x,
+                width: crop.height,
+                height: crop.width,
+            };
+            self.aspect = self.aspect.swapped();
+        }
+    }
+
+    /// Mirror the result, keeping the crop on the same part of the picture
+    pub fn flip(&mut self, horizontal: bool) {
+        let crop = &mut self.crop;
+        if horizontal {
+            self.flip_horizontal = !self.flip_horizontal;
+            crop.x = 1.0 - crop.x - crop.width;
+        } else {
+            self.flip_vertical = !self.flip_vertical;
+            crop.y = 1.0 - crop.y - crop.height;
+        }
+    }
+
+    pub fn set_straighten(&mut self, degrees: f32) {
+        self.straighten = degrees.clamp(-Geometry::MAX_STRAIGHTEN, Geometry::MAX_STRAIGHTEN);
+    }
+
+    /// The geometry to apply to a file whose pixels have been oriented by
+    /// `baked_orientation`. With `auto_level` it is straightened by the
+    /// camera's recorded roll, reversed when the orientation or the flips
+    /// mirror the image (but not both), or not at all when there is none.
+    pub fn levelled(&self, metadata: &GprMetadata, baked_orientation: u16) -> Geometry {
+        if !self.auto_level {
+            return *self;
+        }
+        let mirrored = orientation::is_mirrored(baked_orientation) != (self.flip_horizontal != self.flip_vertical);
+        let roll = metadata.roll_degrees().unwrap_or(0.0) as f32;
+        let mut geometry = Geometry { auto_level: false, ..*self };
+        geometry.set_straighten(if mirrored { -roll } else { roll });
+        geometry
+    }
+
+    /// Crop in pixels of a `width` x `height` straightened image, as `(x,
+    /// y, width, height)`: the crop rectangle, narrowed to the aspect ratio
+    /// about its centre
+    pub fn crop_pixels(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let (w, h) = (width as f64, height as f64);
+        let crop = self.crop;
+        let (mut left, mut top) = (crop.x as f64 * w, crop.y as f64 * h);
+        let (mut crop_width, mut crop_height) = (crop.width as f64 * w, crop.height as f64 * h);
+        if let Some(ratio) = self.aspect.ratio() {
+            if crop_width / crop_height > ratio {
+                let narrowed = crop_height * ratio;
+                left += (crop_width - narrowed) / 2.0;
+                crop_width = narrowed;
+            } else {
+                let narrowed = crop_width / ratio;
+                top += (crop_height - narrowed) / 2.0;
+                crop_height = narrowed;
+            }
+        }
+        let left = (left.round() as u32).min(width - 1);
+        let top = (top.round() as u32).min(height - 1);
+        let crop_width = (crop_width.round() as u32).clamp(1, width - left);
+        let crop_height = (crop_height.round() as u32).clamp(1, height - top);
+        (left, top, crop_width, crop_height)
+    }
+
+    /// Short description for settings and logs
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if self.rotation != Rotation::None {
+            parts.push(self.rotation.as_str().to_string());
+        }
+        if self.flip_horizontal {
+            parts.push("flipped H".to_string());
+        }
+        if self.flip_vertical {
+            parts.push("flipped V".to_string());
+        }
+        if self.auto_level {
+            parts.push("auto-levelled".to_string());
+        } else if self.straighten != 0.0 {
+            parts.push(format!("straightened {:+.1}°", self.straighten));
+        }
+        if self.crop != CropRect::FULL || self.aspect != CropAspect::Free {
+            parts.push(match self.aspect {
+                CropAspect::Free => "cropped".to_string(),
+                aspect => format!("crop {}", aspect.display()),
+            });
+        }
+        if parts.is_empty() {
+            "None".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geometry() {
+        assert_eq!(Rotation::parse("270"), Some(Rotation::Ccw90));
+        assert_eq!(Rotation::parse("-90"), Some(Rotation::Ccw90));
+        assert_eq!(Rotation::parse("45"), None);
+        assert_eq!(CropAspect::parse("4:5"), Some(CropAspect::Ratio(4, 5)));
+        assert_eq!(CropRect::parse("0.5,0,0.6,1"), None);
+
+        // Portrait stays portrait while cycling
+        assert_eq!(CropAspect::Ratio(4, 5).cycle(1), CropAspect::Ratio(9, 16));
+
+        // 16:9 out of a 4000x3000 frame, and out of its left half
+        let mut geometry = Geometry { aspect: CropAspect::Ratio(16, 9), ..Geometry::default() };
+        assert_eq!(geometry.crop_pixels(4000, 3000), (0, 375, 4000, 2250));
+        geometry.crop = CropRect { x: 0.0, y: 0.0, width: 0.5, height: 1.0 };
+        assert_eq!(geometry.crop_pixels(4000, 3000), (0, 938, 2000, 1125));
+        assert_eq!(geometry.summary(), "crop 16:9");
+
+        // Camera rolled 5° clockwise: its right side reads part of gravity
+        let roll = 5f64.to_radians();
+        let metadata = GprMetadata {
+            gravity: Some([9.8 * roll.cos(), -9.8 * roll.sin(), 0.0]),
+            ..GprMetadata::default()
+        };
+        assert!((metadata.roll_degrees().unwrap() - 5.0).abs() < 1e-9);
+        let auto = Geometry { auto_level: true, ..Geometry::default() };
+        assert!((auto.levelled(&metadata, 1).straighten - 5.0).abs() < 1e-4);
+        // A mirroring orientation baked into the pixels, or a flip, reverses
+        // the tilt; both together cancel out
+        assert!((auto.levelled(&metadata, 2).straighten + 5.0).abs() < 1e-4);
+        assert!((auto.levelled(&metadata, 6).straighten - 5.0).abs() < 1e-4);
+        let flipped = Geometry { flip_horizontal: true, ..auto };
+        assert!((flipped.levelled(&metadata, 1).straighten + 5.0).abs() < 1e-4);
+        assert!((flipped.levelled(&metadata, 7).straighten - 5.0).abs() < 1e-4);
+        // Upside down, the same roll is still 5° from level
+        let upside_down = GprMetadata { gravity: Some([-9.8 * roll.cos(), 9.8 * roll.sin(), 0.0]), ..metadata };
+        assert!((upside_down.roll_degrees().unwrap() - 5.0).abs() < 1e-9);
+        // No gravity, or the camera looking at the ground: left alone
+        let down = GprMetadata { gravity: Some([0.5, 0.0, 9.8]), ..GprMetadata::default() };
+        assert_eq!(down.roll_degrees(), None);
+        assert!(auto.levelled(&GprMetadata::default(), 1).is_identity());
+    }
+}
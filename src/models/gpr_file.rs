@@ -1,6 +1,13 @@
 use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 
+use super::camera_profile::CfaPattern;
+use super::orientation;
+
+/// Share of gravity that must lie across the frame (the camera pitched up
+/// or down by at most 60°) for the roll angle to be used
+const MIN_ROLL_GRAVITY: f64 = 0.5;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GprFile {
     pub path: PathBuf,
@@ -9,11 +16,19 @@ pub struct GprFile {
     pub metadata: Option<GprMetadata>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GprMetadata {
     pub camera_model: String,
+    /// Output size: the DNG default crop, or the sensor size when absent
     pub width: u32,
     pub height: u32,
+    /// Full raw image as stored in the file
+    pub sensor_width: u32,
+    pub sensor_height: u32,
+    /// Part of the sensor holding valid image data (DNG ActiveArea)
+    pub active_area: Option<ImageArea>,
+    /// Bayer layout from the DNG CFAPattern tag
+    pub cfa_pattern: Option<CfaPattern>,
     pub iso: Option<u32>,
     pub exposure_time: Option<String>,
     pub f_number: Option<String>,
@@ -21,6 +36,159 @@ pub struct GprMetadata {
     pub date_taken: Option<String>,
     pub gps_latitude: Option<f64>,
     pub gps_longitude: Option<f64>,
+    pub serial_number: Option<String>,
+    pub owner_name: Option<String>,
+    /// EXIF Orientation of the main image
+    pub orientation: Option<u16>,
+    /// EXIF thumbnail (IFD1), when the file has one
+    pub thumbnail: Option<ThumbnailInfo>,
+    /// Mean GPMF accelerometer reading (up/down, right/left, forward/back)
+    pub gravity: Option<[f64; 3]>,
+    /// Which parsers the values came from
+    pub source: MetadataSource,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThumbnailInfo {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub orientation: Option<u16>,
+    /// Size of the embedded JPEG, in bytes
+    pub length: Option<u32>,
+}
+
+/// Rectangle within the sensor, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageArea {
+    pub top: u32,
+    pub left: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MetadataSource {
+    /// EXIF read by kamadak-exif, reconciled with the GPR SDK parser
+    #[default]
+    ExifAndSdk,
+    /// EXIF only; the GPR SDK could not parse the file
+    Exif,
+    /// GPR SDK parser only; the EXIF could not be read
+    Sdk,
+}
+
+impl MetadataSource {
+    pub fn as_str(&self) -> &str {
+        match self {
+            MetadataSource::ExifAndSdk => "EXIF + GPR SDK",
+            MetadataSource::Exif => "EXIF only",
+            MetadataSource::Sdk => "GPR SDK only",
+        }
+    }
+}
+
+impl GprMetadata {
+    /// Whether GPMF shows the camera upside down: gravity mostly along the
+    /// negative up/down axis
+    pub fn is_upside_down(&self) -> bool {
+        self.gravity.is_some_and(|g| {
+            let magnitude = (g[0] * g[0] + g[1] * g[1] + g[2] * g[2]).sqrt();
+            magnitude > 0.0 && g[0] < -0.7 * magnitude
+        })
+    }
+
+    /// Sideways tilt of the camera at capture from GPMF gravity, in degrees
+    /// clockwise as seen from behind. It is measured from the nearest
+    /// quarter turn, so it is also the tilt left once a rotating orientation
+    /// is applied; a mirroring one (2, 4, 5, 7) turns it anticlockwise.
+    /// `None` without gravity, or when the camera points so far up or down
+    /// that roll means little.
+    pub fn roll_degrees(&self) -> Option<f64> {
+        let [up, right, forward] = self.gravity?;
+        let sideways = up.hypot(right);
+        if sideways < MIN_ROLL_GRAVITY * sideways.hypot(forward) {
+            return None;
+        }
+        let roll = (-right).atan2(up).to_degrees();
+        Some(roll - 90.0 * (roll / 90.0).round())
+    }
+
+    pub fn roll_display(&self) -> String {
+        match self.roll_degrees() {
+            Some(roll) if roll.abs() < 0.05 => "Level".to_string(),
+            Some(roll) => format!(
+                "{:.1}° {}",
+                roll.abs(),
+                if roll > 0.0 { "clockwise" } else { "counter-clockwise" }
+            ),
+            None if self.gravity.is_some() => "Unknown (camera facing up or down)".to_string(),
+            None => "Unknown (no GPMF gravity)".to_string(),
+        }
+    }
+
+    /// Orientation to render with: the main image's EXIF Orientation, else
+    /// the thumbnail's, turned a further 180° when GPMF shows the camera was
+    /// mounted upside down
+    pub fn effective_orientation(&self) -> u16 {
+        let tagged = self
+            .orientation
+            .filter(|o| orientation::is_valid(*o))
+            .or_else(|| {
+                self.thumbnail
+                    .and_then(|t| t.orientation)
+                    .filter(|o| orientation::is_valid(*o))
+            })
+            .unwrap_or(1);
+
+        if self.is_upside_down() {
+            orientation::rotated_180(tagged)
+        } else {
+            tagged
+        }
+    }
+
+    pub fn orientation_display(&self) -> String {
+        let effective = self.effective_orientation();
+        let mut display = format!("{} ({})", orientation::orientation_name(effective), effective);
+        if self.is_upside_down() {
+            display.push_str(", mounted upside down");
+        }
+        display
+    }
+
+    pub fn thumbnail_display(&self) -> String {
+        match self.thumbnail {
+            Some(ThumbnailInfo {
+                width: Some(width),
+                height: Some(height),
+                ..
+            }) => format!("{}x{}", width, height),
+            Some(ThumbnailInfo {
+                length: Some(length),
+                ..
+            }) => format!("JPEG, {} bytes", length),
+            Some(_) => "Present".to_string(),
+            None => "None".to_string(),
+        }
+    }
+
+    pub fn sensor_size_display(&self) -> String {
+        format!("{}x{}", self.sensor_width, self.sensor_height)
+    }
+
+    pub fn active_area_display(&self) -> String {
+        match self.active_area {
+            Some(area) => format!(
+                "{}x{} at ({}, {})",
+                area.width, area.height, area.left, area.top
+            ),
+            None => "Not recorded".to_string(),
+        }
+    }
+
+    pub fn output_size_display(&self) -> String {
+        format!("{}x{}", self.width, self.height)
+    }
 }
 
 impl GprFile {
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+/// Radial distortion: a corrected point at radius `r` (1.0 is half the
+/// image diagonal) is taken from radius `r * (1 + k1 r^2 + k2 r^4)` in the
+/// source. Positive coefficients straighten barrel distortion.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Distortion {
+    pub k1: f32,
+    pub k2: f32,
+}
+
+impl Distortion {
+    /// Source radius for a corrected radius
+    pub fn source_radius(&self, r: f32) -> f32 {
+        let r2 = r * r;
+        r * (1.0 + self.k1 * r2 + self.k2 * r2 * r2)
+    }
+}
+
+/// Which distortion to remove
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum LensCorrection {
+    #[default]
+    Off,
+    /// Coefficients measured from a shot of straight lines. GPR is only
+    /// written in the Wide lens, but its distortion varies by camera.
+    Custom(Distortion),
+}
+
+impl LensCorrection {
+    pub fn display(&self) -> String {
+        match self {
+            LensCorrection::Off => "Off".to_string(),
+            LensCorrection::Custom(d) => format!("Custom (k1 {}, k2 {})", d.k1, d.k2),
+        }
+    }
+
+    /// Step k1 by 0.05 from Off up to 2.0, keeping k2; Off when both
+    /// reach zero
+    pub fn cycle(&self, delta: i32) -> LensCorrection {
+        let current = match self {
+            LensCorrection::Off => Distortion { k1: 0.0, k2: 0.0 },
+            LensCorrection::Custom(distortion) => *distortion,
+        };
+        let steps = ((current.k1 * 20.0).round() as i32 + delta).clamp(0, 40);
+        let distortion = Distortion { k1: steps as f32 / 20.0, ..current };
+        if distortion.k1 == 0.0 && distortion.k2 == 0.0 {
+            LensCorrection::Off
+        } else {
+            LensCorrection::Custom(distortion)
+        }
+    }
+
+    /// Parse `off` or custom coefficients `K1[,K2]`
+    pub fn parse(value: &str) -> Option<LensCorrection> {
+        if value.trim().eq_ignore_ascii_case("off") {
+            return Some(LensCorrection::Off);
+        }
+        let mut coefficients = value.split(',').map(|v| v.trim().parse::<f32>());
+        let k1 = coefficients.next()?.ok()?;
+        let k2 = match coefficients.next() {
+            Some(k2) => k2.ok()?,
+            None => 0.0,
+        };
+        (coefficients.next().is_none() && k1.abs() <= 2.0 && k2.abs() <= 2.0)
+            .then_some(LensCorrection::Custom(Distortion { k1, k2 }))
+    }
+}
+
+/// What happens to the parts of the frame the correction leaves empty
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EdgeMode {
+    /// Enlarge until no empty area is left
+    #[default]
+    Crop,
+    /// Keep the whole picture and fill the edges with black
+    Fill,
+}
+
+impl EdgeMode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            EdgeMode::Crop => "Crop",
+            EdgeMode::Fill => "Fill",
+        }
+    }
+
+    pub fn toggle(&self) -> EdgeMode {
+        match self {
+            EdgeMode::Crop => EdgeMode::Fill,
+            EdgeMode::Fill => EdgeMode::Crop,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<EdgeMode> {
+        [EdgeMode::Crop, EdgeMode::Fill]
+            .into_iter()
+            .find(|mode| mode.as_str().eq_ignore_ascii_case(value.trim()))
+    }
+}
+
+/// Lens correction applied to the decoded image
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LensSettings {
+    pub correction: LensCorrection,
+    pub edges: EdgeMode,
+}
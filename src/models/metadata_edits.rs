@@ -0,0 +1,118 @@
+use super::gpr_file::GprMetadata;
+use serde::{Deserialize, Serialize};
+
+/// Descriptive metadata added to outputs on top of what the camera recorded
+///
+/// Edits only change what is written to converted files and sidecars; the
+/// source GPR is never modified.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetadataEdits {
+    /// Artist / creator name
+    pub creator: Option<String>,
+    pub copyright: Option<String>,
+    /// Image description
+    pub caption: Option<String>,
+    pub keywords: Vec<String>,
+    /// Star rating, 0-5
+    pub rating: Option<u8>,
+    /// Latitude and longitude replacing the camera's position
+    pub gps_position: Option<(f64, f64)>,
+}
+
+impl MetadataEdits {
+    /// Split a comma separated keyword list, dropping empty entries
+    pub fn parse_keywords(value: &str) -> Vec<String> {
+        value
+            .split(',')
+            .map(|k| k.trim().to_string())
+            .filter(|k| !k.is_empty())
+            .collect()
+    }
+
+    /// Parse `lat,lon` in decimal degrees
+    pub fn parse_position(value: &str) -> Option<(f64, f64)> {
+        let (lat, lon) = value.split_once(',')?;
+        let lat: f64 = lat.trim().parse().ok()?;
+        let lon: f64 = lon.trim().parse().ok()?;
+        (lat.abs() <= 90.0 && lon.abs() <= 180.0).then_some((lat, lon))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == MetadataEdits::default()
+    }
+
+    /// These edits with the fields set in `own` (a single file's edits)
+    /// taking their place
+    pub fn merged(&self, own: &MetadataEdits) -> MetadataEdits {
+        MetadataEdits {
+            creator: own.creator.clone().or_else(|| self.creator.clone()),
+            copyright: own.copyright.clone().or_else(|| self.copyright.clone()),
+            caption: own.caption.clone().or_else(|| self.caption.clone()),
+            keywords: if own.keywords.is_empty() { self.keywords.clone() } else { own.keywords.clone() },
+            rating: own.rating.or(self.rating),
+            gps_position: own.gps_position.or(self.gps_position),
+        }
+    }
+
+    /// Override the output metadata; privacy rules are applied afterwards
+    pub fn apply(&self, metadata: &mut GprMetadata) {
+        if let Some((lat, lon)) = self.gps_position {
+            metadata.gps_latitude = Some(lat);
+            metadata.gps_longitude = Some(lon);
+        }
+    }
+
+    pub fn keywords_display(&self) -> String {
+        if self.keywords.is_empty() {
+            "None".to_string()
+        } else {
+            self.keywords.join(", ")
+        }
+    }
+
+    pub fn rating_display(&self) -> String {
+        match self.rating {
+            Some(stars) => {
+                let stars = stars.min(5) as usize;
+                format!("{}{}", "★".repeat(stars), "☆".repeat(5 - stars))
+            }
+            None => "None".to_string(),
+        }
+    }
+
+    pub fn gps_display(&self) -> String {
+        match self.gps_position {
+            Some((lat, lon)) => format!("{:.6}, {:.6}", lat, lon),
+            None => "From camera".to_string(),
+        }
+    }
+
+    /// Which fields are set, for one-line summaries
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if self.creator.is_some() {
+            parts.push("artist".to_string());
+        }
+        if self.copyright.is_some() {
+            parts.push("copyright".to_string());
+        }
+        if self.caption.is_some() {
+            parts.push("caption".to_string());
+        }
+        if !self.keywords.is_empty() {
+            parts.push(format!("{} keywords", self.keywords.len()));
+        }
+        if self.rating.is_some() {
+            parts.push("rating".to_string());
+        }
+        if self.gps_position.is_some() {
+            parts.push("GPS".to_string());
+        }
+
+        if parts.is_empty() {
+            "None".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
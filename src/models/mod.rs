@@ -1,5 +1,33 @@
 pub mod gpr_file;
+pub mod camera_profile;
+pub mod color_space;
 pub mod conversion_config;
+pub mod develop;
+pub mod filter;
+pub mod fusion;
+pub mod geometry;
+pub mod lens;
+pub mod metadata_edits;
+pub mod orientation;
+pub mod privacy;
+pub mod resize;
+pub mod shoot_stats;
+pub mod time_shift;
 
 pub use gpr_file::GprFile;
+pub use camera_profile::CameraProfile;
+pub use color_space::ColorSpace;
 pub use conversion_config::{ConversionConfig, OutputFormat};
+pub use develop::{
+    DemosaicMethod, DenoiseMethod, DenoiseSettings, DevelopSettings, ToneCurve, ToneSettings,
+    WhiteBalance,
+};
+pub use filter::Filter;
+pub use geometry::{CropAspect, CropRect, Geometry, Rotation};
+pub use lens::{EdgeMode, LensCorrection, LensSettings};
+pub use metadata_edits::MetadataEdits;
+pub use orientation::OrientationMode;
+pub use privacy::{GpsPolicy, PrivacyPolicy};
+pub use resize::{ResizeFilter, ResizeMode, ResizeSettings, Sharpening};
+pub use shoot_stats::ShootStats;
+pub use time_shift::TimeShift;
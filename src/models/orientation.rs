@@ -0,0 +1,93 @@
+use super::gpr_file::GprMetadata;
+use serde::{Deserialize, Serialize};
+
+/// How the source orientation reaches the output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrientationMode {
+    /// Leave the pixels as decoded and write Orientation = 1. GoPro cameras
+    /// tag their GPR files Orientation = 2 (mirrored) although the SDK
+    /// decodes them the right way round, so the tag is not trusted by default.
+    #[default]
+    Ignore,
+    /// Rotate/flip the pixels and write Orientation = 1
+    Bake,
+    /// Leave the pixels as decoded and write the source Orientation tag
+    Tag,
+}
+
+impl OrientationMode {
+    pub fn all() -> [OrientationMode; 3] {
+        [OrientationMode::Ignore, OrientationMode::Bake, OrientationMode::Tag]
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            OrientationMode::Ignore => "As decoded",
+            OrientationMode::Bake => "Rotate pixels",
+            OrientationMode::Tag => "Tag only",
+        }
+    }
+
+    pub fn cycle(&self, delta: i32) -> OrientationMode {
+        let all = OrientationMode::all();
+        let current = all.iter().position(|m| m == self).unwrap_or(0) as i32;
+        all[(current + delta).rem_euclid(all.len() as i32) as usize]
+    }
+
+    /// Mode to use when the file also has a crop or rotation, which is
+    /// defined on the image as shown and cannot be expressed as a tag
+    pub fn with_geometry(&self) -> OrientationMode {
+        match self {
+            OrientationMode::Tag => OrientationMode::Bake,
+            other => *other,
+        }
+    }
+
+    /// Orientation to rotate and flip the decoded pixels by
+    pub fn baked_orientation(&self, metadata: &GprMetadata) -> u16 {
+        match self {
+            OrientationMode::Bake => metadata.effective_orientation(),
+            OrientationMode::Ignore | OrientationMode::Tag => 1,
+        }
+    }
+}
+
+/// Whether `value` is a valid EXIF Orientation (1-8)
+pub fn is_valid(value: u16) -> bool {
+    (1..=8).contains(&value)
+}
+
+/// Whether an EXIF Orientation mirrors the image (2, 4, 5 and 7), which
+/// reverses the direction of any tilt in it
+pub fn is_mirrored(value: u16) -> bool {
+    matches!(value, 2 | 4 | 5 | 7)
+}
+
+pub fn orientation_name(value: u16) -> &'static str {
+    match value {
+        1 => "Normal",
+        2 => "Mirrored horizontally",
+        3 => "Rotated 180°",
+        4 => "Mirrored vertically",
+        5 => "Mirrored horizontally, rotated 270° CW",
+        6 => "Rotated 90° CW",
+        7 => "Mirrored horizontally, rotated 90° CW",
+        8 => "Rotated 270° CW",
+        _ => "Unknown",
+    }
+}
+
+/// The same orientation with an extra 180° turn, for cameras mounted upside down
+pub fn rotated_180(value: u16) -> u16 {
+    match value {
+        1 => 3,
+        2 => 4,
+        3 => 1,
+        4 => 2,
+        5 => 7,
+        6 => 8,
+        7 => 5,
+        8 => 6,
+        other => other,
+    }
+}
@@ -0,0 +1,145 @@
+use super::gpr_file::GprMetadata;
+use serde::{Deserialize, Serialize};
+
+/// What happens to GPS coordinates when metadata leaves the tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GpsPolicy {
+    Keep,
+    Drop,
+    /// Round latitude/longitude to this many decimal places
+    /// (2 decimals is roughly 1 km, 1 decimal roughly 11 km)
+    Round(u8),
+}
+
+impl GpsPolicy {
+    pub fn as_str(&self) -> &'static str {
+        const ROUND: [&str; 9] = [
+            "Round to 0 decimals",
+            "Round to 1 decimal",
+            "Round to 2 decimals",
+            "Round to 3 decimals",
+            "Round to 4 decimals",
+            "Round to 5 decimals",
+            "Round to 6 decimals",
+            "Round to 7 decimals",
+            "Round to 8 decimals",
+        ];
+        match self {
+            GpsPolicy::Keep => "Keep",
+            GpsPolicy::Drop => "Drop",
+            GpsPolicy::Round(decimals) => ROUND.get(*decimals as usize).copied().unwrap_or("Round"),
+        }
+    }
+
+    /// Next policy in the settings screen cycle: Keep, Round(4..=0), Drop
+    pub fn cycle(&self, delta: i32) -> GpsPolicy {
+        let order: Vec<GpsPolicy> = std::iter::once(GpsPolicy::Keep)
+            .chain((0..=4).rev().map(GpsPolicy::Round))
+            .chain(std::iter::once(GpsPolicy::Drop))
+            .collect();
+        let current = order.iter().position(|p| p == self).unwrap_or(0) as i32;
+        let next = (current + delta).rem_euclid(order.len() as i32) as usize;
+        order[next]
+    }
+}
+
+/// Location and identity scrubbing applied to every metadata export:
+/// output EXIF, sidecars, JSON reports and telemetry exports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivacyPolicy {
+    pub gps: GpsPolicy,
+    /// Remove camera serial numbers and owner names
+    pub drop_identifiers: bool,
+}
+
+impl Default for PrivacyPolicy {
+    fn default() -> Self {
+        Self {
+            gps: GpsPolicy::Keep,
+            drop_identifiers: false,
+        }
+    }
+}
+
+impl PrivacyPolicy {
+    /// Apply the policy to a single coordinate pair
+    pub fn apply_position(&self, latitude: f64, longitude: f64) -> Option<(f64, f64)> {
+        match self.gps {
+            GpsPolicy::Keep => Some((latitude, longitude)),
+            GpsPolicy::Drop => None,
+            GpsPolicy::Round(decimals) => {
+                let scale = 10f64.powi(decimals as i32);
+                Some((
+                    (latitude * scale).round() / scale,
+                    (longitude * scale).round() / scale,
+                ))
+            }
+        }
+    }
+
+    /// Whether altitude and other fine-grained location detail may be kept
+    pub fn keeps_exact_location(&self) -> bool {
+        self.gps == GpsPolicy::Keep
+    }
+
+    pub fn apply(&self, metadata: &mut GprMetadata) {
+        let position = match (metadata.gps_latitude, metadata.gps_longitude) {
+            (Some(lat), Some(lon)) => self.apply_position(lat, lon),
+            _ => None,
+        };
+        metadata.gps_latitude = position.map(|(lat, _)| lat);
+        metadata.gps_longitude = position.map(|(_, lon)| lon);
+
+        if self.drop_identifiers {
+            metadata.serial_number = None;
+            metadata.owner_name = None;
+        }
+    }
+
+    pub fn display(&self) -> String {
+        if self.drop_identifiers {
+            format!("GPS: {}, no serial/owner", self.gps.as_str())
+        } else {
+            format!("GPS: {}", self.gps.as_str())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_position() {
+        let round = PrivacyPolicy {
+            gps: GpsPolicy::Round(2),
+            drop_identifiers: false,
+        };
+        assert_eq!(round.apply_position(47.60621, -122.33207), Some((47.61, -122.33)));
+
+        let drop = PrivacyPolicy {
+            gps: GpsPolicy::Drop,
+            drop_identifiers: true,
+        };
+        assert_eq!(drop.apply_position(47.6, -122.3), None);
+        assert_eq!(PrivacyPolicy::default().apply_position(1.5, 2.5), Some((1.5, 2.5)));
+
+        let mut metadata = GprMetadata {
+            gps_latitude: Some(47.6),
+            gps_longitude: Some(-122.3),
+            serial_number: Some("C3221324567890".to_string()),
+            owner_name: Some("Jane".to_string()),
+            ..GprMetadata::default()
+        };
+        drop.apply(&mut metadata);
+        assert_eq!((metadata.gps_latitude, metadata.gps_longitude), (None, None));
+        assert_eq!((metadata.serial_number, metadata.owner_name), (None, None));
+    }
+
+    #[test]
+    fn test_gps_policy_cycle() {
+        assert_eq!(GpsPolicy::Keep.cycle(1), GpsPolicy::Round(4));
+        assert_eq!(GpsPolicy::Keep.cycle(-1), GpsPolicy::Drop);
+        assert_eq!(GpsPolicy::Drop.cycle(1), GpsPolicy::Keep);
+    }
+}
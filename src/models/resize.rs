@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+
+/// Output size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResizeMode {
+    #[default]
+    Off,
+    /// Shrink so the longer side is at most this long
+    LongEdge(u32),
+    /// Shrink to fit inside the box, keeping the aspect ratio
+    Fit { width: u32, height: u32 },
+    /// Scale to cover the box and crop the overflow from the centre
+    Fill { width: u32, height: u32 },
+    /// Stretch to exactly this size
+    Exact { width: u32, height: u32 },
+}
+
+impl ResizeMode {
+    /// Common deliverable sizes offered in the settings screen
+    pub const PRESETS: &'static [ResizeMode] = &[
+        ResizeMode::Off,
+        ResizeMode::LongEdge(1080),
+        ResizeMode::LongEdge(2048),
+        ResizeMode::LongEdge(3840),
+        ResizeMode::Fit { width: 1920, height: 1080 },
+        ResizeMode::Fit { width: 3840, height: 2160 },
+        ResizeMode::Fill { width: 1080, height: 1080 },
+        ResizeMode::Fill { width: 1080, height: 1350 },
+        ResizeMode::Exact { width: 1920, height: 1080 },
+    ];
+
+    pub fn display(&self) -> String {
+        match self {
+            ResizeMode::Off => "Off".to_string(),
+            ResizeMode::LongEdge(edge) => format!("{} long edge", edge),
+            ResizeMode::Fit { width, height } => format!("Fit {}x{}", width, height),
+            ResizeMode::Fill { width, height } => format!("Fill {}x{}", width, height),
+            ResizeMode::Exact { width, height } => format!("Exact {}x{}", width, height),
+        }
+    }
+
+    /// Step through the presets; a custom size steps back into them
+    pub fn cycle(&self, delta: i32) -> ResizeMode {
+        let presets = ResizeMode::PRESETS;
+        let current = presets.iter().position(|p| p == self).unwrap_or(0) as i32;
+        presets[(current + delta).rem_euclid(presets.len() as i32) as usize]
+    }
+
+    /// Parse `off`, a long edge such as `2048`, or `fit:`, `fill:` or
+    /// `exact:` followed by `WIDTHxHEIGHT` (a bare size fits)
+    pub fn parse(value: &str) -> Option<ResizeMode> {
+        let value = value.trim().to_ascii_lowercase();
+        if value == "off" {
+            return Some(ResizeMode::Off);
+        }
+        if let Ok(edge) = value.parse::<u32>() {
+            return (edge > 0).then_some(ResizeMode::LongEdge(edge));
+        }
+        let (mode, size) = value.split_once(':').unwrap_or(("fit", value.as_str()));
+        let (width, height) = size.split_once('x')?;
+        let (width, height) = (width.trim().parse::<u32>().ok()?, height.trim().parse::<u32>().ok()?);
+        if width == 0 || height == 0 {
+            return None;
+        }
+        match mode.trim() {
+            "fit" => Some(ResizeMode::Fit { width, height }),
+            "fill" => Some(ResizeMode::Fill { width, height }),
+            "exact" => Some(ResizeMode::Exact { width, height }),
+            _ => None,
+        }
+    }
+
+    /// Added to output file names so differently sized deliverables of the
+    /// same source do not overwrite each other
+    pub fn suffix(&self) -> String {
+        match self {
+            ResizeMode::Off => String::new(),
+            ResizeMode::LongEdge(edge) => format!("_{}", edge),
+            ResizeMode::Fit { width, height } => format!("_fit{}x{}", width, height),
+            ResizeMode::Fill { width, height } => format!("_fill{}x{}", width, height),
+            ResizeMode::Exact { width, height } => format!("_{}x{}", width, height),
+        }
+    }
+
+    /// Size of the result for an image of `width` x `height`, and the size
+    /// it is scaled to before any centre crop. Long edge and fit never
+    /// enlarge. `None` leaves the image as it is.
+    pub fn target(&self, width: u32, height: u32) -> Option<((u32, u32), (u32, u32))> {
+        let scaled = |scale: f64| {
+            (
+                ((width as f64 * scale).round() as u32).max(1),
+                ((height as f64 * scale).round() as u32).max(1),
+            )
+        };
+        let (w, h) = (width as f64, height as f64);
+        let result = match *self {
+            ResizeMode::Off => return None,
+            ResizeMode::LongEdge(edge) => {
+                let size = scaled((edge as f64 / w.max(h)).min(1.0));
+                (size, size)
+            }
+            ResizeMode::Fit { width: bw, height: bh } => {
+                let size = scaled((bw as f64 / w).min(bh as f64 / h).min(1.0));
+                (size, size)
+            }
+            ResizeMode::Fill { width: bw, height: bh } => {
+                let scale = (bw as f64 / w).max(bh as f64 / h);
+                let (sw, sh) = scaled(scale);
+                ((bw, bh), (sw.max(bw), sh.max(bh)))
+            }
+            ResizeMode::Exact { width: bw, height: bh } => ((bw, bh), (bw, bh)),
+        };
+        (result != ((width, height), (width, height))).then_some(result)
+    }
+
+    /// The same request for an image shown rotated by 90 degrees
+    pub fn transposed(&self) -> ResizeMode {
+        match *self {
+            ResizeMode::Fit { width, height } => ResizeMode::Fit { width: height, height: width },
+            ResizeMode::Fill { width, height } => ResizeMode::Fill { width: height, height: width },
+            ResizeMode::Exact { width, height } => ResizeMode::Exact { width: height, height: width },
+            other => other,
+        }
+    }
+}
+
+/// Resampling filter used when resizing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResizeFilter {
+    /// Lanczos-3: sharpest, may ring slightly on hard edges
+    #[default]
+    Lanczos,
+    /// Mitchell-Netravali (B = C = 1/3): softer, no ringing
+    Mitchell,
+}
+
+impl ResizeFilter {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ResizeFilter::Lanczos => "Lanczos",
+            ResizeFilter::Mitchell => "Mitchell",
+        }
+    }
+
+    pub fn toggle(&self) -> ResizeFilter {
+        match self {
+            ResizeFilter::Lanczos => ResizeFilter::Mitchell,
+            ResizeFilter::Mitchell => ResizeFilter::Lanczos,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<ResizeFilter> {
+        [ResizeFilter::Lanczos, ResizeFilter::Mitchell]
+            .into_iter()
+            .find(|filter| filter.as_str().eq_ignore_ascii_case(value.trim()))
+    }
+}
+
+/// Resize stage of the output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResizeSettings {
+    pub mode: ResizeMode,
+    pub filter: ResizeFilter,
+}
+
+/// Unsharp mask applied after resizing
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Sharpening {
+    /// Strength of the mask, 1.0 being 100%; 0 turns sharpening off
+    pub amount: f32,
+    /// Blur radius (Gaussian sigma) in output pixels
+    pub radius: f32,
+    /// Smallest brightness difference, in 0-255 levels, that is sharpened,
+    /// so flat areas and noise are left alone
+    pub threshold: u8,
+}
+
+impl Sharpening {
+    pub const MAX_AMOUNT: f32 = 5.0;
+    pub const MIN_RADIUS: f32 = 0.2;
+    pub const MAX_RADIUS: f32 = 5.0;
+
+    pub const OFF: Sharpening = Sharpening {
+        amount: 0.0,
+        radius: 1.0,
+        threshold: 0,
+    };
+
+    pub fn is_off(&self) -> bool {
+        self.amount <= 0.0
+    }
+
+    pub fn amount_display(&self) -> String {
+        if self.is_off() {
+            "Off".to_string()
+        } else {
+            format!("{:.0}%", self.amount * 100.0)
+        }
+    }
+}
+
+impl Default for Sharpening {
+    fn default() -> Self {
+        Self::OFF
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resize_mode() {
+        assert_eq!(ResizeMode::parse("2048"), Some(ResizeMode::LongEdge(2048)));
+        assert_eq!(ResizeMode::parse("1920x1080"), Some(ResizeMode::Fit { width: 1920, height: 1080 }));
+        assert_eq!(ResizeMode::parse("fill:1080x1350"), Some(ResizeMode::Fill { width: 1080, height: 1350 }));
+        assert_eq!(ResizeMode::parse("crop:10x10"), None);
+
+        // 4000x3000 into the common deliverables
+        let size = |mode: ResizeMode| mode.target(4000, 3000);
+        assert_eq!(size(ResizeMode::LongEdge(2048)), Some(((2048, 1536), (2048, 1536))));
+        assert_eq!(size(ResizeMode::Fit { width: 1920, height: 1080 }), Some(((1440, 1080), (1440, 1080))));
+        assert_eq!(size(ResizeMode::Fill { width: 1080, height: 1080 }), Some(((1080, 1080), (1440, 1080))));
+        // Never enlarged to fit
+        assert_eq!(size(ResizeMode::LongEdge(8000)), None);
+    }
+}
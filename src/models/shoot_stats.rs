@@ -0,0 +1,218 @@
+use super::gpr_file::GprFile;
+use super::PrivacyPolicy;
+use crate::utils::datetime::ExifDateTime;
+use crate::utils::file_utils::format_file_size;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// One bar of a histogram
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramBin {
+    pub label: String,
+    pub count: usize,
+}
+
+/// Corners of the area covered by geotagged photos
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct GpsBounds {
+    pub min_latitude: f64,
+    pub min_longitude: f64,
+    pub max_latitude: f64,
+    pub max_longitude: f64,
+}
+
+/// Aggregate figures for a set of GPR files
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ShootStats {
+    pub total_files: usize,
+    pub total_bytes: u64,
+    /// Files whose metadata could not be read
+    pub unreadable: usize,
+    pub cameras: BTreeMap<String, usize>,
+    pub iso: Vec<HistogramBin>,
+    pub shutter_speed: Vec<HistogramBin>,
+    pub first_capture: Option<String>,
+    pub last_capture: Option<String>,
+    /// Photos per capture date (YYYY-MM-DD)
+    pub photos_per_day: BTreeMap<String, usize>,
+    pub geotagged: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gps_bounds: Option<GpsBounds>,
+}
+
+/// ISO in whole stops from base ISO 100
+const ISO_BINS: &[&str] = &["100", "200", "400", "800", "1600", "3200+"];
+
+/// Shutter speeds in whole stops, slowest first
+const SHUTTER_BINS: &[&str] = &[
+    "1/30-", "1/60", "1/125", "1/250", "1/500", "1/1000", "1/2000", "1/4000+",
+];
+
+impl ShootStats {
+    /// Aggregate the metadata of `files`; files without metadata count
+    /// towards totals and `unreadable`
+    pub fn collect(files: &[GprFile]) -> ShootStats {
+        let mut stats = ShootStats {
+            iso: empty_bins(ISO_BINS),
+            shutter_speed: empty_bins(SHUTTER_BINS),
+            ..Default::default()
+        };
+        let mut first: Option<ExifDateTime> = None;
+        let mut last: Option<ExifDateTime> = None;
+
+        for file in files {
+            stats.total_files += 1;
+            stats.total_bytes += file.size;
+
+            let Some(ref metadata) = file.metadata else {
+                stats.unreadable += 1;
+                continue;
+            };
+
+            *stats.cameras.entry(metadata.camera_model.clone()).or_insert(0) += 1;
+
+            if let Some(iso) = metadata.iso.filter(|iso| *iso > 0) {
+                let stop = (iso as f64 / 100.0).log2().round().max(0.0) as usize;
+                stats.iso[stop.min(ISO_BINS.len() - 1)].count += 1;
+            }
+
+            if let Some(seconds) = metadata.exposure_time.as_deref().and_then(parse_exposure) {
+                // 1/30 s and slower land in the first bin
+                let stop = (1.0 / (seconds * 30.0)).log2().round().max(0.0) as usize;
+                stats.shutter_speed[stop.min(SHUTTER_BINS.len() - 1)].count += 1;
+            }
+
+            if let Some(taken) = metadata.date_taken.as_deref().and_then(ExifDateTime::parse) {
+                let day = format!("{:04}-{:02}-{:02}", taken.year, taken.month, taken.day);
+                *stats.photos_per_day.entry(day).or_insert(0) += 1;
+
+                let ts = taken.to_timestamp();
+                if first.is_none_or(|f| ts < f.to_timestamp()) {
+                    first = Some(taken);
+                }
+                if last.is_none_or(|l| ts > l.to_timestamp()) {
+                    last = Some(taken);
+                }
+            }
+
+            if let (Some(lat), Some(lon)) = (metadata.gps_latitude, metadata.gps_longitude) {
+                stats.geotagged += 1;
+                stats.gps_bounds = Some(match stats.gps_bounds {
+                    None => GpsBounds {
+                        min_latitude: lat,
+                        min_longitude: lon,
+                        max_latitude: lat,
+                        max_longitude: lon,
+                    },
+                    Some(b) => GpsBounds {
+                        min_latitude: b.min_latitude.min(lat),
+                        min_longitude: b.min_longitude.min(lon),
+                        max_latitude: b.max_latitude.max(lat),
+                        max_longitude: b.max_longitude.max(lon),
+                    },
+                });
+            }
+        }
+
+        stats.first_capture = first.map(|d| d.to_string());
+        stats.last_capture = last.map(|d| d.to_string());
+        stats
+    }
+
+    /// Coarsen or remove the GPS bounding box before it is shown or saved
+    pub fn apply_privacy(&mut self, policy: &PrivacyPolicy) {
+        self.gps_bounds = self.gps_bounds.and_then(|b| {
+            let (min_latitude, min_longitude) = policy.apply_position(b.min_latitude, b.min_longitude)?;
+            let (max_latitude, max_longitude) = policy.apply_position(b.max_latitude, b.max_longitude)?;
+            Some(GpsBounds {
+                min_latitude,
+                min_longitude,
+                max_latitude,
+                max_longitude,
+            })
+        });
+    }
+
+    pub fn storage_display(&self) -> String {
+        format_file_size(self.total_bytes)
+    }
+
+    pub fn date_range_display(&self) -> String {
+        match (&self.first_capture, &self.last_capture) {
+            (Some(first), Some(last)) => format!("{} to {}", first, last),
+            _ => "Unknown".to_string(),
+        }
+    }
+
+    pub fn gps_bounds_display(&self) -> String {
+        match self.gps_bounds {
+            Some(b) => format!(
+                "{:.4}, {:.4} to {:.4}, {:.4}",
+                b.min_latitude, b.min_longitude, b.max_latitude, b.max_longitude
+            ),
+            None if self.geotagged > 0 => "Withheld".to_string(),
+            None => "No GPS".to_string(),
+        }
+    }
+}
+
+fn empty_bins(labels: &[&str]) -> Vec<HistogramBin> {
+    labels
+        .iter()
+        .map(|label| HistogramBin {
+            label: label.to_string(),
+            count: 0,
+        })
+        .collect()
+}
+
+/// Exposure time in seconds from a "1/250" or "0.5" display value
+pub fn parse_exposure(value: &str) -> Option<f64> {
+    let value = value.trim().trim_end_matches('s').trim();
+    let seconds = match value.split_once('/') {
+        Some((num, den)) => num.trim().parse::<f64>().ok()? / den.trim().parse::<f64>().ok()?,
+        None => value.parse::<f64>().ok()?,
+    };
+    (seconds > 0.0 && seconds.is_finite()).then_some(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::gpr_file::GprMetadata;
+    use std::path::PathBuf;
+
+    fn file(iso: u32, exposure: &str, date: &str) -> GprFile {
+        GprFile {
+            path: PathBuf::from("GOPR0001.GPR"),
+            filename: "GOPR0001.GPR".to_string(),
+            size: 1000,
+            metadata: Some(GprMetadata {
+                camera_model: "GoPro HERO7 Black".to_string(),
+                iso: Some(iso),
+                exposure_time: Some(exposure.to_string()),
+                date_taken: Some(date.to_string()),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn test_collect() {
+        let stats = ShootStats::collect(&[
+            file(100, "1/455", "2024:06:02 08:00:00"),
+            file(252, "1/240", "2024:06:01 09:30:00"),
+            file(6400, "1/30", "2024:06:01 18:00:00"),
+        ]);
+
+        assert_eq!(stats.total_bytes, 3000);
+        assert_eq!(stats.cameras["GoPro HERO7 Black"], 3);
+        let iso: Vec<usize> = stats.iso.iter().map(|b| b.count).collect();
+        assert_eq!(iso, vec![1, 1, 0, 0, 0, 1]);
+        assert_eq!(stats.shutter_speed[4].count, 1); // 1/455 is closest to 1/500
+        assert_eq!(stats.shutter_speed[0].count, 1);
+        assert_eq!(stats.photos_per_day["2024-06-01"], 2);
+        assert_eq!(stats.first_capture.as_deref(), Some("2024:06:01 09:30:00"));
+        assert_eq!(stats.last_capture.as_deref(), Some("2024:06:02 08:00:00"));
+    }
+}
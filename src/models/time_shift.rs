@@ -0,0 +1,50 @@
+use crate::utils::datetime::{format_offset, format_zone_offset, ExifDateTime};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Correction applied to the camera clock when writing dates to outputs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeShift {
+    /// Seconds added to the camera's `DateTimeOriginal`
+    pub offset_seconds: i64,
+    /// Zone offset (seconds east of UTC) written as `OffsetTimeOriginal`
+    pub utc_offset_seconds: Option<i64>,
+}
+
+impl TimeShift {
+    /// Derive the shift from a reference photo whose real capture time is known
+    pub fn from_reference(camera_time: &str, true_time: &str) -> Result<Self> {
+        let camera = ExifDateTime::parse(camera_time)
+            .ok_or_else(|| anyhow!("Invalid camera time: {}", camera_time))?;
+        let actual = ExifDateTime::parse(true_time)
+            .ok_or_else(|| anyhow!("Invalid true time: {}", true_time))?;
+
+        Ok(Self {
+            offset_seconds: actual.to_timestamp() - camera.to_timestamp(),
+            utc_offset_seconds: None,
+        })
+    }
+
+    /// Shift an EXIF date string, returning `None` if it cannot be parsed
+    pub fn apply(&self, date: &str) -> Option<ExifDateTime> {
+        ExifDateTime::parse(date).map(|dt| dt.shifted(self.offset_seconds))
+    }
+
+    /// Value for the `OffsetTimeOriginal` tag, which must be exactly `+HH:MM`
+    pub fn offset_time_original(&self) -> Option<String> {
+        self.utc_offset_seconds.map(format_zone_offset)
+    }
+
+    pub fn offset_display(&self) -> String {
+        if self.offset_seconds == 0 {
+            "None".to_string()
+        } else {
+            format_offset(self.offset_seconds)
+        }
+    }
+
+    pub fn utc_offset_display(&self) -> String {
+        self.offset_time_original()
+            .unwrap_or_else(|| "Not written".to_string())
+    }
+}
@@ -0,0 +1,182 @@
+use super::track::Track;
+use crate::gpr;
+use crate::models::{GprFile, PrivacyPolicy, TimeShift};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// How photo capture times are matched against a track
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeotagOptions {
+    /// Largest distance in time, in seconds, between a photo and the fixes used for it
+    pub max_gap_seconds: i64,
+    /// Seconds added to the clock-corrected capture time to get UTC.
+    /// Defaults to the negated UTC offset from the time shift, if any.
+    pub time_offset_seconds: Option<i64>,
+    /// Replace coordinates the camera already recorded
+    pub overwrite: bool,
+}
+
+impl Default for GeotagOptions {
+    fn default() -> Self {
+        Self {
+            max_gap_seconds: 60,
+            time_offset_seconds: None,
+            overwrite: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum GeotagOutcome {
+    /// Coordinates are `None` when withheld by the privacy policy
+    Matched {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        latitude: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        longitude: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        elevation: Option<f64>,
+    },
+    AlreadyTagged,
+    NoMetadata,
+    NoCaptureTime,
+    OutsideTrack,
+}
+
+impl GeotagOutcome {
+    pub fn describe(&self) -> String {
+        match self {
+            GeotagOutcome::Matched {
+                latitude: Some(latitude),
+                longitude: Some(longitude),
+                ..
+            } => format!("{:.6}, {:.6}", latitude, longitude),
+            GeotagOutcome::Matched { .. } => "matched (location withheld)".to_string(),
+            GeotagOutcome::AlreadyTagged => "already has GPS".to_string(),
+            GeotagOutcome::NoMetadata => "unreadable metadata".to_string(),
+            GeotagOutcome::NoCaptureTime => "no capture time".to_string(),
+            GeotagOutcome::OutsideTrack => "no track fix within max gap".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GeotagEntry {
+    pub path: PathBuf,
+    /// Capture time in UTC as used for matching
+    pub capture_time_utc: Option<String>,
+    #[serde(flatten)]
+    pub outcome: GeotagOutcome,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GeotagReport {
+    pub entries: Vec<GeotagEntry>,
+}
+
+impl GeotagReport {
+    pub fn matched(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| matches!(e.outcome, GeotagOutcome::Matched { .. }))
+            .count()
+    }
+
+    pub fn unmatched(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| !matches!(e.outcome, GeotagOutcome::Matched { .. } | GeotagOutcome::AlreadyTagged))
+            .count()
+    }
+
+    /// Coarsen or remove the reported coordinates before the report is shown or saved
+    pub fn apply_privacy(&mut self, policy: &PrivacyPolicy) {
+        for entry in &mut self.entries {
+            if let GeotagOutcome::Matched {
+                ref mut latitude,
+                ref mut longitude,
+                ref mut elevation,
+            } = entry.outcome
+            {
+                let position = latitude
+                    .zip(*longitude)
+                    .and_then(|(lat, lon)| policy.apply_position(lat, lon));
+                *latitude = position.map(|(lat, _)| lat);
+                *longitude = position.map(|(_, lon)| lon);
+                if !policy.keeps_exact_location() {
+                    *elevation = None;
+                }
+            }
+        }
+    }
+}
+
+/// Fill in GPS coordinates on each file's metadata from `track`
+///
+/// Metadata is loaded for files that do not have it yet. The coordinates only
+/// live on the in-memory [`GprFile`], so they end up in whatever is written
+/// from it (output EXIF, sidecars) without touching the source GPR.
+pub fn geotag_files(
+    files: &mut [GprFile],
+    track: &Track,
+    time_shift: &TimeShift,
+    options: &GeotagOptions,
+) -> GeotagReport {
+    let time_offset = options
+        .time_offset_seconds
+        .unwrap_or_else(|| -time_shift.utc_offset_seconds.unwrap_or(0));
+
+    let mut report = GeotagReport::default();
+
+    for file in files.iter_mut() {
+        if file.metadata.is_none() {
+            match gpr::read_metadata(&file.path) {
+                Ok(metadata) => file.metadata = Some(metadata),
+                Err(e) => log::warn!("Failed to read metadata for {}: {}", file.filename, e),
+            }
+        }
+
+        let mut capture_time_utc = None;
+        let outcome = match file.metadata {
+            None => GeotagOutcome::NoMetadata,
+            Some(ref metadata)
+                if !options.overwrite
+                    && metadata.gps_latitude.is_some()
+                    && metadata.gps_longitude.is_some() =>
+            {
+                GeotagOutcome::AlreadyTagged
+            }
+            Some(ref mut metadata) => {
+                match metadata.date_taken.as_deref().and_then(|d| time_shift.apply(d)) {
+                    None => GeotagOutcome::NoCaptureTime,
+                    Some(taken) => {
+                        let utc = taken.shifted(time_offset);
+                        capture_time_utc = Some(utc.to_string());
+
+                        match track.locate(utc.to_timestamp(), options.max_gap_seconds) {
+                            Some(point) => {
+                                metadata.gps_latitude = Some(point.latitude);
+                                metadata.gps_longitude = Some(point.longitude);
+                                GeotagOutcome::Matched {
+                                    latitude: Some(point.latitude),
+                                    longitude: Some(point.longitude),
+                                    elevation: point.elevation,
+                                }
+                            }
+                            None => GeotagOutcome::OutsideTrack,
+                        }
+                    }
+                }
+            }
+        };
+
+        report.entries.push(GeotagEntry {
+            path: file.path.clone(),
+            capture_time_utc,
+            outcome,
+        });
+    }
+
+    report
+}
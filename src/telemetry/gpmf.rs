@@ -0,0 +1,477 @@
+use super::track::TrackPoint;
+use crate::utils::datetime::ExifDateTime;
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+/// One GPMF key-length-value record
+///
+/// The header is a FourCC key, a type character (0 for nested records), the
+/// size of one sample and a big-endian repeat count. Payloads are padded to
+/// a multiple of four bytes.
+#[derive(Debug, Clone)]
+pub struct Klv<'a> {
+    pub key: [u8; 4],
+    pub type_char: u8,
+    pub struct_size: u8,
+    pub repeat: u16,
+    pub payload: &'a [u8],
+}
+
+impl<'a> Klv<'a> {
+    pub fn key_str(&self) -> String {
+        String::from_utf8_lossy(&self.key).into_owned()
+    }
+
+    pub fn is_nested(&self) -> bool {
+        self.type_char == 0
+    }
+
+    /// Records nested in this one
+    pub fn children(&self) -> Result<Vec<Klv<'a>>> {
+        parse_klv(self.payload)
+    }
+
+    /// Numeric payload as a flat list, in stored order
+    pub fn values(&self) -> Vec<f64> {
+        let (width, read): (usize, fn(&[u8]) -> f64) = match self.type_char {
+            b'b' => (1, |b| b[0] as i8 as f64),
+            b'B' => (1, |b| b[0] as f64),
+            b's' => (2, |b| i16::from_be_bytes([b[0], b[1]]) as f64),
+            b'S' => (2, |b| u16::from_be_bytes([b[0], b[1]]) as f64),
+            b'l' => (4, |b| i32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64),
+            b'L' => (4, |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64),
+            b'f' => (4, |b| f32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64),
+            b'd' => (8, |b| f64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])),
+            _ => return Vec::new(),
+        };
+        self.payload.chunks_exact(width).map(read).collect()
+    }
+
+    /// Values per sample (e.g. 3 for a 3-axis sensor)
+    pub fn channels(&self) -> usize {
+        let width = match self.type_char {
+            b'b' | b'B' => 1,
+            b's' | b'S' => 2,
+            b'l' | b'L' | b'f' => 4,
+            b'd' => 8,
+            _ => return 0,
+        };
+        self.struct_size as usize / width
+    }
+
+    /// Payload as text, for `c` (character) records
+    pub fn as_string(&self) -> Option<String> {
+        (self.type_char == b'c').then(|| {
+            String::from_utf8_lossy(self.payload)
+                .trim_end_matches('\0')
+                .trim()
+                .to_string()
+        })
+    }
+}
+
+/// Parse the records at one nesting level
+pub fn parse_klv(data: &[u8]) -> Result<Vec<Klv<'_>>> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+
+    while offset + 8 <= data.len() {
+        let header = &data[offset..offset + 8];
+        let key = [header[0], header[1], header[2], header[3]];
+
+        // Zero padding at the end of a buffer
+        if key == [0; 4] {
+            break;
+        }
+
+        let struct_size = header[5];
+        let repeat = u16::from_be_bytes([header[6], header[7]]);
+        let size = struct_size as usize * repeat as usize;
+        let start = offset + 8;
+
+        if start + size > data.len() {
+            bail!(
+                "GPMF record {} overruns its buffer ({} bytes at offset {})",
+                String::from_utf8_lossy(&key),
+                size,
+                offset
+            );
+        }
+
+        records.push(Klv {
+            key,
+            type_char: header[4],
+            struct_size,
+            repeat,
+            payload: &data[start..start + size],
+        });
+
+        offset = start + size.div_ceil(4) * 4;
+    }
+
+    Ok(records)
+}
+
+/// Per-device overview of a GPMF payload
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GpmfSummary {
+    pub devices: Vec<GpmfDevice>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GpmfDevice {
+    pub name: String,
+    pub streams: Vec<GpmfStream>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GpmfStream {
+    /// Stream name (STNM), if the camera recorded one
+    pub name: Option<String>,
+    /// Data keys with their sample counts
+    pub keys: Vec<(String, u32)>,
+}
+
+impl GpmfStream {
+    pub fn display(&self) -> String {
+        let keys: Vec<String> = self
+            .keys
+            .iter()
+            .map(|(key, samples)| {
+                if *samples > 1 {
+                    format!("{}×{}", key, samples)
+                } else {
+                    key.clone()
+                }
+            })
+            .collect();
+        match self.name {
+            Some(ref name) => format!("{}: {}", name, keys.join(" ")),
+            None => keys.join(" "),
+        }
+    }
+}
+
+/// Stream keys that describe the data rather than carry it
+const STREAM_METADATA_KEYS: &[&[u8; 4]] = &[
+    b"STNM", b"SIUN", b"UNIT", b"SCAL", b"TYPE", b"TSMP", b"TICK", b"TOCK", b"ORIN", b"ORIO",
+    b"MTRX", b"TIMO", b"EMPT",
+];
+
+/// Summarize the devices and streams in a GPMF payload
+pub fn summarize(data: &[u8]) -> Result<GpmfSummary> {
+    let mut summary = GpmfSummary::default();
+
+    for devc in parse_klv(data)?.iter().filter(|r| &r.key == b"DEVC" && r.is_nested()) {
+        let records = devc.children()?;
+        let name = records
+            .iter()
+            .find(|r| &r.key == b"DVNM")
+            .and_then(|r| r.as_string())
+            .unwrap_or_else(|| "Unnamed device".to_string());
+
+        let mut streams = Vec::new();
+        for strm in records.iter().filter(|r| &r.key == b"STRM" && r.is_nested()) {
+            let children = strm.children()?;
+            let name = children
+                .iter()
+                .find(|r| &r.key == b"STNM")
+                .and_then(|r| r.as_string());
+            let keys = children
+                .iter()
+                .filter(|r| !STREAM_METADATA_KEYS.contains(&&r.key))
+                .map(|r| (r.key_str(), r.repeat as u32))
+                .collect();
+            streams.push(GpmfStream { name, keys });
+        }
+
+        summary.devices.push(GpmfDevice { name, streams });
+    }
+
+    Ok(summary)
+}
+
+/// Samples of the first stream carrying `key`, with the stream's SCAL
+/// divisors applied, one `Vec` per sample
+pub fn scaled_samples(data: &[u8], key: &[u8; 4]) -> Result<Option<Vec<Vec<f64>>>> {
+    for devc in parse_klv(data)?.iter().filter(|r| &r.key == b"DEVC" && r.is_nested()) {
+        for strm in devc.children()?.iter().filter(|r| &r.key == b"STRM" && r.is_nested()) {
+            let records = strm.children()?;
+            let Some(record) = records.iter().find(|r| &r.key == key) else {
+                continue;
+            };
+
+            let channels = record.channels().max(1);
+            // SCAL is either one divisor for every channel or one per channel
+            let scale = records
+                .iter()
+                .find(|r| &r.key == b"SCAL")
+                .map(|r| r.values())
+                .unwrap_or_default();
+
+            let samples = record
+                .values()
+                .chunks_exact(channels)
+                .map(|sample| {
+                    sample
+                        .iter()
+                        .enumerate()
+                        .map(|(i, value)| {
+                            let divisor = scale.get(i).or(scale.first()).copied().unwrap_or(1.0);
+                            if divisor == 0.0 { *value } else { value / divisor }
+                        })
+                        .collect()
+                })
+                .collect();
+            return Ok(Some(samples));
+        }
+    }
+    Ok(None)
+}
+
+/// Mean accelerometer reading in m/s² as (up/down, right/left,
+/// forward/back). At rest this is the reaction to gravity, so an upright
+/// camera reads roughly +9.8 on the first axis.
+pub fn gravity(data: &[u8]) -> Option<[f64; 3]> {
+    let samples = scaled_samples(data, b"ACCL").ok()??;
+    let samples: Vec<&Vec<f64>> = samples.iter().filter(|s| s.len() >= 3).collect();
+    if samples.is_empty() {
+        return None;
+    }
+
+    let mut mean = [0.0; 3];
+    for sample in &samples {
+        for (axis, value) in mean.iter_mut().zip(sample.iter()) {
+            *axis += value / samples.len() as f64;
+        }
+    }
+    Some(mean)
+}
+
+/// Worst dilution of precision (GPSP/100) accepted for a position fix
+const MAX_GPS_DOP: f64 = 5.0;
+
+/// Position fixes from the GPS5 or GPS9 streams of a GPMF payload
+///
+/// GPS5 (HERO5-HERO10) shares one GPSU time per payload, so only its first
+/// sample is used. GPS9 (HERO11 and later) timestamps every sample. Samples
+/// without a 2D/3D lock or with poor precision are skipped.
+pub fn gps_points(data: &[u8]) -> Result<Vec<TrackPoint>> {
+    let mut points = Vec::new();
+
+    for devc in parse_klv(data)?.iter().filter(|r| &r.key == b"DEVC" && r.is_nested()) {
+        for strm in devc.children()?.iter().filter(|r| &r.key == b"STRM" && r.is_nested()) {
+            let records = strm.children()?;
+            let find = |key: &[u8; 4]| records.iter().find(|r| &r.key == key);
+            let scale = find(b"SCAL").map(|r| r.values()).unwrap_or_default();
+            let scaled = |sample: &[f64]| -> Vec<f64> {
+                sample
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| {
+                        let divisor = scale.get(i).or(scale.first()).copied().unwrap_or(1.0);
+                        if divisor == 0.0 { *value } else { value / divisor }
+                    })
+                    .collect()
+            };
+
+            if let Some(gps5) = find(b"GPS5") {
+                let fix = find(b"GPSF").and_then(|r| r.values().first().copied()).unwrap_or(3.0);
+                let dop = find(b"GPSP").and_then(|r| r.values().first().copied()).unwrap_or(0.0) / 100.0;
+                let time = find(b"GPSU").and_then(|r| parse_gpsu(r.payload));
+                let first = gps5.values().chunks_exact(5).next().map(scaled);
+                if let (Some(timestamp), Some(sample)) = (time, first) {
+                    if fix >= 2.0 && dop <= MAX_GPS_DOP {
+                        points.push(TrackPoint {
+                            timestamp,
+                            latitude: sample[0],
+                            longitude: sample[1],
+                            elevation: Some(sample[2]),
+                        });
+                    }
+                }
+            }
+
+            if let Some(gps9) = find(b"GPS9") {
+                let Some(types) = find(b"TYPE").and_then(|r| r.as_string()) else {
+                    continue;
+                };
+                for sample in complex_values(gps9, &types) {
+                    let sample = scaled(&sample);
+                    if sample.len() < 9 || sample[8] < 2.0 || sample[7] > MAX_GPS_DOP {
+                        continue;
+                    }
+                    // Days since 2000-01-01 and seconds since midnight, UTC
+                    let timestamp = GPS9_EPOCH + (sample[5] * 86_400.0 + sample[6]).floor() as i64;
+                    points.push(TrackPoint {
+                        timestamp,
+                        latitude: sample[0],
+                        longitude: sample[1],
+                        elevation: Some(sample[2]),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(points)
+}
+
+/// 2000-01-01 00:00:00 UTC
+const GPS9_EPOCH: i64 = 946_684_800;
+
+/// GPSU is UTC as `yymmddhhmmss.sss`
+fn parse_gpsu(payload: &[u8]) -> Option<i64> {
+    let text = std::str::from_utf8(payload).ok()?;
+    let digits = |range: std::ops::Range<usize>| text.get(range)?.parse::<u32>().ok();
+    let value = format!(
+        "{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+        2000 + digits(0..2)?,
+        digits(2..4)?,
+        digits(4..6)?,
+        digits(6..8)?,
+        digits(8..10)?,
+        digits(10..12)?
+    );
+    Some(ExifDateTime::parse(&value)?.to_timestamp())
+}
+
+/// Samples of a `?` (complex) record laid out by its TYPE string
+fn complex_values(record: &Klv, types: &str) -> Vec<Vec<f64>> {
+    let fields: Vec<Klv> = types
+        .bytes()
+        .map(|type_char| Klv {
+            key: record.key,
+            type_char,
+            struct_size: 0,
+            repeat: 1,
+            payload: &[],
+        })
+        .collect();
+    let widths: Option<Vec<usize>> = fields
+        .iter()
+        .map(|f| match f.type_char {
+            b'b' | b'B' => Some(1),
+            b's' | b'S' => Some(2),
+            b'l' | b'L' | b'f' => Some(4),
+            b'd' => Some(8),
+            _ => None,
+        })
+        .collect();
+    let Some(widths) = widths else {
+        return Vec::new();
+    };
+    let size: usize = widths.iter().sum();
+    if size == 0 || size != record.struct_size as usize {
+        return Vec::new();
+    }
+
+    record
+        .payload
+        .chunks_exact(size)
+        .map(|sample| {
+            let mut offset = 0;
+            fields
+                .iter()
+                .zip(&widths)
+                .filter_map(|(field, width)| {
+                    let value = Klv {
+                        payload: &sample[offset..offset + width],
+                        ..field.clone()
+                    }
+                    .values()
+                    .first()
+                    .copied();
+                    offset += width;
+                    value
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// GPMF stored in a DNG's DNGPrivateData tag, after the "GoPro\n" marker
+pub fn from_dng_private_data(data: &[u8]) -> Option<&[u8]> {
+    data.strip_prefix(b"GoPro\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn klv(key: &[u8; 4], type_char: u8, size: u8, repeat: u16, payload: &[u8]) -> Vec<u8> {
+        let mut out = key.to_vec();
+        out.extend([type_char, size]);
+        out.extend(repeat.to_be_bytes());
+        out.extend(payload);
+        out.resize(out.len().div_ceil(4) * 4, 0);
+        out
+    }
+
+    #[test]
+    fn test_summarize() {
+        let mut strm = klv(b"STNM", b'c', 1, 5, b"Accel");
+        strm.extend(klv(b"ACCL", b's', 6, 2, &[0; 12]));
+        let mut devc = klv(b"DVNM", b'c', 1, 6, b"Camera");
+        devc.extend(klv(b"STRM", 0, 1, strm.len() as u16, &strm));
+        let data = klv(b"DEVC", 0, 1, devc.len() as u16, &devc);
+
+        let summary = summarize(&data).unwrap();
+        assert_eq!(summary.devices.len(), 1);
+        assert_eq!(summary.devices[0].name, "Camera");
+        assert_eq!(summary.devices[0].streams[0].display(), "Accel: ACCL×2");
+
+        assert!(parse_klv(&data[..data.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn test_gravity() {
+        let mut accl = Vec::new();
+        for value in [980i16, -10, 20, 962, 10, -20] {
+            accl.extend(value.to_be_bytes());
+        }
+        let mut strm = klv(b"SCAL", b's', 2, 1, &100i16.to_be_bytes());
+        strm.extend(klv(b"ACCL", b's', 6, 2, &accl));
+        let devc = klv(b"STRM", 0, 1, strm.len() as u16, &strm);
+        let data = klv(b"DEVC", 0, 1, devc.len() as u16, &devc);
+
+        let gravity = gravity(&data).unwrap();
+        assert!((gravity[0] - 9.71).abs() < 1e-9);
+        assert!(gravity[1].abs() < 1e-9 && gravity[2].abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_gps9() {
+        // lat, lon, alt, 2D and 3D speed, days since 2000, seconds, DOP, fix
+        let taken = ExifDateTime::parse("2024:06:14 10:15:00").unwrap().to_timestamp();
+        let days = ((taken - GPS9_EPOCH) / 86_400) as i32;
+        let sample = |latitude: i32, dop: u16, fix: u16| {
+            let mut out = Vec::new();
+            for value in [latitude, -1_223_321_000, 42_500, 1_200, 1_300, days, 36_900_250] {
+                out.extend(value.to_be_bytes());
+            }
+            out.extend(dop.to_be_bytes());
+            out.extend(fix.to_be_bytes());
+            out
+        };
+        let mut scale = Vec::new();
+        for value in [10_000_000i32, 10_000_000, 1000, 1000, 100, 1, 1000, 100, 1] {
+            scale.extend(value.to_be_bytes());
+        }
+        let samples = [sample(476_062_000, 150, 3), sample(476_100_000, 150, 0), sample(476_200_000, 900, 3)];
+
+        let mut strm = klv(b"TYPE", b'c', 1, 9, b"lllllllSS");
+        strm.extend(klv(b"SCAL", b'l', 4, 9, &scale));
+        strm.extend(klv(b"GPS9", b'?', 32, samples.len() as u16, &samples.concat()));
+        let devc = klv(b"STRM", 0, 1, strm.len() as u16, &strm);
+        let data = klv(b"DEVC", 0, 1, devc.len() as u16, &devc);
+
+        // Only the locked, precise fix is kept, timestamped from its own fields
+        let points = gps_points(&data).unwrap();
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].timestamp, taken);
+        assert!((points[0].latitude - 47.6062).abs() < 1e-9);
+        assert!((points[0].longitude + 122.3321).abs() < 1e-9);
+        assert_eq!(points[0].elevation, Some(42.5));
+    }
+}
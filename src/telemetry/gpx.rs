@@ -0,0 +1,126 @@
+use super::track::{Track, TrackPoint};
+use crate::utils::datetime::{parse_offset, ExifDateTime};
+use anyhow::{anyhow, Context, Result};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::path::Path;
+
+/// Load every `<trkpt>` with a timestamp from a GPX file
+pub fn load_gpx(path: &Path) -> Result<Track> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read GPX file: {}", path.display()))?;
+    parse_gpx(&content).with_context(|| format!("Invalid GPX file: {}", path.display()))
+}
+
+pub fn parse_gpx(content: &str) -> Result<Track> {
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().trim_text(true);
+
+    let mut points = Vec::new();
+    let mut current: Option<(f64, f64)> = None;
+    let mut elevation = None;
+    let mut time = None;
+    let mut element = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(ref e) => {
+                let name = e.local_name().as_ref().to_vec();
+                if name == b"trkpt" {
+                    current = Some(read_position(e)?);
+                    elevation = None;
+                    time = None;
+                }
+                element = name;
+            }
+            Event::Text(ref e) if current.is_some() => {
+                let text = e.unescape()?;
+                match element.as_slice() {
+                    b"ele" => elevation = text.trim().parse().ok(),
+                    b"time" => time = parse_gpx_time(&text),
+                    _ => {}
+                }
+            }
+            Event::End(ref e) => {
+                if e.local_name().as_ref() == b"trkpt" {
+                    if let (Some((latitude, longitude)), Some(timestamp)) = (current.take(), time) {
+                        points.push(TrackPoint {
+                            timestamp,
+                            latitude,
+                            longitude,
+                            elevation,
+                        });
+                    }
+                }
+                element.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if points.is_empty() {
+        return Err(anyhow!("No timestamped track points found"));
+    }
+
+    Ok(Track::new(points))
+}
+
+fn read_position(element: &BytesStart) -> Result<(f64, f64)> {
+    let mut latitude = None;
+    let mut longitude = None;
+
+    for attribute in element.attributes() {
+        let attribute = attribute?;
+        let value = attribute.unescape_value()?;
+        match attribute.key.local_name().as_ref() {
+            b"lat" => latitude = value.trim().parse().ok(),
+            b"lon" => longitude = value.trim().parse().ok(),
+            _ => {}
+        }
+    }
+
+    match (latitude, longitude) {
+        (Some(lat), Some(lon)) => Ok((lat, lon)),
+        _ => Err(anyhow!("Track point without valid lat/lon")),
+    }
+}
+
+/// Parse an ISO 8601 GPX timestamp into seconds since the epoch (UTC)
+fn parse_gpx_time(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let local = ExifDateTime::parse(value)?.to_timestamp();
+
+    // Honour an explicit zone designator such as +02:00 after the time
+    let time_part = value.split_once('T').map(|(_, t)| t).unwrap_or(value);
+    let zone = time_part
+        .find(['+', '-'])
+        .and_then(|i| parse_offset(&time_part[i..]))
+        .unwrap_or(0);
+
+    Some(local - zone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gpx() {
+        let gpx = r#"<?xml version="1.0"?>
+            <gpx version="1.1" xmlns="http://www.topografix.com/GPX/1/1">
+              <trk><trkseg>
+                <trkpt lat="47.6" lon="-122.3"><ele>12.5</ele><time>2024-06-01T10:00:00Z</time></trkpt>
+                <trkpt lat="47.7" lon="-122.4"><time>2024-06-01T12:00:30+02:00</time></trkpt>
+                <trkpt lat="0" lon="0"></trkpt>
+              </trkseg></trk>
+            </gpx>"#;
+
+        let track = parse_gpx(gpx).unwrap();
+        let points = track.points();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].elevation, Some(12.5));
+        assert_eq!(points[1].timestamp - points[0].timestamp, 30);
+        assert_eq!(points[1].latitude, 47.7);
+    }
+}
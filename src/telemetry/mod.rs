@@ -0,0 +1,10 @@
+pub mod geotag;
+pub mod gpmf;
+pub mod gpx;
+pub mod mp4;
+pub mod track;
+
+pub use geotag::{geotag_files, GeotagOptions, GeotagReport};
+pub use gpx::load_gpx;
+pub use mp4::load_mp4;
+pub use track::Track;
@@ -0,0 +1,351 @@
+use super::gpmf;
+use super::track::Track;
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Largest `moov` box read into memory; GoPro sample tables are a few MB
+const MAX_MOOV_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Largest single GPMF sample; one second of telemetry is well under 1 MB
+const MAX_SAMPLE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Most samples read from one track; GoPro writes one per second of video
+const MAX_SAMPLE_COUNT: usize = 1_000_000;
+
+/// Load the GPS fixes recorded in a GoPro MP4's GPMF track
+pub fn load_mp4(path: &Path) -> Result<Track> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open video: {}", path.display()))?;
+    let samples = read_gpmf(&mut BufReader::new(file))
+        .with_context(|| format!("Invalid MP4 file: {}", path.display()))?;
+
+    let mut points = Vec::new();
+    for sample in &samples {
+        // A damaged payload loses one second of telemetry, not the whole file
+        if let Ok(fixes) = gpmf::gps_points(sample) {
+            points.extend(fixes);
+        }
+    }
+
+    if points.is_empty() {
+        bail!("No GPS fixes in {}", path.display());
+    }
+    Ok(Track::new(points))
+}
+
+/// Every sample of the first `gpmd` metadata track, in file order
+pub fn read_gpmf<R: Read + Seek>(reader: &mut R) -> Result<Vec<Vec<u8>>> {
+    let moov = read_moov(reader)?;
+    let trak = child_boxes(&moov)?
+        .into_iter()
+        .filter(|(kind, _)| kind == b"trak")
+        .find_map(|(_, trak)| is_gpmf_track(trak).then_some(trak))
+        .ok_or_else(|| anyhow!("No GPMF telemetry track"))?;
+
+    let mut samples = Vec::new();
+    for (offset, size) in sample_locations(trak)? {
+        if size > MAX_SAMPLE_SIZE {
+            bail!("GPMF sample of {} bytes is too large", size);
+        }
+        let mut sample = vec![0; size as usize];
+        reader.seek(SeekFrom::Start(offset))?;
+        reader
+            .read_exact(&mut sample)
+            .context("GPMF sample extends past the end of the file")?;
+        samples.push(sample);
+    }
+    Ok(samples)
+}
+
+/// Find the top-level `moov` box and read its payload
+fn read_moov<R: Read + Seek>(reader: &mut R) -> Result<Vec<u8>> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    let mut position = reader.seek(SeekFrom::Start(0))?;
+
+    while position + 8 <= end {
+        let mut header = [0u8; 16];
+        reader.read_exact(&mut header[..8])?;
+        let mut size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let mut header_len = 8;
+        if size == 1 {
+            reader.read_exact(&mut header[8..])?;
+            size = u64::from_be_bytes(header[8..16].try_into()?);
+            header_len = 16;
+        } else if size == 0 {
+            size = end - position;
+        }
+        if size < header_len || position + size > end {
+            bail!("Box at offset {} has an invalid size", position);
+        }
+
+        if &header[4..8] == b"moov" {
+            let payload_len = size - header_len;
+            if payload_len > MAX_MOOV_SIZE {
+                bail!("moov box of {} bytes is too large", payload_len);
+            }
+            let mut payload = vec![0; payload_len as usize];
+            reader.read_exact(&mut payload)?;
+            return Ok(payload);
+        }
+
+        position += size;
+        reader.seek(SeekFrom::Start(position))?;
+    }
+
+    bail!("No moov box")
+}
+
+/// Boxes directly inside `data`, as (type, payload)
+fn child_boxes(data: &[u8]) -> Result<Vec<([u8; 4], &[u8])>> {
+    let mut boxes = Vec::new();
+    let mut rest = data;
+
+    while rest.len() >= 8 {
+        let mut size = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let kind: [u8; 4] = rest[4..8].try_into()?;
+        let mut header_len = 8;
+        if size == 1 {
+            let large = rest.get(8..16).ok_or_else(|| anyhow!("Truncated box header"))?;
+            size = usize::try_from(u64::from_be_bytes(large.try_into()?))?;
+            header_len = 16;
+        } else if size == 0 {
+            size = rest.len();
+        }
+        if size < header_len || size > rest.len() {
+            bail!("Box {} has an invalid size", String::from_utf8_lossy(&kind));
+        }
+        boxes.push((kind, &rest[header_len..size]));
+        rest = &rest[size..];
+    }
+
+    Ok(boxes)
+}
+
+/// Payload of the first child of type `kind`
+fn child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Result<&'a [u8]> {
+    child_boxes(data)?
+        .into_iter()
+        .find(|(k, _)| k == kind)
+        .map(|(_, payload)| payload)
+        .ok_or_else(|| anyhow!("Missing {} box", String::from_utf8_lossy(kind)))
+}
+
+/// Follow a path of nested boxes, e.g. `mdia/minf/stbl`
+fn descend<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Result<&'a [u8]> {
+    path.iter().try_fold(data, |data, kind| child(data, kind))
+}
+
+/// A timed metadata track (`meta` handler) whose samples are `gpmd`
+fn is_gpmf_track(trak: &[u8]) -> bool {
+    let handler = descend(trak, &[b"mdia", b"hdlr"])
+        .ok()
+        .and_then(|hdlr| hdlr.get(8..12));
+    // stsd: version/flags, entry count, then the first entry's size and format
+    let format = descend(trak, &[b"mdia", b"minf", b"stbl", b"stsd"])
+        .ok()
+        .and_then(|stsd| stsd.get(12..16));
+    handler == Some(b"meta") && format == Some(b"gpmd")
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| anyhow!("Truncated sample table"))
+}
+
+/// Entry count of a sample table whose `entry_size` byte entries start at
+/// `header`, right after the count, checked against the table's length
+fn entry_count(table: &[u8], header: usize, entry_size: usize) -> Result<usize> {
+    let count = read_u32(table, header - 4)? as usize;
+    if count > table.len().saturating_sub(header) / entry_size {
+        bail!("Sample table lists {} entries but is only {} bytes", count, table.len());
+    }
+    Ok(count)
+}
+
+/// File offset and size of every sample, from the stsz, stsc and stco/co64
+/// sample tables
+fn sample_locations(trak: &[u8]) -> Result<Vec<(u64, u64)>> {
+    let stbl = descend(trak, &[b"mdia", b"minf", b"stbl"])?;
+
+    let chunk_offsets: Vec<u64> = match child(stbl, b"stco") {
+        Ok(stco) => (0..entry_count(stco, 8, 4)?)
+            .map(|i| read_u32(stco, 8 + i * 4).map(u64::from))
+            .collect::<Result<_>>()?,
+        Err(_) => {
+            let co64 = child(stbl, b"co64")?;
+            (0..entry_count(co64, 8, 8)?)
+                .map(|i| {
+                    Ok(((read_u32(co64, 8 + i * 8)? as u64) << 32)
+                        | read_u32(co64, 12 + i * 8)? as u64)
+                })
+                .collect::<Result<_>>()?
+        }
+    };
+
+    // stsc runs: (first chunk, 1-based; samples in each chunk of the run)
+    let stsc = child(stbl, b"stsc")?;
+    let runs = (0..entry_count(stsc, 8, 12)?)
+        .map(|i| Ok((read_u32(stsc, 8 + i * 12)?, read_u32(stsc, 12 + i * 12)?)))
+        .collect::<Result<Vec<(u32, u32)>>>()?;
+    let per_chunk: Vec<u32> = (1..=chunk_offsets.len() as u32)
+        .map(|chunk| {
+            runs.iter()
+                .take_while(|(first, _)| *first <= chunk)
+                .last()
+                .map(|(_, count)| *count)
+                .unwrap_or(0)
+        })
+        .collect();
+
+    // The count is untrusted: it may not exceed the samples the chunks hold,
+    // nor the sizes listed when they are not uniform, nor a sane maximum
+    let stsz = child(stbl, b"stsz")?;
+    let uniform_size = read_u32(stsz, 4)?;
+    let sample_count = match uniform_size {
+        0 => entry_count(stsz, 12, 4)?,
+        _ => read_u32(stsz, 8)? as usize,
+    };
+    if sample_count > MAX_SAMPLE_COUNT {
+        bail!("Sample table lists {} samples", sample_count);
+    }
+    let chunked: u64 = per_chunk.iter().map(|count| *count as u64).sum();
+    if sample_count as u64 > chunked {
+        bail!("Sample table lists {} samples but its chunks hold {}", sample_count, chunked);
+    }
+    let sizes = (0..sample_count).map(|i| match uniform_size {
+        0 => read_u32(stsz, 12 + i * 4).map(u64::from),
+        size => Ok(size as u64),
+    });
+
+    let mut locations = Vec::with_capacity(sample_count);
+    let mut sizes = sizes.collect::<Result<Vec<u64>>>()?.into_iter();
+    for (chunk_offset, count) in chunk_offsets.iter().zip(per_chunk) {
+        let mut offset = *chunk_offset;
+        for size in sizes.by_ref().take(count as usize) {
+            locations.push((offset, size));
+            offset += size;
+        }
+    }
+
+    Ok(locations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut out = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend(kind);
+        out.extend(payload);
+        out
+    }
+
+    fn klv(key: &[u8; 4], type_char: u8, size: u8, repeat: u16, payload: &[u8]) -> Vec<u8> {
+        let mut out = key.to_vec();
+        out.extend([type_char, size]);
+        out.extend(repeat.to_be_bytes());
+        out.extend(payload);
+        out.resize(out.len().div_ceil(4) * 4, 0);
+        out
+    }
+
+    fn gps5_payload(time: &[u8], latitude: f64) -> Vec<u8> {
+        let mut gps5 = Vec::new();
+        for value in [(latitude * 1e7) as i32, -1_223_000_000, 42_000, 0, 0] {
+            gps5.extend(value.to_be_bytes());
+        }
+        let mut scale = Vec::new();
+        for value in [10_000_000i32, 10_000_000, 1000, 1000, 100] {
+            scale.extend(value.to_be_bytes());
+        }
+        let mut strm = klv(b"GPSU", b'U', 16, 1, time);
+        strm.extend(klv(b"GPSF", b'L', 4, 1, &3u32.to_be_bytes()));
+        strm.extend(klv(b"GPSP", b'S', 2, 1, &150u16.to_be_bytes()));
+        strm.extend(klv(b"SCAL", b'l', 4, 5, &scale));
+        strm.extend(klv(b"GPS5", b'l', 20, 1, &gps5));
+        let devc = klv(b"STRM", 0, 1, strm.len() as u16, &strm);
+        klv(b"DEVC", 0, 1, devc.len() as u16, &devc)
+    }
+
+    /// A `gpmd` track with one chunk at `chunk_offset` holding `chunk_samples`
+    fn gpmf_trak(stsz: &[u8], chunk_samples: u32, chunk_offset: u32) -> Vec<u8> {
+        let mut hdlr = vec![0; 8];
+        hdlr.extend(b"meta");
+        hdlr.extend([0; 12]);
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(mp4_box(b"gpmd", &[0; 8]));
+        let mut stsc = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        for value in [1u32, chunk_samples, 1] {
+            stsc.extend(value.to_be_bytes());
+        }
+        let mut stco = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stco.extend(chunk_offset.to_be_bytes());
+
+        let stbl = [
+            mp4_box(b"stsd", &stsd),
+            mp4_box(b"stsz", stsz),
+            mp4_box(b"stsc", &stsc),
+            mp4_box(b"stco", &stco),
+        ]
+        .concat();
+        let minf = mp4_box(b"minf", &mp4_box(b"stbl", &stbl));
+        mp4_box(b"mdia", &[mp4_box(b"hdlr", &hdlr), minf].concat())
+    }
+
+    #[test]
+    fn test_read_gpmf_track() {
+        let samples = [
+            gps5_payload(b"240614101500.000", 47.6),
+            gps5_payload(b"240614101501.000", 47.7),
+        ];
+        let ftyp = mp4_box(b"ftyp", b"mp41\0\0\0\0");
+        let mdat_start = ftyp.len() as u32 + 8;
+        let mdat = mp4_box(b"mdat", &samples.concat());
+
+        let mut stsz = vec![0; 8];
+        stsz.extend(2u32.to_be_bytes());
+        for sample in &samples {
+            stsz.extend((sample.len() as u32).to_be_bytes());
+        }
+        let moov = mp4_box(b"moov", &mp4_box(b"trak", &gpmf_trak(&stsz, 2, mdat_start)));
+
+        let file = [ftyp, mdat, moov].concat();
+        let read = read_gpmf(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(read, samples);
+
+        let points: Vec<_> = read.iter().flat_map(|s| gpmf::gps_points(s).unwrap()).collect();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[1].timestamp - points[0].timestamp, 1);
+        assert!((points[1].latitude - 47.7).abs() < 1e-6);
+        assert!((points[0].longitude + 122.3).abs() < 1e-6);
+        assert_eq!(points[0].elevation, Some(42.0));
+
+        assert!(read_gpmf(&mut Cursor::new(&file[..file.len() - 4])).is_err());
+    }
+
+    #[test]
+    fn test_untrusted_sample_count() {
+        // 2^32 - 1 one-byte samples must be refused, not allocated
+        let mut uniform = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        uniform.extend(u32::MAX.to_be_bytes());
+        assert!(sample_locations(&gpmf_trak(&uniform, u32::MAX, 0)).is_err());
+
+        // More samples than the chunks hold
+        uniform[8..12].copy_from_slice(&3u32.to_be_bytes());
+        assert!(sample_locations(&gpmf_trak(&uniform, 2, 0)).is_err());
+
+        // Listed sizes must all be present
+        let mut listed = vec![0; 8];
+        listed.extend(3u32.to_be_bytes());
+        listed.extend(100u32.to_be_bytes());
+        assert!(sample_locations(&gpmf_trak(&listed, 3, 0)).is_err());
+
+        uniform[8..12].copy_from_slice(&2u32.to_be_bytes());
+        assert_eq!(sample_locations(&gpmf_trak(&uniform, 2, 64)).unwrap(), vec![(64, 1), (65, 1)]);
+    }
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+/// A single timestamped position fix
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrackPoint {
+    /// Seconds since the Unix epoch, UTC
+    pub timestamp: i64,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub elevation: Option<f64>,
+}
+
+/// A time-ordered series of position fixes
+#[derive(Debug, Clone, Default)]
+pub struct Track {
+    points: Vec<TrackPoint>,
+}
+
+impl Track {
+    pub fn new(mut points: Vec<TrackPoint>) -> Self {
+        points.sort_by_key(|p| p.timestamp);
+        points.dedup_by_key(|p| p.timestamp);
+        Self { points }
+    }
+
+    pub fn points(&self) -> &[TrackPoint] {
+        &self.points
+    }
+
+    /// First and last timestamps covered by the track
+    pub fn time_range(&self) -> Option<(i64, i64)> {
+        Some((self.points.first()?.timestamp, self.points.last()?.timestamp))
+    }
+
+    /// Add the points of another track, keeping time order
+    pub fn merge(&mut self, other: Track) {
+        let mut points = std::mem::take(&mut self.points);
+        points.extend(other.points);
+        *self = Track::new(points);
+    }
+
+    /// Estimate the position at `timestamp`
+    ///
+    /// Between two fixes no more than `max_gap` seconds apart the position is
+    /// linearly interpolated. Otherwise the nearest fix is used if it lies
+    /// within `max_gap` seconds, and `None` is returned if nothing does.
+    pub fn locate(&self, timestamp: i64, max_gap: i64) -> Option<TrackPoint> {
+        let next = self.points.partition_point(|p| p.timestamp < timestamp);
+        let after = self.points.get(next);
+        let before = next.checked_sub(1).and_then(|i| self.points.get(i));
+
+        match (before, after) {
+            (_, Some(a)) if a.timestamp == timestamp => Some(*a),
+            (Some(b), Some(a)) if a.timestamp - b.timestamp <= max_gap => {
+                let t = (timestamp - b.timestamp) as f64 / (a.timestamp - b.timestamp) as f64;
+                Some(TrackPoint {
+                    timestamp,
+                    latitude: lerp(b.latitude, a.latitude, t),
+                    longitude: lerp(b.longitude, a.longitude, t),
+                    elevation: match (b.elevation, a.elevation) {
+                        (Some(e0), Some(e1)) => Some(lerp(e0, e1, t)),
+                        (e0, e1) => e0.or(e1),
+                    },
+                })
+            }
+            (before, after) => [before, after]
+                .into_iter()
+                .flatten()
+                .filter(|p| (p.timestamp - timestamp).abs() <= max_gap)
+                .min_by_key(|p| (p.timestamp - timestamp).abs())
+                .copied(),
+        }
+    }
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(timestamp: i64, latitude: f64, longitude: f64) -> TrackPoint {
+        TrackPoint {
+            timestamp,
+            latitude,
+            longitude,
+            elevation: None,
+        }
+    }
+
+    #[test]
+    fn test_locate_interpolates_between_fixes() {
+        let track = Track::new(vec![point(100, 10.0, 20.0), point(0, 0.0, 0.0)]);
+
+        let p = track.locate(25, 120).unwrap();
+        assert!((p.latitude - 2.5).abs() < 1e-9);
+        assert!((p.longitude - 5.0).abs() < 1e-9);
+        assert_eq!(track.locate(100, 0), Some(point(100, 10.0, 20.0)));
+    }
+
+    #[test]
+    fn test_locate_respects_max_gap() {
+        let track = Track::new(vec![point(0, 0.0, 0.0), point(1000, 10.0, 10.0)]);
+
+        // Segment too long to interpolate, but the start fix is close enough
+        assert_eq!(track.locate(30, 60), Some(point(0, 0.0, 0.0)));
+        assert_eq!(track.locate(500, 60), None);
+        assert_eq!(track.locate(1050, 60), Some(point(1000, 10.0, 10.0)));
+        assert_eq!(track.locate(-100, 60), None);
+    }
+}
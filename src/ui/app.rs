@@ -1,12 +1,31 @@
-use crate::models::{ConversionConfig, GprFile, OutputFormat};
-use crate::gpr;
+use crate::catalog::Catalog;
+use crate::models::{
+    CameraProfile, ConversionConfig, CropAspect, CropRect, DenoiseSettings, DevelopSettings, Filter,
+    Geometry, GprFile, MetadataEdits, OutputFormat, Sharpening, ShootStats, ToneSettings,
+};
+use crate::models::fusion;
+use crate::models::gpr_file::GprMetadata;
+use crate::utils::file_utils::{find_gpr_files, is_gpr_file};
+use crate::gpr::inspect::{self, FieldDiff};
+use crate::gpr::{geometry, GprConverter};
+use crate::gpr::verify::{self, Verdict, VerifyResult};
+use image::{ImageBuffer, Rgb};
 use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+/// Fraction of the image the crop moves or grows by per key press
+const CROP_STEP: f32 = 0.02;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AppState {
     MainMenu,
     FileBrowser,
     FileInfo,
+    Compare,
+    Stats,
+    Verify,
+    EditMetadata,
+    Crop,
     ConversionConfig,
     Converting,
     Complete,
@@ -17,6 +36,8 @@ pub enum AppState {
 pub enum MainMenuItem {
     BrowseFiles,
     BatchConvert,
+    ShootStats,
+    VerifyFiles,
     Settings,
     Help,
     Quit,
@@ -27,6 +48,8 @@ impl MainMenuItem {
         match self {
             MainMenuItem::BrowseFiles => "Browse and Convert Files",
             MainMenuItem::BatchConvert => "Batch Convert Directory",
+            MainMenuItem::ShootStats => "Shoot Statistics",
+            MainMenuItem::VerifyFiles => "Verify Directory",
             MainMenuItem::Settings => "Settings",
             MainMenuItem::Help => "Help",
             MainMenuItem::Quit => "Quit",
@@ -37,6 +60,8 @@ impl MainMenuItem {
         vec![
             MainMenuItem::BrowseFiles,
             MainMenuItem::BatchConvert,
+            MainMenuItem::ShootStats,
+            MainMenuItem::VerifyFiles,
             MainMenuItem::Settings,
             MainMenuItem::Help,
             MainMenuItem::Quit,
@@ -44,23 +69,227 @@ impl MainMenuItem {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigOption {
+    OutputFormat,
+    Quality,
+    ColorSpace,
+    PreserveMetadata,
+    Orientation,
+    Demosaic,
+    Denoise,
+    LumaNoise,
+    ChromaNoise,
+    WhiteBalance,
+    Temperature,
+    Tint,
+    Exposure,
+    BlackPoint,
+    WhitePoint,
+    ToneCurve,
+    Contrast,
+    Highlights,
+    Shadows,
+    HighlightReconstruction,
+    TimeShift,
+    UtcOffset,
+    GpsPrivacy,
+    DropIdentifiers,
+    XmpSidecar,
+    LensCorrection,
+    LensEdges,
+    AutoLevel,
+    StitchFusion,
+    Resize,
+    ResizeFilter,
+    Sharpen,
+    SharpenRadius,
+    SharpenThreshold,
+    IsolateDecoder,
+    MetadataEdits,
+    OutputDirectory,
+}
+
+impl ConfigOption {
+    pub fn all() -> Vec<ConfigOption> {
+        vec![
+            ConfigOption::OutputFormat,
+            ConfigOption::Quality,
+            ConfigOption::ColorSpace,
+            ConfigOption::PreserveMetadata,
+            ConfigOption::Orientation,
+            ConfigOption::Demosaic,
+            ConfigOption::Denoise,
+            ConfigOption::LumaNoise,
+            ConfigOption::ChromaNoise,
+            ConfigOption::WhiteBalance,
+            ConfigOption::Temperature,
+            ConfigOption::Tint,
+            ConfigOption::Exposure,
+            ConfigOption::BlackPoint,
+            ConfigOption::WhitePoint,
+            ConfigOption::ToneCurve,
+            ConfigOption::Contrast,
+            ConfigOption::Highlights,
+            ConfigOption::Shadows,
+            ConfigOption::HighlightReconstruction,
+            ConfigOption::TimeShift,
+            ConfigOption::UtcOffset,
+            ConfigOption::GpsPrivacy,
+            ConfigOption::DropIdentifiers,
+            ConfigOption::XmpSidecar,
+            ConfigOption::LensCorrection,
+            ConfigOption::LensEdges,
+            ConfigOption::AutoLevel,
+            ConfigOption::StitchFusion,
+            ConfigOption::Resize,
+            ConfigOption::ResizeFilter,
+            ConfigOption::Sharpen,
+            ConfigOption::SharpenRadius,
+            ConfigOption::SharpenThreshold,
+            ConfigOption::IsolateDecoder,
+            ConfigOption::MetadataEdits,
+            ConfigOption::OutputDirectory,
+        ]
+    }
+
+    /// Options opened with `e` rather than adjusted with left/right
+    pub fn is_text(&self) -> bool {
+        matches!(self, ConfigOption::MetadataEdits)
+    }
+}
+
+/// Fields of the metadata editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditField {
+    Artist,
+    Copyright,
+    Caption,
+    Keywords,
+    Rating,
+    Gps,
+}
+
+impl EditField {
+    pub fn all() -> Vec<EditField> {
+        vec![
+            EditField::Artist,
+            EditField::Copyright,
+            EditField::Caption,
+            EditField::Keywords,
+            EditField::Rating,
+            EditField::Gps,
+        ]
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            EditField::Artist => "Artist",
+            EditField::Copyright => "Copyright",
+            EditField::Caption => "Caption",
+            EditField::Keywords => "Keywords",
+            EditField::Rating => "Rating",
+            EditField::Gps => "GPS Position",
+        }
+    }
+
+    /// Fields edited as free text rather than adjusted with left/right
+    pub fn is_text(&self) -> bool {
+        !matches!(self, EditField::Rating)
+    }
+}
+
 pub struct App {
     pub state: AppState,
     pub main_menu_index: usize,
     pub current_directory: PathBuf,
     pub files: Vec<PathBuf>,
     pub file_index: usize,
+    /// Query narrowing the file browser and shoot statistics
+    pub file_filter: Option<Filter>,
+    /// GPR files in the current directory hidden by the filter
+    pub hidden_files: usize,
     pub selected_file: Option<GprFile>,
     pub conversion_config: ConversionConfig,
     pub config_option_index: usize,
+    /// Camera whose preset the settings screen shows. Every converted file
+    /// takes its own camera's preset until the user changes a setting it
+    /// covers by hand.
+    pub applied_preset: Option<String>,
+    pub preset_overridden: bool,
+    /// Buffer for the text option being edited, if any
+    pub text_input: Option<String>,
+    /// Why the last text input was rejected
+    pub input_error: Option<String>,
+    pub edit_field_index: usize,
+    /// Screen to go back to when the metadata editor closes
+    pub edit_return_state: AppState,
+    /// File whose own metadata edits the editor changes; `None` edits those
+    /// for every file
+    pub edit_scope: Option<PathBuf>,
+    /// File marked in the info screen as the left side of a comparison
+    pub compare_base: Option<PathBuf>,
+    pub compare_rows: Vec<FieldDiff>,
+    pub compare_scroll: usize,
+    pub compare_changed_only: bool,
+    /// Statistics for the current directory tree, when the dashboard is open
+    pub shoot_stats: Option<ShootStats>,
+    /// Integrity check results for the current directory tree
+    pub verify_results: Vec<VerifyResult>,
+    pub verify_scroll: usize,
+    /// Geometry being edited in the crop screen for the selected file
+    pub crop_geometry: Geometry,
+    /// Upright preview of the file being cropped
+    pub crop_preview: Option<ImageBuffer<Rgb<u8>, Vec<u8>>>,
+    /// The preview rotated, flipped and straightened, before the crop
+    pub crop_view: Option<ImageBuffer<Rgb<u8>, Vec<u8>>>,
+    /// Why the crop preview could not be decoded
+    pub crop_error: Option<String>,
+    /// Cached metadata and conversion history, saved on exit
+    pub catalog: Catalog,
+    /// Directory conversion running in the background
+    pub batch: Option<BatchJob>,
+    /// Directory check running in the background
+    pub verify_job: Option<VerifyJob>,
     pub conversion_progress: f32,
     pub error_message: Option<String>,
     pub success_message: Option<String>,
 }
 
+/// Update sent by the thread running a batch conversion
+enum BatchMessage {
+    Progress(usize),
+    Finished(anyhow::Result<Vec<(PathBuf, PathBuf)>>),
+}
+
+/// A directory conversion running on its own thread, so the interface keeps
+/// drawing while it works
+pub struct BatchJob {
+    receiver: Receiver<BatchMessage>,
+    pub directory: PathBuf,
+    pub files: usize,
+    pub done: usize,
+}
+
+/// Update sent by the thread checking a directory
+enum VerifyMessage {
+    Progress(usize),
+    Finished(Vec<VerifyResult>),
+}
+
+/// A directory check running on its own thread, as a [`BatchJob`] does
+pub struct VerifyJob {
+    receiver: Receiver<VerifyMessage>,
+    pub directory: PathBuf,
+    pub files: usize,
+    pub done: usize,
+}
+
 impl App {
     pub fn new() -> Self {
         let current_directory = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let mut catalog = Catalog::open_default();
+        catalog.set_isolated(true);
 
         Self {
             state: AppState::MainMenu,
@@ -68,9 +297,40 @@ impl App {
             current_directory,
             files: Vec::new(),
             file_index: 0,
+            file_filter: None,
+            hidden_files: 0,
             selected_file: None,
-            conversion_config: ConversionConfig::default(),
+            // A decoder crash would otherwise take the terminal down with it;
+            // metadata for the catalog is read in the same child process
+            // Each file takes its own camera's preset until the user picks
+            // the settings it covers
+            conversion_config: ConversionConfig {
+                isolate_decoder: true,
+                camera_presets: true,
+                ..ConversionConfig::default()
+            },
             config_option_index: 0,
+            applied_preset: None,
+            preset_overridden: false,
+            text_input: None,
+            input_error: None,
+            edit_field_index: 0,
+            edit_return_state: AppState::FileInfo,
+            edit_scope: None,
+            compare_base: None,
+            compare_rows: Vec::new(),
+            compare_scroll: 0,
+            compare_changed_only: false,
+            shoot_stats: None,
+            verify_results: Vec::new(),
+            verify_scroll: 0,
+            crop_geometry: Geometry::default(),
+            crop_preview: None,
+            crop_view: None,
+            crop_error: None,
+            catalog,
+            batch: None,
+            verify_job: None,
             conversion_progress: 0.0,
             error_message: None,
             success_message: None,
@@ -99,11 +359,9 @@ impl App {
                 self.load_directory();
                 self.state = AppState::FileBrowser;
             }
-            Some(MainMenuItem::BatchConvert) => {
-                // TODO: Implement batch convert
-                self.error_message = Some("Batch convert not yet implemented".to_string());
-                self.state = AppState::Error;
-            }
+            Some(MainMenuItem::BatchConvert) => self.batch_convert_directory(),
+            Some(MainMenuItem::ShootStats) => self.load_shoot_stats(),
+            Some(MainMenuItem::VerifyFiles) => self.verify_directory(),
             Some(MainMenuItem::Settings) => {
                 // TODO: Implement settings
                 self.error_message = Some("Settings not yet implemented".to_string());
@@ -142,6 +400,50 @@ impl App {
 
         self.files.sort();
         self.file_index = 0;
+        self.hidden_files = 0;
+
+        if let Some(filter) = self.file_filter.clone() {
+            let catalog = &mut self.catalog;
+            let before = self.files.len();
+            self.files.retain(|path| {
+                if path.is_dir() {
+                    return true;
+                }
+                let mut file = GprFile::new(path.clone());
+                file.metadata = catalog.metadata(path).ok();
+                filter.matches(&file)
+            });
+            self.hidden_files = before - self.files.len();
+            self.save_catalog();
+        }
+    }
+
+    /// Start editing the file browser filter
+    pub fn begin_filter_edit(&mut self) {
+        self.text_input = Some(
+            self.file_filter
+                .as_ref()
+                .map(|filter| filter.to_string())
+                .unwrap_or_default(),
+        );
+    }
+
+    /// Apply the typed filter; an empty query shows every file again
+    pub fn commit_filter_edit(&mut self) {
+        let Some(input) = self.text_input.take() else {
+            return;
+        };
+        match Filter::parse(&input) {
+            Ok(filter) => {
+                self.file_filter = Some(filter).filter(|f| !f.is_empty());
+                self.input_error = None;
+                self.load_directory();
+            }
+            Err(e) => {
+                self.input_error = Some(e.to_string());
+                self.text_input = Some(input);
+            }
+        }
     }
 
     pub fn next_file(&mut self) {
@@ -188,8 +490,8 @@ impl App {
         }
     }
 
-    fn load_metadata(&self, gpr_file: &mut GprFile) -> Result<(), String> {
-        match gpr::read_metadata(&gpr_file.path) {
+    fn load_metadata(&mut self, gpr_file: &mut GprFile) -> Result<(), String> {
+        match self.catalog.metadata(&gpr_file.path) {
             Ok(metadata) => {
                 gpr_file.metadata = Some(metadata);
                 Ok(())
@@ -205,53 +507,678 @@ impl App {
     }
 
     pub fn go_to_conversion_config(&mut self) {
+        self.apply_camera_preset();
         self.state = AppState::ConversionConfig;
         self.config_option_index = 0;
     }
 
+    /// Show the selected camera's preset unless the user has already picked
+    /// a format or quality themselves
+    fn apply_camera_preset(&mut self) {
+        if self.preset_overridden {
+            return;
+        }
+        let profile = self
+            .selected_file
+            .as_ref()
+            .and_then(|file| file.metadata.as_ref())
+            .and_then(|metadata| CameraProfile::lookup(&metadata.camera_model));
+        if let Some(profile) = profile {
+            profile.preset.apply(&mut self.conversion_config);
+            self.applied_preset = Some(profile.model.to_string());
+        }
+    }
+
+    // Compare
+    pub fn mark_for_compare(&mut self) {
+        if let Some(ref file) = self.selected_file {
+            self.compare_base = Some(file.path.clone());
+        }
+    }
+
+    /// Compare the marked file (A) with the selected one (B)
+    pub fn compare_with_marked(&mut self) {
+        let (Some(base), Some(file)) = (self.compare_base.clone(), self.selected_file.as_ref()) else {
+            return;
+        };
+        if base == file.path {
+            return;
+        }
+
+        let config = &self.conversion_config;
+        match inspect::diff_files(&base, &file.path, &config.privacy, config.isolate_decoder) {
+            Ok(rows) => {
+                self.compare_rows = rows;
+                self.compare_scroll = 0;
+                self.state = AppState::Compare;
+            }
+            Err(e) => {
+                self.error_message = Some(format!("Comparison failed: {}", e));
+                self.state = AppState::Error;
+            }
+        }
+    }
+
+    /// Rows shown in the compare view, after the changed-only filter
+    pub fn visible_compare_rows(&self) -> Vec<&FieldDiff> {
+        self.compare_rows
+            .iter()
+            .filter(|row| !self.compare_changed_only || row.is_different())
+            .collect()
+    }
+
+    pub fn scroll_compare(&mut self, delta: i32) {
+        let max = self.visible_compare_rows().len().saturating_sub(1) as i32;
+        self.compare_scroll = (self.compare_scroll as i32 + delta).clamp(0, max) as usize;
+    }
+
+    pub fn toggle_compare_filter(&mut self) {
+        self.compare_changed_only = !self.compare_changed_only;
+        self.compare_scroll = 0;
+    }
+
+    // Crop and rotate
+    /// Edit the geometry of the selected file over a preview. The result
+    /// applies to this file only, replacing the default geometry.
+    pub fn open_crop_editor(&mut self) {
+        let Some(ref file) = self.selected_file else {
+            return;
+        };
+        self.crop_geometry = self.conversion_config.geometry_for(&file.path);
+        let orientation_mode = self.conversion_config.orientation_mode.with_geometry();
+        match GprConverter::preview(file, orientation_mode, self.conversion_config.isolate_decoder) {
+            Ok(preview) => {
+                self.crop_preview = Some(preview);
+                self.crop_error = None;
+            }
+            Err(e) => {
+                self.crop_preview = None;
+                self.crop_error = Some(format!("Preview unavailable: {}", e));
+            }
+        }
+        self.refresh_crop_view();
+        self.state = AppState::Crop;
+    }
+
+    /// Geometry of the file being cropped with any automatic levelling
+    /// worked out
+    pub fn crop_levelled(&self) -> Geometry {
+        let none = GprMetadata::default();
+        let metadata = self.selected_file.as_ref().and_then(|file| file.metadata.as_ref()).unwrap_or(&none);
+        // Oriented as the crop preview is
+        let orientation = self.conversion_config.orientation_mode.with_geometry().baked_orientation(metadata);
+        self.crop_geometry.levelled(metadata, orientation)
+    }
+
+    fn refresh_crop_view(&mut self) {
+        let uncropped = Geometry {
+            aspect: CropAspect::Free,
+            crop: CropRect::FULL,
+            ..self.crop_levelled()
+        };
+        self.crop_view = self
+            .crop_preview
+            .as_ref()
+            .map(|preview| geometry::apply(preview.clone(), &uncropped));
+    }
+
+    pub fn move_crop(&mut self, dx: i32, dy: i32) {
+        let crop = &mut self.crop_geometry.crop;
+        *crop = crop.moved(dx as f32 * CROP_STEP, dy as f32 * CROP_STEP);
+    }
+
+    pub fn resize_crop(&mut self, delta: i32) {
+        let crop = &mut self.crop_geometry.crop;
+        *crop = crop.resized(delta as f32 * CROP_STEP);
+    }
+
+    pub fn cycle_crop_aspect(&mut self, delta: i32) {
+        let geometry = &mut self.crop_geometry;
+        geometry.aspect = geometry.aspect.cycle(delta);
+    }
+
+    /// Switch the aspect ratio between portrait and landscape
+    pub fn swap_crop_aspect(&mut self) {
+        let geometry = &mut self.crop_geometry;
+        geometry.aspect = geometry.aspect.swapped();
+    }
+
+    pub fn turn_crop(&mut self, quarters: i32) {
+        self.crop_geometry.turn(quarters);
+        self.refresh_crop_view();
+    }
+
+    pub fn flip_crop(&mut self, horizontal: bool) {
+        self.crop_geometry.flip(horizontal);
+        self.refresh_crop_view();
+    }
+
+    /// Straighten in tenths of a degree, starting from the recorded roll
+    /// when the file was levelled automatically
+    pub fn straighten_crop(&mut self, tenths: i32) {
+        self.crop_geometry = self.crop_levelled();
+        let geometry = &mut self.crop_geometry;
+        let steps = (geometry.straighten * 10.0).round() as i32 + tenths;
+        geometry.set_straighten(steps as f32 / 10.0);
+        self.refresh_crop_view();
+    }
+
+    pub fn toggle_crop_auto_level(&mut self) {
+        let geometry = &mut self.crop_geometry;
+        geometry.auto_level = !geometry.auto_level;
+        if !geometry.auto_level {
+            geometry.straighten = 0.0;
+        }
+        self.refresh_crop_view();
+    }
+
+    pub fn reset_crop(&mut self) {
+        self.crop_geometry = Geometry::default();
+        self.refresh_crop_view();
+    }
+
+    /// Keep the edited geometry for the selected file
+    pub fn save_crop(&mut self) {
+        self.set_file_geometry(self.crop_geometry);
+        self.close_crop_editor();
+    }
+
+    /// Switch automatic horizon levelling for the selected file; levelling
+    /// by hand is done in the crop screen
+    pub fn toggle_file_auto_level(&mut self) {
+        if let Some(ref file) = self.selected_file {
+            let mut geometry = self.conversion_config.geometry_for(&file.path);
+            geometry.auto_level = !geometry.auto_level;
+            geometry.straighten = 0.0;
+            self.set_file_geometry(geometry);
+        }
+    }
+
+    fn set_file_geometry(&mut self, geometry: Geometry) {
+        if let Some(ref file) = self.selected_file {
+            let config = &mut self.conversion_config;
+            if geometry == config.geometry {
+                config.file_geometry.remove(&file.path);
+            } else {
+                config.file_geometry.insert(file.path.clone(), geometry);
+            }
+        }
+    }
+
+    pub fn close_crop_editor(&mut self) {
+        self.crop_preview = None;
+        self.crop_view = None;
+        self.crop_error = None;
+        self.state = AppState::FileInfo;
+    }
+
+    // Shoot statistics
+    fn load_shoot_stats(&mut self) {
+        let mut paths = find_gpr_files(&self.current_directory);
+        paths.sort();
+        let mut files: Vec<GprFile> = paths.into_iter().map(GprFile::new).collect();
+        self.catalog.load_all(&mut files);
+        self.save_catalog();
+        if let Some(ref filter) = self.file_filter {
+            files.retain(|file| filter.matches(file));
+        }
+
+        let mut stats = ShootStats::collect(&files);
+        stats.apply_privacy(&self.conversion_config.privacy);
+        self.shoot_stats = Some(stats);
+        self.state = AppState::Stats;
+    }
+
+    /// Save the dashboard figures next to the photos
+    pub fn export_shoot_stats(&mut self) {
+        let Some(ref stats) = self.shoot_stats else {
+            return;
+        };
+        let path = self.current_directory.join("gprprotool-stats.json");
+        let result = serde_json::to_string_pretty(stats)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+
+        match result {
+            Ok(()) => {
+                self.success_message = Some(format!("Statistics saved to {}", path.display()));
+                self.state = AppState::Complete;
+            }
+            Err(e) => {
+                self.error_message = Some(format!("Failed to save statistics: {}", e));
+                self.state = AppState::Error;
+            }
+        }
+    }
+
+    // Integrity check
+    /// Check every GPR file under the current directory on a worker thread,
+    /// with the SDK in a child process when the decoder is isolated
+    fn verify_directory(&mut self) {
+        let mut paths = find_gpr_files(&self.current_directory);
+        paths.sort();
+        let (sender, receiver) = mpsc::channel();
+        self.verify_job = Some(VerifyJob {
+            receiver,
+            directory: self.current_directory.clone(),
+            files: paths.len(),
+            done: 0,
+        });
+        self.conversion_progress = 0.0;
+        self.state = AppState::Converting;
+
+        let isolated = self.conversion_config.isolate_decoder;
+        std::thread::spawn(move || {
+            let mut results = Vec::with_capacity(paths.len());
+            for (done, path) in paths.iter().enumerate() {
+                let _ = sender.send(VerifyMessage::Progress(done));
+                results.push(verify::verify_file(path, isolated));
+            }
+            let _ = sender.send(VerifyMessage::Finished(results));
+        });
+    }
+
+    /// Take in progress from the directory check thread, and its results
+    /// once it is done. Called between redraws while a check is running.
+    pub fn poll_verify(&mut self) {
+        let Some(ref mut job) = self.verify_job else {
+            return;
+        };
+        let results = loop {
+            match job.receiver.try_recv() {
+                Ok(VerifyMessage::Progress(done)) => job.done = done,
+                Ok(VerifyMessage::Finished(results)) => break Ok(results),
+                Err(TryRecvError::Empty) => {
+                    self.conversion_progress = job.done as f32 * 100.0 / job.files.max(1) as f32;
+                    return;
+                }
+                Err(TryRecvError::Disconnected) => break Err("the check thread stopped unexpectedly"),
+            }
+        };
+        self.verify_job = None;
+
+        match results {
+            Ok(results) => {
+                self.verify_results = results;
+                // Problems first; the sort is stable so paths stay in order
+                self.verify_results.sort_by_key(|result| std::cmp::Reverse(result.verdict));
+                self.verify_scroll = 0;
+                self.state = AppState::Verify;
+            }
+            Err(e) => {
+                self.error_message = Some(format!("Verification failed: {}", e));
+                self.state = AppState::Error;
+            }
+        }
+    }
+
+    pub fn verify_count(&self, verdict: Verdict) -> usize {
+        self.verify_results.iter().filter(|r| r.verdict == verdict).count()
+    }
+
+    pub fn scroll_verify(&mut self, delta: i32) {
+        let max = self.verify_results.len().saturating_sub(1) as i32;
+        self.verify_scroll = (self.verify_scroll as i32 + delta).clamp(0, max) as usize;
+    }
+
+    /// Save the results next to the photos
+    pub fn export_verify_results(&mut self) {
+        let path = self.current_directory.join("gprprotool-verify.json");
+        let result = serde_json::to_string_pretty(&self.verify_results)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+
+        match result {
+            Ok(()) => {
+                self.success_message = Some(format!("Results saved to {}", path.display()));
+                self.state = AppState::Complete;
+            }
+            Err(e) => {
+                self.error_message = Some(format!("Failed to save results: {}", e));
+                self.state = AppState::Error;
+            }
+        }
+    }
+
     // Conversion config
     pub fn back_to_file_info(&mut self) {
         self.state = AppState::FileInfo;
     }
 
     pub fn next_config_option(&mut self) {
-        self.config_option_index = (self.config_option_index + 1) % 4;
+        let options = ConfigOption::all();
+        self.config_option_index = (self.config_option_index + 1) % options.len();
     }
 
     pub fn previous_config_option(&mut self) {
+        let options = ConfigOption::all();
         self.config_option_index = if self.config_option_index == 0 {
-            3
+            options.len() - 1
         } else {
             self.config_option_index - 1
         };
     }
 
     pub fn adjust_config_option(&mut self, delta: i32) {
-        match self.config_option_index {
-            0 => {
-                // Toggle output format
+        match ConfigOption::all().get(self.config_option_index) {
+            Some(ConfigOption::OutputFormat) => {
+                self.override_preset();
+                // Cycle output format
                 self.conversion_config.output_format = match self.conversion_config.output_format {
                     OutputFormat::Jpeg => OutputFormat::Png,
-                    OutputFormat::Png => OutputFormat::Jpeg,
+                    OutputFormat::Png => OutputFormat::Tiff,
+                    OutputFormat::Tiff => OutputFormat::Jpeg,
                 };
             }
-            1 => {
+            Some(ConfigOption::Quality) => {
                 // Adjust quality (only for JPEG)
                 if self.conversion_config.output_format == OutputFormat::Jpeg {
+                    self.override_preset();
                     let new_quality = (self.conversion_config.quality as i32 + delta * 5)
                         .clamp(1, 100) as u8;
                     self.conversion_config.quality = new_quality;
                 }
             }
-            2 => {
+            Some(ConfigOption::ColorSpace) => {
+                let develop = &mut self.conversion_config.develop;
+                develop.color_space = develop.color_space.cycle(delta);
+            }
+            Some(ConfigOption::PreserveMetadata) => {
                 // Toggle preserve metadata
                 self.conversion_config.preserve_metadata = !self.conversion_config.preserve_metadata;
             }
-            3 => {
+            Some(ConfigOption::Orientation) => {
+                self.conversion_config.orientation_mode = self.conversion_config.orientation_mode.cycle(delta);
+            }
+            Some(ConfigOption::Demosaic) => {
+                let develop = &mut self.conversion_config.develop;
+                develop.demosaic = develop.demosaic.cycle(delta);
+            }
+            Some(ConfigOption::Denoise) => {
+                let denoise = &mut self.conversion_config.develop.denoise;
+                denoise.method = denoise.method.toggle();
+            }
+            Some(ConfigOption::LumaNoise) => {
+                let denoise = &mut self.conversion_config.develop.denoise;
+                denoise.luma = DenoiseSettings::step_strength(denoise.luma, delta * 5);
+            }
+            Some(ConfigOption::ChromaNoise) => {
+                let denoise = &mut self.conversion_config.develop.denoise;
+                denoise.chroma = DenoiseSettings::step_strength(denoise.chroma, delta * 5);
+            }
+            Some(ConfigOption::WhiteBalance) => {
+                let develop = &mut self.conversion_config.develop;
+                develop.white_balance = develop.white_balance.cycle(delta);
+            }
+            Some(ConfigOption::Temperature) => {
+                let develop = &mut self.conversion_config.develop;
+                develop.white_balance = develop.white_balance.adjusted(delta * 100, 0);
+            }
+            Some(ConfigOption::Tint) => {
+                let develop = &mut self.conversion_config.develop;
+                develop.white_balance = develop.white_balance.adjusted(0, delta * 5);
+            }
+            Some(ConfigOption::Exposure) => {
+                // Third-stop steps, rounded so repeated steps land on 0 again
+                let develop = &mut self.conversion_config.develop;
+                let steps = (develop.exposure_ev * 3.0).round() as i32 + delta;
+                develop.exposure_ev = (steps as f32 / 3.0)
+                    .clamp(-DevelopSettings::MAX_EXPOSURE_EV, DevelopSettings::MAX_EXPOSURE_EV);
+            }
+            Some(ConfigOption::BlackPoint) => {
+                let develop = &mut self.conversion_config.develop;
+                develop.set_levels(develop.black_point + delta as f32 * 0.005, develop.white_point);
+            }
+            Some(ConfigOption::WhitePoint) => {
+                let develop = &mut self.conversion_config.develop;
+                develop.set_levels(develop.black_point, develop.white_point + delta as f32 * 0.02);
+            }
+            Some(ConfigOption::ToneCurve) => {
+                let tone = &mut self.conversion_config.develop.tone;
+                tone.curve = tone.curve.toggle();
+            }
+            Some(ConfigOption::Contrast) => {
+                let tone = &mut self.conversion_config.develop.tone;
+                tone.contrast = ToneSettings::step_slider(tone.contrast, delta * 5);
+            }
+            Some(ConfigOption::Highlights) => {
+                let tone = &mut self.conversion_config.develop.tone;
+                tone.highlights = ToneSettings::step_slider(tone.highlights, delta * 5);
+            }
+            Some(ConfigOption::Shadows) => {
+                let tone = &mut self.conversion_config.develop.tone;
+                tone.shadows = ToneSettings::step_slider(tone.shadows, delta * 5);
+            }
+            Some(ConfigOption::HighlightReconstruction) => {
+                let tone = &mut self.conversion_config.develop.tone;
+                tone.reconstruct_highlights = !tone.reconstruct_highlights;
+            }
+            Some(ConfigOption::TimeShift) => {
+                // Shift the camera clock in 15 minute steps
+                self.conversion_config.time_shift.offset_seconds += delta as i64 * 15 * 60;
+            }
+            Some(ConfigOption::UtcOffset) => {
+                // Step through zone offsets in 30 minute steps, off outside UTC-12..UTC+14
+                let time_shift = &mut self.conversion_config.time_shift;
+                time_shift.utc_offset_seconds = match time_shift.utc_offset_seconds {
+                    None => Some(0),
+                    Some(offset) => {
+                        let next = offset + delta as i64 * 30 * 60;
+                        (-12 * 3600..=14 * 3600).contains(&next).then_some(next)
+                    }
+                };
+            }
+            Some(ConfigOption::GpsPrivacy) => {
+                let privacy = &mut self.conversion_config.privacy;
+                privacy.gps = privacy.gps.cycle(delta);
+            }
+            Some(ConfigOption::DropIdentifiers) => {
+                let privacy = &mut self.conversion_config.privacy;
+                privacy.drop_identifiers = !privacy.drop_identifiers;
+            }
+            Some(ConfigOption::XmpSidecar) => {
+                self.conversion_config.write_xmp_sidecar = !self.conversion_config.write_xmp_sidecar;
+            }
+            Some(ConfigOption::LensCorrection) => {
+                let lens = &mut self.conversion_config.lens;
+                lens.correction = lens.correction.cycle(delta);
+            }
+            Some(ConfigOption::LensEdges) => {
+                let lens = &mut self.conversion_config.lens;
+                lens.edges = lens.edges.toggle();
+            }
+            Some(ConfigOption::AutoLevel) => {
+                let geometry = &mut self.conversion_config.geometry;
+                geometry.auto_level = !geometry.auto_level;
+            }
+            Some(ConfigOption::StitchFusion) => {
+                self.conversion_config.stitch_fusion = !self.conversion_config.stitch_fusion;
+            }
+            Some(ConfigOption::Resize) => {
+                let resize = &mut self.conversion_config.resize;
+                resize.mode = resize.mode.cycle(delta);
+            }
+            Some(ConfigOption::ResizeFilter) => {
+                let resize = &mut self.conversion_config.resize;
+                resize.filter = resize.filter.toggle();
+            }
+            Some(ConfigOption::Sharpen) => {
+                // 25% steps
+                let sharpen = &mut self.conversion_config.sharpen;
+                let steps = (sharpen.amount * 4.0).round() as i32 + delta;
+                sharpen.amount = (steps as f32 / 4.0).clamp(0.0, Sharpening::MAX_AMOUNT);
+            }
+            Some(ConfigOption::SharpenRadius) => {
+                let sharpen = &mut self.conversion_config.sharpen;
+                let steps = (sharpen.radius * 10.0).round() as i32 + delta;
+                sharpen.radius = (steps as f32 / 10.0).clamp(Sharpening::MIN_RADIUS, Sharpening::MAX_RADIUS);
+            }
+            Some(ConfigOption::SharpenThreshold) => {
+                let sharpen = &mut self.conversion_config.sharpen;
+                sharpen.threshold = (sharpen.threshold as i32 + delta).clamp(0, u8::MAX as i32) as u8;
+            }
+            Some(ConfigOption::IsolateDecoder) => {
+                self.conversion_config.isolate_decoder = !self.conversion_config.isolate_decoder;
+                self.catalog.set_isolated(self.conversion_config.isolate_decoder);
+            }
+            Some(ConfigOption::OutputDirectory) => {
                 // Output directory selection (TODO)
             }
+            Some(_) | None => {}
+        }
+    }
+
+    fn override_preset(&mut self) {
+        self.preset_overridden = true;
+        self.applied_preset = None;
+        self.conversion_config.camera_presets = false;
+    }
+
+    /// Open the editor for the selected option if it has one; metadata
+    /// edited from the settings screen applies to every file
+    pub fn begin_config_edit(&mut self) {
+        if let Some(ConfigOption::MetadataEdits) = ConfigOption::all().get(self.config_option_index) {
+            self.open_metadata_editor_for(None);
+        }
+    }
+
+    // Metadata editor
+    /// Edit the metadata written to the selected file's outputs. The edits
+    /// are kept for that file, including when it is converted in a batch.
+    pub fn open_metadata_editor(&mut self) {
+        let path = self.selected_file.as_ref().map(|file| file.path.clone());
+        if path.is_some() {
+            self.open_metadata_editor_for(path);
+        }
+    }
+
+    /// Edit the metadata of the file at `scope`, or with `None` of every
+    /// file converted afterwards
+    fn open_metadata_editor_for(&mut self, scope: Option<PathBuf>) {
+        self.edit_return_state = self.state;
+        self.edit_scope = scope;
+        self.edit_field_index = 0;
+        self.state = AppState::EditMetadata;
+    }
+
+    pub fn close_metadata_editor(&mut self) {
+        self.cancel_text_edit();
+        self.conversion_config.file_edits.retain(|_, edits| !edits.is_empty());
+        self.state = self.edit_return_state;
+    }
+
+    /// The edits the editor is changing, as they stand
+    pub fn scoped_edits(&self) -> MetadataEdits {
+        match self.edit_scope {
+            Some(ref path) => self.conversion_config.file_edits.get(path).cloned().unwrap_or_default(),
+            None => self.conversion_config.metadata_edits.clone(),
+        }
+    }
+
+    fn scoped_edits_mut(&mut self) -> &mut MetadataEdits {
+        match self.edit_scope {
+            Some(ref path) => self.conversion_config.file_edits.entry(path.clone()).or_default(),
+            None => &mut self.conversion_config.metadata_edits,
+        }
+    }
+
+    pub fn next_edit_field(&mut self) {
+        self.edit_field_index = (self.edit_field_index + 1) % EditField::all().len();
+    }
+
+    pub fn previous_edit_field(&mut self) {
+        let count = EditField::all().len();
+        self.edit_field_index = (self.edit_field_index + count - 1) % count;
+    }
+
+    pub fn adjust_edit_field(&mut self, delta: i32) {
+        if let Some(EditField::Rating) = EditField::all().get(self.edit_field_index) {
+            // None, then 0-5 stars
+            let edits = self.scoped_edits_mut();
+            let current = edits.rating.map(|r| r as i32 + 1).unwrap_or(0);
+            let next = (current + delta).clamp(0, 6);
+            edits.rating = if next == 0 { None } else { Some((next - 1) as u8) };
+        }
+    }
+
+    /// Remove the selected edit so the camera's value (if any) is used
+    pub fn clear_edit_field(&mut self) {
+        let field = EditField::all().get(self.edit_field_index).copied();
+        let edits = self.scoped_edits_mut();
+        match field {
+            Some(EditField::Artist) => edits.creator = None,
+            Some(EditField::Copyright) => edits.copyright = None,
+            Some(EditField::Caption) => edits.caption = None,
+            Some(EditField::Keywords) => edits.keywords.clear(),
+            Some(EditField::Rating) => edits.rating = None,
+            Some(EditField::Gps) => edits.gps_position = None,
+            None => {}
+        }
+    }
+
+    /// Start editing the selected field if it takes free text
+    pub fn begin_text_edit(&mut self) {
+        let edits = self.scoped_edits();
+        self.text_input = match EditField::all().get(self.edit_field_index) {
+            Some(EditField::Artist) => Some(edits.creator.clone().unwrap_or_default()),
+            Some(EditField::Copyright) => Some(edits.copyright.clone().unwrap_or_default()),
+            Some(EditField::Caption) => Some(edits.caption.clone().unwrap_or_default()),
+            Some(EditField::Keywords) => Some(edits.keywords.join(", ")),
+            Some(EditField::Gps) => Some(
+                edits
+                    .gps_position
+                    .map(|(lat, lon)| format!("{}, {}", lat, lon))
+                    .unwrap_or_default(),
+            ),
+            _ => None,
+        };
+    }
+
+    pub fn text_input_char(&mut self, c: char) {
+        if let Some(ref mut input) = self.text_input {
+            input.push(c);
+        }
+    }
+
+    pub fn text_input_backspace(&mut self) {
+        if let Some(ref mut input) = self.text_input {
+            input.pop();
+        }
+    }
+
+    pub fn cancel_text_edit(&mut self) {
+        self.text_input = None;
+        self.input_error = None;
+    }
+
+    pub fn commit_text_edit(&mut self) {
+        let Some(input) = self.text_input.take() else {
+            return;
+        };
+        let value = Some(input.trim().to_string()).filter(|v| !v.is_empty());
+
+        let field = EditField::all().get(self.edit_field_index).copied();
+        let position = match (field, &value) {
+            (Some(EditField::Gps), Some(position)) => match MetadataEdits::parse_position(position) {
+                Some(position) => Some(position),
+                None => {
+                    self.input_error = Some("Expected latitude, longitude".to_string());
+                    self.text_input = Some(input);
+                    return;
+                }
+            },
+            _ => None,
+        };
+        let edits = self.scoped_edits_mut();
+        match field {
+            Some(EditField::Artist) => edits.creator = value,
+            Some(EditField::Copyright) => edits.copyright = value,
+            Some(EditField::Caption) => edits.caption = value,
+            Some(EditField::Keywords) => edits.keywords = MetadataEdits::parse_keywords(&input),
+            Some(EditField::Gps) => edits.gps_position = position,
             _ => {}
         }
+        self.input_error = None;
     }
 
     pub fn start_conversion(&mut self) {
@@ -259,9 +1186,28 @@ impl App {
             self.state = AppState::Converting;
             self.conversion_progress = 0.0;
 
-            // Perform actual conversion
-            match crate::gpr::GprConverter::convert(gpr_file, &self.conversion_config) {
+            // Perform actual conversion; a Fusion file is stitched with its
+            // other half when that is enabled and the partner is present
+            let pair = fusion::pair_paths(&gpr_file.path).filter(|_| self.conversion_config.stitch_fusion);
+            let result = match pair {
+                Some((ref front, ref back)) => crate::gpr::GprConverter::convert_pair(
+                    &GprFile::new(front.clone()),
+                    &GprFile::new(back.clone()),
+                    &self.conversion_config,
+                ),
+                None => crate::gpr::GprConverter::convert(gpr_file, &self.conversion_config),
+            };
+            match result {
                 Ok(output_path) => {
+                    let sources = match pair {
+                        Some((front, back)) => vec![front, back],
+                        None => vec![gpr_file.path.clone()],
+                    };
+                    for source in &sources {
+                        self.catalog
+                            .record_conversion(source, &output_path, &self.conversion_config);
+                    }
+                    self.save_catalog();
                     self.conversion_progress = 100.0;
                     self.success_message = Some(format!(
                         "Conversion completed successfully!\n\nOutput: {}",
@@ -279,11 +1225,114 @@ impl App {
         }
     }
 
+    /// Convert every GPR file the browser lists for the current directory,
+    /// honouring the filter, with the current settings and metadata edits
+    fn batch_convert_directory(&mut self) {
+        self.load_directory();
+        let mut files: Vec<GprFile> = self
+            .files
+            .iter()
+            .filter(|path| path.is_file() && is_gpr_file(path))
+            .cloned()
+            .map(GprFile::new)
+            .collect();
+        if files.is_empty() {
+            self.error_message = Some(format!(
+                "No .gpr files to convert in {}",
+                self.current_directory.display()
+            ));
+            self.state = AppState::Error;
+            return;
+        }
+
+        self.catalog.load_all(&mut files);
+        let (sender, receiver) = mpsc::channel();
+        self.batch = Some(BatchJob {
+            receiver,
+            directory: self.current_directory.clone(),
+            files: files.len(),
+            done: 0,
+        });
+        self.conversion_progress = 0.0;
+        self.state = AppState::Converting;
+
+        let config = self.conversion_config.clone();
+        std::thread::spawn(move || {
+            let progress = sender.clone();
+            let callback = Box::new(move |done, _| {
+                let _ = progress.send(BatchMessage::Progress(done));
+            });
+            let result = GprConverter::batch_convert(&files, &config, Some(callback));
+            let _ = sender.send(BatchMessage::Finished(result));
+        });
+    }
+
+    /// Take in progress from the batch conversion thread, and its result
+    /// once it is done. Called between redraws while a batch is running.
+    pub fn poll_batch(&mut self) {
+        let Some(ref mut job) = self.batch else {
+            return;
+        };
+        let result = loop {
+            match job.receiver.try_recv() {
+                Ok(BatchMessage::Progress(done)) => job.done = done,
+                Ok(BatchMessage::Finished(result)) => break result,
+                Err(TryRecvError::Empty) => {
+                    self.conversion_progress = job.done as f32 * 100.0 / job.files.max(1) as f32;
+                    return;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    break Err(anyhow::anyhow!("the conversion thread stopped unexpectedly"))
+                }
+            }
+        };
+        let Some(job) = self.batch.take() else {
+            return;
+        };
+
+        match result {
+            Ok(outputs) => {
+                for (source, output) in &outputs {
+                    self.catalog.record_conversion(source, output, &self.conversion_config);
+                }
+                self.save_catalog();
+                self.conversion_progress = 100.0;
+                self.success_message = Some(format!(
+                    "Converted {} of {} files in {}\n\nMetadata edits: {}{}",
+                    outputs.len(),
+                    job.files,
+                    job.directory.display(),
+                    self.conversion_config.metadata_edits.summary(),
+                    match self.conversion_config.file_edits.len() {
+                        0 => String::new(),
+                        files => format!(" (and the own edits of {} files)", files),
+                    }
+                ));
+                self.state = AppState::Complete;
+            }
+            Err(e) => {
+                self.error_message = Some(format!("Batch conversion failed: {}", e));
+                self.state = AppState::Error;
+            }
+        }
+    }
+
     pub fn cancel_conversion(&mut self) {
+        // A running batch or check cannot be stopped part way through a file
+        if self.batch.is_some() || self.verify_job.is_some() {
+            return;
+        }
         self.state = AppState::ConversionConfig;
         self.conversion_progress = 0.0;
     }
 
+    /// Write the catalog, logging rather than interrupting on failure
+    pub fn save_catalog(&mut self) {
+        if let Err(e) = self.catalog.save() {
+            log::warn!("{:#}", e);
+        }
+    }
+
     // Navigation
     pub fn back_to_main_menu(&mut self) {
         self.state = AppState::MainMenu;
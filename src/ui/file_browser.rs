@@ -12,7 +12,7 @@ pub fn render_file_browser(f: &mut Frame, app: &App, area: Rect) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(3),
+            Constraint::Length(4),
             Constraint::Min(0),
             Constraint::Length(3),
         ])
@@ -20,8 +20,35 @@ pub fn render_file_browser(f: &mut Frame, app: &App, area: Rect) {
 
     // Header
     let current_path = app.current_directory.display().to_string();
-    let header = Paragraph::new(format!("Current Directory: {}", current_path))
-        .block(Block::default().borders(Borders::ALL).title("File Browser"));
+    let filter_line = if let Some(ref input) = app.text_input {
+        let mut spans = vec![
+            Span::styled("Filter: ", Style::default().fg(Color::Yellow)),
+            Span::raw(format!("{}_", input)),
+        ];
+        if let Some(ref error) = app.input_error {
+            spans.push(Span::styled(format!("  {}", error), Style::default().fg(Color::Red)));
+        }
+        Line::from(spans)
+    } else if let Some(ref filter) = app.file_filter {
+        Line::from(vec![
+            Span::styled("Filter: ", Style::default().fg(Color::Yellow)),
+            Span::raw(filter.to_string()),
+            Span::styled(
+                format!(" ({} files hidden)", app.hidden_files),
+                Style::default().fg(Color::Gray),
+            ),
+        ])
+    } else {
+        Line::from(Span::styled(
+            "No filter, e.g. iso>800 camera:HERO7 date:2024-06",
+            Style::default().fg(Color::Gray),
+        ))
+    };
+    let header = Paragraph::new(vec![
+        Line::from(format!("Current Directory: {}", current_path)),
+        filter_line,
+    ])
+    .block(Block::default().borders(Borders::ALL).title("File Browser"));
     f.render_widget(header, chunks[0]);
 
     // File list
@@ -69,6 +96,8 @@ pub fn render_file_browser(f: &mut Frame, app: &App, area: Rect) {
             Span::raw("Select | "),
             Span::styled("Backspace: ", Style::default().fg(Color::Gray)),
             Span::raw("Parent Dir | "),
+            Span::styled("/: ", Style::default().fg(Color::Gray)),
+            Span::raw("Filter | "),
             Span::styled("Esc/q: ", Style::default().fg(Color::Gray)),
             Span::raw("Back"),
         ]),
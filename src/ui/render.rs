@@ -2,11 +2,16 @@ use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Gauge, List, ListItem, Paragraph, Wrap},
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, Gauge, List, ListItem, ListState, Paragraph, Wrap},
     Frame,
 };
 
-use super::app::{App, AppState, MainMenuItem};
+use super::app::{App, AppState, ConfigOption, EditField, MainMenuItem};
+use crate::gpr::verify::Verdict;
+use crate::models::shoot_stats::HistogramBin;
+use crate::gpr::geometry::straightened_size;
+use crate::models::{CameraProfile, DenoiseSettings, ToneSettings};
+use image::{ImageBuffer, Rgb};
 use super::file_browser;
 
 pub fn render(f: &mut Frame, app: &App) {
@@ -14,6 +19,11 @@ pub fn render(f: &mut Frame, app: &App) {
         AppState::MainMenu => render_main_menu(f, app),
         AppState::FileBrowser => file_browser::render_file_browser(f, app, f.area()),
         AppState::FileInfo => render_file_info(f, app),
+        AppState::Compare => render_compare(f, app),
+        AppState::Stats => render_stats(f, app),
+        AppState::Verify => render_verify(f, app),
+        AppState::EditMetadata => render_edit_metadata(f, app),
+        AppState::Crop => render_crop(f, app),
         AppState::ConversionConfig => render_conversion_config(f, app),
         AppState::Converting => render_converting(f, app),
         AppState::Complete => render_complete(f, app),
@@ -128,6 +138,15 @@ fn render_file_info(f: &mut Frame, app: &App) {
         ]));
         lines.push(Line::from(""));
 
+        let geometry = app.conversion_config.geometry_for(&file.path);
+        if !geometry.is_identity() {
+            lines.push(Line::from(vec![
+                Span::styled("Crop & Rotate: ", Style::default().fg(Color::Gray)),
+                Span::raw(geometry.summary()),
+            ]));
+            lines.push(Line::from(""));
+        }
+
         if let Some(ref metadata) = file.metadata {
             lines.push(Line::from(Span::styled(
                 "Metadata:",
@@ -140,9 +159,76 @@ fn render_file_info(f: &mut Frame, app: &App) {
                 Span::raw(&metadata.camera_model),
             ]));
 
+            match CameraProfile::lookup(&metadata.camera_model) {
+                Some(profile) => {
+                    lines.push(Line::from(vec![
+                        Span::styled("Sensor: ", Style::default().fg(Color::Gray)),
+                        Span::raw(format!(
+                            "{}, {} CFA",
+                            profile.sensor_size_display(),
+                            profile.cfa_pattern.as_str()
+                        )),
+                    ]));
+                    lines.push(Line::from(vec![
+                        Span::styled("Lens Modes: ", Style::default().fg(Color::Gray)),
+                        Span::raw(profile.fov_modes.join(", ")),
+                    ]));
+                    lines.push(Line::from(vec![
+                        Span::styled("No RAW With: ", Style::default().fg(Color::Gray)),
+                        Span::raw(profile.raw_disabled_display()),
+                    ]));
+                    if let Some(noise) = profile.noise {
+                        let noise = noise.at_iso(metadata.iso.unwrap_or(100));
+                        lines.push(Line::from(vec![
+                            Span::styled("Noise Profile: ", Style::default().fg(Color::Gray)),
+                            Span::raw(format!("S {:.2e}, O {:.2e}", noise.scale, noise.offset)),
+                        ]));
+                    }
+                    if metadata.cfa_pattern.is_some_and(|cfa| cfa != profile.cfa_pattern) {
+                        lines.push(Line::from(Span::styled(
+                            format!(
+                                "Warning: CFA pattern differs from a {} ({})",
+                                profile.name(),
+                                profile.cfa_pattern.as_str()
+                            ),
+                            Style::default().fg(Color::Yellow),
+                        )));
+                    }
+                    if (profile.sensor_width, profile.sensor_height)
+                        != (metadata.sensor_width, metadata.sensor_height)
+                    {
+                        lines.push(Line::from(Span::styled(
+                            format!(
+                                "Warning: sensor size differs from a {} ({}x{})",
+                                profile.name(),
+                                profile.sensor_width,
+                                profile.sensor_height
+                            ),
+                            Style::default().fg(Color::Yellow),
+                        )));
+                    }
+                }
+                None => {
+                    lines.push(Line::from(Span::styled(
+                        "Warning: unknown camera model, capabilities not known",
+                        Style::default().fg(Color::Yellow),
+                    )));
+                }
+            }
+
+            lines.push(Line::from(vec![
+                Span::styled("Sensor Size: ", Style::default().fg(Color::Gray)),
+                Span::raw(metadata.sensor_size_display()),
+            ]));
+
+            lines.push(Line::from(vec![
+                Span::styled("Active Area: ", Style::default().fg(Color::Gray)),
+                Span::raw(metadata.active_area_display()),
+            ]));
+
             lines.push(Line::from(vec![
-                Span::styled("Dimensions: ", Style::default().fg(Color::Gray)),
-                Span::raw(format!("{}x{}", metadata.width, metadata.height)),
+                Span::styled("Output Size: ", Style::default().fg(Color::Gray)),
+                Span::raw(metadata.output_size_display()),
             ]));
 
             if let Some(iso) = metadata.iso {
@@ -165,6 +251,60 @@ fn render_file_info(f: &mut Frame, app: &App) {
                     Span::raw(f_num),
                 ]));
             }
+
+            lines.push(Line::from(vec![
+                Span::styled("Orientation: ", Style::default().fg(Color::Gray)),
+                Span::raw(metadata.orientation_display()),
+            ]));
+
+            // Levelling this file will get, automatic or by hand
+            let config = app.conversion_config.for_file(&file.path);
+            let geometry = config.geometry;
+            let levelling = if geometry.auto_level {
+                match metadata.roll_degrees() {
+                    Some(_) => {
+                        let levelled = geometry.levelled(metadata, config.baked_orientation(metadata));
+                        format!("Auto, turned {:+.1}°", levelled.straighten)
+                    }
+                    None => "Auto, no angle recorded".to_string(),
+                }
+            } else if geometry.straighten != 0.0 {
+                format!("By hand, turned {:+.1}°", geometry.straighten)
+            } else {
+                "Off".to_string()
+            };
+            lines.push(Line::from(vec![
+                Span::styled("Camera Roll: ", Style::default().fg(Color::Gray)),
+                Span::raw(metadata.roll_display()),
+            ]));
+            lines.push(Line::from(vec![
+                Span::styled("Levelling: ", Style::default().fg(Color::Gray)),
+                Span::raw(levelling),
+            ]));
+
+            lines.push(Line::from(vec![
+                Span::styled("Thumbnail: ", Style::default().fg(Color::Gray)),
+                Span::raw(metadata.thumbnail_display()),
+            ]));
+
+            if let Some(ref serial) = metadata.serial_number {
+                lines.push(Line::from(vec![
+                    Span::styled("Serial: ", Style::default().fg(Color::Gray)),
+                    Span::raw(serial),
+                ]));
+            }
+
+            lines.push(Line::from(vec![
+                Span::styled("Read Via: ", Style::default().fg(Color::Gray)),
+                Span::raw(metadata.source.as_str()),
+            ]));
+
+            if let Some(last) = app.catalog.get(&file.path).and_then(|e| e.last_conversion()) {
+                lines.push(Line::from(vec![
+                    Span::styled("Last Converted: ", Style::default().fg(Color::Gray)),
+                    Span::raw(format!("{} to {}", last.converted_at, last.output.display())),
+                ]));
+            }
         } else {
             lines.push(Line::from(Span::styled(
                 "No metadata available",
@@ -179,9 +319,26 @@ fn render_file_info(f: &mut Frame, app: &App) {
     f.render_widget(info, chunks[0]);
 
     // Help
+    let compare_hint = match app.compare_base {
+        Some(ref base) if app.selected_file.as_ref().is_some_and(|f| &f.path != base) => format!(
+            "Compare with {} | ",
+            base.file_name().and_then(|n| n.to_str()).unwrap_or("marked file")
+        ),
+        _ => "Compare with marked | ".to_string(),
+    };
     let help_text = vec![Line::from(vec![
         Span::styled("c: ", Style::default().fg(Color::Gray)),
         Span::raw("Convert | "),
+        Span::styled("e: ", Style::default().fg(Color::Gray)),
+        Span::raw("Edit this file's metadata | "),
+        Span::styled("r: ", Style::default().fg(Color::Gray)),
+        Span::raw("Crop/rotate | "),
+        Span::styled("l: ", Style::default().fg(Color::Gray)),
+        Span::raw("Auto level | "),
+        Span::styled("m: ", Style::default().fg(Color::Gray)),
+        Span::raw("Mark for compare | "),
+        Span::styled("d: ", Style::default().fg(Color::Gray)),
+        Span::raw(compare_hint),
         Span::styled("Esc/q: ", Style::default().fg(Color::Gray)),
         Span::raw("Back"),
     ])];
@@ -191,6 +348,556 @@ fn render_file_info(f: &mut Frame, app: &App) {
     f.render_widget(help, chunks[1]);
 }
 
+fn render_compare(f: &mut Frame, app: &App) {
+    let area = f.area();
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Min(0),
+            Constraint::Length(3),
+        ])
+        .split(area);
+
+    let file_name = |path: Option<&std::path::PathBuf>| {
+        path.and_then(|p| p.file_name())
+            .and_then(|n| n.to_str())
+            .unwrap_or("?")
+            .to_string()
+    };
+    let changed = app.compare_rows.iter().filter(|row| row.is_different()).count();
+    let header = Paragraph::new(vec![
+        Line::from(vec![
+            Span::styled("A: ", Style::default().fg(Color::Cyan)),
+            Span::raw(file_name(app.compare_base.as_ref())),
+            Span::styled("  B: ", Style::default().fg(Color::Cyan)),
+            Span::raw(file_name(app.selected_file.as_ref().map(|f| &f.path))),
+        ]),
+        Line::from(format!("{} of {} fields differ", changed, app.compare_rows.len())),
+    ])
+    .block(Block::default().borders(Borders::ALL).title("Compare Metadata"));
+    f.render_widget(header, chunks[0]);
+
+    let rows = app.visible_compare_rows();
+    let label_width = rows.iter().map(|row| row.label().chars().count()).max().unwrap_or(0);
+    let mut lines = Vec::new();
+    for row in rows.iter().skip(app.compare_scroll) {
+        let label = format!("{:width$}  ", row.label(), width = label_width);
+        let left = row.left.clone().unwrap_or_else(|| "-".to_string());
+        let right = row.right.clone().unwrap_or_else(|| "-".to_string());
+        if row.is_different() {
+            let style = Style::default().fg(Color::Yellow);
+            lines.push(Line::from(vec![
+                Span::styled(label, style.add_modifier(Modifier::BOLD)),
+                Span::styled(format!("A: {}", left), style),
+            ]));
+            lines.push(Line::from(vec![
+                Span::raw(" ".repeat(label_width + 2)),
+                Span::styled(format!("B: {}", right), style),
+            ]));
+        } else {
+            lines.push(Line::from(vec![
+                Span::styled(label, Style::default().fg(Color::Gray)),
+                Span::raw(left),
+            ]));
+        }
+    }
+
+    let body = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("Fields"));
+    f.render_widget(body, chunks[1]);
+
+    let filter_label = if app.compare_changed_only {
+        "Show all | "
+    } else {
+        "Only differences | "
+    };
+    let help = Paragraph::new(Line::from(vec![
+        Span::styled("↑/↓: ", Style::default().fg(Color::Gray)),
+        Span::raw("Scroll | "),
+        Span::styled("f: ", Style::default().fg(Color::Gray)),
+        Span::raw(filter_label),
+        Span::styled("Esc/q: ", Style::default().fg(Color::Gray)),
+        Span::raw("Back"),
+    ]))
+    .block(Block::default().borders(Borders::ALL).title("Help"));
+    f.render_widget(help, chunks[2]);
+}
+
+fn render_stats(f: &mut Frame, app: &App) {
+    let area = f.area();
+    let Some(ref stats) = app.shoot_stats else {
+        return;
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(6),
+            Constraint::Percentage(50),
+            Constraint::Min(0),
+            Constraint::Length(3),
+        ])
+        .split(area);
+
+    let summary = Paragraph::new(vec![
+        Line::from(vec![
+            Span::styled("Files: ", Style::default().fg(Color::Gray)),
+            Span::raw(format!("{} ({} unreadable)", stats.total_files, stats.unreadable)),
+            Span::styled("  Storage: ", Style::default().fg(Color::Gray)),
+            Span::raw(stats.storage_display()),
+        ]),
+        Line::from(vec![
+            Span::styled("Dates: ", Style::default().fg(Color::Gray)),
+            Span::raw(stats.date_range_display()),
+        ]),
+        Line::from(vec![
+            Span::styled("Geotagged: ", Style::default().fg(Color::Gray)),
+            Span::raw(format!("{} ({})", stats.geotagged, stats.gps_bounds_display())),
+        ]),
+        Line::from(vec![
+            Span::styled("Directory: ", Style::default().fg(Color::Gray)),
+            Span::raw(app.current_directory.display().to_string()),
+        ]),
+    ])
+    .block(Block::default().borders(Borders::ALL).title("Shoot Statistics"));
+    f.render_widget(summary, chunks[0]);
+
+    let top = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(chunks[1]);
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(chunks[2]);
+
+    let to_bins = |map: &std::collections::BTreeMap<String, usize>| -> Vec<HistogramBin> {
+        map.iter()
+            .map(|(label, count)| HistogramBin {
+                label: label.clone(),
+                count: *count,
+            })
+            .collect()
+    };
+
+    f.render_widget(bar_chart("Cameras", &to_bins(&stats.cameras), None), top[0]);
+    f.render_widget(bar_chart("Photos per Day", &to_bins(&stats.photos_per_day), None), top[1]);
+    f.render_widget(bar_chart("ISO", &stats.iso, Some(bottom[0].width)), bottom[0]);
+    f.render_widget(
+        bar_chart("Shutter Speed", &stats.shutter_speed, Some(bottom[1].width)),
+        bottom[1],
+    );
+
+    let help = Paragraph::new(Line::from(vec![
+        Span::styled("s: ", Style::default().fg(Color::Gray)),
+        Span::raw("Save JSON | "),
+        Span::styled("Esc/q: ", Style::default().fg(Color::Gray)),
+        Span::raw("Back"),
+    ]))
+    .block(Block::default().borders(Borders::ALL).title("Help"));
+    f.render_widget(help, chunks[3]);
+}
+
+fn render_verify(f: &mut Frame, app: &App) {
+    let area = f.area();
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Min(0),
+            Constraint::Length(3),
+        ])
+        .split(area);
+
+    let header = Paragraph::new(vec![
+        Line::from(vec![
+            Span::styled("OK: ", Style::default().fg(Color::Green)),
+            Span::raw(app.verify_count(Verdict::Ok).to_string()),
+            Span::styled("  Warnings: ", Style::default().fg(Color::Yellow)),
+            Span::raw(app.verify_count(Verdict::Warning).to_string()),
+            Span::styled("  Corrupt: ", Style::default().fg(Color::Red)),
+            Span::raw(app.verify_count(Verdict::Corrupt).to_string()),
+        ]),
+        Line::from(vec![
+            Span::styled("Directory: ", Style::default().fg(Color::Gray)),
+            Span::raw(app.current_directory.display().to_string()),
+        ]),
+    ])
+    .block(Block::default().borders(Borders::ALL).title("Verify Files"));
+    f.render_widget(header, chunks[0]);
+
+    let mut lines = Vec::new();
+    for result in app.verify_results.iter().skip(app.verify_scroll) {
+        let color = match result.verdict {
+            Verdict::Ok => Color::Green,
+            Verdict::Warning => Color::Yellow,
+            Verdict::Corrupt => Color::Red,
+        };
+        let name = result
+            .path
+            .strip_prefix(&app.current_directory)
+            .unwrap_or(&result.path)
+            .display()
+            .to_string();
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{:<8}", result.verdict.as_str()),
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(name),
+        ]));
+        if result.verdict != Verdict::Ok {
+            lines.push(Line::from(Span::styled(
+                format!("        {}", result.reason),
+                Style::default().fg(Color::Gray),
+            )));
+        }
+    }
+    if app.verify_results.is_empty() {
+        lines.push(Line::from("No .gpr files found"));
+    }
+
+    let body = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Files"));
+    f.render_widget(body, chunks[1]);
+
+    let help = Paragraph::new(Line::from(vec![
+        Span::styled("↑/↓: ", Style::default().fg(Color::Gray)),
+        Span::raw("Scroll | "),
+        Span::styled("s: ", Style::default().fg(Color::Gray)),
+        Span::raw("Save JSON | "),
+        Span::styled("Esc/q: ", Style::default().fg(Color::Gray)),
+        Span::raw("Back"),
+    ]))
+    .block(Block::default().borders(Borders::ALL).title("Help"));
+    f.render_widget(help, chunks[2]);
+}
+
+/// Histogram as a bar chart. Without a width the bars are horizontal, which
+/// leaves room for long labels; otherwise vertical bars share the width.
+fn bar_chart<'a>(title: &'a str, bins: &[HistogramBin], width: Option<u16>) -> BarChart<'a> {
+    let bars: Vec<Bar> = bins
+        .iter()
+        .map(|bin| {
+            Bar::default()
+                .label(Line::from(bin.label.clone()))
+                .value(bin.count as u64)
+        })
+        .collect();
+
+    let chart = BarChart::default()
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .data(BarGroup::default().bars(&bars))
+        .bar_style(Style::default().fg(Color::Cyan))
+        .value_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+
+    match width {
+        None => chart.direction(Direction::Horizontal).bar_width(1).bar_gap(0),
+        Some(width) => {
+            let slot = width.saturating_sub(2) / (bins.len().max(1) as u16);
+            chart.bar_width(slot.saturating_sub(1).clamp(1, 7)).bar_gap(1)
+        }
+    }
+}
+
+fn render_edit_metadata(f: &mut Frame, app: &App) {
+    let area = f.area();
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Min(0),
+            Constraint::Length(3),
+        ])
+        .split(area);
+
+    let file = app.selected_file.as_ref();
+    let (scope, note) = match app.edit_scope {
+        Some(ref path) => (
+            path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+            "Written to this file's outputs and sidecars, also in batches, over the edits for all files.",
+        ),
+        None => (
+            "All files".to_string(),
+            "Written to outputs and sidecars of every conversion, including batches, unless a file has its own.",
+        ),
+    };
+    let header = Paragraph::new(vec![
+        Line::from(vec![
+            Span::styled("Editing: ", Style::default().fg(Color::Cyan)),
+            Span::raw(scope),
+        ]),
+        Line::from(Span::styled(
+            format!("{} Source files are not changed.", note),
+            Style::default().fg(Color::Gray),
+        )),
+    ])
+    .block(Block::default().borders(Borders::ALL).title("Edit Metadata"));
+    f.render_widget(header, chunks[0]);
+
+    // A file's own edits, falling back to those for all files
+    let own = app.scoped_edits();
+    let edits = match app.edit_scope {
+        Some(_) => &app.conversion_config.metadata_edits.merged(&own),
+        None => &own,
+    };
+    let camera_gps = file
+        .and_then(|f| f.metadata.as_ref())
+        .and_then(|m| Some((m.gps_latitude?, m.gps_longitude?)));
+    let none = || "None".to_string();
+    let items: Vec<ListItem> = EditField::all()
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let selected = i == app.edit_field_index;
+            let value = match (selected, &app.text_input) {
+                (true, Some(input)) => format!("{}_", input),
+                _ => match field {
+                    EditField::Artist => edits.creator.clone().unwrap_or_else(none),
+                    EditField::Copyright => edits.copyright.clone().unwrap_or_else(none),
+                    EditField::Caption => edits.caption.clone().unwrap_or_else(none),
+                    EditField::Keywords => edits.keywords_display(),
+                    EditField::Rating => edits.rating_display(),
+                    EditField::Gps => match (edits.gps_position, camera_gps) {
+                        (None, Some((lat, lon))) => format!("From camera ({:.6}, {:.6})", lat, lon),
+                        _ => edits.gps_display(),
+                    },
+                },
+            };
+
+            let (prefix, style) = if selected {
+                ("> ", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+            } else {
+                ("  ", Style::default().fg(Color::White))
+            };
+            ListItem::new(format!("{}{}: {}", prefix, field.as_str(), value)).style(style)
+        })
+        .collect();
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title("Fields"));
+    f.render_widget(list, chunks[1]);
+
+    let help_text = if app.text_input.is_some() {
+        let mut spans = vec![
+            Span::styled("Enter: ", Style::default().fg(Color::Gray)),
+            Span::raw("Save | "),
+            Span::styled("Esc: ", Style::default().fg(Color::Gray)),
+            Span::raw("Cancel"),
+        ];
+        if let Some(ref error) = app.input_error {
+            spans.push(Span::styled(format!("  {}", error), Style::default().fg(Color::Red)));
+        }
+        Line::from(spans)
+    } else {
+        let text_field = EditField::all()
+            .get(app.edit_field_index)
+            .is_some_and(|field| field.is_text());
+        let (edit_key, edit_label) = if text_field {
+            ("e/Enter: ", "Edit | ")
+        } else {
+            ("←/→: ", "Adjust | ")
+        };
+        Line::from(vec![
+            Span::styled("↑/↓: ", Style::default().fg(Color::Gray)),
+            Span::raw("Navigate | "),
+            Span::styled(edit_key, Style::default().fg(Color::Gray)),
+            Span::raw(edit_label),
+            Span::styled("x: ", Style::default().fg(Color::Gray)),
+            Span::raw("Clear | "),
+            Span::styled("Esc: ", Style::default().fg(Color::Gray)),
+            Span::raw("Back"),
+        ])
+    };
+    let help = Paragraph::new(help_text)
+        .block(Block::default().borders(Borders::ALL).title("Help"));
+    f.render_widget(help, chunks[2]);
+}
+
+fn render_crop(f: &mut Frame, app: &App) {
+    let area = f.area();
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(4)])
+        .split(area);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(32)])
+        .split(rows[0]);
+
+    // Preview with the crop outlined and everything outside it dimmed
+    let title = app
+        .selected_file
+        .as_ref()
+        .map(|file| format!("Crop & Rotate: {}", file.filename))
+        .unwrap_or_else(|| "Crop & Rotate".to_string());
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let inner = block.inner(columns[0]);
+    let preview = match (&app.crop_view, &app.crop_error) {
+        (Some(view), _) => {
+            let crop = app.crop_geometry.crop_pixels(view.width(), view.height());
+            Paragraph::new(preview_lines(view, crop, inner.width, inner.height))
+        }
+        (None, error) => Paragraph::new(Span::styled(
+            error.clone().unwrap_or_else(|| "No preview".to_string()),
+            Style::default().fg(Color::Yellow),
+        ))
+        .wrap(Wrap { trim: true }),
+    };
+    f.render_widget(preview.block(block), columns[0]);
+
+    // Settings, with the output size when the full size is known
+    let geometry = &app.crop_geometry;
+    let levelled = app.crop_levelled();
+    let flips = match (geometry.flip_horizontal, geometry.flip_vertical) {
+        (false, false) => "None",
+        (true, false) => "Horizontal",
+        (false, true) => "Vertical",
+        (true, true) => "Both",
+    };
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("Rotation: ", Style::default().fg(Color::Gray)),
+            Span::raw(geometry.rotation.as_str()),
+        ]),
+        Line::from(vec![
+            Span::styled("Flip: ", Style::default().fg(Color::Gray)),
+            Span::raw(flips),
+        ]),
+        Line::from(vec![
+            Span::styled("Straighten: ", Style::default().fg(Color::Gray)),
+            Span::raw(format!(
+                "{:+.1}°{}",
+                levelled.straighten,
+                if geometry.auto_level { " (auto)" } else { "" }
+            )),
+        ]),
+        Line::from(vec![
+            Span::styled("Aspect: ", Style::default().fg(Color::Gray)),
+            Span::raw(geometry.aspect.display()),
+        ]),
+    ];
+    let full_size = app
+        .selected_file
+        .as_ref()
+        .and_then(|file| file.metadata.as_ref())
+        .map(|metadata| (metadata.width, metadata.height));
+    if let (Some((width, height)), Some(ref view)) = (full_size, &app.crop_view) {
+        // The full image turned the same way as the preview
+        let (long, short) = (width.max(height), width.min(height));
+        let (view_width, view_height) = if view.width() >= view.height() {
+            (long, short)
+        } else {
+            (short, long)
+        };
+        let (view_width, view_height) = straightened_size(view_width, view_height, levelled.straighten);
+        let (_, _, crop_width, crop_height) = geometry.crop_pixels(view_width, view_height);
+        lines.push(Line::from(vec![
+            Span::styled("Cropped Size: ", Style::default().fg(Color::Gray)),
+            Span::raw(format!("{}x{}", crop_width, crop_height)),
+        ]));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Applies to this file only",
+        Style::default().fg(Color::Gray),
+    )));
+    let settings = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("Geometry"))
+        .wrap(Wrap { trim: true });
+    f.render_widget(settings, columns[1]);
+
+    let key = |k: &'static str| Span::styled(k, Style::default().fg(Color::Gray));
+    let help_text = vec![
+        Line::from(vec![
+            key("←↑↓→: "),
+            Span::raw("Move crop | "),
+            key("+/-: "),
+            Span::raw("Size | "),
+            key("a: "),
+            Span::raw("Aspect | "),
+            key("o: "),
+            Span::raw("Portrait/landscape | "),
+            key("x: "),
+            Span::raw("Reset"),
+        ]),
+        Line::from(vec![
+            key("r/R: "),
+            Span::raw("Rotate | "),
+            key("f/v: "),
+            Span::raw("Flip | "),
+            key("[/]: "),
+            Span::raw("Straighten 0.5° | "),
+            key("{/}: "),
+            Span::raw("0.1° | "),
+            key("L: "),
+            Span::raw("Auto level | "),
+            key("Enter: "),
+            Span::raw("Save | "),
+            key("Esc: "),
+            Span::raw("Cancel"),
+        ]),
+    ];
+    let help = Paragraph::new(help_text)
+        .block(Block::default().borders(Borders::ALL).title("Help"));
+    f.render_widget(help, rows[1]);
+}
+
+/// Draw an image with half-block characters, two pixels per cell, scaled to
+/// fit `width` x `height` cells. Outside the `crop` rectangle (`x, y,
+/// width, height` in image pixels) it is dimmed, and the rectangle's edge
+/// is drawn in yellow.
+fn preview_lines(
+    image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    crop: (u32, u32, u32, u32),
+    width: u16,
+    height: u16,
+) -> Vec<Line<'static>> {
+    let (image_width, image_height) = (image.width() as f32, image.height() as f32);
+    let scale = (width as f32 / image_width).min(height as f32 * 2.0 / image_height);
+    if scale <= 0.0 {
+        return Vec::new();
+    }
+    let (columns, rows) = ((image_width * scale) as u16, (image_height * scale / 2.0) as u16);
+    let left_pad = " ".repeat(((width - columns.min(width)) / 2) as usize);
+
+    // Crop edges in display pixels
+    let (cx, cy, cw, ch) = crop;
+    let x0 = (cx as f32 * scale).round() as i32;
+    let y0 = (cy as f32 * scale).round() as i32;
+    let x1 = (((cx + cw) as f32 * scale).round() as i32 - 1).max(x0);
+    let y1 = (((cy + ch) as f32 * scale).round() as i32 - 1).max(y0);
+
+    let colour = |x: i32, y: i32| {
+        let inside = (x0..=x1).contains(&x) && (y0..=y1).contains(&y);
+        if inside && (x == x0 || x == x1 || y == y0 || y == y1) {
+            return Color::Yellow;
+        }
+        let source_x = ((x as f32 + 0.5) / scale) as u32;
+        let source_y = ((y as f32 + 0.5) / scale) as u32;
+        let [r, g, b] = image
+            .get_pixel(source_x.min(image.width() - 1), source_y.min(image.height() - 1))
+            .0;
+        if inside {
+            Color::Rgb(r, g, b)
+        } else {
+            Color::Rgb(r / 3, g / 3, b / 3)
+        }
+    };
+
+    (0..rows as i32)
+        .map(|row| {
+            let mut spans = vec![Span::raw(left_pad.clone())];
+            spans.extend((0..columns as i32).map(|x| {
+                Span::styled("▀", Style::default().fg(colour(x, row * 2)).bg(colour(x, row * 2 + 1)))
+            }));
+            Line::from(spans)
+        })
+        .collect()
+}
+
 fn render_conversion_config(f: &mut Frame, app: &App) {
     let area = f.area();
 
@@ -203,24 +910,72 @@ fn render_conversion_config(f: &mut Frame, app: &App) {
         ])
         .split(area);
 
-    // Title
-    let title = Paragraph::new("Configure conversion settings")
-        .block(Block::default().borders(Borders::ALL).title("Conversion Settings"));
+    // Title, with the active privacy policy so it is visible before converting
+    let title = Paragraph::new(Line::from(vec![
+        Span::raw("Configure conversion settings | "),
+        Span::styled("Privacy: ", Style::default().fg(Color::Gray)),
+        Span::raw(app.conversion_config.privacy.display()),
+        Span::raw(
+            app.applied_preset
+                .as_ref()
+                .map(|model| format!(" | Camera defaults per file ({} shown)", model))
+                .unwrap_or_default(),
+        ),
+    ]))
+    .block(Block::default().borders(Borders::ALL).title("Conversion Settings"));
     f.render_widget(title, chunks[0]);
 
     // Options
     let config = &app.conversion_config;
-    let options = vec![
-        format!("Output Format: {}", config.output_format.as_str()),
-        format!("Quality: {}", config.quality_display()),
-        format!("Preserve Metadata: {}", if config.preserve_metadata { "Yes" } else { "No" }),
-        format!("Output Directory: {}", config.output_directory.as_ref().unwrap_or(&"Same as source".to_string())),
-    ];
+    let kelvin_tint = config.develop.white_balance.kelvin_tint();
+    let tone = &config.develop.tone;
+    let options: Vec<(&str, String)> = ConfigOption::all()
+        .iter()
+        .map(|option| match option {
+            ConfigOption::OutputFormat => ("Output Format", config.output_format.as_str().to_string()),
+            ConfigOption::Quality => ("Quality", config.quality_display()),
+            ConfigOption::ColorSpace => ("Colour Space", config.develop.color_space.as_str().to_string()),
+            ConfigOption::PreserveMetadata => ("Preserve Metadata", yes_no(config.preserve_metadata)),
+            ConfigOption::Orientation => ("Orientation", config.orientation_mode.as_str().to_string()),
+            ConfigOption::Demosaic => ("Demosaic", config.develop.demosaic.as_str().to_string()),
+            ConfigOption::Denoise => ("Noise Reduction", config.develop.denoise.method.as_str().to_string()),
+            ConfigOption::LumaNoise => ("Luma Noise", DenoiseSettings::strength_display(config.develop.denoise.luma)),
+            ConfigOption::ChromaNoise => ("Colour Noise", DenoiseSettings::strength_display(config.develop.denoise.chroma)),
+            ConfigOption::WhiteBalance => ("White Balance", config.develop.white_balance.display()),
+            ConfigOption::Temperature => ("Temperature", kelvin_tint.map_or("As Shot".to_string(), |(k, _)| format!("{}K", k))),
+            ConfigOption::Tint => ("Tint", kelvin_tint.map_or("As Shot".to_string(), |(_, t)| format!("{:+}", t))),
+            ConfigOption::Exposure => ("Exposure", config.develop.exposure_display()),
+            ConfigOption::BlackPoint => ("Black Point", format!("{:.1}%", config.develop.black_point * 100.0)),
+            ConfigOption::WhitePoint => ("White Point", format!("{:.1}%", config.develop.white_point * 100.0)),
+            ConfigOption::ToneCurve => ("Tone Curve", tone.curve.as_str().to_string()),
+            ConfigOption::Contrast => ("Contrast", ToneSettings::slider_display(tone.contrast)),
+            ConfigOption::Highlights => ("Highlights", ToneSettings::slider_display(tone.highlights)),
+            ConfigOption::Shadows => ("Shadows", ToneSettings::slider_display(tone.shadows)),
+            ConfigOption::HighlightReconstruction => ("Rebuild Clipped Highlights", yes_no(tone.reconstruct_highlights)),
+            ConfigOption::TimeShift => ("Time Shift", config.time_shift.offset_display()),
+            ConfigOption::UtcOffset => ("UTC Offset Tag", config.time_shift.utc_offset_display()),
+            ConfigOption::GpsPrivacy => ("GPS Privacy", config.privacy.gps.as_str().to_string()),
+            ConfigOption::DropIdentifiers => ("Drop Serial/Owner", yes_no(config.privacy.drop_identifiers)),
+            ConfigOption::XmpSidecar => ("XMP Sidecar", yes_no(config.write_xmp_sidecar)),
+            ConfigOption::LensCorrection => ("Lens Correction", config.lens.correction.display()),
+            ConfigOption::LensEdges => ("Lens Edges", config.lens.edges.as_str().to_string()),
+            ConfigOption::AutoLevel => ("Auto Level Horizon", yes_no(config.geometry.auto_level)),
+            ConfigOption::StitchFusion => ("Stitch Fusion 360", yes_no(config.stitch_fusion)),
+            ConfigOption::Resize => ("Resize", config.resize.mode.display()),
+            ConfigOption::ResizeFilter => ("Resize Filter", config.resize.filter.as_str().to_string()),
+            ConfigOption::Sharpen => ("Sharpen", config.sharpen.amount_display()),
+            ConfigOption::SharpenRadius => ("Sharpen Radius", format!("{:.1} px", config.sharpen.radius)),
+            ConfigOption::SharpenThreshold => ("Sharpen Threshold", config.sharpen.threshold.to_string()),
+            ConfigOption::IsolateDecoder => ("Isolated Decoder", yes_no(config.isolate_decoder)),
+            ConfigOption::MetadataEdits => ("Metadata Edits (All Files)", config.metadata_edits.summary()),
+            ConfigOption::OutputDirectory => ("Output Directory", config.output_directory.clone().unwrap_or_else(|| "Same as source".to_string())),
+        })
+        .collect();
 
     let items: Vec<ListItem> = options
         .iter()
         .enumerate()
-        .map(|(i, opt)| {
+        .map(|(i, (label, value))| {
             let style = if i == app.config_option_index {
                 Style::default()
                     .fg(Color::Yellow)
@@ -235,20 +990,30 @@ fn render_conversion_config(f: &mut Frame, app: &App) {
                 "  "
             };
 
-            ListItem::new(format!("{}{}", prefix, opt)).style(style)
+            ListItem::new(format!("{}{}: {}", prefix, label, value)).style(style)
         })
         .collect();
 
+    // Scroll with the selection once the options outgrow the screen
     let list = List::new(items)
         .block(Block::default().borders(Borders::ALL).title("Options"));
-    f.render_widget(list, chunks[1]);
+    let mut list_state = ListState::default().with_selected(Some(app.config_option_index));
+    f.render_stateful_widget(list, chunks[1], &mut list_state);
 
     // Help
+    let editable = ConfigOption::all()
+        .get(app.config_option_index)
+        .is_some_and(|option| option.is_text());
+    let (adjust_key, adjust_label) = if editable {
+        ("e: ", "Edit | ")
+    } else {
+        ("←/→: ", "Adjust | ")
+    };
     let help_text = vec![Line::from(vec![
         Span::styled("↑/↓: ", Style::default().fg(Color::Gray)),
         Span::raw("Navigate | "),
-        Span::styled("←/→: ", Style::default().fg(Color::Gray)),
-        Span::raw("Adjust | "),
+        Span::styled(adjust_key, Style::default().fg(Color::Gray)),
+        Span::raw(adjust_label),
         Span::styled("Enter: ", Style::default().fg(Color::Gray)),
         Span::raw("Convert | "),
         Span::styled("Esc: ", Style::default().fg(Color::Gray)),
@@ -271,14 +1036,36 @@ fn render_converting(f: &mut Frame, app: &App) {
         ])
         .split(area);
 
-    let filename = app
-        .selected_file
-        .as_ref()
-        .map(|f| f.filename.as_str())
-        .unwrap_or("Unknown");
+    let (title, description) = match (&app.batch, &app.verify_job) {
+        (Some(job), _) => (
+            "Converting",
+            format!(
+                "Converting {} of {} files in {}",
+                (job.done + 1).min(job.files),
+                job.files,
+                job.directory.display()
+            ),
+        ),
+        (None, Some(job)) => (
+            "Verifying",
+            format!(
+                "Checking {} of {} files in {}",
+                (job.done + 1).min(job.files),
+                job.files,
+                job.directory.display()
+            ),
+        ),
+        (None, None) => (
+            "Converting",
+            format!(
+                "Converting: {}",
+                app.selected_file.as_ref().map(|f| f.filename.as_str()).unwrap_or("Unknown")
+            ),
+        ),
+    };
 
-    let info = Paragraph::new(format!("Converting: {}", filename))
-        .block(Block::default().borders(Borders::ALL).title("Converting"))
+    let info = Paragraph::new(description)
+        .block(Block::default().borders(Borders::ALL).title(title))
         .alignment(Alignment::Center);
     f.render_widget(info, chunks[0]);
 
@@ -345,3 +1132,7 @@ fn render_error(f: &mut Frame, app: &App) {
 
     f.render_widget(paragraph, area);
 }
+
+fn yes_no(value: bool) -> String {
+    if value { "Yes" } else { "No" }.to_string()
+}
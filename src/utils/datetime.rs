@@ -0,0 +1,261 @@
+use std::fmt;
+
+/// A calendar date and time as stored in EXIF `DateTime*` tags
+///
+/// EXIF timestamps carry no time zone, so all arithmetic here treats the
+/// value as a naive local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ExifDateTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl ExifDateTime {
+    /// Parse `YYYY:MM:DD HH:MM:SS`, also accepting `-` as the date separator
+    /// and `T` between date and time (ISO 8601 style)
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().trim_matches('"');
+        let (date, time) = value.split_once([' ', 'T'])?;
+
+        let mut date_parts = date.split([':', '-']);
+        let year = date_parts.next()?.parse().ok()?;
+        let month = date_parts.next()?.parse().ok()?;
+        let day = date_parts.next()?.parse().ok()?;
+
+        // Drop fractional seconds and any trailing zone designator
+        let time = time.trim_end_matches('Z');
+        let time = time.split(['+', '-', '.']).next()?;
+        let mut time_parts = time.split(':');
+        let hour = time_parts.next()?.parse().ok()?;
+        let minute = time_parts.next()?.parse().ok()?;
+        let second = time_parts.next().unwrap_or("0").parse().ok()?;
+
+        let parsed = Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        };
+
+        if parsed.is_valid() {
+            Some(parsed)
+        } else {
+            None
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        (1..=12).contains(&self.month)
+            && self.day >= 1
+            && self.day <= days_in_month(self.year, self.month)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+
+    /// Seconds since 1970-01-01 00:00:00, treating the value as UTC
+    pub fn to_timestamp(self) -> i64 {
+        let days = days_from_civil(self.year, self.month, self.day);
+        days * 86_400 + (self.hour * 3600 + self.minute * 60 + self.second) as i64
+    }
+
+    /// Inverse of [`ExifDateTime::to_timestamp`]
+    pub fn from_timestamp(timestamp: i64) -> Self {
+        let days = timestamp.div_euclid(86_400);
+        let secs = timestamp.rem_euclid(86_400) as u32;
+        let (year, month, day) = civil_from_days(days);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: secs / 3600,
+            minute: (secs % 3600) / 60,
+            second: secs % 60,
+        }
+    }
+
+    /// Return this time moved by `seconds` (which may be negative)
+    pub fn shifted(&self, seconds: i64) -> Self {
+        Self::from_timestamp(self.to_timestamp() + seconds)
+    }
+
+    /// `YYYYMMDD`, for use in file names
+    pub fn date_compact(&self) -> String {
+        format!("{:04}{:02}{:02}", self.year, self.month, self.day)
+    }
+
+    /// `HHMMSS`, for use in file names
+    pub fn time_compact(&self) -> String {
+        format!("{:02}{:02}{:02}", self.hour, self.minute, self.second)
+    }
+}
+
+impl fmt::Display for ExifDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Parse a signed clock offset such as `+02:00`, `-5:30:15`, `+0530`, `90s`,
+/// `-3h` or `45m` into seconds
+pub fn parse_offset(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("z") {
+        return Some(0);
+    }
+
+    let (sign, rest) = match value.as_bytes().first()? {
+        b'+' => (1, &value[1..]),
+        b'-' => (-1, &value[1..]),
+        _ => (1, value),
+    };
+
+    let magnitude = if let Some(hours) = rest.strip_suffix('h') {
+        hours.parse::<i64>().ok()? * 3600
+    } else if let Some(minutes) = rest.strip_suffix('m') {
+        minutes.parse::<i64>().ok()? * 60
+    } else if let Some(seconds) = rest.strip_suffix('s') {
+        seconds.parse::<i64>().ok()?
+    } else if rest.contains(':') {
+        let mut parts = rest.split(':');
+        let hours: i64 = parts.next()?.parse().ok()?;
+        let minutes: i64 = parts.next()?.parse().ok()?;
+        let seconds: i64 = parts.next().unwrap_or("0").parse().ok()?;
+        if parts.next().is_some() || minutes >= 60 || seconds >= 60 {
+            return None;
+        }
+        hours * 3600 + minutes * 60 + seconds
+    } else if rest.len() == 4 && rest.bytes().all(|b| b.is_ascii_digit()) {
+        // Compact `HHMM` as used in ISO 8601 zone designators
+        rest[..2].parse::<i64>().ok()? * 3600 + rest[2..].parse::<i64>().ok()? * 60
+    } else {
+        return None;
+    };
+
+    Some(sign * magnitude)
+}
+
+/// Parse a time zone offset for `OffsetTime*` tags, which EXIF limits to
+/// whole minutes within UTC-12:00..UTC+14:00
+pub fn parse_zone_offset(value: &str) -> Option<i64> {
+    parse_offset(value).filter(|seconds| seconds % 60 == 0 && (-12 * 3600..=14 * 3600).contains(seconds))
+}
+
+/// Format a zone offset as exactly `+HH:MM`, rounding to the nearest minute
+pub fn format_zone_offset(seconds: i64) -> String {
+    let minutes = (seconds as f64 / 60.0).round() as i64;
+    let sign = if minutes < 0 { '-' } else { '+' };
+    let abs = minutes.unsigned_abs();
+    format!("{}{:02}:{:02}", sign, abs / 60, abs % 60)
+}
+
+/// Format an offset in seconds as `+HH:MM:SS` (or `+HH:MM` when whole minutes)
+pub fn format_offset(seconds: i64) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let abs = seconds.unsigned_abs();
+    let (hours, minutes, secs) = (abs / 3600, (abs % 3600) / 60, abs % 60);
+
+    if secs == 0 {
+        format!("{}{:02}:{:02}", sign, hours, minutes)
+    } else {
+        format!("{}{:02}:{:02}:{:02}", sign, hours, minutes, secs)
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+// Howard Hinnant's days-from-civil algorithm (proleptic Gregorian calendar)
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year } as i64;
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_exif_datetime() {
+        let dt = ExifDateTime::parse("2018:09:26 08:25:27").unwrap();
+        assert_eq!((dt.year, dt.month, dt.day), (2018, 9, 26));
+        assert_eq!((dt.hour, dt.minute, dt.second), (8, 25, 27));
+        assert_eq!(dt.to_string(), "2018:09:26 08:25:27");
+
+        assert_eq!(ExifDateTime::parse("2018-09-26T08:25:27Z"), Some(dt));
+        assert!(ExifDateTime::parse("2018:02:30 08:25:27").is_none());
+        assert!(ExifDateTime::parse("garbage").is_none());
+    }
+
+    #[test]
+    fn test_timestamp_round_trip() {
+        let dt = ExifDateTime::parse("2016:01:01 23:07:27").unwrap();
+        assert_eq!(dt.to_timestamp(), 1_451_689_647);
+        assert_eq!(ExifDateTime::from_timestamp(dt.to_timestamp()), dt);
+
+        let shifted = dt.shifted(3600);
+        assert_eq!(shifted.to_string(), "2016:01:02 00:07:27");
+        assert_eq!(shifted.shifted(-3600), dt);
+    }
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(parse_offset("+02:00"), Some(7200));
+        assert_eq!(parse_offset("-5:30:15"), Some(-19815));
+        assert_eq!(parse_offset("+0530"), Some(19800));
+        assert_eq!(parse_offset("-3h"), Some(-10800));
+        assert_eq!(parse_offset("45m"), Some(2700));
+        assert_eq!(parse_offset("Z"), Some(0));
+        assert_eq!(parse_offset("+1:75"), None);
+        assert_eq!(format_offset(-19815), "-05:30:15");
+        assert_eq!(format_offset(7200), "+02:00");
+
+        // Zone offsets are whole minutes and always `+HH:MM`
+        assert_eq!(parse_zone_offset("+05:45"), Some(20700));
+        assert_eq!(parse_zone_offset("-5:30:15"), None);
+        assert_eq!(parse_zone_offset("+15h"), None);
+        assert_eq!(format_zone_offset(-19815), "-05:30");
+        assert_eq!(format_zone_offset(-19845), "-05:31");
+        assert_eq!(format_zone_offset(0), "+00:00");
+    }
+}
@@ -19,6 +19,25 @@ pub fn find_gpr_files(directory: &Path) -> Vec<PathBuf> {
         .collect()
 }
 
+/// Find all .mp4 files in a directory (recursively)
+pub fn find_video_files(directory: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkDir::new(directory)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .filter(|e| {
+            e.path()
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| ext.eq_ignore_ascii_case("mp4"))
+                .unwrap_or(false)
+        })
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    files.sort();
+    files
+}
+
 /// Check if a file is a .gpr file
 #[allow(dead_code)]
 pub fn is_gpr_file(path: &Path) -> bool {
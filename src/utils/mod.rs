@@ -1 +1,2 @@
 pub mod file_utils;
+pub mod datetime;